@@ -0,0 +1,20 @@
+precision mediump float;
+
+uniform sampler2D u_state;
+uniform vec2 u_state_size;
+
+varying vec2 v_uv;
+
+float cell(vec2 offset) {
+    // The world wraps around the edges
+    return texture2D(u_state, fract(v_uv + offset / u_state_size)).r;
+}
+
+void main() {
+    float neighbours = cell(vec2(-1, -1)) + cell(vec2(0, -1)) + cell(vec2(1, -1))
+        + cell(vec2(-1, 0)) + cell(vec2(1, 0))
+        + cell(vec2(-1, 1)) + cell(vec2(0, 1)) + cell(vec2(1, 1));
+    float count = floor(neighbours + 0.5);
+    bool alive = count == 3.0 || cell(vec2(0)) > 0.5 && count == 2.0;
+    gl_FragColor = vec4(alive ? 1.0 : 0.0, 0, 0, 1);
+}
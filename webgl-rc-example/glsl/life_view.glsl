@@ -0,0 +1,10 @@
+precision mediump float;
+
+uniform sampler2D u_state;
+
+varying vec2 v_uv;
+
+void main() {
+    float alive = texture2D(u_state, v_uv).r;
+    gl_FragColor = vec4(mix(vec3(0.1, 0.1, 0.15), vec3(0.4, 0.9, 0.5), alive), 1);
+}
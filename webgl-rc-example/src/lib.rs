@@ -4,7 +4,8 @@ use wasm_bindgen::prelude::*;
 use web_sys::HtmlCanvasElement;
 
 use webgl_rc::{
-    Attributes, Gl, ItemsBuffer, load_glsl, PrimitiveType, Program, Settings, Uniforms,
+    Attributes, ClearParams, Gl, ItemsBuffer, IterativeSimulation, load_glsl, PrimitiveType,
+    Program, ScreenPass, Settings, Texture, TextureType, Uniforms,
 };
 use webgl_rc::buffer_usage::BufferUsage;
 
@@ -62,19 +63,74 @@ pub fn create_context(canvas: &HtmlCanvasElement) -> TriangleContext {
 }
 
 #[wasm_bindgen]
-pub fn draw_triangle(context: &TriangleContext, width: i32, height: i32) {
+pub fn draw_triangle(context: &TriangleContext, width: i32, height: i32) -> Result<(), JsValue> {
     let gl = &context.gl;
-    gl.apply(
-        Gl::settings()
-            .clear_color(1.0, 1.0, 1.0, 1.0)
-            .viewport(0, 0, width, height),
-        || {
-            gl.clear_color_buffer();
-            context.program.draw_arrays(
-                PrimitiveType::Triangles,
-                &TriangleUniforms { time: 0.0 },
-                &context.points,
-            );
-        },
-    );
+    gl.apply(Gl::settings().viewport(0, 0, width, height), || {
+        gl.clear(ClearParams {
+            color: Some([1.0, 1.0, 1.0, 1.0]),
+            ..Default::default()
+        });
+        context.program.draw_arrays(
+            PrimitiveType::Triangles,
+            &TriangleUniforms { time: 0.0 },
+            &context.points,
+        )
+    })?;
+    Ok(())
+}
+
+const LIFE_SIZE: u32 = 256;
+
+#[derive(Clone, Uniforms)]
+pub struct LifeViewUniforms {
+    pub state: Texture,
+}
+
+#[wasm_bindgen]
+pub struct LifeContext {
+    gl: Gl,
+    simulation: IterativeSimulation,
+    view: ScreenPass,
+}
+
+#[wasm_bindgen]
+pub fn create_life(canvas: &HtmlCanvasElement) -> LifeContext {
+    let gl = Gl::new(canvas).unwrap();
+    let simulation = IterativeSimulation::new(
+        gl.clone(),
+        LIFE_SIZE,
+        LIFE_SIZE,
+        TextureType::Byte,
+        load_glsl!("life.glsl"),
+    )
+    .unwrap();
+    let cells: Vec<u8> = (0..LIFE_SIZE * LIFE_SIZE)
+        .flat_map(|_| {
+            let alive = if js_sys::Math::random() < 0.3 { 255 } else { 0 };
+            [alive, 0, 0, 255]
+        })
+        .collect();
+    simulation.texture().write_bytes(&cells).unwrap();
+    let view = ScreenPass::new(gl.clone(), load_glsl!("life_view.glsl")).unwrap();
+
+    LifeContext {
+        gl,
+        simulation,
+        view,
+    }
+}
+
+#[wasm_bindgen]
+pub fn draw_life(context: &LifeContext, width: i32, height: i32) -> Result<(), JsValue> {
+    let gl = &context.gl;
+    context.simulation.step(&())?;
+    gl.apply(Gl::settings().viewport(0, 0, width, height), || {
+        context.view.draw(
+            None,
+            &LifeViewUniforms {
+                state: context.simulation.texture(),
+            },
+        )
+    })?;
+    Ok(())
 }
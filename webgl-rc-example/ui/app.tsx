@@ -5,9 +5,12 @@ import { Canvas } from "./canvas";
 (async () => {
     await Promise.resolve();
 
-    const { create_context, draw_triangle } = await import("..");
+    const { create_context, draw_triangle, create_life, draw_life } = await import("..");
 
     function GlExample() {
+        if (location.hash === "#life") {
+            return <Canvas init={create_life} paint={draw_life} />;
+        }
         return <Canvas init={create_context} paint={draw_triangle} />;
     }
 
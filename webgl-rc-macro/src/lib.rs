@@ -3,15 +3,19 @@
 //! [load_glsl] macro is similar to [std::include_str] but it has some differences:
 //! * it loads files from `glsl` directory at the project root,
 //! * it supports `#include <lib/color.glsl>` or `#incluide "../lib/color.glsl"` macro in the files.
+//! * it resolves `#if feature(NAME)` and `#if cfg(feature = "name")` sections, see [load_glsl].
 
 #![allow(dead_code)]
 
 extern crate proc_macro;
+extern crate proc_macro2;
 extern crate regex;
 
 use proc_macro::{TokenTree, TokenStream, LexError};
+use proc_macro2::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span};
+use std::fmt;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::io::Read;
 use regex::Regex;
 use std::env::VarError;
@@ -20,6 +24,12 @@ use std::env::VarError;
 enum Error {
     Unknown(String),
     StructNameNotFound,
+    EnumNameNotFound,
+    /// Error reported at the tokens it's caused by
+    Spanned {
+        span: Span,
+        message: String,
+    },
     Syntax(LexError),
     InvalidArguments(String),
     RootDirNotFound(VarError),
@@ -30,12 +40,56 @@ enum Error {
     Multiple(Vec<Error>),
 }
 
-impl ToString for Error {
-    fn to_string(&self) -> String {
-        format!("{:?}", self)
+impl Error {
+    fn spanned(span: proc_macro::Span, message: String) -> Error {
+        Error::Spanned { span: span.into(), message }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            Error::Spanned { span, .. } => *span,
+            _ => Span::call_site(),
+        }
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unknown(message) => f.write_str(message),
+            Error::StructNameNotFound => f.write_str("expected a struct with named fields"),
+            Error::EnumNameNotFound => f.write_str("expected an enum"),
+            Error::Spanned { message, .. } => f.write_str(message),
+            Error::Syntax(error) => write!(f, "generated code can't be parsed: {}", error),
+            Error::InvalidArguments(message) => f.write_str(message),
+            Error::RootDirNotFound(error) => write!(f, "CARGO_MANIFEST_DIR isn't available: {}", error),
+            Error::IOError { file, error } => write!(f, "can't read {}: {}", file, error),
+            Error::Multiple(errors) => {
+                let messages = errors.iter().map(|error| error.to_string()).collect::<Vec<_>>();
+                f.write_str(&messages.join("\n"))
+            },
+        }
+    }
+}
+
+/// `compile_error!` pointing at the span.
+fn compile_error(span: Span, message: &str) -> proc_macro2::TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut arguments = Group::new(Delimiter::Parenthesis, proc_macro2::TokenTree::from(literal).into());
+    arguments.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut semicolon = Punct::new(';', Spacing::Alone);
+    semicolon.set_span(span);
+    vec![
+        proc_macro2::TokenTree::from(Ident::new("compile_error", span)),
+        bang.into(),
+        arguments.into(),
+        semicolon.into(),
+    ].into_iter().collect()
+}
+
 impl From<LexError> for Error {
     fn from(error: LexError) -> Self {
         Error::Syntax(error)
@@ -50,7 +104,11 @@ impl From<VarError> for Error {
 
 impl From<Error> for TokenStream {
     fn from(error: Error) -> Self {
-        format!("compile_error!(r####\"{:?}\"####);", error).parse().unwrap()
+        match error {
+            // Each error is reported at its own place
+            Error::Multiple(errors) => errors.into_iter().map(TokenStream::from).collect(),
+            error => compile_error(error.span(), &error.to_string()).into(),
+        }
     }
 }
 
@@ -58,6 +116,33 @@ impl From<Error> for TokenStream {
 struct Field {
     name: String,
     type_name: String,
+    /// Type as it's written, so the errors point at it
+    type_tokens: Vec<TokenTree>,
+    /// Names and arguments of the `#[attributes(...)]` and `#[instance(...)]` field attributes
+    options: Vec<(String, TokenStream)>,
+}
+
+/// Field options of `derive(Attributes)` and `derive(Instances)`, like
+/// `#[attributes(skip)]`, `#[attributes(with = "self.color.to_srgb8()", type = "[u8; 4]")]`
+/// or `#[instance(divisor = 4)]`.
+#[derive(Clone, Debug)]
+struct AttributeOptions {
+    skip: bool,
+    with: Option<String>,
+    /// Type override and the span of its literal
+    type_name: Option<(String, proc_macro::Span)>,
+    divisor: u32,
+}
+
+impl Default for AttributeOptions {
+    fn default() -> Self {
+        AttributeOptions {
+            skip: false,
+            with: None,
+            type_name: None,
+            divisor: 1,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -66,33 +151,108 @@ struct Struct {
     pub fields: Vec<Field>,
 }
 
+#[derive(Clone, Debug)]
+struct Enum {
+    pub name: String,
+    pub variants: Vec<String>,
+}
+
 fn parse_field<I>(iter: &mut I) -> Option<Field>
 where
     I : Iterator<Item = TokenTree> + Clone,
 {
-    let field_name = iter.take_while(|v| match v {
+    let head = iter.take_while(|v| match v {
         TokenTree::Punct(punct) => punct.to_string() != ":",
         _ => true,
-    }).last().map(|v| match v {
-        TokenTree::Ident(ident) => Some(ident.to_string()),
+    }).collect::<Vec<_>>();
+    let field_name = match head.last()? {
+        TokenTree::Ident(ident) => ident.to_string(),
+        _ => return None,
+    };
+    let options = head.windows(2).filter_map(|pair| match pair {
+        [TokenTree::Punct(punct), TokenTree::Group(group)] if punct.as_char() == '#' => {
+            let mut tokens = group.stream().into_iter();
+            match (tokens.next(), tokens.next()) {
+                (Some(TokenTree::Ident(name)), Some(TokenTree::Group(arguments)))
+                    if name.to_string() == "attributes" || name.to_string() == "instance" => {
+                    Some((name.to_string(), arguments.stream()))
+                },
+                _ => None,
+            }
+        },
         _ => None,
-    })??;
+    }).collect();
 
-    let type_name = iter.take_while(|v| match v {
+    let type_tokens = iter.take_while(|v| match v {
         TokenTree::Punct(punct) => punct.to_string() != ",",
         _ => true,
-    }).map(|v| v.to_string()).collect::<Vec<_>>().join("");
+    }).collect::<Vec<_>>();
+    let type_name = type_tokens.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("");
 
     if type_name != "" {
         Some(Field {
             name: field_name,
             type_name: type_name,
+            type_tokens,
+            options,
         })
     } else {
         None
     }
 }
 
+/// Content of a string literal, plain or raw.
+fn string_literal(token: &TokenTree) -> Option<String> {
+    let source = match token {
+        TokenTree::Literal(literal) => literal.to_string(),
+        _ => return None,
+    };
+    if let Some(raw) = source.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        raw.get(hashes + 1..raw.len() - hashes - 1).map(|v| v.to_string())
+    } else {
+        source.strip_prefix('"')?.strip_suffix('"')
+            .map(|v| v.replace("\\\"", "\"").replace("\\\\", "\\"))
+    }
+}
+
+fn parse_attribute_options(field: &Field) -> Result<AttributeOptions, Error> {
+    let mut result = AttributeOptions::default();
+    for (name, stream) in field.options.iter() {
+        let tokens = stream.clone().into_iter().collect::<Vec<_>>();
+        for option in tokens.split(|token| match token {
+            TokenTree::Punct(punct) => punct.as_char() == ',',
+            _ => false,
+        }).filter(|option| !option.is_empty()) {
+            let invalid = || Error::spanned(option[0].span(), format!(
+                "invalid option of the field `{}`: `{}`",
+                field.name,
+                option.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "),
+            ));
+            match option {
+                [TokenTree::Ident(key), TokenTree::Punct(punct), TokenTree::Literal(value)]
+                    if name == "instance" && key.to_string() == "divisor" && punct.as_char() == '=' => {
+                    result.divisor = value.to_string().parse().ok()
+                        .filter(|divisor| *divisor > 0)
+                        .ok_or_else(invalid)?;
+                },
+                _ if name == "instance" => return Err(invalid()),
+                [TokenTree::Ident(key)] if key.to_string() == "skip" => result.skip = true,
+                [TokenTree::Ident(key), TokenTree::Punct(punct), value] if punct.as_char() == '=' => {
+                    let value = string_literal(value).ok_or_else(invalid)?;
+                    match key.to_string().as_str() {
+                        "with" => result.with = Some(value),
+                        "type" => result.type_name = Some((value, option[2].span())),
+                        _ => return Err(invalid()),
+                    }
+                },
+                _ => return Err(invalid()),
+            }
+        }
+    }
+    Ok(result)
+}
+
 fn parse_struct<I>(iter: &mut I) -> Result<Struct, Error>
 where
     I : Iterator<Item = TokenTree> + Clone,
@@ -125,6 +285,83 @@ where
     Ok(Struct { name, fields })
 }
 
+fn parse_enum<I>(iter: &mut I) -> Result<Enum, Error>
+where
+    I : Iterator<Item = TokenTree> + Clone,
+{
+    let name = match iter.skip_while(|item| {
+        match item {
+            TokenTree::Ident(ident) => ident.to_string() != "enum",
+            _ => true
+        }
+    }).nth(1).ok_or(Error::EnumNameNotFound)? {
+        TokenTree::Ident(ident) => Ok(ident.to_string()),
+        _ => Err(Error::EnumNameNotFound)
+    }?;
+
+    let body = match iter.last() {
+        Some(TokenTree::Group(group)) => group.stream(),
+        _ => return Err(Error::EnumNameNotFound),
+    };
+
+    // Variants are separated by commas, attributes and discriminants are skipped
+    let mut variants = Vec::new();
+    let mut variant: Option<(String, proc_macro::Span)> = None;
+    let mut discriminant = false;
+    for token in body {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                variants.extend(variant.take().map(|(variant, _)| variant));
+                discriminant = false;
+            }
+            TokenTree::Punct(punct) if punct.as_char() == '=' => discriminant = true,
+            TokenTree::Ident(ident) if variant.is_none() && !discriminant => {
+                variant = Some((ident.to_string(), ident.span()));
+            }
+            TokenTree::Group(_) if variant.is_some() && !discriminant => {
+                let (variant, span) = variant.unwrap();
+                return Err(Error::spanned(span, format!(
+                    "`{}::{}` has fields, only fieldless variants can be uniforms",
+                    name, variant
+                )));
+            }
+            _ => {}
+        }
+    }
+    variants.extend(variant.map(|(variant, _)| variant));
+
+    Ok(Enum { name, variants })
+}
+
+/// Tokens with the span, so the errors in the generated code point at it.
+fn respan(stream: proc_macro2::TokenStream, span: Span) -> proc_macro2::TokenStream {
+    stream.into_iter().map(|mut token| {
+        if let proc_macro2::TokenTree::Group(group) = &token {
+            token = Group::new(group.delimiter(), respan(group.stream(), span)).into();
+        }
+        token.set_span(span);
+        token
+    }).collect()
+}
+
+/// Constant failing to compile at the type tokens, if the type doesn't implement the traits.
+/// Otherwise the errors of the derived code point at the derive.
+fn assert_type(type_tokens: proc_macro2::TokenStream, bounds: &str) -> Result<proc_macro2::TokenStream, Error> {
+    let mut check = proc_macro2::TokenStream::from(
+        format!("const fn check<T: {}>() {{}} check::<", bounds).parse::<TokenStream>()?
+    );
+    check.extend(type_tokens);
+    check.extend(proc_macro2::TokenStream::from(">();".parse::<TokenStream>()?));
+    let mut result = proc_macro2::TokenStream::from("const _: () =".parse::<TokenStream>()?);
+    result.extend(Some(proc_macro2::TokenTree::from(Group::new(Delimiter::Brace, check))));
+    result.extend(proc_macro2::TokenStream::from(";".parse::<TokenStream>()?));
+    Ok(result)
+}
+
+fn field_type(field: &Field) -> proc_macro2::TokenStream {
+    field.type_tokens.iter().cloned().collect::<TokenStream>().into()
+}
+
 fn uniforms_impl(tokens: TokenStream) -> Result<TokenStream, Error> {
     let parsed = parse_struct(&mut tokens.into_iter())?;
     let source = format!(
@@ -135,16 +372,33 @@ fn uniforms_impl(tokens: TokenStream) -> Result<TokenStream, Error> {
                     {content}
                 ]
             }}
+        }}
+        impl webgl_rc::program_check::DeclaredUniforms for {struct_name} {{
+            const UNIFORMS: &'static [webgl_rc::program_check::Declaration] = &[
+                {declarations}
+            ];
         }}"####,
         struct_name = parsed.name,
         content = &parsed.fields.iter().map(|field| {
             format!(
-                r###"webgl_rc::uniforms::Field {{ name: r#"u_{name}"#, value: self.{name}.into_uniform() }},"###,
+                r###"webgl_rc::uniforms::Field {{ name: r#"u_{name}"#.into(), value: self.{name}.into_uniform() }},"###,
+                name = field.name,
+            )
+        }).collect::<Vec<_>>().join(""),
+        declarations = &parsed.fields.iter().map(|field| {
+            format!(
+                r###"webgl_rc::program_check::Declaration {{ name: r#"u_{name}"#, data_type: <{type_name} as webgl_rc::uniforms::IntoUniform>::UNIFORM_TYPE }},"###,
                 name = field.name,
+                type_name = field.type_name,
             )
-        }).collect::<Vec<_>>().join("")
+        }).collect::<Vec<_>>().join(""),
     );
-    Ok(source.parse()?)
+    let mut result = proc_macro2::TokenStream::new();
+    for field in parsed.fields.iter() {
+        result.extend(assert_type(field_type(field), "webgl_rc::uniforms::IntoUniform")?);
+    }
+    result.extend(proc_macro2::TokenStream::from(source.parse::<TokenStream>()?));
+    Ok(result.into())
 }
 
 #[proc_macro_derive(Uniforms)]
@@ -152,13 +406,86 @@ pub fn uniforms(tokens: TokenStream) -> TokenStream {
     uniforms_impl(tokens).unwrap_or_else(|error| error.into())
 }
 
+fn into_uniform_impl(tokens: TokenStream) -> Result<TokenStream, Error> {
+    let parsed = parse_enum(&mut tokens.into_iter())?;
+    let source = format!(
+        r####"
+            impl webgl_rc::uniforms::IntoUniform for {enum_name} {{
+                const UNIFORM_TYPE: Option<webgl_rc::types::DataType> = Some(webgl_rc::types::DataType::Int);
+
+                fn into_uniform(&self) -> webgl_rc::uniforms::UniformValue {{
+                    webgl_rc::uniforms::UniformValue::Int(match self {{
+                        {variants}
+                    }})
+                }}
+            }}
+            impl webgl_rc::types::TypeMark for {enum_name} {{
+                const DATA_TYPE: Option<webgl_rc::types::DataType> = Some(webgl_rc::types::DataType::Int);
+            }}
+        "####,
+        enum_name = parsed.name,
+        variants = &parsed.variants.iter().map(|variant| {
+            format!(
+                r###"{enum_name}::{variant} => {enum_name}::{variant} as i32,"###,
+                enum_name = parsed.name,
+                variant = variant,
+            )
+        }).collect::<Vec<_>>().join(""),
+    );
+    Ok(source.parse()?)
+}
+
+/// Pass a fieldless enum as an `int` uniform, variants are mapped to their discriminants,
+/// so they can be matched with the constants declared in the shader.
+/// ```ignore
+/// #[derive(Clone, Copy, IntoUniform)]
+/// enum Mode {
+///     Color = 0,
+///     Texture = 1,
+/// }
+/// ```
+#[proc_macro_derive(IntoUniform)]
+pub fn into_uniform(tokens: TokenStream) -> TokenStream {
+    into_uniform_impl(tokens).unwrap_or_else(|error| error.into())
+}
+
+/// Attribute written into the buffer, `value` is an expression of `type_name`.
+struct AttributeField {
+    name: String,
+    type_name: String,
+    type_tokens: proc_macro2::TokenStream,
+    value: String,
+    divisor: u32,
+}
+
 fn attributes_impl(prefix: &str, tokens: TokenStream) -> Result<TokenStream, Error> {
     let parsed = parse_struct(&mut tokens.into_iter())?;
+    let mut fields = Vec::new();
+    for field in parsed.fields.iter() {
+        let options = parse_attribute_options(field)?;
+        if !options.skip {
+            let (type_name, type_tokens) = match options.type_name {
+                Some((type_name, span)) => {
+                    let tokens = proc_macro2::TokenStream::from(type_name.parse::<TokenStream>()?);
+                    (type_name, respan(tokens, span.into()))
+                },
+                None => (field.type_name.clone(), field_type(field)),
+            };
+            fields.push(AttributeField {
+                name: field.name.clone(),
+                type_name,
+                type_tokens,
+                value: options.with.unwrap_or_else(|| format!("self.{}", field.name)),
+                divisor: options.divisor,
+            });
+        }
+    }
     let source = format!(
         r####"
             impl webgl_rc::data_buffer::Item for {struct_name} {{
                 fn layout() -> Vec<webgl_rc::data_buffer::Layout> {{
                     use webgl_rc::types::TypeMark;
+                    use webgl_rc::data_buffer::Writable;
                     vec![
                         {layout_items}
                     ]
@@ -174,48 +501,263 @@ fn attributes_impl(prefix: &str, tokens: TokenStream) -> Result<TokenStream, Err
                     {stride_items}
                 }}
             }}
+            impl webgl_rc::program_check::DeclaredAttributes for {struct_name} {{
+                const ATTRIBUTES: &'static [webgl_rc::program_check::Declaration] = &[
+                    {declarations}
+                ];
+            }}
         "####,
         struct_name = parsed.name,
-        layout_items = &parsed.fields.iter().map(|field| {
+        layout_items = &fields.iter().map(|field| {
             format!(
-                r###"webgl_rc::data_buffer::Layout {{ name: r#"{prefix}_{name}"#, data_type: <{type_name} as TypeMark>::data_type() }},"###,
+                r###"webgl_rc::data_buffer::Layout {{ name: r#"{prefix}_{name}"#, data_type: <{type_name} as TypeMark>::data_type(), format: <{type_name} as Writable>::format(), divisor: {divisor} }},"###,
                 prefix = prefix,
                 name = field.name,
                 type_name = field.type_name,
+                divisor = field.divisor,
             )
         }).collect::<Vec<_>>().join(""),
-        write_items = &parsed.fields.iter().map(|field| {
+        write_items = &fields.iter().map(|field| {
             format!(
-                r###"self.{name}.write(output);"###,
-                name = field.name,
+                r###"{{ let value: {type_name} = {value}; value.write(output); }}"###,
+                type_name = field.type_name,
+                value = field.value,
             )
         }).collect::<Vec<_>>().join(""),
-        stride_items = &parsed.fields.iter().map(|field| {
+        stride_items = &fields.iter().map(|field| {
             format!(
                 r###"<{type_name} as Writable>::stride()"###,
                 type_name = field.type_name,
             )
-        }).collect::<Vec<_>>().join(" + "),
+        }).chain(Some("0".to_string())).collect::<Vec<_>>().join(" + "),
+        declarations = &fields.iter().map(|field| {
+            format!(
+                r###"webgl_rc::program_check::Declaration {{ name: r#"{prefix}_{name}"#, data_type: <{type_name} as webgl_rc::types::TypeMark>::DATA_TYPE }},"###,
+                prefix = prefix,
+                name = field.name,
+                type_name = field.type_name,
+            )
+        }).collect::<Vec<_>>().join(""),
+    );
+    let mut result = proc_macro2::TokenStream::new();
+    for field in fields.iter() {
+        result.extend(assert_type(
+            field.type_tokens.clone(),
+            "webgl_rc::data_buffer::Writable + webgl_rc::types::TypeMark",
+        )?);
+        result.extend(assert_attribute_type(&parsed.name, field)?);
+    }
+    result.extend(proc_macro2::TokenStream::from(source.parse::<TokenStream>()?));
+    Ok(result.into())
+}
+
+/// Constant failing to compile at the field type, if its GLSL type can't be an attribute,
+/// like a matrix or a sampler.
+fn assert_attribute_type(struct_name: &str, field: &AttributeField) -> Result<proc_macro2::TokenStream, Error> {
+    let span = field.type_tokens.clone().into_iter().next().map(|token| token.span()).unwrap_or_else(Span::call_site);
+    let source = format!(
+        r###"const _: () = assert!(match <{type_name} as webgl_rc::types::TypeMark>::DATA_TYPE {{ Some(data_type) => data_type.is_attribute(), None => true }}, r#"field `{name}` of `{struct_name}` has type `{type_name}` which can't be a vertex attribute, supported types are float, vec2, vec3 and vec4, like `f32`, `[f32; 3]` or `Color`; pass matrices as separate vec4 columns"#);"###,
+        type_name = field.type_name,
+        name = field.name,
+        struct_name = struct_name,
     );
-    source.parse().map_err(|error: LexError| error.into())
+    Ok(respan(proc_macro2::TokenStream::from(source.parse::<TokenStream>()?), span))
 }
 
-#[proc_macro_derive(Attributes)]
+/// Vertex attributes named `a_<field>`. Fields marked `#[attributes(skip)]` stay on the CPU,
+/// `#[attributes(with = "expression", type = "Type")]` writes the expression, which can use `self`,
+/// instead of the field value.
+#[proc_macro_derive(Attributes, attributes(attributes))]
 pub fn attributes(tokens: TokenStream) -> TokenStream {
     attributes_impl("a", tokens).unwrap_or_else(|error| error.into())
 }
 
-#[proc_macro_derive(Instances)]
+/// Instance attributes named `i_<field>`, supports the field options of [macro@Attributes].
+/// `#[instance(divisor = 4)]` shares the field values between groups of four instances,
+/// so the first quarter of the items provides them.
+#[proc_macro_derive(Instances, attributes(attributes, instance))]
 pub fn instances(tokens: TokenStream) -> TokenStream {
     attributes_impl("i", tokens).unwrap_or_else(|error| error.into())
 }
 
+/// File a shader is built from.
+struct Dependency {
+    file: String,
+    /// Path relative to the `glsl` folder
+    path: String,
+    hash: u64,
+}
+
+/// Part of a shader, `cfg` sections are resolved by the compiler of the crate using the macro.
+enum Segment {
+    Text(String),
+    Cfg {
+        predicate: String,
+        enabled: Vec<Segment>,
+        disabled: Vec<Segment>,
+    },
+}
+
 struct Content {
-    content: String,
-    dependencies: Vec<String>,
+    segments: Vec<Segment>,
+    dependencies: Vec<Dependency>,
 }
 
-fn load_glsl_file(root: &Path, file: &Path) -> Result<Content, Error> {
+/// Condition of an `#if feature(NAME)` or `#if cfg(feature = "name")` section.
+enum Condition {
+    Constant(bool),
+    Cfg(String),
+}
+
+/// Line that ends a section.
+enum Terminator {
+    Else,
+    EndIf,
+    End,
+}
+
+/// `None` for the conditions left to the GLSL preprocessor.
+fn parse_condition(directive: &str, features: &[String]) -> Option<Condition> {
+    let condition = directive.strip_prefix("#if")?;
+    if !condition.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let condition = condition.trim();
+    let (negated, condition) = match condition.strip_prefix('!') {
+        Some(condition) => (true, condition.trim_start()),
+        None => (false, condition),
+    };
+    let argument = |name: &str| {
+        condition.strip_prefix(name)?.trim_start()
+            .strip_prefix('(')?
+            .strip_suffix(')')
+            .map(|value| value.trim())
+    };
+    if let Some(feature) = argument("feature") {
+        return Some(Condition::Constant(features.iter().any(|value| value == feature) != negated));
+    }
+    argument("cfg").map(|predicate| Condition::Cfg(if negated {
+        format!("not({})", predicate)
+    } else {
+        predicate.into()
+    }))
+}
+
+fn push_text(segments: &mut Vec<Segment>, text: &str) {
+    if let Some(Segment::Text(last)) = segments.last_mut() {
+        last.push_str(text);
+    } else if !text.is_empty() {
+        segments.push(Segment::Text(text.into()));
+    }
+}
+
+/// Resolve the `feature` sections, the other `#if` sections are kept for the GLSL preprocessor.
+fn parse_sections(
+    lines: &mut dyn Iterator<Item = (usize, &str)>,
+    features: &[String],
+    file: &Path,
+) -> Result<(Vec<Segment>, Terminator), Error> {
+    let unterminated = |line: usize| Error::Unknown(format!(
+        "{:?}:{}: `#if` section isn't terminated with `#endif`",
+        file,
+        line + 1,
+    ));
+    let mut segments = Vec::new();
+    let mut depth = 0;
+    while let Some((index, line)) = lines.next() {
+        let directive = line.trim_start();
+        if let Some(condition) = parse_condition(directive, features) {
+            let (enabled, terminator) = parse_sections(lines, features, file)?;
+            let disabled = match terminator {
+                Terminator::Else => match parse_sections(lines, features, file)? {
+                    (disabled, Terminator::EndIf) => disabled,
+                    _ => return Err(unterminated(index)),
+                },
+                Terminator::EndIf => Vec::new(),
+                Terminator::End => return Err(unterminated(index)),
+            };
+            match condition {
+                Condition::Constant(true) => segments.extend(enabled),
+                Condition::Constant(false) => segments.extend(disabled),
+                Condition::Cfg(predicate) => segments.push(Segment::Cfg {
+                    predicate,
+                    enabled,
+                    disabled,
+                }),
+            }
+            continue;
+        }
+        if directive.starts_with("#if") {
+            depth += 1;
+        } else if directive.starts_with("#endif") {
+            if depth == 0 {
+                return Ok((segments, Terminator::EndIf));
+            }
+            depth -= 1;
+        } else if depth == 0 && directive.starts_with("#else") {
+            return Ok((segments, Terminator::Else));
+        } else if depth == 0 && directive.starts_with("#elif") {
+            return Err(Error::Unknown(format!(
+                "{:?}:{}: `#elif` isn't supported in `feature` and `cfg` sections",
+                file,
+                index + 1,
+            )));
+        }
+        push_text(&mut segments, line);
+    }
+    Ok((segments, Terminator::End))
+}
+
+fn cfg_predicates(segments: &[Segment], predicates: &mut Vec<String>) {
+    for segment in segments {
+        if let Segment::Cfg { predicate, enabled, disabled } = segment {
+            if !predicates.contains(predicate) {
+                predicates.push(predicate.clone());
+            }
+            cfg_predicates(enabled, predicates);
+            cfg_predicates(disabled, predicates);
+        }
+    }
+}
+
+fn join_segments(segments: &[Segment], is_enabled: &dyn Fn(&str) -> bool, output: &mut String) {
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => output.push_str(text),
+            Segment::Cfg { predicate, enabled, disabled } => join_segments(
+                if is_enabled(predicate) { enabled } else { disabled },
+                is_enabled,
+                output,
+            ),
+        }
+    }
+}
+
+/// FNV-1a, stable between builds and compiler versions, unlike the std hashers.
+fn content_hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Path relative to the root with `..` resolved and `/` separators.
+fn relative_path(root: &Path, file: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in file.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            },
+            Component::CurDir => {},
+            other => normalized.push(other),
+        }
+    }
+    normalized.strip_prefix(root).unwrap_or(&normalized)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn load_glsl_file(root: &Path, file: &Path, features: &[String]) -> Result<Content, Error> {
     let mut handle = File::open(file).map_err(|error| {
         Error::IOError {
             file: file.to_str().unwrap().into(),
@@ -230,36 +772,23 @@ fn load_glsl_file(root: &Path, file: &Path) -> Result<Content, Error> {
         }
     })?;
 
-    let mut dependencies = vec![file.to_str().unwrap().into()];
+    let mut dependencies = vec![Dependency {
+        file: file.to_str().unwrap().into(),
+        path: relative_path(root, file),
+        hash: content_hash(source.as_bytes()),
+    }];
     let mut errors = Vec::new();
 
-    let source_with_includes = Regex::new(r#"#include\s*(<.+?>|".+?")"#)
-        .unwrap()
-        .replace_all(&source, &mut |captures: &regex::Captures<'_>| {
-            let capture = captures.get(1).unwrap().as_str();
-            let file_name = if capture.starts_with("<") {
-                root.join(capture.get(1..(capture.len() - 1)).unwrap())
-            } else {
-                file.parent().unwrap().join(capture.get(1..(capture.len() - 1)).unwrap())
-            };
-
-            match load_glsl_file(root, &file_name) {
-                Ok(content) => {
-                    for file in content.dependencies {
-                        dependencies.push(file);
-                    }
-                    content.content
-                }
-                Err(error) => {
-                    errors.push(error);
-                    format!("#error Failed to include file {:?}\n", file_name)
-                }
-            }
-        });
+    let mut lines = source.split_inclusive('\n').enumerate();
+    let segments = match parse_sections(&mut lines, features, file)? {
+        (segments, Terminator::End) => segments,
+        (_, _) => return Err(Error::Unknown(format!("{:?}: `#endif` without `#if`", file))),
+    };
+    let segments = resolve_includes(segments, root, file, features, &mut dependencies, &mut errors);
 
     if errors.is_empty() {
         Ok(Content {
-            content: source_with_includes.into(),
+            segments,
             dependencies,
         })
     } else {
@@ -267,14 +796,97 @@ fn load_glsl_file(root: &Path, file: &Path) -> Result<Content, Error> {
     }
 }
 
+fn resolve_includes(
+    segments: Vec<Segment>,
+    root: &Path,
+    file: &Path,
+    features: &[String],
+    dependencies: &mut Vec<Dependency>,
+    errors: &mut Vec<Error>,
+) -> Vec<Segment> {
+    let include = Regex::new(r#"#include\s*(<.+?>|".+?")"#).unwrap();
+    let mut result = Vec::new();
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => {
+                let mut last = 0;
+                for captures in include.captures_iter(&text) {
+                    let directive = captures.get(0).unwrap();
+                    push_text(&mut result, &text[last..directive.start()]);
+                    last = directive.end();
+
+                    let capture = captures.get(1).unwrap().as_str();
+                    let file_name = if capture.starts_with("<") {
+                        root.join(capture.get(1..(capture.len() - 1)).unwrap())
+                    } else {
+                        file.parent().unwrap().join(capture.get(1..(capture.len() - 1)).unwrap())
+                    };
+
+                    match load_glsl_file(root, &file_name, features) {
+                        Ok(content) => {
+                            dependencies.extend(content.dependencies);
+                            result.extend(content.segments);
+                        }
+                        Err(error) => {
+                            errors.push(error);
+                            push_text(&mut result, &format!("#error Failed to include file {:?}\n", file_name));
+                        }
+                    }
+                }
+                push_text(&mut result, &text[last..]);
+            },
+            Segment::Cfg { predicate, enabled, disabled } => result.push(Segment::Cfg {
+                predicate,
+                enabled: resolve_includes(enabled, root, file, features, dependencies, errors),
+                disabled: resolve_includes(disabled, root, file, features, dependencies, errors),
+            }),
+        }
+    }
+    result
+}
+
+/// Maximal number of distinct `cfg` conditions in a shader, a source is generated for each combination.
+const MAX_CFG_PREDICATES: usize = 8;
+
 fn load_glsl_impl(stream: TokenStream) -> Result<TokenStream, Error> {
     let tokens = stream.into_iter().collect::<Vec<_>>();
-    return if tokens.is_empty() {
+    let mut arguments = tokens.split(|token| match token {
+        TokenTree::Punct(punct) => punct.as_char() == ',',
+        _ => false,
+    });
+    let file_argument = arguments.next().unwrap_or(&[]);
+    let mut manifest = false;
+    let mut features = Vec::new();
+    for argument in arguments.filter(|argument| !argument.is_empty()) {
+        match argument {
+            [TokenTree::Ident(ident)] if ident.to_string() == "manifest" => manifest = true,
+            [TokenTree::Ident(ident), TokenTree::Punct(punct), TokenTree::Group(group)]
+                if ident.to_string() == "features"
+                    && punct.as_char() == '='
+                    && group.delimiter() == proc_macro::Delimiter::Bracket => {
+                let items = group.stream().into_iter().filter(|token| match token {
+                    TokenTree::Punct(punct) => punct.as_char() != ',',
+                    _ => true,
+                });
+                for item in items {
+                    features.push(string_literal(&item).ok_or_else(|| Error::spanned(
+                        item.span(),
+                        format!("Feature name should be a string but `{}` provided", item),
+                    ))?);
+                }
+            },
+            _ => return Err(Error::spanned(argument[0].span(), format!(
+                "Unknown argument `{}`, expected `manifest` or `features = [...]`",
+                argument.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "),
+            ))),
+        }
+    }
+    if file_argument.is_empty() {
         Err(Error::InvalidArguments("File name not provided".into()))
-    } else if tokens.len() > 1 {
-        Err(Error::InvalidArguments("Too many arguments".into()))
+    } else if file_argument.len() > 1 {
+        Err(Error::spanned(file_argument[1].span(), "Too many arguments".into()))
     } else {
-        let name = match tokens.first().unwrap() {
+        let name = match file_argument.first().unwrap() {
             TokenTree::Literal(value) => {
                 Ok(
                     value.to_string().chars().into_iter()
@@ -286,21 +898,76 @@ fn load_glsl_impl(stream: TokenStream) -> Result<TokenStream, Error> {
                         .join("")
                 )
             },
-            other => Err(Error::Unknown(format!("File name should be a string but {:?} provided", other)))
+            other => Err(Error::spanned(other.span(), format!("File name should be a string but `{}` provided", other)))
         }?;
 
         let root = Path::new(
             &std::env::var("CARGO_MANIFEST_DIR")?
         ).join("glsl");
 
-        let content = load_glsl_file(root.as_path(), root.join(name).as_path())?;
+        let content = load_glsl_file(root.as_path(), root.join(name).as_path(), &features)?;
+
+        let mut files: Vec<&Dependency> = Vec::new();
+        for dependency in content.dependencies.iter() {
+            if files.iter().all(|file| file.file != dependency.file) {
+                files.push(dependency);
+            }
+        }
+
+        let mut predicates = Vec::new();
+        cfg_predicates(&content.segments, &mut predicates);
+        if predicates.len() > MAX_CFG_PREDICATES {
+            return Err(Error::InvalidArguments(format!(
+                "Too many `#if cfg(...)` conditions, at most {} are supported",
+                MAX_CFG_PREDICATES,
+            )));
+        }
+        let source = if predicates.is_empty() {
+            let mut source = String::new();
+            join_segments(&content.segments, &|_| false, &mut source);
+            format!(r#####"r####"{}"####"#####, source)
+        } else {
+            let variants = (0..1usize << predicates.len()).map(|mask| {
+                let enabled = |predicate: &str| predicates.iter()
+                    .position(|value| value == predicate)
+                    .is_some_and(|index| mask & (1 << index) != 0);
+                let mut source = String::new();
+                join_segments(&content.segments, &enabled, &mut source);
+                format!(
+                    r#####"#[cfg(all({conditions}))] const SOURCE: &str = r####"{source}"####;"#####,
+                    conditions = predicates.iter().enumerate().map(|(index, predicate)| {
+                        if mask & (1 << index) != 0 {
+                            predicate.clone()
+                        } else {
+                            format!("not({})", predicate)
+                        }
+                    }).collect::<Vec<_>>().join(", "),
+                    source = source,
+                )
+            }).collect::<Vec<_>>().join("");
+            format!("{{ {} SOURCE }}", variants)
+        };
 
         Ok(format!(
-            r#####"{{ {dependencies}; r####"{content}"#### }}"#####,
-            dependencies = content.dependencies.into_iter().map(|file| {
-                format!(r##"const _: &[u8] = include_bytes!(r#"{file}"#);"##, file = file)
+            r#####"{{ {dependencies}; {value} }}"#####,
+            dependencies = files.iter().map(|dependency| {
+                format!(r##"const _: &[u8] = include_bytes!(r#"{file}"#);"##, file = dependency.file)
             }).collect::<Vec<_>>().join(""),
-            content = content.content
+            value = if manifest {
+                format!(
+                    "webgl_rc::glsl_manifest::GlslManifest {{ source: {source}, files: &[{files}] }}",
+                    source = source,
+                    files = files.iter().map(|dependency| {
+                        format!(
+                            r##"webgl_rc::glsl_manifest::GlslFile {{ path: r#"{path}"#, hash: {hash:#x} }},"##,
+                            path = dependency.path,
+                            hash = dependency.hash,
+                        )
+                    }).collect::<Vec<_>>().join(""),
+                )
+            } else {
+                source
+            },
         ).parse()?)
     }
 }
@@ -310,7 +977,244 @@ fn load_glsl_impl(stream: TokenStream) -> Result<TokenStream, Error> {
 /// let vertex: &str = webgl_rc::load_glsl!("draw/vertex.glsl");
 /// let fragment: &str = webgl_rc::load_glsl!("draw/fragment.glsl");
 /// ```
+///
+/// With the `manifest` argument it returns a `GlslManifest` constant, the source along with
+/// the files it's built from and their content hashes, for hot-reload tooling.
+/// ```ignore
+/// const VERTEX: webgl_rc::GlslManifest = webgl_rc::load_glsl!("draw/vertex.glsl", manifest);
+/// ```
+///
+/// Sections under `#if feature(NAME)` are kept when the name is listed in the `features` argument,
+/// and sections under `#if cfg(feature = "name")` when the cargo feature of the calling crate is
+/// enabled. Both can be negated with `!` and have an `#else` branch, other `#if` and `#ifdef`
+/// directives are left to the GLSL preprocessor.
+/// ```glsl
+/// #if feature(SHADOWS)
+/// uniform sampler2D u_shadow_map;
+/// #endif
+/// ```
+/// ```ignore
+/// let fragment: &str = webgl_rc::load_glsl!("pbr.glsl", features = ["SHADOWS"]);
+/// ```
 #[proc_macro]
 pub fn load_glsl(tokens: TokenStream) -> TokenStream {
     load_glsl_impl(tokens).unwrap_or_else(|error| error.into())
-}
\ No newline at end of file
+}
+/// All the text of a shader, both branches of the `cfg` sections.
+fn all_segments(segments: &[Segment], output: &mut String) {
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => output.push_str(text),
+            Segment::Cfg { enabled, disabled, .. } => {
+                all_segments(enabled, output);
+                all_segments(disabled, output);
+            },
+        }
+    }
+}
+
+fn glsl_data_type(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "bool" => "Boolean",
+        "int" => "Int",
+        "ivec2" => "IVec2",
+        "ivec3" => "IVec3",
+        "ivec4" => "IVec4",
+        "float" => "Float",
+        "vec2" => "Vec2",
+        "vec3" => "Vec3",
+        "vec4" => "Vec4",
+        "mat2" => "Mat2",
+        "mat3" => "Mat3",
+        "mat4" => "Mat4",
+        "sampler2D" => "Sampler",
+        "samplerCube" => "SamplerCube",
+        _ => return None,
+    })
+}
+
+/// `uniform` declarations, and with `attributes` the `attribute` and `in` ones, as `Declaration` literals.
+fn glsl_declarations(source: &str, attributes: bool) -> Vec<String> {
+    let source = Regex::new(r"(?s)/\*.*?\*/|//[^\n]*").unwrap().replace_all(source, " ");
+    let declaration = Regex::new(
+        r"(?m)^\s*(?:layout\s*\([^)]*\)\s*)?(uniform|attribute|in)\s+(?:(?:lowp|mediump|highp)\s+)?(\w+)\s+([^;{]+);"
+    ).unwrap();
+    let mut result: Vec<(String, String)> = Vec::new();
+    for captures in declaration.captures_iter(&source) {
+        if (&captures[1] == "uniform") == attributes {
+            continue;
+        }
+        for declarator in captures[3].split(',') {
+            let declarator = declarator.trim();
+            let name = declarator.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("");
+            if name.is_empty() || result.iter().any(|(value, _)| value == name) {
+                continue;
+            }
+            let data_type = match glsl_data_type(&captures[2]) {
+                Some(data_type) if !declarator.contains('[') => format!("Some(webgl_rc::types::DataType::{})", data_type),
+                _ => "None".into(),
+            };
+            result.push((name.into(), data_type));
+        }
+    }
+    result.into_iter().map(|(name, data_type)| {
+        format!(
+            r##"webgl_rc::program_check::Declaration {{ name: r#"{name}"#, data_type: {data_type} }},"##,
+            name = name,
+            data_type = data_type,
+        )
+    }).collect()
+}
+
+fn check_program_impl(stream: TokenStream) -> Result<TokenStream, Error> {
+    let tokens = stream.into_iter().collect::<Vec<_>>();
+    let arguments = tokens.split(|token| match token {
+        TokenTree::Punct(punct) => punct.as_char() == ',',
+        _ => false,
+    }).filter(|argument| !argument.is_empty()).collect::<Vec<_>>();
+
+    let mut files = Vec::new();
+    let mut options: Vec<(String, String)> = Vec::new();
+    let mut defaults = Vec::new();
+    let mut features = Vec::new();
+    for argument in arguments.iter() {
+        match argument {
+            [value] if files.len() < 2 && options.is_empty() => {
+                files.push(string_literal(value).ok_or_else(|| Error::spanned(
+                    value.span(),
+                    format!("File name should be a string but `{}` provided", value),
+                ))?);
+            },
+            [TokenTree::Ident(ident), TokenTree::Punct(punct), TokenTree::Group(group)]
+                if (ident.to_string() == "defaults" || ident.to_string() == "features")
+                    && punct.as_char() == '='
+                    && group.delimiter() == proc_macro::Delimiter::Bracket => {
+                let items = group.stream().into_iter().filter(|token| match token {
+                    TokenTree::Punct(punct) => punct.as_char() != ',',
+                    _ => true,
+                });
+                for item in items {
+                    let value = string_literal(&item).ok_or_else(|| Error::spanned(
+                        item.span(),
+                        format!("Expected a string but `{}` provided", item),
+                    ))?;
+                    if ident.to_string() == "defaults" {
+                        defaults.push(value);
+                    } else {
+                        features.push(value);
+                    }
+                }
+            },
+            [TokenTree::Ident(ident), TokenTree::Punct(punct), value @ ..]
+                if ["uniforms", "attributes", "instances"].contains(&ident.to_string().as_str())
+                    && punct.as_char() == '='
+                    && !value.is_empty() => {
+                options.push((
+                    ident.to_string(),
+                    value.iter().map(|token| token.to_string()).collect::<Vec<_>>().join(""),
+                ));
+            },
+            _ => return Err(Error::spanned(argument[0].span(), format!(
+                "Unknown argument `{}`, expected `uniforms`, `attributes`, `instances`, `defaults` or `features`",
+                argument.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "),
+            ))),
+        }
+    }
+    if files.len() != 2 {
+        return Err(Error::InvalidArguments("Vertex and fragment shader file names expected".into()));
+    }
+    let option = |name: &str| options.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+
+    let root = Path::new(
+        &std::env::var("CARGO_MANIFEST_DIR")?
+    ).join("glsl");
+    let mut sources = Vec::new();
+    let mut dependencies = Vec::new();
+    for file in files.iter() {
+        let content = load_glsl_file(root.as_path(), root.join(file).as_path(), &features)?;
+        let mut source = String::new();
+        all_segments(&content.segments, &mut source);
+        sources.push(source);
+        for dependency in content.dependencies {
+            if dependencies.iter().all(|file: &Dependency| file.file != dependency.file) {
+                dependencies.push(dependency);
+            }
+        }
+    }
+
+    let mut checks = Vec::new();
+    if let Some(uniforms) = option("uniforms") {
+        let mut declared = glsl_declarations(&sources[0], false);
+        for declaration in glsl_declarations(&sources[1], false) {
+            if !declared.contains(&declaration) {
+                declared.push(declaration);
+            }
+        }
+        checks.push(format!(
+            r####"webgl_rc::program_check::check_uniforms(
+                &[{declared}],
+                <{uniforms} as webgl_rc::program_check::DeclaredUniforms>::UNIFORMS,
+                &[{defaults}],
+                r###"`{uniforms}`"###,
+                r###"`{vertex}` and `{fragment}`"###,
+            );"####,
+            declared = declared.join(""),
+            uniforms = uniforms,
+            defaults = defaults.iter().map(|name| format!(r###"r##"{}"##,"###, name)).collect::<Vec<_>>().join(""),
+            vertex = files[0],
+            fragment = files[1],
+        ));
+    }
+    if option("attributes").is_some() || option("instances").is_some() {
+        let declared = |name: &str| option(name).map_or("&[]".to_string(), |value| {
+            format!("<{} as webgl_rc::program_check::DeclaredAttributes>::ATTRIBUTES", value)
+        });
+        checks.push(format!(
+            r####"webgl_rc::program_check::check_attributes(
+                &[{declared}],
+                {attributes},
+                {instances},
+                r###"{structs}"###,
+                r###"`{vertex}`"###,
+            );"####,
+            declared = glsl_declarations(&sources[0], true).join(""),
+            attributes = declared("attributes"),
+            instances = declared("instances"),
+            structs = ["attributes", "instances"].iter()
+                .filter_map(|name| option(name))
+                .map(|value| format!("`{}`", value))
+                .collect::<Vec<_>>()
+                .join(" or "),
+            vertex = files[0],
+        ));
+    }
+
+    Ok(format!(
+        r#####"const _: () = {{ {dependencies} {checks} }};"#####,
+        dependencies = dependencies.iter().map(|dependency| {
+            format!(r##"const _: &[u8] = include_bytes!(r#"{file}"#);"##, file = dependency.file)
+        }).collect::<Vec<_>>().join(""),
+        checks = checks.join(""),
+    ).parse()?)
+}
+
+/// Compare the declarations of the shaders from the `glsl` folder with the derive structs
+/// and fail the build if they don't match.
+/// ```ignore
+/// webgl_rc::check_program!(
+///     "vertex.glsl",
+///     "fragment.glsl",
+///     uniforms = Uniforms,
+///     attributes = Attributes,
+///     instances = Instances,
+/// );
+/// ```
+///
+/// Every field should be declared in the shaders with the same type, and every shader input
+/// should be provided by a field. Uniforms set with `Program::set_default_uniform` are listed
+/// in `defaults = ["u_name"]`, automatic uniforms like `u_time` don't need a field. The `features`
+/// argument is the one of [load_glsl!], both branches of the `cfg` sections are checked.
+#[proc_macro]
+pub fn check_program(tokens: TokenStream) -> TokenStream {
+    check_program_impl(tokens).unwrap_or_else(|error| error.into())
+}
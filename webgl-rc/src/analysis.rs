@@ -0,0 +1,215 @@
+use std::cell::RefCell;
+
+use crate::post::{RenderTarget, ScreenPass};
+use crate::precision::ENCODE_FLOAT_GLSL;
+use crate::{Gl, GlError, Settings, Texture, TextureFilter, TextureType, Uniforms};
+
+const REDUCE_SHADER: &str = r#"
+    precision highp float;
+    uniform sampler2D u_source;
+    uniform vec2 u_size;
+    uniform bool u_first_pass;
+    uniform bool u_histogram;
+    uniform float u_first_bin;
+    uniform float u_bins;
+    uniform vec2 u_range;
+
+    float luminance(vec3 color) {
+        return dot(color, vec3(0.2126, 0.7152, 0.0722));
+    }
+
+    vec3 bins(float value) {
+        float position = clamp((value - u_range.x) / (u_range.y - u_range.x), 0.0, 0.99999);
+        return vec3(equal(vec3(floor(position * u_bins)), u_first_bin + vec3(0.0, 1.0, 2.0)));
+    }
+
+    void main() {
+        // Each texel reduces a 4x4 block, alpha is the covered part of the block,
+        // so the blocks on the right and top edges are weighted by their size
+        vec2 origin = floor(gl_FragCoord.xy) * 4.0;
+        vec3 sum = vec3(0.0);
+        float maximum = 0.0;
+        float weight = 0.0;
+        for (int y = 0; y < 4; y++) {
+            for (int x = 0; x < 4; x++) {
+                vec2 texel = origin + vec2(float(x), float(y));
+                if (texel.x < u_size.x && texel.y < u_size.y) {
+                    vec4 value = texture2D(u_source, (texel + 0.5) / u_size);
+                    if (u_first_pass) {
+                        float l = luminance(value.rgb);
+                        value = u_histogram ? vec4(bins(l), 1.0) : vec4(l, l, 0.0, 1.0);
+                    }
+                    sum += value.rgb * value.a;
+                    if (value.a > 0.0) {
+                        maximum = max(maximum, value.g);
+                    }
+                    weight += value.a;
+                }
+            }
+        }
+        vec3 average = sum / max(weight, 0.000001);
+        gl_FragColor = vec4(u_histogram ? average : vec3(average.r, maximum, 0.0), weight / 16.0);
+    }
+"#;
+
+const ENCODE_SHADER: &str = r#"
+    precision highp float;
+    uniform sampler2D u_source;
+
+    void main() {
+        vec4 value = texture2D(u_source, vec2(0.5));
+        float channel = gl_FragCoord.x < 1.0 ? value.r : gl_FragCoord.x < 2.0 ? value.g : value.b;
+        // Maps [0, inf) to [0, 1), the value is restored after reading
+        gl_FragColor = encode_float(channel / (1.0 + channel));
+    }
+"#;
+
+#[derive(Clone, Uniforms)]
+struct ReduceUniforms {
+    source: Texture,
+    size: [f32; 2],
+    first_pass: bool,
+    histogram: bool,
+    first_bin: f32,
+    bins: f32,
+    range: [f32; 2],
+}
+
+#[derive(Clone, Uniforms)]
+struct EncodeUniforms {
+    source: Texture,
+}
+
+/// Luminance statistics of a texture, see [TextureAnalyzer::luminance].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Luminance {
+    pub average: f32,
+    pub max: f32,
+}
+
+/// Computes luminance statistics and histograms of textures on the GPU, for auto-exposure
+/// and data visualization.
+///
+/// The texture is reduced by 4x4 blocks into float targets of the best
+/// [render precision](Gl::render_precision) until a single texel is left, only it's read back.
+/// With byte targets the values above 1 are clamped and the precision is 8-bit.
+/// Create the analyzer once and reuse it, it keeps its programs and intermediate targets.
+#[derive(Debug)]
+pub struct TextureAnalyzer {
+    gl: Gl,
+    reduce: ScreenPass,
+    encode: ScreenPass,
+    levels: RefCell<Vec<RenderTarget>>,
+    output: RenderTarget,
+}
+
+impl TextureAnalyzer {
+    pub fn new(gl: Gl) -> Result<TextureAnalyzer, GlError> {
+        Ok(TextureAnalyzer {
+            reduce: ScreenPass::new(gl.clone(), REDUCE_SHADER)?,
+            encode: ScreenPass::new(
+                gl.clone(),
+                &format!("{}{}", ENCODE_FLOAT_GLSL, ENCODE_SHADER),
+            )?,
+            levels: Default::default(),
+            output: RenderTarget::new(),
+            gl,
+        })
+    }
+
+    /// Average and maximum luminance of the RGB channels, in linear Rec. 709 weights.
+    pub fn luminance(&self, texture: &Texture) -> Result<Luminance, GlError> {
+        let [average, max, _] = self.reduce(texture, false, 0, 1, (0.0, 1.0))?;
+        Ok(Luminance { average, max })
+    }
+
+    /// Part of the texels with luminance in each of `bins` equal intervals of `[min, max]`,
+    /// values outside of the range are counted in the first and the last bins.
+    pub fn histogram(
+        &self,
+        texture: &Texture,
+        bins: u32,
+        min: f32,
+        max: f32,
+    ) -> Result<Vec<f32>, GlError> {
+        let mut result = Vec::with_capacity(bins as usize);
+        for first_bin in (0..bins).step_by(3) {
+            let values = self.reduce(texture, true, first_bin, bins, (min, max))?;
+            result.extend(values.iter().take((bins - first_bin) as usize));
+        }
+        Ok(result)
+    }
+
+    /// Reduce the texture to a single texel and read its RGB channels.
+    fn reduce(
+        &self,
+        texture: &Texture,
+        histogram: bool,
+        first_bin: u32,
+        bins: u32,
+        range: (f32, f32),
+    ) -> Result<[f32; 3], GlError> {
+        let gl = self.gl.clone();
+        let data_type = gl.render_precision().texture_type();
+        let mut input = texture.clone();
+        let mut level = 0;
+        loop {
+            let (width, height) = (
+                input.width().div_ceil(4).max(1),
+                input.height().div_ceil(4).max(1),
+            );
+            let (target, output) = {
+                let mut levels = self.levels.borrow_mut();
+                if levels.len() == level {
+                    levels.push(RenderTarget::new());
+                }
+                levels[level].get(&gl, width, height, data_type)?
+            };
+            gl.apply(
+                Gl::settings().texture_filter(input.clone(), TextureFilter::Nearest),
+                || {
+                    self.reduce.draw(
+                        Some(&target),
+                        &ReduceUniforms {
+                            source: input.clone(),
+                            size: [input.width() as f32, input.height() as f32],
+                            first_pass: level == 0,
+                            histogram,
+                            first_bin: first_bin as f32,
+                            bins: bins as f32,
+                            range: [range.0, range.1],
+                        },
+                    )
+                },
+            )?;
+            input = output;
+            level += 1;
+            if (width, height) == (1, 1) {
+                break;
+            }
+        }
+
+        let (target, output) = self.output.get(&gl, 3, 1, TextureType::Byte)?;
+        gl.apply(
+            Gl::settings().texture_filter(input.clone(), TextureFilter::Nearest),
+            || {
+                self.encode.draw(
+                    Some(&target),
+                    &EncodeUniforms {
+                        source: input.clone(),
+                    },
+                )
+            },
+        )?;
+
+        let mut result = [0.0; 3];
+        for (value, pixel) in result.iter_mut().zip(output.read_pixels_array()?.chunks(4)) {
+            let encoded = pixel
+                .iter()
+                .rev()
+                .fold(0.0, |result, value| (result + *value as f32) / 255.0);
+            *value = encoded / (1.0 - encoded);
+        }
+        Ok(result)
+    }
+}
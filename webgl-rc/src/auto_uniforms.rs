@@ -0,0 +1,70 @@
+use crate::settings::ViewportSetting;
+use crate::types::DataType;
+use crate::uniforms::UniformValue;
+
+/// Seconds since the first frame, `float`.
+pub const TIME: &str = "u_time";
+/// Seconds since the previous frame, `float`.
+pub const TIME_DELTA: &str = "u_time_delta";
+/// Index of the frame, from zero, `int` or `float`.
+pub const FRAME: &str = "u_frame";
+/// Size of the current viewport in pixels, `vec2`, or `vec3` with `z = 1` like in Shadertoy.
+pub const RESOLUTION: &str = "u_resolution";
+
+/// Frame values behind the automatic uniforms, see [Gl::set_auto_uniforms](crate::Gl::set_auto_uniforms).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameInfo {
+    pub time: f32,
+    pub time_delta: f32,
+    pub frame: u32,
+}
+
+/// Frame counter advanced by the render loop.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FrameClock {
+    /// Time of the first frame and of the last one, in milliseconds
+    times: Option<(f64, f64)>,
+    info: FrameInfo,
+}
+
+impl FrameClock {
+    pub fn info(&self) -> FrameInfo {
+        self.info
+    }
+
+    pub fn advance(&mut self, time: f64) {
+        match self.times {
+            None => {
+                self.times = Some((time, time));
+                self.info = FrameInfo::default();
+            }
+            Some((start, last)) => {
+                self.times = Some((start, time));
+                self.info = FrameInfo {
+                    time: ((time - start) / 1000.0) as f32,
+                    time_delta: ((time - last).max(0.0) / 1000.0) as f32,
+                    frame: self.info.frame.wrapping_add(1),
+                };
+            }
+        }
+    }
+}
+
+/// Value of a well-known uniform for the declared type, `None` for other uniforms.
+pub(crate) fn auto_uniform(
+    name: &str,
+    data_type: DataType,
+    frame: FrameInfo,
+    viewport: ViewportSetting,
+) -> Option<UniformValue> {
+    let (width, height) = (viewport.width as f32, viewport.height as f32);
+    match (name, data_type) {
+        (TIME, DataType::Float) => Some(UniformValue::Float(frame.time)),
+        (TIME_DELTA, DataType::Float) => Some(UniformValue::Float(frame.time_delta)),
+        (FRAME, DataType::Int) => Some(UniformValue::Int(frame.frame as i32)),
+        (FRAME, DataType::Float) => Some(UniformValue::Float(frame.frame as f32)),
+        (RESOLUTION, DataType::Vec2) => Some(UniformValue::Vec2([width, height])),
+        (RESOLUTION, DataType::Vec3) => Some(UniformValue::Vec3([width, height, 1.0])),
+        _ => None,
+    }
+}
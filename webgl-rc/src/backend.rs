@@ -0,0 +1,178 @@
+//! Backends execute the commands issued through [Gl](crate::Gl).
+//!
+//! Resources reference GL objects by ids assigned by `Gl`,
+//! each backend maps the ids to its own native objects.
+
+use std::fmt;
+
+use js_sys::Uint8Array;
+use wasm_bindgen::JsValue;
+use web_sys::{
+    AngleInstancedArrays, HtmlImageElement, HtmlVideoElement, ImageBitmap, WebGl2RenderingContext,
+    WebGlRenderingContext as Context,
+};
+
+use crate::extensions::Extension;
+use crate::recorder::Command;
+use crate::state::StateValue;
+use crate::types::DataType;
+use crate::{GlError, WebGlVersion};
+
+#[derive(Clone, Debug)]
+pub(crate) struct AttributeInfo {
+    pub name: String,
+    pub location: u32,
+    pub data_type: DataType,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct UniformInfo {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+/// Program parameters applied before linking.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LinkOptions {
+    pub attribute_locations: Vec<(String, u32)>,
+    /// Varyings captured by transform feedback with the buffer mode, WebGL2 only
+    pub transform_feedback: Option<(Vec<String>, u32)>,
+}
+
+/// Active attributes and uniforms of a linked program.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProgramLayout {
+    pub attributes: Vec<AttributeInfo>,
+    pub uniforms: Vec<UniformInfo>,
+}
+
+/// Initial texture content.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum TextureSource<'a> {
+    None,
+    Bytes(&'a [u8]),
+    Image(&'a HtmlImageElement),
+    /// Decoded image, see [decode_image](crate::decode_image)
+    ImageBitmap(&'a ImageBitmap),
+    /// Current frame of the video
+    Video(&'a HtmlVideoElement),
+}
+
+/// Pixels for partial texture updates, the element type should match the texture data type.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum PixelData<'a> {
+    Bytes(&'a [u8]),
+    Shorts(&'a [u16]),
+    Floats(&'a [f32]),
+}
+
+pub(crate) trait Backend: fmt::Debug {
+    /// Only object creation may fail, the other commands report errors through GL state.
+    fn execute(&self, command: &Command) -> Result<(), GlError>;
+
+    fn compile_shader(&self, id: u64, shader_type: u32, source: &str) -> Result<(), GlError>;
+
+    fn link_program(
+        &self,
+        id: u64,
+        vertex_shader: u64,
+        fragment_shader: u64,
+        options: &LinkOptions,
+    ) -> Result<ProgramLayout, GlError>;
+
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32);
+
+    /// Replace a part of the bound buffer, `offset` is in bytes.
+    fn buffer_sub_data(&self, target: u32, offset: usize, data: &[u8]);
+
+    /// Upload the whole level of the bound texture, `target` is `TEXTURE_2D` or a cube map face.
+    #[allow(clippy::too_many_arguments)]
+    fn tex_image(
+        &self,
+        target: u32,
+        format: u32,
+        width: u32,
+        height: u32,
+        data_type: u32,
+        source: TextureSource,
+    ) -> Result<(), GlError>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn tex_sub_image(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        format: u32,
+        data_type: u32,
+        data: PixelData,
+    ) -> Result<(), GlError>;
+
+    /// Read RGBA 8-bit pixels of the current frame buffer.
+    fn read_pixels(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        output: &mut [u8],
+    ) -> Result<(), GlError>;
+
+    fn read_pixels_into_buffer(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        output: &Uint8Array,
+    ) -> Result<(), GlError>;
+
+    fn frame_buffer_status(&self) -> u32;
+
+    /// `getParameter` result converted to the same kind of value as `cached`,
+    /// `None` if it can't be queried.
+    fn parameter(
+        &self,
+        _parameter: u32,
+        _texture_unit: Option<u32>,
+        _cached: &StateValue,
+    ) -> Option<StateValue> {
+        None
+    }
+
+    fn depth_texture_supported(&self) -> bool;
+
+    fn element_index_uint_supported(&self) -> bool;
+
+    /// Whether the extension is available, it's enabled by the check.
+    fn extension_supported(&self, name: &str) -> bool;
+
+    /// Extension object, requested on the first call.
+    fn extension(&self, _extension: Extension) -> Option<&JsValue> {
+        None
+    }
+
+    fn version(&self) -> WebGlVersion;
+
+    /// Size of the drawing buffer when the context is created, it's the initial viewport
+    fn drawing_buffer_size(&self) -> (i32, i32);
+
+    fn is_context_lost(&self) -> bool;
+
+    /// Lose or restore the context, like the browser does when the GPU is reset
+    fn simulate_context_loss(&self, lost: bool) -> Result<(), GlError>;
+
+    /// Underlying WebGL context, if there is one
+    fn context(&self) -> Option<&Context> {
+        None
+    }
+
+    fn webgl2_context(&self) -> Option<&WebGl2RenderingContext> {
+        None
+    }
+
+    fn instanced_arrays(&self) -> Option<&AngleInstancedArrays> {
+        None
+    }
+}
@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+
+use crate::data_buffer::{DynamicLayout, RawItemsBuffer};
+use crate::types::DataType;
+use crate::uniforms::{unprefixed, Field, UniformValue, Uniforms};
+use crate::{BufferUsage, DrawParams, GlError, Program};
+
+/// Draws many objects sharing a program and vertices, each one with its own small set
+/// of uniforms, like the positions and colors of a crowd.
+///
+/// Fields of the object uniforms matching a float instance attribute of the vertex shader,
+/// `u_color` and `attribute vec4 i_color`, are packed into an instance buffer instead of
+/// being set for every object. The other fields, like textures or integers, stay uniforms,
+/// consecutive objects with equal values of them are drawn with a single instanced draw call.
+/// Without any packed field every object is drawn with its own uniforms.
+///
+/// ```ignore
+/// let batch = UniformBatch::new(program);
+/// batch.draw(
+///     DrawParams::new(PrimitiveType::Triangles, &camera.uniforms(), &mesh),
+///     &soldiers,
+/// )?;
+/// ```
+#[derive(Debug)]
+pub struct UniformBatch {
+    program: Program,
+    instances: RefCell<Option<RawItemsBuffer>>,
+}
+
+impl UniformBatch {
+    pub fn new(program: Program) -> UniformBatch {
+        UniformBatch {
+            program,
+            instances: Default::default(),
+        }
+    }
+
+    pub fn program(&self) -> Program {
+        self.program.clone()
+    }
+
+    /// Draw the objects in order, `params` uniforms are shared by all of them
+    /// and its instances and range are ignored.
+    pub fn draw<U: Uniforms>(&self, params: DrawParams, objects: &[U]) -> Result<(), GlError> {
+        let objects: Vec<Vec<Field>> = objects.iter().map(Uniforms::uniforms).collect();
+        let first = match objects.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        let packed: Vec<Option<(String, DataType)>> = first
+            .iter()
+            .map(|field| self.instance_attribute(field))
+            .collect();
+        let layout = packed
+            .iter()
+            .flatten()
+            .fold(DynamicLayout::new(), |layout, (name, data_type)| {
+                layout.attribute(name, *data_type)
+            });
+
+        let shared = params.uniforms;
+        if layout.stride() == 0 {
+            for object in objects.iter() {
+                self.program.draw(DrawParams {
+                    uniforms: &(shared, &object[..]),
+                    instances: None,
+                    ..params.clone()
+                })?;
+            }
+            return Ok(());
+        }
+
+        let mut data = Vec::with_capacity(layout.stride() * objects.len());
+        let mut uniforms: Vec<Vec<Field>> = Vec::with_capacity(objects.len());
+        for object in objects.iter() {
+            let mut rest = Vec::new();
+            for (field, packed) in object.iter().zip(packed.iter()) {
+                match packed {
+                    Some((name, data_type)) => match float_values(&field.value)
+                        .filter(|values| Some(values.len()) == data_type.size_in_floats())
+                    {
+                        Some(values) => data.extend_from_slice(values),
+                        None => {
+                            return Err(GlError::UnsupportedType(Some(format!(
+                                "Uniform {} can't be packed into {} attribute {}, it's {:?}",
+                                field.name, data_type, name, field.value
+                            ))))
+                        }
+                    },
+                    None => rest.push(field.clone()),
+                }
+            }
+            uniforms.push(rest);
+        }
+        let instances = self.instances(layout, &data)?;
+
+        // Runs of objects sharing the values of the fields left as uniforms
+        let mut start = 0;
+        while start < objects.len() {
+            let end = (start + 1..objects.len())
+                .find(|i| uniforms[*i] != uniforms[start])
+                .unwrap_or(objects.len());
+            self.program.draw(DrawParams {
+                uniforms: &(shared, &uniforms[start][..]),
+                instances: Some(&instances),
+                range: Some(start..end),
+                ..params.clone()
+            })?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Instance attribute of the program the field can be packed into.
+    fn instance_attribute(&self, field: &Field) -> Option<(String, DataType)> {
+        let name = format!("i_{}", unprefixed(&field.name));
+        let data_type = self.program.attribute_type(&name)?;
+        let size = float_values(&field.value)?.len();
+        (data_type.size_in_floats() == Some(size)).then_some((name, data_type))
+    }
+
+    /// Instance buffer with the data, reused while the layout stays the same.
+    fn instances(&self, layout: DynamicLayout, data: &[f32]) -> Result<RawItemsBuffer, GlError> {
+        let mut instances = self.instances.borrow_mut();
+        match instances.as_ref() {
+            Some(buffer) if *buffer.layout() == layout => {
+                buffer.set_content(data, BufferUsage::Stream)?;
+            }
+            _ => {
+                *instances = Some(self.program.gl().raw_items_buffer(
+                    layout,
+                    data,
+                    BufferUsage::Stream,
+                )?);
+            }
+        }
+        Ok(instances.clone().unwrap())
+    }
+}
+
+fn float_values(value: &UniformValue) -> Option<&[f32]> {
+    match value {
+        UniformValue::Float(value) => Some(std::slice::from_ref(value)),
+        UniformValue::Vec2(value) => Some(value),
+        UniformValue::Vec3(value) => Some(value),
+        UniformValue::Vec4(value) => Some(value),
+        _ => None,
+    }
+}
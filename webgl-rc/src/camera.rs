@@ -0,0 +1,166 @@
+use crate::matrix::{look_at, multiply, orthographic, perspective, Mat4, IDENTITY};
+use crate::uniforms::{Field, UniformValue, Uniforms};
+use crate::Gl;
+
+/// How a [Camera] maps the view space to the viewport.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Orthographic projection in pixels with `(0, 0)` at the top left corner
+    /// and `y` growing down, like DOM coordinates. Visible depth is `[-1, 1]`.
+    Pixels,
+    /// Perspective with the vertical field of view in radians.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+}
+
+/// View and projection matrices for drawing without a math crate.
+///
+/// The projection follows the viewport size, resize the camera with the canvas,
+/// like in [WorkerScene::resize](crate::WorkerScene::resize), or call
+/// [Camera::fit_viewport] before drawing. Matrices are column-major, like GLSL ones.
+///
+/// ```ignore
+/// let mut camera = Camera::perspective(std::f32::consts::FRAC_PI_4, width, height);
+/// camera.look_at([0.0, 2.0, 5.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+/// program.draw_arrays(PrimitiveType::Triangles, &(camera.uniforms(), &material), &mesh)?;
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    projection: Projection,
+    view: Mat4,
+    width: u32,
+    height: u32,
+}
+
+impl Camera {
+    pub fn new(projection: Projection, width: u32, height: u32) -> Camera {
+        Camera {
+            projection,
+            view: IDENTITY,
+            width,
+            height,
+        }
+    }
+
+    /// 2D camera in pixels, see [Projection::Pixels].
+    pub fn pixels(width: u32, height: u32) -> Camera {
+        Camera::new(Projection::Pixels, width, height)
+    }
+
+    /// 3D camera at the origin looking along `-Z`, visible from `0.1` to `1000` units.
+    pub fn perspective(fov_y: f32, width: u32, height: u32) -> Camera {
+        Camera::new(
+            Projection::Perspective {
+                fov_y,
+                near: 0.1,
+                far: 1000.0,
+            },
+            width,
+            height,
+        )
+    }
+
+    pub fn projection_kind(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Width divided by height, `1` for an empty viewport.
+    pub fn aspect(&self) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            1.0
+        } else {
+            self.width as f32 / self.height as f32
+        }
+    }
+
+    /// Size of the viewport in pixels, the view is kept.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Resize to the viewport of the currently applied settings.
+    pub fn fit_viewport(&mut self, gl: &Gl) {
+        let viewport = gl.viewport();
+        self.resize(viewport.width.max(0) as u32, viewport.height.max(0) as u32);
+    }
+
+    /// Place the camera at `eye` looking at `target`.
+    pub fn look_at(&mut self, eye: [f32; 3], target: [f32; 3], up: [f32; 3]) {
+        self.view = look_at(eye, target, up);
+    }
+
+    pub fn view(&self) -> [f32; 16] {
+        self.view
+    }
+
+    pub fn set_view(&mut self, view: [f32; 16]) {
+        self.view = view;
+    }
+
+    pub fn projection(&self) -> [f32; 16] {
+        match self.projection {
+            Projection::Pixels => orthographic(
+                0.0,
+                self.width.max(1) as f32,
+                self.height.max(1) as f32,
+                0.0,
+                -1.0,
+                1.0,
+            ),
+            Projection::Perspective { fov_y, near, far } => {
+                perspective(fov_y, self.aspect(), near, far)
+            }
+        }
+    }
+
+    pub fn view_projection(&self) -> [f32; 16] {
+        multiply(&self.projection(), &self.view)
+    }
+
+    pub fn uniforms(&self) -> CameraUniforms {
+        CameraUniforms {
+            view: self.view,
+            projection: self.projection(),
+            view_projection: self.view_projection(),
+        }
+    }
+}
+
+/// Uniforms of a [Camera], combine them with the scene ones using a tuple:
+///
+/// * `u_view: mat4`
+/// * `u_projection: mat4`
+/// * `u_view_projection: mat4` - projection after the view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraUniforms {
+    pub view: [f32; 16],
+    pub projection: [f32; 16],
+    pub view_projection: [f32; 16],
+}
+
+impl Uniforms for CameraUniforms {
+    fn uniforms(&self) -> Vec<Field> {
+        vec![
+            Field {
+                name: "u_view".into(),
+                value: UniformValue::Mat4(self.view),
+            },
+            Field {
+                name: "u_projection".into(),
+                value: UniformValue::Mat4(self.projection),
+            },
+            Field {
+                name: "u_view_projection".into(),
+                value: UniformValue::Mat4(self.view_projection),
+            },
+        ]
+    }
+}
@@ -0,0 +1,250 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data_buffer::{AttributeFormat, Writable};
+use crate::types::{DataType, TypeMark};
+use crate::uniforms::{IntoUniform, UniformValue};
+
+/// GLSL helpers for colors passed as [Color] attributes, which carry sRGB bytes.
+pub const COLOR_GLSL: &str = r#"
+    vec3 srgb_to_linear(vec3 srgb) {
+        return mix(
+            srgb / 12.92,
+            pow((srgb + 0.055) / 1.055, vec3(2.4)),
+            step(vec3(0.04045), srgb)
+        );
+    }
+    vec4 srgb_to_linear(vec4 srgb) {
+        return vec4(srgb_to_linear(srgb.rgb), srgb.a);
+    }
+    vec3 linear_to_srgb(vec3 linear) {
+        return mix(
+            linear * 12.92,
+            1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055,
+            step(vec3(0.0031308), linear)
+        );
+    }
+    vec4 linear_to_srgb(vec4 linear) {
+        return vec4(linear_to_srgb(linear.rgb), linear.a);
+    }
+"#;
+
+/// sRGB component to the linear one, both in `[0, 1]`.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Linear component to the sRGB one, both in `[0, 1]`.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// RGBA color stored in the linear space with straight alpha.
+///
+/// Colors coming from CSS, images and color pickers are sRGB, so they are converted
+/// by the constructors. As a uniform it's a linear `vec4`, lighting and blending
+/// in the shaders should be done in the linear space. As an attribute it's four
+/// normalized sRGB bytes, 4 bytes instead of 16, convert it with [COLOR_GLSL].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::linear(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::linear(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Color = Color::linear(1.0, 1.0, 1.0, 1.0);
+
+    pub const fn linear(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Color from sRGB components in `[0, 1]`, alpha is linear.
+    pub fn srgb(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color::linear(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    /// Color from sRGB bytes, like the CSS `rgba(r, g, b, a / 255)`.
+    pub fn srgb8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        let unit = |value: u8| value as f32 / 255.0;
+        Color::srgb(unit(r), unit(g), unit(b), unit(a))
+    }
+
+    /// Parse `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, the `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<Color, ParseColorError> {
+        let error = || ParseColorError(hex.into());
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(error());
+        }
+        let byte = |i: usize, width: usize| -> Result<u8, ParseColorError> {
+            let value =
+                u8::from_str_radix(&digits[i * width..(i + 1) * width], 16).map_err(|_| error())?;
+            Ok(if width == 1 { value * 17 } else { value })
+        };
+        let (width, components) = match digits.len() {
+            3 => (1, 3),
+            4 => (1, 4),
+            6 => (2, 3),
+            8 => (2, 4),
+            _ => return Err(error()),
+        };
+        let alpha = if components == 4 {
+            byte(3, width)?
+        } else {
+            255
+        };
+        Ok(Color::srgb8(
+            byte(0, width)?,
+            byte(1, width)?,
+            byte(2, width)?,
+            alpha,
+        ))
+    }
+
+    pub fn with_alpha(self, a: f32) -> Color {
+        Color { a, ..self }
+    }
+
+    pub fn to_linear(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// sRGB components, the values to clear an sRGB canvas with.
+    pub fn to_srgb(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    pub fn to_srgb8(self) -> [u8; 4] {
+        let [r, g, b, a] = self.to_srgb();
+        let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [byte(r), byte(g), byte(b), byte(a)]
+    }
+
+    /// `#rrggbbaa` of the sRGB bytes.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_srgb8();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(value: &str) -> Result<Color, ParseColorError> {
+        Color::from_hex(value.trim())
+    }
+}
+
+/// The string isn't a hex color.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseColorError(pub String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid hex color {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl TypeMark for Color {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec4);
+}
+
+impl IntoUniform for Color {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::Vec4(self.to_linear())
+    }
+}
+
+impl From<Color> for UniformValue {
+    fn from(color: Color) -> Self {
+        color.into_uniform()
+    }
+}
+
+impl Writable for Color {
+    fn write(&self, output: &mut Vec<f32>) {
+        output.push(f32::from_bits(u32::from_le_bytes(self.to_srgb8())));
+    }
+    fn stride() -> usize {
+        1
+    }
+    fn format() -> AttributeFormat {
+        AttributeFormat::UnsignedByteNormalized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(hex: &str) -> [u8; 4] {
+        Color::from_hex(hex).unwrap().to_srgb8()
+    }
+
+    #[test]
+    fn parse_short_hex() {
+        assert_eq!(bytes("#f80"), [0xff, 0x88, 0x00, 0xff]);
+        assert_eq!(bytes("f80"), [0xff, 0x88, 0x00, 0xff]);
+        assert_eq!(bytes("#F80c"), [0xff, 0x88, 0x00, 0xcc]);
+    }
+
+    #[test]
+    fn parse_long_hex() {
+        assert_eq!(bytes("#1a2b3c"), [0x1a, 0x2b, 0x3c, 0xff]);
+        assert_eq!(bytes("#1A2B3C80"), [0x1a, 0x2b, 0x3c, 0x80]);
+        assert_eq!(" #000000 ".parse::<Color>(), Ok(Color::BLACK));
+        assert_eq!(
+            "#ffffff00".parse::<Color>(),
+            Ok(Color::WHITE.with_alpha(0.0))
+        );
+    }
+
+    #[test]
+    fn round_trip_hex() {
+        for hex in ["#00000000", "#1a2b3c4d", "#7f7f7f7f", "#ffffffff"] {
+            assert_eq!(Color::from_hex(hex).unwrap().to_hex(), hex);
+        }
+    }
+
+    #[test]
+    fn invalid_hex_is_rejected() {
+        for hex in [
+            "",
+            "#",
+            "#f",
+            "#ff",
+            "#fffff",
+            "#fffffff",
+            "#fffffffff",
+            "#ggg",
+            "#+ff",
+            "##fff",
+            "#ff ff",
+            "#éé",
+            "red",
+        ] {
+            assert_eq!(Color::from_hex(hex), Err(ParseColorError(hex.into())));
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::data_buffer::{ArrayBuffer, DynamicLayout, VertexSource};
+use crate::element_buffer::ElementsBuffer;
+use crate::gl::{Gl, GlError};
+use crate::presets::Preset;
+use crate::program::{DrawParams, PrimitiveType, Program};
+use crate::settings::Settings;
+use crate::uniforms::{Field, UniformValue, Uniforms};
+
+/// Vertex source kept by a queued draw.
+#[derive(Clone)]
+struct Source(Rc<dyn VertexSource>);
+
+impl VertexSource for Source {
+    fn array_buffer(&self) -> &ArrayBuffer {
+        self.0.array_buffer()
+    }
+
+    fn layout(&self) -> Cow<'_, DynamicLayout> {
+        self.0.layout()
+    }
+
+    fn first(&self) -> usize {
+        self.0.first()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Draw queued in a [CommandBuffer], it owns everything needed to submit it later.
+///
+/// ```ignore
+/// let draw = DrawCommand::new(&program, PrimitiveType::Triangles, &uniforms, &attributes)
+///     .elements(&elements)
+///     .preset("transparent");
+/// ```
+#[derive(Clone)]
+pub struct DrawCommand {
+    program: Program,
+    primitive_type: PrimitiveType,
+    uniforms: Vec<Field>,
+    attributes: Source,
+    elements: Option<ElementsBuffer>,
+    element_range: Option<Range<usize>>,
+    instances: Option<Source>,
+    preset: Option<Preset>,
+    layer: i32,
+}
+
+impl DrawCommand {
+    pub fn new<A: VertexSource + Clone + 'static, U: Uniforms + ?Sized>(
+        program: &Program,
+        primitive_type: PrimitiveType,
+        uniforms: &U,
+        attributes: &A,
+    ) -> DrawCommand {
+        DrawCommand {
+            program: program.clone(),
+            primitive_type,
+            uniforms: uniforms.uniforms(),
+            attributes: Source(Rc::new(attributes.clone())),
+            elements: None,
+            element_range: None,
+            instances: None,
+            preset: None,
+            layer: 0,
+        }
+    }
+
+    pub fn elements(mut self, elements: &ElementsBuffer) -> DrawCommand {
+        self.elements = Some(elements.clone());
+        self
+    }
+
+    /// Draw only the indices in the range, see [DrawParams::element_range].
+    pub fn element_range(mut self, range: Range<usize>) -> DrawCommand {
+        self.element_range = Some(range);
+        self
+    }
+
+    pub fn instances<I: VertexSource + Clone + 'static>(mut self, instances: &I) -> DrawCommand {
+        self.instances = Some(Source(Rc::new(instances.clone())));
+        self
+    }
+
+    /// Draw with the preset registered with [Gl::register_preset].
+    /// Unknown presets are reported and the draw uses the current settings.
+    pub fn preset(mut self, name: &str) -> DrawCommand {
+        self.preset = self.program.gl().preset(name);
+        if self.preset.is_none() {
+            trace_event!(ERROR, name, "unknown settings preset");
+            debug_assert!(false, "unknown settings preset {:?}", name);
+        }
+        self
+    }
+
+    /// Draw with the settings, draws with equal settings are grouped by their `Debug` output.
+    pub fn settings(mut self, settings: impl Settings + 'static) -> DrawCommand {
+        self.preset = Some(Preset::new(&format!("{:?}", settings), settings));
+        self
+    }
+
+    /// Layers are submitted in the ascending order, draws are sorted only within a layer.
+    /// Use them when the order matters, like for transparent geometry drawn after the opaque one.
+    pub fn layer(mut self, layer: i32) -> DrawCommand {
+        self.layer = layer;
+        self
+    }
+
+    fn textures(&self) -> Vec<u64> {
+        self.uniforms
+            .iter()
+            .filter_map(|field| match &field.value {
+                UniformValue::Texture(texture) => Some(texture.id()),
+                UniformValue::CubeMap(cube_map) => Some(cube_map.id()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn preset_name(&self) -> &str {
+        self.preset.as_ref().map(Preset::name).unwrap_or("")
+    }
+
+    /// Draws in the same group are submitted under a single program and preset.
+    fn same_group(&self, other: &DrawCommand) -> bool {
+        self.layer == other.layer
+            && self.program.id() == other.program.id()
+            && self.preset_name() == other.preset_name()
+    }
+
+    fn draw(&self) -> Result<(), GlError> {
+        self.program.draw(DrawParams {
+            instances: self.instances.as_ref().map(|v| v as &dyn VertexSource),
+            elements: self.elements.as_ref(),
+            element_range: self.element_range.clone(),
+            ..DrawParams::new(self.primitive_type, &&self.uniforms[..], &self.attributes)
+        })
+    }
+}
+
+impl fmt::Debug for DrawCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrawCommand")
+            .field("program", &self.program.id())
+            .field("primitive_type", &self.primitive_type)
+            .field("attributes", &self.attributes.array_buffer().id())
+            .field("elements", &self.elements.as_ref().map(ElementsBuffer::id))
+            .field("element_range", &self.element_range)
+            .field(
+                "instances",
+                &self.instances.as_ref().map(|v| v.array_buffer().id()),
+            )
+            .field("preset", &self.preset.as_ref().map(Preset::name))
+            .field("layer", &self.layer)
+            .finish()
+    }
+}
+
+/// Draws queued during the frame and submitted together, sorted by layer, program,
+/// settings and textures, so scenes with many different draws switch the state less often.
+///
+/// Draws in a layer are reordered, so the ones depending on the order,
+/// like blended geometry, should be put to separate layers.
+/// Program and settings are set once for the draws sharing them, with [ApplyStrategy::Diff](crate::ApplyStrategy::Diff)
+/// the textures are also bound only when they change.
+pub struct CommandBuffer {
+    gl: Gl,
+    draws: Vec<DrawCommand>,
+}
+
+impl CommandBuffer {
+    pub fn new(gl: &Gl) -> CommandBuffer {
+        CommandBuffer {
+            gl: gl.clone(),
+            draws: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, draw: DrawCommand) {
+        self.draws.push(draw);
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Drop the queued draws without submitting them.
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    /// Sort and draw the queued draws, the buffer is empty afterwards.
+    /// Stops at the first failed draw, the rest are dropped.
+    pub fn submit(&mut self) -> Result<(), GlError> {
+        let mut draws: Vec<(Vec<u64>, DrawCommand)> = self
+            .draws
+            .drain(..)
+            .map(|draw| (draw.textures(), draw))
+            .collect();
+        // Stable, so the draws sharing the whole key keep the queued order
+        draws.sort_by(|(a_textures, a), (b_textures, b)| {
+            (a.layer, a.program.id(), a.preset_name(), a_textures).cmp(&(
+                b.layer,
+                b.program.id(),
+                b.preset_name(),
+                b_textures,
+            ))
+        });
+        let _span = trace_span!(DEBUG, "submit", draws = draws.len());
+
+        let gl = &self.gl;
+        let mut rest = &draws[..];
+        while let Some((_, first)) = rest.first() {
+            let count = rest
+                .iter()
+                .take_while(|(_, draw)| draw.same_group(first))
+                .count();
+            let (group, tail) = rest.split_at(count);
+            rest = tail;
+
+            // Nested draws find the program, its attributes and the preset already applied
+            let draw_group = || {
+                first
+                    .program
+                    .enable_attributes(|| group.iter().try_for_each(|(_, draw)| draw.draw()))
+            };
+            gl.apply(
+                Gl::settings().program(first.program.clone()),
+                || match &first.preset {
+                    Some(preset) => gl.apply(preset.clone(), draw_group),
+                    None => draw_group(),
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CommandBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandBuffer")
+            .field("draws", &self.draws)
+            .finish()
+    }
+}
@@ -0,0 +1,301 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use web_sys::{HtmlImageElement, WebGlRenderingContext as Context};
+
+use super::backend::TextureSource;
+use super::gl::{ErrorContext, Gl, GlError};
+use super::matrix::perspective;
+use super::registry::{write_name, Resource, ResourceInfo, ResourceKind};
+use super::settings::Settings;
+use super::texture::{TextureFilter, TextureFormat, TextureType};
+use crate::Command;
+
+/// Face of a cube map, in the order of the GL face targets.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PositiveX = Context::TEXTURE_CUBE_MAP_POSITIVE_X,
+    NegativeX = Context::TEXTURE_CUBE_MAP_NEGATIVE_X,
+    PositiveY = Context::TEXTURE_CUBE_MAP_POSITIVE_Y,
+    NegativeY = Context::TEXTURE_CUBE_MAP_NEGATIVE_Y,
+    PositiveZ = Context::TEXTURE_CUBE_MAP_POSITIVE_Z,
+    NegativeZ = Context::TEXTURE_CUBE_MAP_NEGATIVE_Z,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Direction to the face center, and directions of the face `s` and `t` texture axes.
+    /// A face pixel at `(s, t)` in `[-1, 1]` is sampled by `forward + s * right + t * up`.
+    pub fn axes(self) -> [[f32; 3]; 3] {
+        match self {
+            CubeFace::PositiveX => [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, -1.0, 0.0]],
+            CubeFace::NegativeX => [[-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, -1.0, 0.0]],
+            CubeFace::PositiveY => [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+            CubeFace::NegativeY => [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+            CubeFace::PositiveZ => [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]],
+            CubeFace::NegativeZ => [[0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]],
+        }
+    }
+
+    /// View matrix looking from `position` through the face, column-major.
+    /// Combined with [CubeFace::projection] it maps the face directions to the whole viewport.
+    pub fn view_matrix(self, position: [f32; 3]) -> [f32; 16] {
+        let [forward, right, up] = self.axes();
+        let dot = |a: [f32; 3]| -(a[0] * position[0] + a[1] * position[1] + a[2] * position[2]);
+        [
+            right[0],
+            up[0],
+            -forward[0],
+            0.0, //
+            right[1],
+            up[1],
+            -forward[1],
+            0.0, //
+            right[2],
+            up[2],
+            -forward[2],
+            0.0, //
+            dot(right),
+            dot(up),
+            -dot(forward),
+            1.0,
+        ]
+    }
+
+    /// Square perspective projection with the 90° field of view, shared by all faces.
+    pub fn projection(near: f32, far: f32) -> [f32; 16] {
+        perspective(std::f32::consts::FRAC_PI_2, 1.0, near, far)
+    }
+}
+
+struct CubeMapInfo {
+    gl: Gl,
+    id: u64,
+    size: u32,
+    data_type: TextureType,
+    format: TextureFormat,
+    filter: Cell<TextureFilter>,
+    label: RefCell<Option<String>>,
+}
+
+impl PartialEq<CubeMapInfo> for CubeMapInfo {
+    fn eq(&self, other: &CubeMapInfo) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for CubeMapInfo {}
+
+impl fmt::Debug for CubeMapInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CubeMapInfo")
+            .field("id", &self.id)
+            .field("size", &self.size)
+            .field("data_type", &self.data_type)
+            .field("format", &self.format)
+            .field("filter", &self.filter)
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+impl Drop for CubeMapInfo {
+    fn drop(&mut self) {
+        trace_event!(DEBUG, id = self.id, "delete cube map");
+        self.gl.unregister(self.id);
+        self.gl.delete(Command::DeleteTexture { id: self.id });
+    }
+}
+
+impl Resource for CubeMapInfo {
+    fn info(&self) -> ResourceInfo {
+        ResourceInfo {
+            id: self.id,
+            kind: ResourceKind::CubeMap,
+            size: (6 * self.size * self.size * self.format.channels() * self.data_type.size())
+                as usize,
+            label: self.label.borrow().clone(),
+        }
+    }
+}
+
+/// Texture of six square faces, sampled by direction with `samplerCube`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CubeMap {
+    data: Rc<CubeMapInfo>,
+}
+
+impl fmt::Display for CubeMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.data.label.borrow();
+        write_name(f, ResourceKind::CubeMap, self.data.id, label.as_deref())
+    }
+}
+
+impl CubeMap {
+    /// Create a cube map with uninitialized faces of `size × size` pixels.
+    pub fn new(
+        gl: Gl,
+        size: u32,
+        data_type: TextureType,
+        format: TextureFormat,
+    ) -> Result<CubeMap, GlError> {
+        if format.is_depth() {
+            return Err(GlError::UnsupportedType(Some(format!(
+                "Depth format {:?} is not supported by cube maps",
+                format
+            ))));
+        }
+        format.validate(data_type, gl.version())?;
+
+        let id = gl
+            .create_object(|id| Command::CreateTexture { id })
+            .map_err(|_| GlError::UnknownError(Some("Cube map creation failed".into())))?;
+        trace_event!(DEBUG, id, size, ?data_type, ?format, "create cube map");
+
+        let result = CubeMap {
+            data: Rc::new(CubeMapInfo {
+                gl: gl.clone(),
+                id,
+                size,
+                data_type,
+                format,
+                filter: Default::default(),
+                label: Default::default(),
+            }),
+        };
+        gl.register(id, &result.data);
+
+        gl.apply(
+            Gl::settings().active_texture(0).cube_map(0, result.clone()),
+            || {
+                CubeMap::set_parameter(&gl, Context::TEXTURE_WRAP_S, Context::CLAMP_TO_EDGE as i32);
+                CubeMap::set_parameter(&gl, Context::TEXTURE_WRAP_T, Context::CLAMP_TO_EDGE as i32);
+                CubeMap::set_parameter(
+                    &gl,
+                    Context::TEXTURE_MAG_FILTER,
+                    TextureFilter::default().into(),
+                );
+                CubeMap::set_parameter(
+                    &gl,
+                    Context::TEXTURE_MIN_FILTER,
+                    TextureFilter::default().into(),
+                );
+            },
+        );
+
+        for face in CubeFace::ALL {
+            result.write(face, TextureSource::None)?;
+        }
+
+        Ok(result)
+    }
+
+    fn set_parameter(gl: &Gl, parameter: u32, value: i32) {
+        gl.execute(Command::TexParameter {
+            target: Context::TEXTURE_CUBE_MAP,
+            parameter,
+            value,
+        });
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.data.gl.clone()
+    }
+
+    pub fn id(&self) -> u64 {
+        self.data.id
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.label.borrow().clone()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.data.label.replace(Some(label.into()));
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
+    /// Width and height of each face.
+    pub fn size(&self) -> u32 {
+        self.data.size
+    }
+    pub fn data_type(&self) -> TextureType {
+        self.data.data_type
+    }
+    pub fn format(&self) -> TextureFormat {
+        self.data.format
+    }
+
+    pub fn filter(&self) -> TextureFilter {
+        self.data.filter.get()
+    }
+
+    pub fn set_filter(&self, filter: TextureFilter) {
+        if self.filter() != filter {
+            let gl = &self.data.gl;
+            gl.apply(
+                Gl::settings().active_texture(0).cube_map(0, self.clone()),
+                || {
+                    CubeMap::set_parameter(gl, Context::TEXTURE_MAG_FILTER, filter.into());
+                    CubeMap::set_parameter(gl, Context::TEXTURE_MIN_FILTER, filter.into());
+                    self.data.filter.set(filter);
+                },
+            );
+        }
+    }
+
+    pub fn write_face_image(
+        &self,
+        face: CubeFace,
+        image: &HtmlImageElement,
+    ) -> Result<(), GlError> {
+        self.write(face, TextureSource::Image(image))
+    }
+
+    /// Write the whole face, the data should match the cube map format and type.
+    pub fn write_face_bytes(&self, face: CubeFace, bytes: &[u8]) -> Result<(), GlError> {
+        let expected =
+            self.size() * self.size() * self.format().channels() * self.data_type().size();
+        if bytes.len() as u32 != expected {
+            return Err(GlError::InvalidBufferSize {
+                expected,
+                received: bytes.len() as u32,
+            });
+        }
+        self.write(face, TextureSource::Bytes(bytes))
+    }
+
+    fn write(&self, face: CubeFace, source: TextureSource) -> Result<(), GlError> {
+        let gl = self.gl();
+        gl.apply(
+            Gl::settings().active_texture(0).cube_map(0, self.clone()),
+            || {
+                gl.tex_image(
+                    face.into(),
+                    self.format().into(),
+                    self.size(),
+                    self.size(),
+                    self.data_type().into(),
+                    source,
+                )
+            },
+        )
+        .with_context(|| format!("uploading {:?} face of {}", face, self))
+    }
+}
@@ -0,0 +1,113 @@
+use crate::matrix::multiply;
+use crate::{
+    ClearParams, CubeFace, CubeMap, DepthBuffer, DepthBufferFormat, FrameBuffer, Gl, GlError,
+    Settings, TextureFormat, TextureType,
+};
+
+/// Matrices of a cube map face rendered by [CubeMapTarget::render], column-major.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CubeFaceView {
+    pub face: CubeFace,
+    pub view: [f32; 16],
+    pub projection: [f32; 16],
+    pub view_projection: [f32; 16],
+}
+
+/// Cube map render target with a shared depth buffer, for dynamic environment maps
+/// like reflection probes. The scene is drawn once per face from the probe position:
+///
+/// ```ignore
+/// probe.render([0.0, 1.0, 0.0], 0.1, 100.0, |view| {
+///     scene.draw(&view.view, &view.projection)
+/// })?;
+/// let reflections = probe.cube_map();
+/// ```
+#[derive(Clone, Debug)]
+pub struct CubeMapTarget {
+    gl: Gl,
+    cube_map: CubeMap,
+    frame_buffer: FrameBuffer,
+}
+
+impl CubeMapTarget {
+    /// RGBA cube map with faces of `size` pixels, float types need color buffer float support.
+    pub fn new(gl: Gl, size: u32, data_type: TextureType) -> Result<CubeMapTarget, GlError> {
+        let cube_map = gl.cube_map(size, data_type, TextureFormat::Rgba)?;
+        let depth = gl.depth_buffer(size, size, DepthBufferFormat::Depth16)?;
+        CubeMapTarget::with_depth_buffer(cube_map, depth)
+    }
+
+    /// Render into an existing cube map, the depth buffer should have the face size.
+    pub fn with_depth_buffer(
+        cube_map: CubeMap,
+        depth: DepthBuffer,
+    ) -> Result<CubeMapTarget, GlError> {
+        let gl = cube_map.gl();
+        let mut frame_buffer = gl.frame_buffer()?;
+        frame_buffer.set_color_cube_face(cube_map.clone(), CubeFace::PositiveX)?;
+        frame_buffer.set_depth_buffer(Some(depth))?;
+        frame_buffer.check_color_renderable()?;
+        Ok(CubeMapTarget {
+            gl,
+            cube_map,
+            frame_buffer,
+        })
+    }
+
+    pub fn cube_map(&self) -> CubeMap {
+        self.cube_map.clone()
+    }
+
+    pub fn frame_buffer(&self) -> FrameBuffer {
+        self.frame_buffer.clone()
+    }
+
+    pub fn size(&self) -> u32 {
+        self.cube_map.size()
+    }
+
+    /// Draw the six faces seen from `position`, each one is cleared before the callback.
+    /// Stops at the first failed face.
+    pub fn render(
+        &mut self,
+        position: [f32; 3],
+        near: f32,
+        far: f32,
+        mut callback: impl FnMut(&CubeFaceView) -> Result<(), GlError>,
+    ) -> Result<(), GlError> {
+        let projection = CubeFace::projection(near, far);
+        for face in CubeFace::ALL {
+            self.render_face(face, |gl| {
+                gl.clear(ClearParams {
+                    color: Some([0.0, 0.0, 0.0, 0.0]),
+                    depth: Some(1.0),
+                    ..Default::default()
+                });
+                let view = face.view_matrix(position);
+                callback(&CubeFaceView {
+                    face,
+                    view,
+                    projection,
+                    view_projection: multiply(&projection, &view),
+                })
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Draw into a single face, like when updating one face per frame to spread the cost.
+    pub fn render_face<R>(&mut self, face: CubeFace, callback: impl FnOnce(&Gl) -> R) -> R {
+        let _span = trace_span!(DEBUG, "render cube face", face = ?face);
+        self.frame_buffer
+            .set_color_cube_face(self.cube_map.clone(), face)
+            .expect("the frame buffer is created by the context of the cube map");
+        let size = self.size() as i32;
+        let gl = &self.gl;
+        gl.apply(
+            Gl::settings()
+                .frame_buffer(self.frame_buffer.clone())
+                .viewport(0, 0, size, size),
+            || callback(gl),
+        )
+    }
+}
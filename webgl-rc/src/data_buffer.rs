@@ -1,28 +1,132 @@
 use crate::buffer_usage::BufferUsage;
-use std::cell::Cell;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fmt;
 use std::marker::PhantomData;
-use std::rc::Rc;
-use web_sys::{WebGlBuffer, WebGlRenderingContext as Context, WebGlRenderingContext};
+use std::ops::Range;
+use std::rc::{Rc, Weak};
+use web_sys::{OesTextureHalfFloat, WebGl2RenderingContext, WebGlRenderingContext as Context};
 
-use super::gl::{Gl, GlError};
+use super::gl::{Gl, GlError, WebGlVersion};
+use super::recorder::Command;
+use super::registry::{write_name, BufferInfo, Resource, ResourceInfo, ResourceKind};
 use super::settings::Settings;
 use super::types::DataType;
 
+#[diagnostic::on_unimplemented(
+    message = "type `{Self}` does not implement Writable",
+    label = "can't be written into a vertex buffer",
+    note = "use a float type like `f32`, `[f32; 3]` or `f16`, or `#[attributes(with = ..., type = ...)]` to convert it"
+)]
 pub trait Writable: Copy {
     fn write(&self, output: &mut Vec<f32>);
     fn stride() -> usize;
+
+    /// Component type in the buffer, packed formats write their bits as floats.
+    fn format() -> AttributeFormat {
+        AttributeFormat::Float
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Component type of a vertex attribute in the buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AttributeFormat {
+    #[default]
+    Float,
+    /// 16-bit floats, two components per float, see [f16](crate::f16).
+    HalfFloat,
+    /// Signed normalized `vec4` packed into a float, 10 bits for `xyz` and 2 bits for `w`,
+    /// see [PackedNormal](crate::PackedNormal). WebGL2 only.
+    Int2_10_10_10Rev,
+    /// Bytes normalized to `[0, 1]`, four components per float, see [Color](crate::Color).
+    UnsignedByteNormalized,
+}
+
+impl AttributeFormat {
+    /// Space taken by the attribute in floats, packed components are padded to whole floats.
+    pub(crate) fn size_in_floats(self, data_type: DataType) -> Option<usize> {
+        let components = data_type.size_in_floats()?;
+        Some(match self {
+            AttributeFormat::Float => components,
+            AttributeFormat::HalfFloat => components.div_ceil(2),
+            AttributeFormat::Int2_10_10_10Rev => 1,
+            AttributeFormat::UnsignedByteNormalized => components.div_ceil(4),
+        })
+    }
+
+    pub(crate) fn gl_type(self) -> u32 {
+        match self {
+            AttributeFormat::Float => Context::FLOAT,
+            AttributeFormat::HalfFloat => OesTextureHalfFloat::HALF_FLOAT_OES,
+            AttributeFormat::Int2_10_10_10Rev => WebGl2RenderingContext::INT_2_10_10_10_REV,
+            AttributeFormat::UnsignedByteNormalized => Context::UNSIGNED_BYTE,
+        }
+    }
+
+    pub(crate) fn is_normalized(self) -> bool {
+        matches!(
+            self,
+            AttributeFormat::Int2_10_10_10Rev | AttributeFormat::UnsignedByteNormalized
+        )
+    }
+
+    pub fn is_supported(self, version: WebGlVersion) -> bool {
+        self != AttributeFormat::Int2_10_10_10Rev || version == WebGlVersion::WebGl2
+    }
+}
+
+#[derive(Clone)]
 pub struct ArrayBufferData {
     pub(self) gl: Gl,
-    pub(self) handle: WebGlBuffer,
+    pub(self) id: u64,
     pub(self) length: Cell<usize>,
+    pub(self) size: Cell<usize>,
+    pub(self) usage: Cell<BufferUsage>,
+    pub(self) initial_usage: BufferUsage,
+    pub(self) uploads: Cell<usize>,
+    pub(self) label: RefCell<Option<String>>,
+}
+
+impl fmt::Debug for ArrayBufferData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArrayBufferData")
+            .field("id", &self.id)
+            .field("length", &self.length)
+            .field("size", &self.size)
+            .field("usage", &self.usage)
+            .field("label", &self.label)
+            .finish()
+    }
 }
 
 impl Drop for ArrayBufferData {
     fn drop(&mut self) {
-        self.gl.context().delete_buffer(Some(&self.handle));
+        trace_event!(DEBUG, id = self.id, "delete array buffer");
+        self.gl.unregister(self.id);
+        self.gl.delete(Command::DeleteBuffer { id: self.id });
+    }
+}
+
+impl Resource for ArrayBufferData {
+    fn info(&self) -> ResourceInfo {
+        ResourceInfo {
+            id: self.id,
+            kind: ResourceKind::ArrayBuffer,
+            size: self.size.get(),
+            label: self.label.borrow().clone(),
+        }
+    }
+
+    fn buffer(&self) -> Option<BufferInfo> {
+        Some(BufferInfo {
+            id: self.id,
+            kind: ResourceKind::ArrayBuffer,
+            usage: self.usage.get(),
+            initial_usage: self.initial_usage,
+            capacity_bytes: self.size.get(),
+            uploads: self.uploads.get(),
+            label: self.label.borrow().clone(),
+        })
     }
 }
 
@@ -33,45 +137,119 @@ pub struct ArrayBuffer {
 
 impl PartialEq<ArrayBuffer> for ArrayBuffer {
     fn eq(&self, other: &ArrayBuffer) -> bool {
-        self.data.handle == other.data.handle
+        self.data.id == other.data.id
     }
 }
 
 impl Eq for ArrayBuffer {}
 
+impl fmt::Display for ArrayBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.data.label.borrow();
+        write_name(f, ResourceKind::ArrayBuffer, self.data.id, label.as_deref())
+    }
+}
+
+/// Array buffer handle which doesn't keep the buffer alive, see [TextureWeak](crate::TextureWeak).
+#[derive(Clone, Debug)]
+pub struct ArrayBufferWeak {
+    id: u64,
+    data: Weak<ArrayBufferData>,
+}
+
+impl PartialEq for ArrayBufferWeak {
+    fn eq(&self, other: &ArrayBufferWeak) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ArrayBufferWeak {}
+
+impl ArrayBufferWeak {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The buffer, `None` if all its strong handles are dropped.
+    pub fn upgrade(&self) -> Option<ArrayBuffer> {
+        self.data.upgrade().map(|data| ArrayBuffer { data })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.data.strong_count() > 0
+    }
+}
+
 impl ArrayBuffer {
     pub fn new<T: Writable>(
         gl: Gl,
         data: &[T],
         usage: BufferUsage,
     ) -> Result<ArrayBuffer, GlError> {
-        let ref context: &WebGlRenderingContext = gl.context();
-        let buffer = context
-            .create_buffer()
-            .ok_or(GlError::BufferAllocationError)?;
+        let id = gl
+            .create_object(|id| Command::CreateBuffer { id })
+            .map_err(|error| match error {
+                GlError::ContextLost => error,
+                _ => GlError::BufferAllocationError,
+            })?;
+        trace_event!(
+            DEBUG,
+            id,
+            length = data.len(),
+            ?usage,
+            "create array buffer"
+        );
 
         let result = ArrayBuffer {
             data: Rc::new(ArrayBufferData {
                 gl: gl.clone(),
-                handle: buffer,
+                id,
                 length: Default::default(),
+                size: Default::default(),
+                usage: Cell::new(usage),
+                initial_usage: usage,
+                uploads: Default::default(),
+                label: Default::default(),
             }),
         };
+        gl.register(id, &result.data);
 
         result.set_content(data, usage);
 
         return Ok(result);
     }
 
-    pub(crate) fn handle(&self) -> WebGlBuffer {
-        self.data.handle.clone()
+    pub fn gl(&self) -> Gl {
+        self.data.gl.clone()
     }
 
-    pub fn set_content<T: Writable>(&self, items: &[T], usage: BufferUsage) {
-        let mut data: Vec<f32> = Vec::with_capacity(T::stride() * items.len());
-        for i in items {
-            i.write(&mut data);
+    pub fn id(&self) -> u64 {
+        self.data.id
+    }
+
+    /// Weak handle of the same object.
+    pub fn downgrade(&self) -> ArrayBufferWeak {
+        ArrayBufferWeak {
+            id: self.data.id,
+            data: Rc::downgrade(&self.data),
         }
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.label.borrow().clone()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.data.label.replace(Some(label.into()));
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
+    pub fn set_content<T: Writable>(&self, items: &[T], usage: BufferUsage) {
+        let data = write_items(items);
 
         self.data
             .gl
@@ -79,35 +257,123 @@ impl ArrayBuffer {
                 let bytes = unsafe {
                     std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4)
                 };
-                self.data.gl.context().buffer_data_with_u8_array(
-                    Context::ARRAY_BUFFER,
-                    &bytes,
-                    usage.into(),
-                );
+                self.data
+                    .gl
+                    .buffer_data(Context::ARRAY_BUFFER, bytes, usage.into());
             });
 
         self.data.length.set(items.len());
+        self.data.size.set(data.len() * 4);
+        self.data.usage.set(usage);
+        self.data.uploads.set(self.data.uploads.get() + 1);
+    }
+
+    /// Replace the items starting at `first`, keeping the buffer size.
+    /// Items past the end of the buffer are ignored.
+    pub fn set_sub_content<T: Writable>(&self, first: usize, items: &[T]) {
+        let count = items.len().min(self.len().saturating_sub(first));
+        debug_assert_eq!(count, items.len(), "items past the end of the buffer");
+        if count == 0 {
+            return;
+        }
+        let data = write_items(&items[..count]);
+
+        self.data
+            .gl
+            .apply(Gl::settings().array_buffer(self.clone()), || {
+                let bytes = unsafe {
+                    std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4)
+                };
+                self.data
+                    .gl
+                    .buffer_sub_data(Context::ARRAY_BUFFER, first * T::stride() * 4, bytes);
+            });
+        self.data.uploads.set(self.data.uploads.get() + 1);
     }
 
     pub fn len(&self) -> usize {
         self.data.length.get()
     }
+
+    /// Usage hint of the last upload
+    pub fn usage(&self) -> BufferUsage {
+        self.data.usage.get()
+    }
+
+    /// Usage hint the buffer was created with
+    pub fn initial_usage(&self) -> BufferUsage {
+        self.data.initial_usage
+    }
+
+    /// Size of the GPU storage in bytes
+    pub fn capacity_bytes(&self) -> usize {
+        self.data.size.get()
+    }
+}
+
+fn write_items<T: Writable>(items: &[T]) -> Vec<f32> {
+    let mut data: Vec<f32> = Vec::with_capacity(T::stride() * items.len());
+    for i in items {
+        i.write(&mut data);
+    }
+    data
+}
+
+/// Range of the items which differ between the buffers of the same length,
+/// compared by their written bits.
+fn changed_items<T: Writable>(old: &[T], new: &[T]) -> Option<Range<usize>> {
+    let stride = T::stride().max(1);
+    let (old, new) = (write_items(old), write_items(new));
+    let differs =
+        |(old, new): (&[f32], &[f32])| old.iter().zip(new).any(|(a, b)| a.to_bits() != b.to_bits());
+    let start = old
+        .chunks(stride)
+        .zip(new.chunks(stride))
+        .position(differs)?;
+    let end = old
+        .chunks(stride)
+        .zip(new.chunks(stride))
+        .rposition(differs)?;
+    Some(start..end + 1)
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Layout {
     pub name: &'static str,
     pub data_type: DataType,
+    pub format: AttributeFormat,
+    /// Instances sharing an item, see [DynamicAttribute::divisor]
+    pub divisor: u32,
 }
 
 pub trait Item: Writable {
     fn layout() -> Vec<Layout>;
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Buffer of typed items. It keeps a copy of the items, so they can be edited in place with
+/// [ItemsBuffer::update], use [ArrayBuffer] directly to avoid the copy.
+#[derive(Clone)]
 pub struct ItemsBuffer<T: Item> {
     pub(self) phantom: PhantomData<T>,
     pub(crate) buffer: ArrayBuffer,
+    /// Items uploaded to the buffer the last time
+    pub(self) shadow: Rc<RefCell<Vec<T>>>,
+}
+
+impl<T: Item> PartialEq for ItemsBuffer<T> {
+    fn eq(&self, other: &ItemsBuffer<T>) -> bool {
+        self.buffer == other.buffer
+    }
+}
+
+impl<T: Item> Eq for ItemsBuffer<T> {}
+
+impl<T: Item> fmt::Debug for ItemsBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ItemsBuffer")
+            .field("buffer", &self.buffer)
+            .finish()
+    }
 }
 
 impl<T: Item> ItemsBuffer<T> {
@@ -115,14 +381,355 @@ impl<T: Item> ItemsBuffer<T> {
         Ok(ItemsBuffer {
             phantom: Default::default(),
             buffer: ArrayBuffer::new(gl, data, usage)?,
+            shadow: Rc::new(RefCell::new(data.to_vec())),
         })
     }
 
+    pub fn id(&self) -> u64 {
+        self.buffer.id()
+    }
+
     pub fn set_content(&self, items: &[T], usage: BufferUsage) {
         self.buffer.set_content(items, usage);
+        self.shadow.replace(items.to_vec());
+    }
+
+    /// Edit the items in place, only the changed range is uploaded.
+    /// The buffer is reallocated with the last usage if the number of items changes.
+    ///
+    /// ```ignore
+    /// particles.update(|items| items[index].position = position);
+    /// ```
+    pub fn update<R>(&self, callback: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        let mut items = self.shadow.borrow_mut();
+        let old = items.clone();
+        let result = callback(&mut items);
+        if items.len() != old.len() {
+            self.buffer.set_content(&items, self.usage());
+        } else if let Some(range) = changed_items(&old, &items) {
+            trace_event!(
+                DEBUG,
+                id = self.id(),
+                first = range.start,
+                count = range.len(),
+                "update array buffer"
+            );
+            self.buffer.set_sub_content(range.start, &items[range]);
+        }
+        result
+    }
+
+    /// Copy of the items in the buffer.
+    pub fn items(&self) -> Vec<T> {
+        self.shadow.borrow().clone()
+    }
+
+    pub fn usage(&self) -> BufferUsage {
+        self.buffer.usage()
+    }
+
+    pub fn initial_usage(&self) -> BufferUsage {
+        self.buffer.initial_usage()
+    }
+
+    pub fn capacity_bytes(&self) -> usize {
+        self.buffer.capacity_bytes()
+    }
+
+    /// View of the items in the range, clamped to the buffer length.
+    /// Several objects can share the buffer and be drawn separately,
+    /// element indices are relative to the slice start.
+    pub fn slice(&self, range: Range<usize>) -> ItemsBufferSlice<T> {
+        let range = clamp_range(range, self.len());
+        ItemsBufferSlice {
+            buffer: self.clone(),
+            first: range.start,
+            count: range.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemsBufferSlice<T: Item> {
+    buffer: ItemsBuffer<T>,
+    first: usize,
+    count: usize,
+}
+
+impl<T: Item> ItemsBufferSlice<T> {
+    pub fn buffer(&self) -> &ItemsBuffer<T> {
+        &self.buffer
+    }
+
+    pub fn first(&self) -> usize {
+        self.first
     }
 
     pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+pub(crate) fn clamp_range(range: Range<usize>, length: usize) -> Range<usize> {
+    let end = range.end.min(length);
+    range.start.min(end)..end
+}
+
+/// Vertex data accepted by the draw calls.
+pub trait VertexSource {
+    fn array_buffer(&self) -> &ArrayBuffer;
+
+    fn layout(&self) -> Cow<'_, DynamicLayout>;
+
+    /// Index of the first item to draw
+    fn first(&self) -> usize {
+        0
+    }
+
+    /// Number of items
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Item> VertexSource for ItemsBuffer<T> {
+    fn array_buffer(&self) -> &ArrayBuffer {
+        &self.buffer
+    }
+
+    fn layout(&self) -> Cow<'_, DynamicLayout> {
+        Cow::Owned(DynamicLayout::of::<T>())
+    }
+
+    fn len(&self) -> usize {
         self.buffer.len()
     }
 }
+
+impl<T: Item> VertexSource for ItemsBufferSlice<T> {
+    fn array_buffer(&self) -> &ArrayBuffer {
+        &self.buffer.buffer
+    }
+
+    fn layout(&self) -> Cow<'_, DynamicLayout> {
+        Cow::Owned(DynamicLayout::of::<T>())
+    }
+
+    fn first(&self) -> usize {
+        self.first
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DynamicAttribute {
+    pub name: String,
+    pub data_type: DataType,
+    pub format: AttributeFormat,
+    /// Offset from the item start in floats
+    pub offset: usize,
+    /// Instances sharing an item when the buffer provides instance attributes, 1 by default.
+    /// With the divisor 4 the items `0..n/4` hold the values for groups of four instances.
+    pub divisor: u32,
+}
+
+/// Vertex layout described at runtime, for data whose structure is known only
+/// after loading an asset.
+///
+/// ```ignore
+/// let layout = DynamicLayout::new()
+///     .attribute("a_position", DataType::Vec3)
+///     .attribute("a_uv", DataType::Vec2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DynamicLayout {
+    attributes: Vec<DynamicAttribute>,
+    /// Item size in floats
+    stride: usize,
+}
+
+impl DynamicLayout {
+    pub fn new() -> DynamicLayout {
+        Default::default()
+    }
+
+    /// Layout of a derived [Item].
+    pub fn of<T: Item>() -> DynamicLayout {
+        T::layout()
+            .into_iter()
+            .fold(DynamicLayout::new(), |layout, item| {
+                layout
+                    .attribute_with_format(item.name, item.data_type, item.format)
+                    .attribute_divisor(item.name, item.divisor)
+            })
+            .with_stride(T::stride())
+    }
+
+    /// Append the attribute after the previous ones.
+    pub fn attribute(self, name: &str, data_type: DataType) -> DynamicLayout {
+        self.attribute_with_format(name, data_type, AttributeFormat::Float)
+    }
+
+    /// Append the attribute stored in the format, like half floats.
+    pub fn attribute_with_format(
+        self,
+        name: &str,
+        data_type: DataType,
+        format: AttributeFormat,
+    ) -> DynamicLayout {
+        let offset = self.stride;
+        self.attribute_at_with_format(name, data_type, format, offset)
+    }
+
+    /// Place the attribute at the offset in floats, the stride grows to fit it.
+    pub fn attribute_at(self, name: &str, data_type: DataType, offset: usize) -> DynamicLayout {
+        self.attribute_at_with_format(name, data_type, AttributeFormat::Float, offset)
+    }
+
+    pub fn attribute_at_with_format(
+        mut self,
+        name: &str,
+        data_type: DataType,
+        format: AttributeFormat,
+        offset: usize,
+    ) -> DynamicLayout {
+        let size = format.size_in_floats(data_type).unwrap_or(0);
+        self.stride = self.stride.max(offset + size);
+        self.attributes.push(DynamicAttribute {
+            name: name.into(),
+            data_type,
+            format,
+            offset,
+            divisor: 1,
+        });
+        self
+    }
+
+    /// Share the values of the instance attribute between `divisor` instances.
+    pub fn attribute_divisor(mut self, name: &str, divisor: u32) -> DynamicLayout {
+        for attribute in self.attributes.iter_mut().filter(|v| v.name == name) {
+            attribute.divisor = divisor.max(1);
+        }
+        self
+    }
+
+    /// Item size in floats, for items with padding after the attributes.
+    pub fn with_stride(mut self, stride: usize) -> DynamicLayout {
+        self.stride = self.stride.max(stride);
+        self
+    }
+
+    pub fn attributes(&self) -> &[DynamicAttribute] {
+        &self.attributes
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+}
+
+/// Buffer of floats interleaved according to a [DynamicLayout].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawItemsBuffer {
+    layout: Rc<DynamicLayout>,
+    buffer: ArrayBuffer,
+}
+
+impl RawItemsBuffer {
+    pub fn new(
+        gl: Gl,
+        layout: DynamicLayout,
+        data: &[f32],
+        usage: BufferUsage,
+    ) -> Result<RawItemsBuffer, GlError> {
+        if let Some(attribute) = layout
+            .attributes()
+            .iter()
+            .find(|attribute| attribute.data_type.size_in_floats().is_none())
+        {
+            return Err(GlError::UnsupportedType(Some(format!(
+                "Attribute {} can't have {:?} type",
+                attribute.name, attribute.data_type
+            ))));
+        }
+        RawItemsBuffer::check_size(&layout, data)?;
+        Ok(RawItemsBuffer {
+            buffer: ArrayBuffer::new(gl, data, usage)?,
+            layout: Rc::new(layout),
+        })
+    }
+
+    fn check_size(layout: &DynamicLayout, data: &[f32]) -> Result<(), GlError> {
+        if layout.stride() == 0 || !data.len().is_multiple_of(layout.stride()) {
+            Err(GlError::InvalidBufferSize {
+                expected: (data.len() / layout.stride().max(1) * layout.stride()) as u32,
+                received: data.len() as u32,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.buffer.id()
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.buffer.label()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.buffer.set_label(label);
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
+    /// Replace the content, the data size should be a multiple of the layout stride.
+    pub fn set_content(&self, data: &[f32], usage: BufferUsage) -> Result<(), GlError> {
+        RawItemsBuffer::check_size(&self.layout, data)?;
+        self.buffer.set_content(data, usage);
+        Ok(())
+    }
+
+    pub fn layout(&self) -> &DynamicLayout {
+        &self.layout
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len() / self.layout.stride()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl VertexSource for RawItemsBuffer {
+    fn array_buffer(&self) -> &ArrayBuffer {
+        &self.buffer
+    }
+
+    fn layout(&self) -> Cow<'_, DynamicLayout> {
+        Cow::Borrowed(&self.layout)
+    }
+
+    fn len(&self) -> usize {
+        RawItemsBuffer::len(self)
+    }
+}
@@ -1,18 +1,101 @@
-use crate::{Gl, GlError, Settings};
+use crate::registry::{write_name, Resource, ResourceInfo, ResourceKind};
+use crate::{Command, Gl, GlError, Settings};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::cell::RefCell;
+use std::fmt;
 use std::rc::Rc;
-use web_sys::{WebGlRenderbuffer, WebGlRenderingContext};
+use web_sys::{WebGl2RenderingContext, WebGlRenderingContext};
 
-#[derive(Clone, Debug)]
+/// Renderbuffer storage format.
+///
+/// `Depth16`, `Stencil8` and `DepthStencil` are available everywhere,
+/// the rest of the formats require a WebGL2 context.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
+pub enum DepthBufferFormat {
+    #[default]
+    Depth16 = WebGlRenderingContext::DEPTH_COMPONENT16,
+    Depth24 = WebGl2RenderingContext::DEPTH_COMPONENT24,
+    Depth32F = WebGl2RenderingContext::DEPTH_COMPONENT32F,
+    Stencil8 = WebGlRenderingContext::STENCIL_INDEX8,
+    DepthStencil = WebGlRenderingContext::DEPTH_STENCIL,
+    Depth24Stencil8 = WebGl2RenderingContext::DEPTH24_STENCIL8,
+}
+
+impl DepthBufferFormat {
+    pub fn has_depth(self) -> bool {
+        self != DepthBufferFormat::Stencil8
+    }
+
+    pub fn has_stencil(self) -> bool {
+        matches!(
+            self,
+            DepthBufferFormat::Stencil8
+                | DepthBufferFormat::DepthStencil
+                | DepthBufferFormat::Depth24Stencil8
+        )
+    }
+
+    /// Estimated bytes per pixel, drivers may pad the storage.
+    pub fn size(self) -> u32 {
+        match self {
+            DepthBufferFormat::Stencil8 => 1,
+            DepthBufferFormat::Depth16 => 2,
+            DepthBufferFormat::Depth24
+            | DepthBufferFormat::Depth32F
+            | DepthBufferFormat::DepthStencil
+            | DepthBufferFormat::Depth24Stencil8 => 4,
+        }
+    }
+
+    /// Frame buffer attachment point the buffer should be bound to.
+    pub fn attachment(self) -> u32 {
+        match (self.has_depth(), self.has_stencil()) {
+            (true, true) => WebGlRenderingContext::DEPTH_STENCIL_ATTACHMENT,
+            (false, true) => WebGlRenderingContext::STENCIL_ATTACHMENT,
+            _ => WebGlRenderingContext::DEPTH_ATTACHMENT,
+        }
+    }
+}
+
+#[derive(Clone)]
 struct DepthBufferInfo {
     gl: Gl,
-    handle: WebGlRenderbuffer,
+    id: u64,
     width: u32,
     height: u32,
+    format: DepthBufferFormat,
+    label: RefCell<Option<String>>,
+}
+
+impl fmt::Debug for DepthBufferInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DepthBufferInfo")
+            .field("id", &self.id)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("format", &self.format)
+            .field("label", &self.label)
+            .finish()
+    }
 }
 
 impl Drop for DepthBufferInfo {
     fn drop(&mut self) {
-        self.gl.context().delete_renderbuffer(Some(&self.handle));
+        trace_event!(DEBUG, id = self.id, "delete depth buffer");
+        self.gl.unregister(self.id);
+        self.gl.delete(Command::DeleteRenderbuffer { id: self.id });
+    }
+}
+
+impl Resource for DepthBufferInfo {
+    fn info(&self) -> ResourceInfo {
+        ResourceInfo {
+            id: self.id,
+            kind: ResourceKind::DepthBuffer,
+            size: (self.width * self.height * self.format.size()) as usize,
+            label: self.label.borrow().clone(),
+        }
     }
 }
 
@@ -23,45 +106,79 @@ pub struct DepthBuffer {
 
 impl PartialEq for DepthBuffer {
     fn eq(&self, other: &Self) -> bool {
-        self.data.handle == other.data.handle
+        self.data.id == other.data.id
     }
 }
 
 impl Eq for DepthBuffer {}
 
+impl fmt::Display for DepthBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.data.label.borrow();
+        write_name(f, ResourceKind::DepthBuffer, self.data.id, label.as_deref())
+    }
+}
+
 impl DepthBuffer {
-    pub fn new(gl: Gl, width: u32, height: u32) -> Result<DepthBuffer, GlError> {
-        let handle = gl
-            .context()
-            .create_renderbuffer()
-            .ok_or(GlError::DepthBufferError)?;
+    pub fn new(
+        gl: Gl,
+        width: u32,
+        height: u32,
+        format: DepthBufferFormat,
+    ) -> Result<DepthBuffer, GlError> {
+        let id = gl
+            .create_object(|id| Command::CreateRenderbuffer { id })
+            .map_err(|_| GlError::DepthBufferError)?;
+        trace_event!(DEBUG, id, width, height, ?format, "create depth buffer");
         let buffer = DepthBuffer {
             data: Rc::new(DepthBufferInfo {
                 gl: gl.clone(),
-                handle,
+                id,
                 width,
                 height,
+                format,
+                label: Default::default(),
             }),
         };
+        gl.register(id, &buffer.data);
         gl.apply(Gl::settings().depth_buffer(buffer.clone()), || {
-            gl.context().renderbuffer_storage(
-                WebGlRenderingContext::RENDERBUFFER,
-                WebGlRenderingContext::DEPTH_COMPONENT16,
-                width as i32,
-                height as i32,
-            )
+            gl.execute(Command::RenderbufferStorage {
+                format: format.into(),
+                width,
+                height,
+            })
         });
         Ok(buffer)
     }
 
+    pub fn gl(&self) -> Gl {
+        self.data.gl.clone()
+    }
+
+    pub fn id(&self) -> u64 {
+        self.data.id
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.label.borrow().clone()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.data.label.replace(Some(label.into()));
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
     pub fn width(&self) -> u32 {
         self.data.width
     }
     pub fn height(&self) -> u32 {
         self.data.height
     }
-
-    pub(crate) fn handle(&self) -> &WebGlRenderbuffer {
-        &self.data.handle
+    pub fn format(&self) -> DepthBufferFormat {
+        self.data.format
     }
 }
@@ -0,0 +1,143 @@
+use crate::{
+    Attributes, BufferUsage, Gl, GlError, ItemsBuffer, PrimitiveType, Program, Settings, Texture,
+    TextureContent, TextureFilter, TextureFormat, TextureType, Uniforms,
+};
+
+const VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    void main() {
+        gl_Position = vec4(a_position, 0.0, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    precision highp float;
+    uniform sampler2D u_depth;
+    uniform vec2 u_offset;
+    uniform vec2 u_size;
+    void main() {
+        float depth = min(texture2D(u_depth, (u_offset + gl_FragCoord.xy) / u_size).r, 0.99999994);
+        vec4 encoded = fract(depth * vec4(1.0, 255.0, 65025.0, 16581375.0));
+        gl_FragColor = encoded - encoded.yzww * vec4(1.0 / 255.0, 1.0 / 255.0, 1.0 / 255.0, 0.0);
+    }
+"#;
+
+#[derive(Clone, Copy, Debug, Attributes)]
+struct QuadAttributes {
+    position: [f32; 2],
+}
+
+#[derive(Clone, Uniforms)]
+struct DepthUniforms {
+    depth: Texture,
+    offset: [f32; 2],
+    size: [f32; 2],
+}
+
+/// Reads depth values from depth textures.
+///
+/// WebGL1 can't read depth directly, so the values are encoded into an RGBA 8-bit target first.
+/// Create the reader once and reuse it, since it compiles its own program.
+#[derive(Clone, Debug)]
+pub struct DepthReader {
+    gl: Gl,
+    program: Program,
+    quad: ItemsBuffer<QuadAttributes>,
+}
+
+impl DepthReader {
+    pub fn new(gl: Gl) -> Result<DepthReader, GlError> {
+        Ok(DepthReader {
+            program: gl.program(FRAGMENT_SHADER, VERTEX_SHADER)?,
+            quad: gl.items_buffer(
+                &[
+                    QuadAttributes {
+                        position: [-1.0, -1.0],
+                    },
+                    QuadAttributes {
+                        position: [1.0, -1.0],
+                    },
+                    QuadAttributes {
+                        position: [-1.0, 1.0],
+                    },
+                    QuadAttributes {
+                        position: [1.0, 1.0],
+                    },
+                ],
+                BufferUsage::Static,
+            )?,
+            gl,
+        })
+    }
+
+    /// Read depth values of the region in the range `[0, 1]`, rows are ordered bottom to top.
+    pub fn read(
+        &self,
+        depth: &Texture,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<f32>, GlError> {
+        if !depth.format().is_depth() {
+            return Err(GlError::ReadPixelsError(Some(format!(
+                "Depth texture format expected, {:?} found",
+                depth.format()
+            ))));
+        }
+        if x.checked_add(width)
+            .is_none_or(|right| right > depth.width())
+            || y.checked_add(height).is_none_or(|top| top > depth.height())
+        {
+            return Err(GlError::ReadPixelsError(Some(format!(
+                "Region {}x{} at ({}, {}) is out of the texture bounds {}x{}",
+                width,
+                height,
+                x,
+                y,
+                depth.width(),
+                depth.height()
+            ))));
+        }
+
+        let gl = &self.gl;
+        let target = gl.texture(
+            width,
+            height,
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::None,
+        )?;
+        gl.apply(
+            Gl::settings()
+                .frame_buffer(gl.frame_buffer_with_color(target.clone())?)
+                .viewport(0, 0, width as i32, height as i32)
+                .blend(false)
+                .depth_test(false)
+                .color_mask(true, true, true, true)
+                .texture_filter(depth.clone(), TextureFilter::Nearest),
+            || {
+                self.program.draw_arrays(
+                    PrimitiveType::TriangleStrip,
+                    &DepthUniforms {
+                        depth: depth.clone(),
+                        offset: [x as f32, y as f32],
+                        size: [depth.width() as f32, depth.height() as f32],
+                    },
+                    &self.quad,
+                )
+            },
+        )?;
+
+        Ok(target
+            .read_pixels_array()?
+            .chunks(4)
+            .map(|pixel| {
+                pixel
+                    .iter()
+                    .rev()
+                    .fold(0.0, |result, value| (result + *value as f32) / 255.0)
+            })
+            .collect())
+    }
+}
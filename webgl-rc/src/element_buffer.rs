@@ -1,19 +1,107 @@
+use crate::registry::{write_name, BufferInfo, Resource, ResourceInfo, ResourceKind};
 use crate::settings::Settings;
-use crate::{BufferUsage, Gl, GlError};
-use std::cell::Cell;
-use std::rc::Rc;
-use web_sys::{WebGlBuffer, WebGlRenderingContext};
+use crate::{BufferUsage, Command, Feature, Gl, GlError};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::{Rc, Weak};
+use web_sys::WebGlRenderingContext;
 
-#[derive(Debug, Clone)]
+/// Largest index of a 16-bit elements buffer.
+pub const MAX_SHORT_INDEX: u32 = u16::MAX as u32;
+
+/// Index ending the current strip, fan or line loop, the next index starts a new one.
+/// WebGL2 always restarts at the largest value of the index type, so it's stored that way.
+/// See [join_strips] and [grid_strip_indices].
+pub const PRIMITIVE_RESTART: u32 = u32::MAX;
+
+/// Type of the indices in an elements buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum IndexType {
+    #[default]
+    UnsignedShort = WebGlRenderingContext::UNSIGNED_SHORT,
+    /// Requires `OES_element_index_uint` in WebGL1
+    UnsignedInt = WebGlRenderingContext::UNSIGNED_INT,
+}
+
+impl IndexType {
+    /// Size of an index in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            IndexType::UnsignedShort => 2,
+            IndexType::UnsignedInt => 4,
+        }
+    }
+
+    /// Index value restarting primitives in WebGL2.
+    pub fn restart_index(self) -> u32 {
+        match self {
+            IndexType::UnsignedShort => u16::MAX as u32,
+            IndexType::UnsignedInt => u32::MAX,
+        }
+    }
+}
+
+impl From<IndexType> for u32 {
+    fn from(value: IndexType) -> Self {
+        value as u32
+    }
+}
+
+#[derive(Clone)]
 pub struct ElementBufferData {
     pub(self) gl: Gl,
-    pub(self) handle: WebGlBuffer,
+    pub(self) id: u64,
     pub(self) length: Cell<usize>,
+    pub(self) index_type: Cell<IndexType>,
+    pub(self) max_index: Cell<u32>,
+    pub(self) usage: Cell<BufferUsage>,
+    pub(self) initial_usage: BufferUsage,
+    pub(self) uploads: Cell<usize>,
+    pub(self) label: RefCell<Option<String>>,
+}
+
+impl fmt::Debug for ElementBufferData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ElementBufferData")
+            .field("id", &self.id)
+            .field("length", &self.length)
+            .field("index_type", &self.index_type)
+            .field("usage", &self.usage)
+            .field("label", &self.label)
+            .finish()
+    }
 }
 
 impl Drop for ElementBufferData {
     fn drop(&mut self) {
-        self.gl.context().delete_buffer(Some(&self.handle));
+        trace_event!(DEBUG, id = self.id, "delete elements buffer");
+        self.gl.unregister(self.id);
+        self.gl.delete(Command::DeleteBuffer { id: self.id });
+    }
+}
+
+impl Resource for ElementBufferData {
+    fn info(&self) -> ResourceInfo {
+        ResourceInfo {
+            id: self.id,
+            kind: ResourceKind::ElementsBuffer,
+            size: self.length.get() * self.index_type.get().size(),
+            label: self.label.borrow().clone(),
+        }
+    }
+
+    fn buffer(&self) -> Option<BufferInfo> {
+        Some(BufferInfo {
+            id: self.id,
+            kind: ResourceKind::ElementsBuffer,
+            usage: self.usage.get(),
+            initial_usage: self.initial_usage,
+            capacity_bytes: self.length.get() * self.index_type.get().size(),
+            uploads: self.uploads.get(),
+            label: self.label.borrow().clone(),
+        })
     }
 }
 
@@ -24,54 +112,299 @@ pub struct ElementsBuffer {
 
 impl PartialEq<ElementsBuffer> for ElementsBuffer {
     fn eq(&self, other: &ElementsBuffer) -> bool {
-        self.data.handle == other.data.handle
+        self.data.id == other.data.id
     }
 }
 
 impl Eq for ElementsBuffer {}
 
+impl fmt::Display for ElementsBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.data.label.borrow();
+        write_name(
+            f,
+            ResourceKind::ElementsBuffer,
+            self.data.id,
+            label.as_deref(),
+        )
+    }
+}
+
+/// Elements buffer handle which doesn't keep the buffer alive,
+/// see [TextureWeak](crate::TextureWeak).
+#[derive(Clone, Debug)]
+pub struct ElementsBufferWeak {
+    id: u64,
+    data: Weak<ElementBufferData>,
+}
+
+impl PartialEq for ElementsBufferWeak {
+    fn eq(&self, other: &ElementsBufferWeak) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ElementsBufferWeak {}
+
+impl ElementsBufferWeak {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The buffer, `None` if all its strong handles are dropped.
+    pub fn upgrade(&self) -> Option<ElementsBuffer> {
+        self.data.upgrade().map(|data| ElementsBuffer { data })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.data.strong_count() > 0
+    }
+}
+
 impl ElementsBuffer {
     pub fn new(gl: Gl, data: &[u32], usage: BufferUsage) -> Result<ElementsBuffer, GlError> {
-        let ref context: &WebGlRenderingContext = gl.context();
-        let buffer = context
-            .create_buffer()
-            .ok_or(GlError::BufferAllocationError)?;
+        let id = gl
+            .create_object(|id| Command::CreateBuffer { id })
+            .map_err(|error| match error {
+                GlError::ContextLost => error,
+                _ => GlError::BufferAllocationError,
+            })?;
+        trace_event!(
+            DEBUG,
+            id,
+            length = data.len(),
+            ?usage,
+            "create elements buffer"
+        );
 
         let result = ElementsBuffer {
             data: Rc::new(ElementBufferData {
                 gl: gl.clone(),
-                handle: buffer,
+                id,
                 length: Default::default(),
+                index_type: Default::default(),
+                max_index: Default::default(),
+                usage: Cell::new(usage),
+                initial_usage: usage,
+                uploads: Default::default(),
+                label: Default::default(),
             }),
         };
+        gl.register(id, &result.data);
 
-        result.set_content(data, usage);
+        result.set_content(data, usage)?;
 
         return Ok(result);
     }
 
-    pub(crate) fn handle(&self) -> WebGlBuffer {
-        self.data.handle.clone()
+    pub fn gl(&self) -> Gl {
+        self.data.gl.clone()
     }
 
-    pub fn set_content(&self, data: &[u32], usage: BufferUsage) {
-        self.data
-            .gl
-            .apply(Gl::settings().element_buffer(self.clone()), || {
-                let bytes = unsafe {
-                    std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4)
-                };
-                self.data.gl.context().buffer_data_with_u8_array(
-                    WebGlRenderingContext::ELEMENT_ARRAY_BUFFER,
-                    &bytes,
-                    usage.into(),
-                );
-            });
+    pub fn id(&self) -> u64 {
+        self.data.id
+    }
+
+    /// Weak handle of the same object.
+    pub fn downgrade(&self) -> ElementsBufferWeak {
+        ElementsBufferWeak {
+            id: self.data.id,
+            data: Rc::downgrade(&self.data),
+        }
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.label.borrow().clone()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.data.label.replace(Some(label.into()));
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
+    /// Replace the indices. They're stored as 16-bit when all of them fit, larger indices
+    /// require `OES_element_index_uint` in WebGL1, use [split_indices] to draw
+    /// large meshes without it. [PRIMITIVE_RESTART] indices require WebGL2.
+    pub fn set_content(&self, data: &[u32], usage: BufferUsage) -> Result<(), GlError> {
+        let gl = &self.data.gl;
+        gl.check_context()?;
+        let restarts = data.contains(&PRIMITIVE_RESTART);
+        if restarts && !gl.supports(Feature::PrimitiveRestart) {
+            return Err(GlError::FeatureNotSupported(Feature::PrimitiveRestart)
+                .with_context(format!("uploading primitive restart indices into {}", self)));
+        }
+        let max = data
+            .iter()
+            .copied()
+            .filter(|&index| index != PRIMITIVE_RESTART)
+            .max()
+            .unwrap_or(0);
+        // WebGL2 treats the largest 16-bit value as a restart, so it can't be a vertex index
+        let max_short = if gl.supports(Feature::PrimitiveRestart) {
+            MAX_SHORT_INDEX - 1
+        } else {
+            MAX_SHORT_INDEX
+        };
+        let index_type = if max <= max_short {
+            IndexType::UnsignedShort
+        } else if gl.element_index_uint_supported() {
+            IndexType::UnsignedInt
+        } else {
+            return Err(
+                GlError::ExtensionNotFound("OES_element_index_uint".into()).with_context(format!(
+                    "uploading index {} into {}, 16-bit indices are limited to {}",
+                    max, self, MAX_SHORT_INDEX
+                )),
+            );
+        };
+
+        gl.apply(Gl::settings().element_buffer(self.clone()), || {
+            let target = WebGlRenderingContext::ELEMENT_ARRAY_BUFFER;
+            match index_type {
+                IndexType::UnsignedShort => {
+                    // The restart index truncates to `u16::MAX`
+                    let shorts: Vec<u16> = data.iter().map(|&index| index as u16).collect();
+                    let bytes = unsafe {
+                        std::slice::from_raw_parts(shorts.as_ptr() as *const u8, shorts.len() * 2)
+                    };
+                    gl.buffer_data(target, bytes, usage.into());
+                }
+                IndexType::UnsignedInt => {
+                    let bytes = unsafe {
+                        std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4)
+                    };
+                    gl.buffer_data(target, bytes, usage.into());
+                }
+            }
+        });
 
         self.data.length.set(data.len());
+        self.data.index_type.set(index_type);
+        self.data.max_index.set(max);
+        self.data.usage.set(usage);
+        self.data.uploads.set(self.data.uploads.get() + 1);
+        Ok(())
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        self.data.index_type.get()
     }
 
     pub fn len(&self) -> usize {
         self.data.length.get()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.length.get() == 0
+    }
+
+    /// Largest index in the buffer without the restarts, zero for an empty buffer
+    pub fn max_index(&self) -> u32 {
+        self.data.max_index.get()
+    }
+
+    /// Usage hint of the last upload
+    pub fn usage(&self) -> BufferUsage {
+        self.data.usage.get()
+    }
+
+    /// Usage hint the buffer was created with
+    pub fn initial_usage(&self) -> BufferUsage {
+        self.data.initial_usage
+    }
+
+    /// Size of the GPU storage in bytes
+    pub fn capacity_bytes(&self) -> usize {
+        self.data.length.get() * self.data.index_type.get().size()
+    }
+}
+
+/// Part of a mesh with 16-bit indices, see [split_indices].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexBatch {
+    /// Original indices of the batch vertices
+    pub vertices: Vec<u32>,
+    /// Indices into `vertices`
+    pub indices: Vec<u16>,
+}
+
+impl IndexBatch {
+    /// Vertices of the batch picked from the original ones.
+    pub fn gather<T: Copy>(&self, items: &[T]) -> Vec<T> {
+        self.vertices
+            .iter()
+            .map(|&index| items[index as usize])
+            .collect()
+    }
+
+    /// Indices for [ElementsBuffer::new].
+    pub fn elements(&self) -> Vec<u32> {
+        self.indices.iter().map(|&index| index as u32).collect()
+    }
+}
+
+/// Split a mesh into batches with at most 65536 vertices each, so it can be drawn
+/// without `OES_element_index_uint`. Primitives of `primitive_size` indices,
+/// like 3 for triangles or 2 for lines, are kept whole, vertices shared between
+/// batches are duplicated.
+pub fn split_indices(indices: &[u32], primitive_size: usize) -> Vec<IndexBatch> {
+    let primitive_size = primitive_size.max(1);
+    let limit = MAX_SHORT_INDEX as usize + 1;
+    let mut batches = Vec::new();
+    let mut batch = IndexBatch::default();
+    let mut mapping: HashMap<u32, u16> = HashMap::new();
+
+    for primitive in indices.chunks(primitive_size) {
+        let new_vertices = primitive
+            .iter()
+            .filter(|index| !mapping.contains_key(index))
+            .count();
+        if batch.vertices.len() + new_vertices > limit {
+            batches.push(std::mem::take(&mut batch));
+            mapping.clear();
+        }
+        for &index in primitive {
+            let local = *mapping.entry(index).or_insert_with(|| {
+                batch.vertices.push(index);
+                (batch.vertices.len() - 1) as u16
+            });
+            batch.indices.push(local);
+        }
+    }
+    if !batch.indices.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+/// Concatenate strips, or fans, separated by [PRIMITIVE_RESTART],
+/// so they are drawn with a single draw call in WebGL2.
+pub fn join_strips<S: AsRef<[u32]>>(strips: impl IntoIterator<Item = S>) -> Vec<u32> {
+    let mut result = Vec::new();
+    for strip in strips {
+        if !result.is_empty() {
+            result.push(PRIMITIVE_RESTART);
+        }
+        result.extend_from_slice(strip.as_ref());
+    }
+    result
+}
+
+/// Triangle strip indices of a grid with `columns × rows` cells, one strip per row
+/// separated by restarts. Vertices are numbered row by row, `columns + 1` in a row,
+/// like for a terrain height map. Takes about half the indices of separate triangles.
+pub fn grid_strip_indices(columns: u32, rows: u32) -> Vec<u32> {
+    let stride = columns + 1;
+    join_strips((0..rows).map(|row| {
+        (0..stride)
+            .flat_map(|column| {
+                let index = row * stride + column;
+                [index, index + stride]
+            })
+            .collect::<Vec<u32>>()
+    }))
 }
@@ -0,0 +1,108 @@
+use std::cell::OnceCell;
+
+use wasm_bindgen::JsValue;
+use web_sys::WebGlRenderingContext as Context;
+
+use crate::WebGlVersion;
+
+/// WebGL extensions with typed getters on [Gl](crate::Gl).
+///
+/// Extensions are requested on the first use and memoized. Most of them are promoted
+/// to the core in WebGL2, where they aren't available as extensions anymore,
+/// see [Extension::is_core].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Extension {
+    AngleInstancedArrays,
+    ExtColorBufferFloat,
+    ExtColorBufferHalfFloat,
+    ExtFragDepth,
+    ExtShaderTextureLod,
+    ExtTextureFilterAnisotropic,
+    OesElementIndexUint,
+    OesStandardDerivatives,
+    OesTextureFloat,
+    OesTextureFloatLinear,
+    OesTextureHalfFloat,
+    OesTextureHalfFloatLinear,
+    WebglColorBufferFloat,
+    WebglDepthTexture,
+    WebglLoseContext,
+}
+
+impl Extension {
+    pub const ALL: [Extension; 15] = [
+        Extension::AngleInstancedArrays,
+        Extension::ExtColorBufferFloat,
+        Extension::ExtColorBufferHalfFloat,
+        Extension::ExtFragDepth,
+        Extension::ExtShaderTextureLod,
+        Extension::ExtTextureFilterAnisotropic,
+        Extension::OesElementIndexUint,
+        Extension::OesStandardDerivatives,
+        Extension::OesTextureFloat,
+        Extension::OesTextureFloatLinear,
+        Extension::OesTextureHalfFloat,
+        Extension::OesTextureHalfFloatLinear,
+        Extension::WebglColorBufferFloat,
+        Extension::WebglDepthTexture,
+        Extension::WebglLoseContext,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Extension::AngleInstancedArrays => "ANGLE_instanced_arrays",
+            Extension::ExtColorBufferFloat => "EXT_color_buffer_float",
+            Extension::ExtColorBufferHalfFloat => "EXT_color_buffer_half_float",
+            Extension::ExtFragDepth => "EXT_frag_depth",
+            Extension::ExtShaderTextureLod => "EXT_shader_texture_lod",
+            Extension::ExtTextureFilterAnisotropic => "EXT_texture_filter_anisotropic",
+            Extension::OesElementIndexUint => "OES_element_index_uint",
+            Extension::OesStandardDerivatives => "OES_standard_derivatives",
+            Extension::OesTextureFloat => "OES_texture_float",
+            Extension::OesTextureFloatLinear => "OES_texture_float_linear",
+            Extension::OesTextureHalfFloat => "OES_texture_half_float",
+            Extension::OesTextureHalfFloatLinear => "OES_texture_half_float_linear",
+            Extension::WebglColorBufferFloat => "WEBGL_color_buffer_float",
+            Extension::WebglDepthTexture => "WEBGL_depth_texture",
+            Extension::WebglLoseContext => "WEBGL_lose_context",
+        }
+    }
+
+    /// The functionality is a part of the version without the extension.
+    pub fn is_core(self, version: WebGlVersion) -> bool {
+        match version {
+            WebGlVersion::WebGl1 => false,
+            WebGlVersion::WebGl2 => !matches!(
+                self,
+                Extension::ExtColorBufferFloat
+                    | Extension::ExtColorBufferHalfFloat
+                    | Extension::ExtTextureFilterAnisotropic
+                    | Extension::OesTextureFloatLinear
+                    | Extension::WebglLoseContext
+            ),
+        }
+    }
+}
+
+/// Extension objects requested once per context.
+#[derive(Debug, Default)]
+pub(crate) struct ExtensionCache {
+    extensions: [OnceCell<Option<JsValue>>; Extension::ALL.len()],
+}
+
+impl ExtensionCache {
+    pub fn get(&self, context: &Context, extension: Extension) -> Option<&JsValue> {
+        self.extensions[extension as usize]
+            .get_or_init(|| {
+                let result = context.get_extension(extension.name()).ok().flatten();
+                trace_event!(
+                    DEBUG,
+                    extension = extension.name(),
+                    available = result.is_some(),
+                    "extension requested"
+                );
+                result.map(JsValue::from)
+            })
+            .as_ref()
+    }
+}
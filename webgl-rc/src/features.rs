@@ -0,0 +1,174 @@
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::precision::probe;
+use crate::{Gl, TextureType, WebGlVersion};
+
+/// Optional capability of a context, see [Gl::supports].
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    WebGl2 = 1 << 0,
+    /// WebGL2, or `ANGLE_instanced_arrays` in WebGL1
+    Instancing = 1 << 1,
+    DepthTexture = 1 << 2,
+    ElementIndexUint = 1 << 3,
+    FloatTexture = 1 << 4,
+    HalfFloatTexture = 1 << 5,
+    FloatLinearFiltering = 1 << 6,
+    HalfFloatLinearFiltering = 1 << 7,
+    /// Probed by rendering into a float texture
+    FloatRenderTarget = 1 << 8,
+    /// Probed by rendering into a half-float texture
+    HalfFloatRenderTarget = 1 << 9,
+    AnisotropicFiltering = 1 << 10,
+    /// Strips and fans restarted by [PRIMITIVE_RESTART](crate::PRIMITIVE_RESTART) indices, WebGL2 only
+    PrimitiveRestart = 1 << 11,
+    /// `gl_FragDepthEXT` in fragment shaders, see [Feature::shader_directive]
+    FragDepth = 1 << 12,
+    /// `dFdx`, `dFdy` and `fwidth` in fragment shaders
+    StandardDerivatives = 1 << 13,
+    /// `texture2DLodEXT` and the other explicit LOD lookups in fragment shaders
+    ShaderTextureLod = 1 << 14,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 15] = [
+        Feature::WebGl2,
+        Feature::Instancing,
+        Feature::DepthTexture,
+        Feature::ElementIndexUint,
+        Feature::FloatTexture,
+        Feature::HalfFloatTexture,
+        Feature::FloatLinearFiltering,
+        Feature::HalfFloatLinearFiltering,
+        Feature::FloatRenderTarget,
+        Feature::HalfFloatRenderTarget,
+        Feature::AnisotropicFiltering,
+        Feature::PrimitiveRestart,
+        Feature::FragDepth,
+        Feature::StandardDerivatives,
+        Feature::ShaderTextureLod,
+    ];
+
+    /// Extension a WebGL1 fragment shader enables with `#extension <name> : enable`
+    /// to use the feature, it's core in WebGL2 `#version 300 es` shaders.
+    pub fn shader_directive(self) -> Option<&'static str> {
+        match self {
+            Feature::FragDepth => Some("GL_EXT_frag_depth"),
+            Feature::StandardDerivatives => Some("GL_OES_standard_derivatives"),
+            Feature::ShaderTextureLod => Some("GL_EXT_shader_texture_lod"),
+            _ => None,
+        }
+    }
+}
+
+/// Set of features, built with `|`, like `Feature::Instancing | Feature::DepthTexture`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Features(u32);
+
+impl Features {
+    pub fn empty() -> Features {
+        Features(0)
+    }
+
+    pub fn contains(self, features: impl Into<Features>) -> bool {
+        let features = features.into();
+        self.0 & features.0 == features.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Feature> {
+        Feature::ALL
+            .iter()
+            .copied()
+            .filter(move |feature| self.contains(*feature))
+    }
+}
+
+impl fmt::Debug for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl From<Feature> for Features {
+    fn from(feature: Feature) -> Self {
+        Features(feature as u32)
+    }
+}
+
+impl<T: Into<Features>> BitOr<T> for Feature {
+    type Output = Features;
+
+    fn bitor(self, other: T) -> Features {
+        Features(self as u32 | other.into().0)
+    }
+}
+
+impl<T: Into<Features>> BitOr<T> for Features {
+    type Output = Features;
+
+    fn bitor(self, other: T) -> Features {
+        Features(self.0 | other.into().0)
+    }
+}
+
+impl<T: Into<Features>> BitOrAssign<T> for Features {
+    fn bitor_assign(&mut self, other: T) {
+        self.0 |= other.into().0;
+    }
+}
+
+/// Features are checked once per context, [Gl::supports] caches the results.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FeatureCache {
+    checked: Features,
+    supported: Features,
+}
+
+impl FeatureCache {
+    pub fn get(&self, feature: Feature) -> Option<bool> {
+        if self.checked.contains(feature) {
+            Some(self.supported.contains(feature))
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, feature: Feature, supported: bool) {
+        self.checked |= feature;
+        if supported {
+            self.supported |= feature;
+        }
+    }
+}
+
+pub(crate) fn detect(gl: &Gl, feature: Feature) -> bool {
+    let webgl2 = gl.version() == WebGlVersion::WebGl2;
+    let extension = |name: &str| gl.extension_supported(name);
+    match feature {
+        Feature::WebGl2 => webgl2,
+        Feature::Instancing => webgl2 || extension("ANGLE_instanced_arrays"),
+        Feature::DepthTexture => gl.depth_texture_supported(),
+        Feature::ElementIndexUint => gl.element_index_uint_supported(),
+        Feature::FloatTexture => webgl2 || extension("OES_texture_float"),
+        Feature::HalfFloatTexture => webgl2 || extension("OES_texture_half_float"),
+        Feature::FloatLinearFiltering => extension("OES_texture_float_linear"),
+        Feature::HalfFloatLinearFiltering => webgl2 || extension("OES_texture_half_float_linear"),
+        Feature::FloatRenderTarget => {
+            gl.supports(Feature::FloatTexture) && probe(gl, TextureType::Float)
+        }
+        Feature::HalfFloatRenderTarget => {
+            gl.supports(Feature::HalfFloatTexture) && probe(gl, TextureType::HalfFloat)
+        }
+        Feature::AnisotropicFiltering => extension("EXT_texture_filter_anisotropic"),
+        Feature::PrimitiveRestart => webgl2,
+        Feature::FragDepth => webgl2 || extension("EXT_frag_depth"),
+        Feature::StandardDerivatives => webgl2 || extension("OES_standard_derivatives"),
+        Feature::ShaderTextureLod => webgl2 || extension("EXT_shader_texture_lod"),
+    }
+}
@@ -0,0 +1,222 @@
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::buffer_usage::BufferUsage;
+use crate::data_buffer::{ArrayBuffer, DynamicLayout, Item, VertexSource};
+use crate::gl::{Gl, GlError};
+
+struct FrameArenaData {
+    buffer: ArrayBuffer,
+    /// Data allocated in the current frame, in floats
+    staging: RefCell<Vec<f32>>,
+    /// Floats of the staging data already in the buffer
+    uploaded: Cell<usize>,
+    /// Buffer size in floats
+    capacity: Cell<usize>,
+    frame: Cell<u64>,
+}
+
+impl FrameArenaData {
+    /// Upload the data allocated since the last upload. The first upload in a frame reallocates
+    /// the buffer, so draws of the previous frame don't stall the new writes.
+    fn upload(&self) {
+        let staging = self.staging.borrow();
+        let uploaded = self.uploaded.get();
+        if uploaded == staging.len() {
+            return;
+        }
+        if uploaded == 0 || staging.len() > self.capacity.get() {
+            let capacity = self.capacity.get().max(staging.len().next_power_of_two());
+            trace_event!(
+                DEBUG,
+                id = self.buffer.id(),
+                capacity = capacity * 4,
+                "allocate frame arena"
+            );
+            let mut data = Vec::with_capacity(capacity);
+            data.extend_from_slice(&staging);
+            data.resize(capacity, 0.0);
+            self.buffer.set_content(&data, BufferUsage::Stream);
+            self.capacity.set(capacity);
+        } else {
+            self.buffer.set_sub_content(uploaded, &staging[uploaded..]);
+        }
+        self.uploaded.set(staging.len());
+    }
+}
+
+/// Streaming buffer for the vertex data changing every frame, like particles, text or debug lines.
+///
+/// Items allocated during the frame are uploaded together when the first of them is drawn,
+/// instead of creating or updating a buffer per object. Call [FrameArena::reset] at the start
+/// of every frame, slices allocated before it can't be drawn anymore.
+///
+/// ```ignore
+/// arena.reset();
+/// let lines = arena.alloc(&debug_lines);
+/// let sprites = arena.alloc(&sprite_vertices);
+/// program.draw_arrays(PrimitiveType::Lines, &uniforms, &lines)?;
+/// ```
+#[derive(Clone)]
+pub struct FrameArena {
+    data: Rc<FrameArenaData>,
+}
+
+impl FrameArena {
+    /// Arena with the initial `capacity` in bytes, it grows when a frame needs more.
+    pub fn new(gl: Gl, capacity: usize) -> Result<FrameArena, GlError> {
+        let capacity = capacity.div_ceil(4);
+        let buffer = ArrayBuffer::new(gl, &vec![0.0f32; capacity], BufferUsage::Stream)?;
+        Ok(FrameArena {
+            data: Rc::new(FrameArenaData {
+                buffer,
+                staging: RefCell::new(Vec::with_capacity(capacity)),
+                uploaded: Cell::new(0),
+                capacity: Cell::new(capacity),
+                frame: Cell::new(0),
+            }),
+        })
+    }
+
+    pub fn buffer(&self) -> &ArrayBuffer {
+        &self.data.buffer
+    }
+
+    /// Copy the items to the arena, the slice can be drawn until the next [FrameArena::reset].
+    pub fn alloc<T: Item>(&self, items: &[T]) -> FrameSlice<T> {
+        let stride = T::stride().max(1);
+        let mut staging = self.data.staging.borrow_mut();
+        // Slices start at a whole item, since attributes are addressed by the item index
+        let first = staging.len().div_ceil(stride);
+        staging.resize(first * stride, 0.0);
+        for item in items {
+            item.write(&mut staging);
+        }
+        FrameSlice {
+            arena: self.data.clone(),
+            frame: self.data.frame.get(),
+            first,
+            count: items.len(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Start a new frame, the allocated space is reused.
+    pub fn reset(&self) {
+        self.data.staging.borrow_mut().clear();
+        self.data.uploaded.set(0);
+        self.data.frame.set(self.data.frame.get() + 1);
+    }
+
+    /// Upload the allocated data now, otherwise it's uploaded when a slice is drawn.
+    pub fn upload(&self) {
+        self.data.upload();
+    }
+
+    /// Bytes allocated in the current frame
+    pub fn len(&self) -> usize {
+        self.data.staging.borrow().len() * 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.staging.borrow().is_empty()
+    }
+
+    /// Buffer size in bytes
+    pub fn capacity(&self) -> usize {
+        self.data.capacity.get() * 4
+    }
+}
+
+impl fmt::Debug for FrameArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameArena")
+            .field("buffer", &self.data.buffer)
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("frame", &self.data.frame.get())
+            .finish()
+    }
+}
+
+/// Items allocated in a [FrameArena], valid until the arena is reset.
+pub struct FrameSlice<T: Item> {
+    arena: Rc<FrameArenaData>,
+    frame: u64,
+    first: usize,
+    count: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Item> FrameSlice<T> {
+    pub fn buffer(&self) -> &ArrayBuffer {
+        &self.arena.buffer
+    }
+
+    /// Offset in items from the buffer start
+    pub fn first(&self) -> usize {
+        self.first
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The arena wasn't reset since the slice was allocated.
+    pub fn is_valid(&self) -> bool {
+        self.frame == self.arena.frame.get()
+    }
+}
+
+impl<T: Item> Clone for FrameSlice<T> {
+    fn clone(&self) -> Self {
+        FrameSlice {
+            arena: self.arena.clone(),
+            frame: self.frame,
+            first: self.first,
+            count: self.count,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Item> fmt::Debug for FrameSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameSlice")
+            .field("buffer", &self.arena.buffer.id())
+            .field("frame", &self.frame)
+            .field("first", &self.first)
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+impl<T: Item> VertexSource for FrameSlice<T> {
+    fn array_buffer(&self) -> &ArrayBuffer {
+        if !self.is_valid() {
+            trace_event!(ERROR, frame = self.frame, "frame slice used after reset");
+            debug_assert!(false, "frame slice used after the arena reset");
+        }
+        self.arena.upload();
+        &self.arena.buffer
+    }
+
+    fn layout(&self) -> Cow<'_, DynamicLayout> {
+        Cow::Owned(DynamicLayout::of::<T>())
+    }
+
+    fn first(&self) -> usize {
+        self.first
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+}
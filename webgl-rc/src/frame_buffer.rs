@@ -1,16 +1,45 @@
-use crate::{DepthBuffer, Gl, GlError, Settings, Texture};
+use crate::registry::{write_name, Resource, ResourceInfo, ResourceKind};
+use crate::{
+    Command, CubeFace, CubeMap, DepthBuffer, Gl, GlError, Settings, Texture, TextureFormat,
+};
+use std::cell::RefCell;
+use std::fmt;
 use std::rc::Rc;
-use web_sys::{WebGlFramebuffer, WebGlRenderingContext};
+use web_sys::WebGlRenderingContext;
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 struct FrameBufferInfo {
     gl: Gl,
-    handle: WebGlFramebuffer,
+    id: u64,
+    label: RefCell<Option<String>>,
+}
+
+impl fmt::Debug for FrameBufferInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameBufferInfo")
+            .field("id", &self.id)
+            .field("label", &self.label)
+            .finish()
+    }
 }
 
 impl Drop for FrameBufferInfo {
     fn drop(&mut self) {
-        self.gl.context().delete_framebuffer(Some(&self.handle));
+        trace_event!(DEBUG, id = self.id, "delete frame buffer");
+        self.gl.unregister(self.id);
+        self.gl.delete(Command::DeleteFramebuffer { id: self.id });
+    }
+}
+
+impl Resource for FrameBufferInfo {
+    /// Attachments are tracked separately
+    fn info(&self) -> ResourceInfo {
+        ResourceInfo {
+            id: self.id,
+            kind: ResourceKind::FrameBuffer,
+            size: 0,
+            label: self.label.borrow().clone(),
+        }
     }
 }
 
@@ -18,68 +47,247 @@ impl Drop for FrameBufferInfo {
 pub struct FrameBuffer {
     data: Rc<FrameBufferInfo>,
     color_buffer: Option<Texture>,
+    color_cube_face: Option<(CubeMap, CubeFace)>,
     depth_buffer: Option<DepthBuffer>,
+    depth_texture: Option<Texture>,
 }
 
 impl PartialEq for FrameBuffer {
     fn eq(&self, other: &Self) -> bool {
-        self.data.handle == other.data.handle
+        self.data.id == other.data.id
     }
 }
 
 impl Eq for FrameBuffer {}
 
+impl fmt::Display for FrameBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.data.label.borrow();
+        write_name(f, ResourceKind::FrameBuffer, self.data.id, label.as_deref())
+    }
+}
+
 impl FrameBuffer {
     pub fn new(gl: Gl) -> Result<FrameBuffer, GlError> {
+        let id = gl
+            .create_object(|id| Command::CreateFramebuffer { id })
+            .map_err(|_| GlError::FrameBufferError)?;
+        trace_event!(DEBUG, id, "create frame buffer");
+        let data = Rc::new(FrameBufferInfo {
+            gl: gl.clone(),
+            id,
+            label: Default::default(),
+        });
+        gl.register(id, &data);
         Ok(FrameBuffer {
-            data: Rc::new(FrameBufferInfo {
-                handle: gl
-                    .context()
-                    .create_framebuffer()
-                    .ok_or(GlError::FrameBufferError)?,
-                gl,
-            }),
+            data,
             color_buffer: None,
+            color_cube_face: None,
             depth_buffer: None,
+            depth_texture: None,
         })
     }
-    pub fn set_color_buffer(&mut self, texture: Option<Texture>) -> &mut Self {
+    /// Attachments should belong to the context of the frame buffer,
+    /// others fail with [GlError::WrongContext].
+    pub fn set_color_buffer(&mut self, texture: Option<Texture>) -> Result<&mut Self, GlError> {
+        if let Some(texture) = texture.as_ref() {
+            self.data.gl.check_own(&texture.gl(), texture)?;
+        }
         self.color_buffer = texture.clone();
+        self.color_cube_face = None;
         self.data
             .gl
             .apply(Gl::settings().frame_buffer(self.clone()), || {
-                self.data.gl.context().framebuffer_texture_2d(
-                    WebGlRenderingContext::FRAMEBUFFER,
-                    WebGlRenderingContext::COLOR_ATTACHMENT0,
-                    WebGlRenderingContext::TEXTURE_2D,
-                    self.color_buffer.as_ref().map(|buffer| buffer.handle()),
-                    0,
-                );
+                self.data.gl.execute(Command::FramebufferTexture2d {
+                    attachment: WebGlRenderingContext::COLOR_ATTACHMENT0,
+                    target: WebGlRenderingContext::TEXTURE_2D,
+                    texture: self.color_buffer.as_ref().map(|buffer| buffer.id()),
+                });
             });
-        self
+        Ok(self)
+    }
+    /// Render into a face of the cube map, replaces the color buffer.
+    pub fn set_color_cube_face(
+        &mut self,
+        cube_map: CubeMap,
+        face: CubeFace,
+    ) -> Result<&mut Self, GlError> {
+        self.data.gl.check_own(&cube_map.gl(), &cube_map)?;
+        self.color_buffer = None;
+        self.color_cube_face = Some((cube_map, face));
+        self.data
+            .gl
+            .apply(Gl::settings().frame_buffer(self.clone()), || {
+                self.data.gl.execute(Command::FramebufferTexture2d {
+                    attachment: WebGlRenderingContext::COLOR_ATTACHMENT0,
+                    target: face.into(),
+                    texture: self
+                        .color_cube_face
+                        .as_ref()
+                        .map(|(cube_map, _)| cube_map.id()),
+                });
+            });
+        Ok(self)
+    }
+    pub fn set_depth_buffer(&mut self, buffer: Option<DepthBuffer>) -> Result<&mut Self, GlError> {
+        if let Some(buffer) = buffer.as_ref() {
+            self.data.gl.check_own(&buffer.gl(), buffer)?;
+        }
+        let previous = std::mem::replace(&mut self.depth_buffer, buffer);
+        self.data
+            .gl
+            .apply(Gl::settings().frame_buffer(self.clone()), || {
+                let gl = &self.data.gl;
+                let attachment = self
+                    .depth_buffer
+                    .as_ref()
+                    .map(|buffer| buffer.format().attachment());
+                if let Some(previous) = previous {
+                    let previous_attachment = previous.format().attachment();
+                    if Some(previous_attachment) != attachment {
+                        gl.execute(Command::FramebufferRenderbuffer {
+                            attachment: previous_attachment,
+                            renderbuffer: None,
+                        });
+                    }
+                }
+                if let Some(buffer) = self.depth_buffer.as_ref() {
+                    gl.execute(Command::FramebufferRenderbuffer {
+                        attachment: buffer.format().attachment(),
+                        renderbuffer: Some(buffer.id()),
+                    });
+                }
+            });
+        Ok(self)
     }
-    pub fn set_depth_buffer(&mut self, buffer: Option<DepthBuffer>) -> &mut Self {
-        self.depth_buffer = buffer;
+    /// Attach a depth texture, it should have `Depth` or `DepthStencil` format.
+    pub fn set_depth_texture(&mut self, texture: Option<Texture>) -> Result<&mut Self, GlError> {
+        if let Some(texture) = texture.as_ref() {
+            if !texture.format().is_depth() {
+                return Err(GlError::UnsupportedType(Some(format!(
+                    "{}: depth texture format expected, {:?} found",
+                    texture,
+                    texture.format()
+                ))));
+            }
+            self.data.gl.check_own(&texture.gl(), texture)?;
+        }
+
+        let previous = std::mem::replace(&mut self.depth_texture, texture);
         self.data
             .gl
             .apply(Gl::settings().frame_buffer(self.clone()), || {
-                self.data.gl.context().framebuffer_renderbuffer(
-                    WebGlRenderingContext::FRAMEBUFFER,
-                    WebGlRenderingContext::DEPTH_ATTACHMENT,
-                    WebGlRenderingContext::RENDERBUFFER,
-                    self.depth_buffer.as_ref().map(|buffer| buffer.handle()),
-                );
+                let gl = &self.data.gl;
+                let attachment = self
+                    .depth_texture
+                    .as_ref()
+                    .map(|texture| Self::depth_texture_attachment(texture.format()));
+                if let Some(previous) = previous {
+                    let previous_attachment = Self::depth_texture_attachment(previous.format());
+                    if Some(previous_attachment) != attachment {
+                        gl.execute(Command::FramebufferTexture2d {
+                            attachment: previous_attachment,
+                            target: WebGlRenderingContext::TEXTURE_2D,
+                            texture: None,
+                        });
+                    }
+                }
+                if let Some(texture) = self.depth_texture.as_ref() {
+                    gl.execute(Command::FramebufferTexture2d {
+                        attachment: Self::depth_texture_attachment(texture.format()),
+                        target: WebGlRenderingContext::TEXTURE_2D,
+                        texture: Some(texture.id()),
+                    });
+                }
             });
+        Ok(self)
+    }
+
+    fn depth_texture_attachment(format: TextureFormat) -> u32 {
+        if format == TextureFormat::DepthStencil {
+            WebGlRenderingContext::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            WebGlRenderingContext::DEPTH_ATTACHMENT
+        }
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.data.gl.clone()
+    }
+
+    pub fn id(&self) -> u64 {
+        self.data.id
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.label.borrow().clone()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.data.label.replace(Some(label.into()));
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
         self
     }
+
     pub fn color_buffer(&self) -> Option<Texture> {
         self.color_buffer.clone()
     }
+    pub fn color_cube_face(&self) -> Option<(CubeMap, CubeFace)> {
+        self.color_cube_face.clone()
+    }
+    /// Size of the color attachment, either a texture or a cube map face.
+    pub fn color_size(&self) -> Option<(u32, u32)> {
+        match (&self.color_buffer, &self.color_cube_face) {
+            (Some(texture), _) => Some(texture.size()),
+            (None, Some((cube_map, _))) => Some((cube_map.size(), cube_map.size())),
+            (None, None) => None,
+        }
+    }
     pub fn depth_buffer(&self) -> Option<DepthBuffer> {
         self.depth_buffer.clone()
     }
+    pub fn depth_texture(&self) -> Option<Texture> {
+        self.depth_texture.clone()
+    }
+
+    /// Check the frame buffer status, returns the status code if it's incomplete.
+    /// Color attachments of non-renderable formats are reported before querying the status.
+    pub fn check_status(&self) -> Result<(), GlError> {
+        self.check_color_renderable()?;
+        let gl = &self.data.gl;
+        let status = gl.apply(Gl::settings().frame_buffer(self.clone()), || {
+            gl.frame_buffer_status()
+        });
+        if status == WebGlRenderingContext::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(GlError::FrameBufferIncomplete(status).with_context(format!("checking {}", self)))
+        }
+    }
 
-    pub(crate) fn handle(&self) -> &WebGlFramebuffer {
-        &self.data.handle
+    pub(crate) fn check_color_renderable(&self) -> Result<(), GlError> {
+        let color = match (&self.color_buffer, &self.color_cube_face) {
+            (Some(texture), _) => {
+                Some((texture.to_string(), texture.format(), texture.data_type()))
+            }
+            (None, Some((cube_map, _))) => Some((
+                cube_map.to_string(),
+                cube_map.format(),
+                cube_map.data_type(),
+            )),
+            (None, None) => None,
+        };
+        match color {
+            Some((name, format, data_type)) if !format.is_color_renderable(data_type) => {
+                Err(GlError::UnsupportedType(Some(format!(
+                    "{}: {:?} format with {:?} data type can't be rendered into",
+                    name, format, data_type
+                ))))
+            }
+            _ => Ok(()),
+        }
     }
 }
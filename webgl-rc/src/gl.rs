@@ -1,18 +1,42 @@
-use js_sys::JsString;
-use std::cell::RefCell;
-use std::rc::Rc;
+use js_sys::{JsString, Uint8Array};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::{Rc, Weak};
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
-    AngleInstancedArrays, ExtColorBufferHalfFloat, HtmlCanvasElement, OesElementIndexUint,
-    OesTextureHalfFloat, OesTextureHalfFloatLinear, WebGlRenderingContext as Context,
+    AngleInstancedArrays, ExtColorBufferFloat, ExtColorBufferHalfFloat, ExtFragDepth,
+    ExtShaderTextureLod, ExtTextureFilterAnisotropic, HtmlCanvasElement, ImageBitmap,
+    OesElementIndexUint, OesStandardDerivatives, OesTextureFloat, OesTextureFloatLinear,
+    OesTextureHalfFloat, OesTextureHalfFloatLinear, OffscreenCanvas, WebGl2RenderingContext,
+    WebGlRenderingContext as Context, WebglColorBufferFloat, WebglDepthTexture, WebglLoseContext,
 };
 
-use super::data_buffer::ItemsBuffer;
-use super::program::Program;
-use super::settings::{EmptySetting, Settings, SettingsCache};
-use super::texture::{Texture, TextureContent, TextureFormat, TextureType};
+use super::data_buffer::{DynamicLayout, ItemsBuffer, RawItemsBuffer};
+use super::frame_arena::FrameArena;
+use super::program::{Program, ProgramBuilder, Shader};
+use super::settings::{
+    ApplyStrategy, CachedSettings, ClearColorSetting, ClearDepthSetting, ClearStencilSetting,
+    ColorMask, CullFace, DepthFunction, DepthMask, EmptySetting, PixelStore, Settings,
+    SettingsCache, StencilMask, ViewportSetting,
+};
+use super::texture::{Texture, TextureContent, TextureFormat, TextureType, TEXTURES_COUNT};
+use crate::auto_uniforms::{FrameClock, FrameInfo};
+use crate::backend::{Backend, LinkOptions, PixelData, ProgramLayout, TextureSource};
 use crate::buffer_usage::BufferUsage;
-use crate::{DepthBuffer, ElementsBuffer, FrameBuffer};
+use crate::extensions::Extension;
+use crate::features::{Feature, FeatureCache, Features};
+use crate::gl_registry::SharedSources;
+use crate::precision::RenderPrecision;
+use crate::presets::Preset;
+use crate::recorder::{Command, Recorder, Recording};
+use crate::registry::{BufferStats, MemoryUsage, Registry, Resource, Resources};
+use crate::state::{StateDump, StateEntry, StateValue};
+use crate::webgl_backend::WebGlBackend;
+use crate::{
+    CubeMap, DepthBuffer, DepthBufferFormat, DepthReader, ElementsBuffer, FrameBuffer, ShadowMap,
+};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum GlError {
@@ -38,11 +62,95 @@ pub enum GlError {
     },
     DepthBufferError,
     FrameBufferError,
+    FrameBufferIncomplete(u32),
+    InvalidRecording(String),
+    ObjectNotFound(u64),
+    ParseError(String),
+    /// The resource is used with a context other than the one it's created by
+    WrongContext(String),
+    /// No preset is registered with the name, see [Gl::register_preset]
+    UnknownPreset(String),
+    /// The context is lost, rendering should be paused until it's restored
+    ContextLost,
+    /// The operation requires a feature the context doesn't have
+    FeatureNotSupported(Feature),
+    /// Draw parameters which would produce wrong output, indices are checked in debug builds only
+    InvalidDraw(String),
+    /// The draw uses more textures and cube maps than there are texture units
+    TooManyTextures {
+        required: usize,
+        available: usize,
+    },
+    /// Operation which failed because of the `source` error, see [GlError::with_context]
+    Context {
+        context: String,
+        source: Box<GlError>,
+    },
+}
+
+impl GlError {
+    /// Wrap the error with a description of the failed operation,
+    /// like `"uploading texture 'albedo'"`.
+    pub fn with_context(self, context: impl Into<String>) -> GlError {
+        GlError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The innermost error, without contexts.
+    pub fn root_cause(&self) -> &GlError {
+        match self {
+            GlError::Context { source, .. } => source.root_cause(),
+            error => error,
+        }
+    }
+
+    /// Contexts from the outermost to the innermost one.
+    pub fn contexts(&self) -> Vec<&str> {
+        let mut result = Vec::new();
+        let mut error = self;
+        while let GlError::Context { context, source } = error {
+            result.push(context.as_str());
+            error = source;
+        }
+        result
+    }
+}
+
+/// Contexts separated by colons, followed by the debug form of the root cause.
+impl fmt::Display for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlError::Context { context, source } => write!(f, "{}: {}", context, source),
+            error => write!(f, "{:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for GlError {}
+
+/// Adds a context to the error of a `Result`, see [GlError::with_context].
+pub trait ErrorContext<T> {
+    fn context(self, context: &str) -> Result<T, GlError>;
+
+    /// The context is built only on errors.
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, GlError>;
+}
+
+impl<T> ErrorContext<T> for Result<T, GlError> {
+    fn context(self, context: &str) -> Result<T, GlError> {
+        self.map_err(|error| error.with_context(context))
+    }
+
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, GlError> {
+        self.map_err(|error| error.with_context(context()))
+    }
 }
 
 impl From<GlError> for js_sys::Error {
     fn from(error: GlError) -> Self {
-        js_sys::Error::new(&format!("{:?}", error))
+        js_sys::Error::new(&error.to_string())
     }
 }
 
@@ -66,15 +174,62 @@ impl Into<GlError> for JsValue {
     }
 }
 
+/// WebGL version of the context.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WebGlVersion {
+    /// WebGL1, instancing, half-float textures and 32-bit indices depend on the extensions
+    #[default]
+    WebGl1,
+    WebGl2,
+}
+
+/// Values to clear the buffers with, `None` leaves the buffer untouched.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClearParams {
+    pub color: Option<[f32; 4]>,
+    pub depth: Option<f32>,
+    pub stencil: Option<i32>,
+}
+
+impl ClearParams {
+    pub fn mask(&self) -> u32 {
+        let mut mask = 0;
+        if self.color.is_some() {
+            mask |= Context::COLOR_BUFFER_BIT;
+        }
+        if self.depth.is_some() {
+            mask |= Context::DEPTH_BUFFER_BIT;
+        }
+        if self.stencil.is_some() {
+            mask |= Context::STENCIL_BUFFER_BIT;
+        }
+        mask
+    }
+}
+
 #[derive(Debug)]
 pub(self) struct GlInfo {
-    pub(crate) context: Context,
+    pub(self) backend: Box<dyn Backend>,
     pub(self) settings_cache: RefCell<SettingsCache>,
-    pub(self) ex_instanced_arrays: AngleInstancedArrays,
-    pub(self) ex_color_buffer_half_float: ExtColorBufferHalfFloat,
-    pub(self) ex_texture_half_float: OesTextureHalfFloat,
-    pub(self) ex_texture_half_float_linear: OesTextureHalfFloatLinear,
-    pub(self) ex_element_index_uint: OesElementIndexUint,
+    pub(self) registry: Registry,
+    pub(self) last_id: Cell<u64>,
+    pub(self) recording: RefCell<Option<Vec<Command>>>,
+    pub(self) shared_sources: RefCell<Option<Rc<SharedSources>>>,
+    pub(self) render_precision: Cell<Option<RenderPrecision>>,
+    pub(self) features: Cell<FeatureCache>,
+    pub(self) texture_units: Cell<Option<u32>>,
+    pub(self) auto_uniforms: Cell<bool>,
+    pub(self) frame_clock: Cell<FrameClock>,
+    /// Compiled shaders by the stage and the source, alive while a program uses them
+    pub(self) shader_cache: RefCell<HashMap<(u32, String), Weak<Shader>>>,
+    pub(self) presets: RefCell<HashMap<String, Preset>>,
+    pub(self) apply_strategy: Cell<ApplyStrategy>,
+    /// Nesting of the [Gl::apply] calls, deletions are deferred while it's above zero
+    pub(self) apply_depth: Cell<u32>,
+    /// Deletions of the resources dropped inside [Gl::apply] calls, see [Gl::collect]
+    pub(self) pending_deletions: RefCell<Vec<Command>>,
+    /// Divisors set for the attribute locations, missing ones are zero
+    pub(self) attribute_divisors: RefCell<HashMap<u32, u32>>,
 }
 
 #[derive(Clone, Debug)]
@@ -82,62 +237,893 @@ pub struct Gl {
     data: Rc<GlInfo>,
 }
 
-impl Gl {
-    fn get_extension<Ex: JsCast>(context: &Context, name: &str) -> Result<Ex, GlError> {
-        context
-            .get_extension(name)
-            .map_err(|_| GlError::ExtensionNotFound(name.into()))
-            .map(|value| match value {
-                Some(extension) => Ok(Ex::unchecked_from_js(extension.into())),
-                None => Err(GlError::ExtensionNotFound(name.into())),
-            })
-            .unwrap_or_else(|error| Err(error))
+/// Handles of the same context are equal
+impl PartialEq for Gl {
+    fn eq(&self, other: &Gl) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
     }
+}
 
+impl Eq for Gl {}
+
+impl Gl {
     pub fn new(canvas: &HtmlCanvasElement) -> Result<Gl, GlError> {
-        let context = canvas
-            .get_context("webgl")
+        Gl::with_version(canvas, WebGlVersion::WebGl1)
+    }
+
+    /// Create a context of the specific version. A canvas keeps the first context created on it,
+    /// so falling back to WebGL1 on the same canvas works only if WebGL2 isn't available.
+    pub fn with_version(canvas: &HtmlCanvasElement, version: WebGlVersion) -> Result<Gl, GlError> {
+        let name = match version {
+            WebGlVersion::WebGl1 => "webgl",
+            WebGlVersion::WebGl2 => "webgl2",
+        };
+        Gl::from_context(canvas.get_context(name), version)
+    }
+
+    /// Create a context on an `OffscreenCanvas`, like the one transferred into a worker,
+    /// see [RenderProxy](crate::RenderProxy).
+    pub fn with_offscreen_canvas(
+        canvas: &OffscreenCanvas,
+        version: WebGlVersion,
+    ) -> Result<Gl, GlError> {
+        let name = match version {
+            WebGlVersion::WebGl1 => "webgl",
+            WebGlVersion::WebGl2 => "webgl2",
+        };
+        Gl::from_context(
+            canvas.get_context(name).map(|v| v.map(JsValue::from)),
+            version,
+        )
+    }
+
+    fn from_context(
+        context: Result<Option<impl Into<JsValue>>, JsValue>,
+        version: WebGlVersion,
+    ) -> Result<Gl, GlError> {
+        let context: JsValue = context
             .map_err(|err| GlError::UnknownError(Some(JsString::from(err).into())))?
-            .map(|context| Context::from(JsValue::from(context)))
-            .ok_or_else(|| GlError::UnknownError(None))?;
+            .map(Into::into)
+            .ok_or(GlError::UnknownError(None))?;
+
+        let backend = match version {
+            WebGlVersion::WebGl1 => WebGlBackend::webgl1(context.into())?,
+            WebGlVersion::WebGl2 => WebGlBackend::webgl2(context.into())?,
+        };
+        Ok(Gl::with_backend(Box::new(backend)))
+    }
 
-        Ok(Gl {
+    /// Context without WebGL behind it, so the code built on top of it can be tested
+    /// with plain `cargo test`. Commands are not executed, use [Gl::record] to inspect them.
+    /// Readbacks return zeros, the drawing buffer is 300×150 like the one of a default canvas.
+    #[cfg(feature = "mock")]
+    pub fn mock() -> Gl {
+        Gl::with_backend(Box::new(crate::mock_backend::MockBackend::default()))
+    }
+
+    /// Mock context lacking the extensions, commands which depend on them fail
+    /// like they do in WebGL1, e.g. divisors without `ANGLE_instanced_arrays`.
+    #[cfg(feature = "mock")]
+    pub fn mock_without_extensions(extensions: &[&str]) -> Gl {
+        Gl::with_backend(Box::new(crate::mock_backend::MockBackend::without_extensions(
+            extensions,
+        )))
+    }
+
+    fn with_backend(backend: Box<dyn Backend>) -> Gl {
+        // The viewport starts covering the drawing buffer, the cache should match it,
+        // so that restoring and fractions of the initial viewport work
+        let settings_cache = RefCell::new(SettingsCache::default());
+        let (width, height) = backend.drawing_buffer_size();
+        ViewportSetting::write_cached(
+            &mut settings_cache.borrow_mut(),
+            &ViewportSetting {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+        );
+        Gl {
             data: Rc::new(GlInfo {
-                ex_instanced_arrays: Gl::get_extension(&context, "ANGLE_instanced_arrays")?,
-                ex_color_buffer_half_float: Gl::get_extension(
-                    &context,
-                    "EXT_color_buffer_half_float",
-                )?,
-                ex_texture_half_float: Gl::get_extension(&context, "OES_texture_half_float")?,
-                ex_texture_half_float_linear: Gl::get_extension(
-                    &context,
-                    "OES_texture_half_float_linear",
-                )?,
-                ex_element_index_uint: Gl::get_extension(&context, "OES_element_index_uint")?,
-                settings_cache: Default::default(),
-                context,
+                backend,
+                settings_cache,
+                registry: Default::default(),
+                last_id: Default::default(),
+                recording: Default::default(),
+                shared_sources: Default::default(),
+                render_precision: Default::default(),
+                features: Default::default(),
+                texture_units: Default::default(),
+                auto_uniforms: Default::default(),
+                frame_clock: Default::default(),
+                shader_cache: Default::default(),
+                presets: Default::default(),
+                apply_strategy: Default::default(),
+                apply_depth: Default::default(),
+                pending_deletions: Default::default(),
+                attribute_divisors: Default::default(),
             }),
-        })
+        }
+    }
+
+    pub(crate) fn set_shared_sources(&self, sources: Option<Rc<SharedSources>>) {
+        self.data.shared_sources.replace(sources);
+    }
+
+    /// Shader source as it's compiled, preprocessed by the [GlRegistry](crate::GlRegistry)
+    /// the context belongs to.
+    pub(crate) fn shader_source<'a>(&self, source: &'a str) -> Cow<'a, str> {
+        match self.data.shared_sources.borrow().as_ref() {
+            Some(shared) => Cow::Owned(shared.source(source).to_string()),
+            None => Cow::Borrowed(source),
+        }
+    }
+
+    /// Resources can be used only with the context they're created by. Draws and frame buffer
+    /// attachments check it with [Gl::check_own] and fail, settings applied directly assert it
+    /// in debug builds and bind null objects in place of the foreign resources.
+    pub(crate) fn is_own<R: fmt::Display>(&self, owner: &Gl, resource: &R) -> bool {
+        match self.check_own(owner, resource) {
+            Ok(()) => true,
+            Err(error) => {
+                trace_event!(ERROR, %error, "resource of another context");
+                debug_assert!(false, "{}", error);
+                false
+            }
+        }
+    }
+
+    /// [GlError::WrongContext] if the resource belongs to another context.
+    pub(crate) fn check_own<R: fmt::Display>(
+        &self,
+        owner: &Gl,
+        resource: &R,
+    ) -> Result<(), GlError> {
+        if self == owner {
+            Ok(())
+        } else {
+            Err(GlError::WrongContext(resource.to_string()))
+        }
+    }
+
+    /// Unique identifier for a new GL object,
+    /// it's used to reference the object in commands, traces and recordings.
+    fn next_id(&self) -> u64 {
+        let id = self.data.last_id.get() + 1;
+        self.data.last_id.set(id);
+        id
+    }
+
+    /// Track the resource in the registry, it should be removed with [Gl::unregister] on drop.
+    pub(crate) fn register<R: Resource + 'static>(&self, id: u64, resource: &Rc<R>) {
+        let resource: Rc<dyn Resource> = resource.clone();
+        self.data.registry.insert(id, Rc::downgrade(&resource));
+    }
+
+    pub(crate) fn unregister(&self, id: u64) {
+        self.data.registry.remove(id);
+    }
+
+    /// Live resources created with the context, useful to find leaks caused by retained handles.
+    pub fn resources(&self) -> Resources {
+        self.data.registry.snapshot()
+    }
+
+    /// Estimated memory of the live textures and buffers.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.resources().memory_usage()
+    }
+
+    /// Usage hints, sizes and upload counts of the live array and elements buffers.
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.data.registry.buffer_stats()
     }
 
+    /// Add the command to the active recording, the closure is called only while recording.
+    pub(crate) fn record_command(&self, command: impl FnOnce() -> Command) {
+        if let Some(commands) = self.data.recording.borrow_mut().as_mut() {
+            commands.push(command());
+        }
+    }
+
+    pub(crate) fn swap_recording(&self, value: Option<Vec<Command>>) -> Option<Vec<Command>> {
+        self.data.recording.replace(value)
+    }
+
+    /// Record the commands issued in the callback, see [Recorder].
+    pub fn record<R>(&self, callback: impl FnOnce() -> R) -> (R, Recording) {
+        let recorder = Recorder::start(self);
+        let result = callback();
+        (result, recorder.finish())
+    }
+
+    pub(crate) fn try_execute(&self, command: Command) -> Result<(), GlError> {
+        self.data
+            .backend
+            .execute(&command)
+            .map_err(|error| self.lost_or(error))?;
+        self.record_command(|| command);
+        Ok(())
+    }
+
+    /// Label the commands issued by the callback, like `"shadow pass"`. Groups nest,
+    /// they're shown in the trace spans, recordings and the console of debug builds.
+    pub fn debug_group<R>(&self, name: &str, callback: impl FnOnce() -> R) -> R {
+        let _span = trace_span!(DEBUG, "debug_group", name);
+        self.execute(Command::PushDebugGroup { name: name.into() });
+        let result = callback();
+        self.execute(Command::PopDebugGroup {});
+        result
+    }
+
+    /// Set the divisor of the attribute location if it differs from the current one,
+    /// so draws without instancing don't need `ANGLE_instanced_arrays` in WebGL1.
+    pub(crate) fn set_attribute_divisor(&self, location: u32, divisor: u32) {
+        let previous = self
+            .data
+            .attribute_divisors
+            .borrow_mut()
+            .insert(location, divisor)
+            .unwrap_or(0);
+        if previous != divisor {
+            self.execute(Command::VertexAttribDivisor { location, divisor });
+        }
+    }
+
+    /// Execute a command which can't fail, i.e. anything except object creation.
+    /// Commands of a lost context are ignored by WebGL.
+    pub(crate) fn execute(&self, command: Command) {
+        let result = self.try_execute(command);
+        debug_assert!(
+            matches!(result, Ok(()) | Err(GlError::ContextLost)),
+            "{:?}",
+            result
+        );
+    }
+
+    /// Delete the GL object of a dropped resource. Inside [Gl::apply] the deletion is queued,
+    /// since the settings being restored may still reference the object,
+    /// it's done when the outermost `apply` returns or by [Gl::collect].
+    pub(crate) fn delete(&self, command: Command) {
+        if self.data.apply_depth.get() > 0 {
+            trace_event!(TRACE, ?command, "defer deletion");
+            self.data.pending_deletions.borrow_mut().push(command);
+        } else {
+            self.execute(command);
+        }
+    }
+
+    /// Delete the GL objects of the resources dropped inside [Gl::apply] calls.
+    /// It's done automatically when the outermost `apply` returns and in [Gl::begin_frame],
+    /// call it to release the memory earlier, like after dropping large textures
+    /// inside a long running `apply` scope. The settings cache keeps the resources it binds
+    /// alive, so the deleted objects are never bound by the cached settings.
+    pub fn collect(&self) {
+        let pending = self.data.pending_deletions.take();
+        if !pending.is_empty() {
+            trace_event!(DEBUG, count = pending.len(), "collect deleted resources");
+        }
+        for command in pending {
+            self.execute(command);
+        }
+    }
+
+    /// Number of deletions waiting for [Gl::collect].
+    pub fn pending_deletions(&self) -> usize {
+        self.data.pending_deletions.borrow().len()
+    }
+
+    /// [GlError::ContextLost] when the context is lost, checked before draws and uploads,
+    /// so a lost context is reported instead of the failures it causes.
+    pub fn check_context(&self) -> Result<(), GlError> {
+        if self.data.backend.is_context_lost() {
+            // Shaders of the lost context can't be linked into new programs
+            self.data.shader_cache.borrow_mut().clear();
+            Err(GlError::ContextLost)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Errors of a lost context are replaced with [GlError::ContextLost].
+    fn lost_or(&self, error: GlError) -> GlError {
+        match self.check_context() {
+            Err(lost) => lost,
+            Ok(()) => error,
+        }
+    }
+
+    pub(crate) fn cached_shader(&self, shader_type: u32, source: &str) -> Option<Rc<Shader>> {
+        self.data
+            .shader_cache
+            .borrow()
+            .get(&(shader_type, source.to_string()))
+            .and_then(|shader| shader.upgrade())
+    }
+
+    pub(crate) fn cache_shader(&self, shader: &Rc<Shader>) {
+        self.data.shader_cache.borrow_mut().insert(
+            (shader.shader_type(), shader.source().to_string()),
+            Rc::downgrade(shader),
+        );
+    }
+
+    /// Remove the entry of a dropped shader.
+    pub(crate) fn uncache_shader(&self, shader_type: u32, source: &str) {
+        let mut cache = self.data.shader_cache.borrow_mut();
+        let key = (shader_type, source.to_string());
+        if cache
+            .get(&key)
+            .is_some_and(|shader| shader.upgrade().is_none())
+        {
+            cache.remove(&key);
+        }
+    }
+
+    /// Create an object with the command built for a new id.
+    pub(crate) fn create_object(
+        &self,
+        command: impl FnOnce(u64) -> Command,
+    ) -> Result<u64, GlError> {
+        self.check_context()?;
+        let id = self.next_id();
+        self.try_execute(command(id))?;
+        Ok(id)
+    }
+
+    pub(crate) fn compile_shader(&self, shader_type: u32, source: &str) -> Result<u64, GlError> {
+        self.check_context()?;
+        let id = self.next_id();
+        self.data
+            .backend
+            .compile_shader(id, shader_type, source)
+            .map_err(|error| self.lost_or(error))?;
+        self.record_command(|| Command::CreateShader { id, shader_type });
+        Ok(id)
+    }
+
+    pub(crate) fn link_program(
+        &self,
+        vertex_shader: u64,
+        fragment_shader: u64,
+        options: &LinkOptions,
+    ) -> Result<(u64, ProgramLayout), GlError> {
+        let id = self.next_id();
+        let layout = self
+            .data
+            .backend
+            .link_program(id, vertex_shader, fragment_shader, options)
+            .map_err(|error| self.lost_or(error))?;
+        self.record_command(|| Command::CreateProgram {
+            id,
+            vertex_shader,
+            fragment_shader,
+        });
+        Ok((id, layout))
+    }
+
+    pub(crate) fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        self.record_command(|| Command::BufferData {
+            target,
+            size: data.len(),
+            usage,
+        });
+        self.data.backend.buffer_data(target, data, usage);
+    }
+
+    pub(crate) fn buffer_sub_data(&self, target: u32, offset: usize, data: &[u8]) {
+        self.record_command(|| Command::BufferSubData {
+            target,
+            offset,
+            size: data.len(),
+        });
+        self.data.backend.buffer_sub_data(target, offset, data);
+    }
+
+    /// Upload the bound texture, `target` is `TEXTURE_2D` or a cube map face
+    pub(crate) fn tex_image(
+        &self,
+        target: u32,
+        format: u32,
+        width: u32,
+        height: u32,
+        data_type: u32,
+        source: TextureSource,
+    ) -> Result<(), GlError> {
+        self.check_context()?;
+        self.data
+            .backend
+            .tex_image(target, format, width, height, data_type, source)
+            .map_err(|error| self.lost_or(error))?;
+        self.record_command(|| Command::TexImage2d {
+            target,
+            level: 0,
+            format,
+            width,
+            height,
+            data_type,
+        });
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn tex_sub_image(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        format: u32,
+        data_type: u32,
+        data: PixelData,
+    ) -> Result<(), GlError> {
+        self.check_context()?;
+        self.data
+            .backend
+            .tex_sub_image(x, y, width, height, format, data_type, data)
+            .map_err(|error| self.lost_or(error))?;
+        self.record_command(|| Command::TexSubImage2d {
+            target: Context::TEXTURE_2D,
+            level: 0,
+            x,
+            y,
+            width,
+            height,
+            format,
+            data_type,
+        });
+        Ok(())
+    }
+
+    fn record_read_pixels(&self, x: i32, y: i32, width: u32, height: u32) {
+        self.record_command(|| Command::ReadPixels {
+            x,
+            y,
+            width: width as i32,
+            height: height as i32,
+            format: Context::RGBA,
+            data_type: Context::UNSIGNED_BYTE,
+        });
+    }
+
+    /// Read RGBA 8-bit pixels of the current frame buffer
+    pub(crate) fn read_pixels(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        output: &mut [u8],
+    ) -> Result<(), GlError> {
+        self.check_context()?;
+        self.record_read_pixels(x, y, width, height);
+        self.data
+            .backend
+            .read_pixels(x, y, width, height, output)
+            .map_err(|error| self.lost_or(error))
+    }
+
+    pub(crate) fn read_pixels_into_buffer(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        output: &Uint8Array,
+    ) -> Result<(), GlError> {
+        self.check_context()?;
+        self.record_read_pixels(x, y, width, height);
+        self.data
+            .backend
+            .read_pixels_into_buffer(x, y, width, height, output)
+            .map_err(|error| self.lost_or(error))
+    }
+
+    /// Status of the current frame buffer
+    pub(crate) fn frame_buffer_status(&self) -> u32 {
+        self.data.backend.frame_buffer_status()
+    }
+
+    /// # Panics
+    /// If the context is not backed by WebGL, like [Gl::mock].
     pub fn context(&self) -> &Context {
-        &self.data.context
+        self.data
+            .backend
+            .context()
+            .expect("The context isn't backed by WebGL")
     }
 
+    /// WebGL2 interface of the context, `None` for WebGL1 contexts.
+    pub fn webgl2_context(&self) -> Option<&WebGl2RenderingContext> {
+        self.data.backend.webgl2_context()
+    }
+
+    /// Extension object, requested on the first call and memoized. Extensions promoted
+    /// to the core aren't available in WebGL2, see [Extension::is_core].
+    pub fn extension<T: JsCast>(&self, extension: Extension) -> Result<&T, GlError> {
+        self.data
+            .backend
+            .extension(extension)
+            .map(|value| value.unchecked_ref())
+            .ok_or_else(|| GlError::ExtensionNotFound(extension.name().into()))
+    }
+
+    pub fn ext_instanced_arrays(&self) -> Result<&AngleInstancedArrays, GlError> {
+        self.extension(Extension::AngleInstancedArrays)
+    }
+
+    pub fn ext_standard_derivatives(&self) -> Result<&OesStandardDerivatives, GlError> {
+        self.extension(Extension::OesStandardDerivatives)
+    }
+
+    pub fn ext_shader_texture_lod(&self) -> Result<&ExtShaderTextureLod, GlError> {
+        self.extension(Extension::ExtShaderTextureLod)
+    }
+
+    pub fn ext_frag_depth(&self) -> Result<&ExtFragDepth, GlError> {
+        self.extension(Extension::ExtFragDepth)
+    }
+
+    pub fn ext_depth_texture(&self) -> Result<&WebglDepthTexture, GlError> {
+        self.extension(Extension::WebglDepthTexture)
+    }
+
+    pub fn ext_element_index_uint(&self) -> Result<&OesElementIndexUint, GlError> {
+        self.extension(Extension::OesElementIndexUint)
+    }
+
+    pub fn ext_texture_float(&self) -> Result<&OesTextureFloat, GlError> {
+        self.extension(Extension::OesTextureFloat)
+    }
+
+    pub fn ext_texture_float_linear(&self) -> Result<&OesTextureFloatLinear, GlError> {
+        self.extension(Extension::OesTextureFloatLinear)
+    }
+
+    pub fn ext_texture_half_float(&self) -> Result<&OesTextureHalfFloat, GlError> {
+        self.extension(Extension::OesTextureHalfFloat)
+    }
+
+    pub fn ext_texture_half_float_linear(&self) -> Result<&OesTextureHalfFloatLinear, GlError> {
+        self.extension(Extension::OesTextureHalfFloatLinear)
+    }
+
+    pub fn ext_color_buffer_float(&self) -> Result<&ExtColorBufferFloat, GlError> {
+        self.extension(Extension::ExtColorBufferFloat)
+    }
+
+    pub fn ext_color_buffer_half_float(&self) -> Result<&ExtColorBufferHalfFloat, GlError> {
+        self.extension(Extension::ExtColorBufferHalfFloat)
+    }
+
+    pub fn ext_webgl_color_buffer_float(&self) -> Result<&WebglColorBufferFloat, GlError> {
+        self.extension(Extension::WebglColorBufferFloat)
+    }
+
+    pub fn ext_texture_filter_anisotropic(&self) -> Result<&ExtTextureFilterAnisotropic, GlError> {
+        self.extension(Extension::ExtTextureFilterAnisotropic)
+    }
+
+    pub fn ext_lose_context(&self) -> Result<&WebglLoseContext, GlError> {
+        self.extension(Extension::WebglLoseContext)
+    }
+
+    pub fn is_context_lost(&self) -> bool {
+        self.data.backend.is_context_lost()
+    }
+
+    /// Lose the context with `WEBGL_lose_context`, so the recovery code can be tested.
+    /// The browser fires `webglcontextlost` on the canvas, the mock context only
+    /// reports the loss with [Gl::is_context_lost].
+    pub fn simulate_context_loss(&self) -> Result<(), GlError> {
+        trace_event!(WARN, "simulated context loss");
+        self.data.backend.simulate_context_loss(true)
+    }
+
+    /// Restore the context lost with [Gl::simulate_context_loss]. The browser fires
+    /// `webglcontextrestored`, resources created before the loss should be recreated.
+    pub fn restore_context(&self) -> Result<(), GlError> {
+        trace_event!(INFO, "context restore requested");
+        self.data.shader_cache.borrow_mut().clear();
+        // Restored contexts start with zero divisors
+        self.data.attribute_divisors.borrow_mut().clear();
+        self.data.backend.simulate_context_loss(false)
+    }
+
+    /// # Panics
+    /// If the context is not a WebGL1 one, instancing is a part of the WebGL2 API.
     pub fn instanced_arrays(&self) -> &AngleInstancedArrays {
-        &self.data.ex_instanced_arrays
+        self.data
+            .backend
+            .instanced_arrays()
+            .expect("ANGLE_instanced_arrays is available only in WebGL1 contexts supporting it, see Gl::ext_instanced_arrays")
+    }
+
+    pub fn version(&self) -> WebGlVersion {
+        self.data.backend.version()
+    }
+
+    /// Depth textures are available in WebGL2 and with `WEBGL_depth_texture` extension in WebGL1.
+    pub fn depth_texture_supported(&self) -> bool {
+        self.data.backend.depth_texture_supported()
+    }
+
+    /// The most precise texture type which can be rendered into, detected on the first call.
+    /// Float and half-float targets depend on the extensions, `EncodedByte` is always available.
+    pub fn render_precision(&self) -> RenderPrecision {
+        if let Some(precision) = self.data.render_precision.get() {
+            return precision;
+        }
+        let precision = if self.supports(Feature::FloatRenderTarget) {
+            RenderPrecision::Float
+        } else if self.supports(Feature::HalfFloatRenderTarget) {
+            RenderPrecision::HalfFloat
+        } else {
+            RenderPrecision::EncodedByte
+        };
+        trace_event!(INFO, ?precision, "render precision detected");
+        self.data.render_precision.set(Some(precision));
+        precision
+    }
+
+    /// Texture units a draw can use for textures and cube maps together,
+    /// the context limit capped by [TEXTURES_COUNT]. Queried once.
+    pub fn texture_units(&self) -> u32 {
+        if let Some(units) = self.data.texture_units.get() {
+            return units;
+        }
+        let units = match self.data.backend.parameter(
+            Context::MAX_TEXTURE_IMAGE_UNITS,
+            None,
+            &StateValue::Int(0),
+        ) {
+            Some(StateValue::Int(units)) if units > 0 => (units as u32).min(TEXTURES_COUNT),
+            _ => TEXTURES_COUNT,
+        };
+        trace_event!(DEBUG, units, "texture units");
+        self.data.texture_units.set(Some(units));
+        units
+    }
+
+    /// Check that all the features are available, like
+    /// `gl.supports(Feature::HalfFloatRenderTarget | Feature::Instancing)`.
+    /// Each feature is detected once, render targets are probed by rendering into a texture.
+    pub fn supports(&self, features: impl Into<Features>) -> bool {
+        features.into().iter().all(|feature| {
+            if let Some(supported) = self.data.features.get().get(feature) {
+                return supported;
+            }
+            let supported = crate::features::detect(self, feature);
+            trace_event!(DEBUG, ?feature, supported, "feature detected");
+            let mut cache = self.data.features.get();
+            cache.set(feature, supported);
+            self.data.features.set(cache);
+            supported
+        })
+    }
+
+    /// All the available features.
+    pub fn features(&self) -> Features {
+        Feature::ALL
+            .iter()
+            .copied()
+            .filter(|feature| self.supports(*feature))
+            .fold(Features::empty(), |features, feature| features | feature)
+    }
+
+    /// Whether RGBA textures of the type can be rendered into.
+    pub fn is_render_type_supported(&self, data_type: TextureType) -> bool {
+        match data_type {
+            TextureType::Byte => true,
+            TextureType::Float => self.supports(Feature::FloatRenderTarget),
+            TextureType::HalfFloat => self.supports(Feature::HalfFloatRenderTarget),
+            _ => false,
+        }
+    }
+
+    /// Whether the extension is available, it's enabled by the check.
+    pub fn extension_supported(&self, name: &str) -> bool {
+        self.data.backend.extension_supported(name)
+    }
+
+    /// Indices above 65535 are available in WebGL2 and with `OES_element_index_uint` in WebGL1.
+    pub fn element_index_uint_supported(&self) -> bool {
+        self.data.backend.element_index_uint_supported()
     }
 
     pub fn settings() -> impl Settings {
         EmptySetting {}
     }
 
+    /// Settings for 2D drawing into a `width × height` viewport, without depth test
+    /// and culling, colors with straight alpha are blended over the background.
+    /// Compose them with other settings to adjust, like `Gl::settings_2d(w, h).blend(false)`.
+    pub fn settings_2d(width: u32, height: u32) -> impl Settings {
+        Gl::settings()
+            .viewport(0, 0, width as i32, height as i32)
+            .depth_test(false)
+            .cull(false)
+            .blend_alpha(false)
+    }
+
+    /// Settings for opaque 3D geometry in a `width × height` viewport, with depth test
+    /// and writes, back faces culled and no blending.
+    pub fn settings_3d(width: u32, height: u32) -> impl Settings {
+        Gl::settings()
+            .viewport(0, 0, width as i32, height as i32)
+            .depth_test(true)
+            .depth_function(DepthFunction::Less)
+            .depth_mask(true)
+            .cull(true)
+            .cull_face(CullFace::Back)
+            .blend(false)
+    }
+
     pub fn apply<R>(&self, settings: impl Settings, callback: impl FnOnce() -> R) -> R {
-        settings.apply(self, &self.data.settings_cache, callback)
+        self.data.apply_depth.set(self.data.apply_depth.get() + 1);
+        let result = settings.apply(self, &self.data.settings_cache, callback);
+        self.end_apply();
+        result
+    }
+
+    /// Leave an `apply` scope, the deletions queued inside it are done after the outermost one.
+    fn end_apply(&self) {
+        let depth = self.data.apply_depth.get() - 1;
+        self.data.apply_depth.set(depth);
+        if depth == 0 {
+            self.collect();
+        }
+    }
+
+    /// Apply the settings with the strategy, nested `apply` calls use the one of the context.
+    pub fn apply_with<R>(
+        &self,
+        strategy: ApplyStrategy,
+        settings: impl Settings,
+        callback: impl FnOnce() -> R,
+    ) -> R {
+        let previous = self.data.apply_strategy.replace(strategy);
+        self.data.apply_depth.set(self.data.apply_depth.get() + 1);
+        let result = settings.apply(self, &self.data.settings_cache, || {
+            self.data.apply_strategy.set(previous);
+            let result = callback();
+            self.data.apply_strategy.set(strategy);
+            result
+        });
+        self.data.apply_strategy.set(previous);
+        self.end_apply();
+        result
     }
 
+    /// Select how settings are restored after the [Gl::apply] callbacks, see [ApplyStrategy].
+    pub fn set_apply_strategy(&self, strategy: ApplyStrategy) {
+        self.data.apply_strategy.set(strategy);
+    }
+
+    pub fn apply_strategy(&self) -> ApplyStrategy {
+        self.data.apply_strategy.get()
+    }
+
+    /// Register settings under a name, so the pipeline state like `"opaque"`, `"transparent"`
+    /// or `"ui"` is defined in one place. A preset with the same name is replaced.
+    /// Resources referenced by the settings are kept alive as long as the context.
+    pub fn register_preset(&self, name: &str, settings: impl Settings + 'static) {
+        self.data
+            .presets
+            .borrow_mut()
+            .insert(name.into(), Preset::new(name, settings));
+    }
+
+    pub fn remove_preset(&self, name: &str) -> Option<Preset> {
+        self.data.presets.borrow_mut().remove(name)
+    }
+
+    pub fn preset(&self, name: &str) -> Option<Preset> {
+        self.data.presets.borrow().get(name).cloned()
+    }
+
+    /// Apply the registered preset, see [Gl::register_preset].
+    /// Unknown names fail with [GlError::UnknownPreset] without calling the callback.
+    pub fn apply_preset<R>(&self, name: &str, callback: impl FnOnce() -> R) -> Result<R, GlError> {
+        match self.preset(name) {
+            Some(preset) => Ok(self.apply(preset, callback)),
+            None => {
+                trace_event!(ERROR, name, "unknown settings preset");
+                Err(GlError::UnknownPreset(name.into()))
+            }
+        }
+    }
+
+    /// Settings cache compared with the state queried from GL.
+    ///
+    /// Useful when the rendering depends on state changed bypassing `Gl`,
+    /// print it to see the mismatches. Queries stall the pipeline, so it's for debugging only.
+    pub fn dump_state(&self) -> StateDump {
+        let parameters = self.data.settings_cache.borrow().parameters();
+        StateDump {
+            entries: parameters
+                .into_iter()
+                .map(|parameter| StateEntry {
+                    actual: parameter.parameter.and_then(|name| {
+                        self.data
+                            .backend
+                            .parameter(name, parameter.texture_unit, &parameter.value)
+                    }),
+                    name: parameter.name,
+                    cached: parameter.value,
+                })
+                .collect(),
+        }
+    }
+
+    /// Supply [auto_uniforms](crate::auto_uniforms), `u_time`, `u_resolution` and others,
+    /// to any program declaring them. Values of the [Uniforms](crate::uniforms::Uniforms)
+    /// passed to a draw and the program defaults take precedence.
+    pub fn set_auto_uniforms(&self, enabled: bool) {
+        self.data.auto_uniforms.set(enabled);
+    }
+
+    pub fn auto_uniforms(&self) -> bool {
+        self.data.auto_uniforms.get()
+    }
+
+    /// Advance the frame of the automatic uniforms, `time` is in milliseconds,
+    /// like the `requestAnimationFrame` timestamp. [WorkerRenderer](crate::WorkerRenderer)
+    /// calls it before rendering each frame, custom loops should do the same.
+    /// The deletions left from the previous frame are done, see [Gl::collect].
+    pub fn begin_frame(&self, time: f64) {
+        self.collect();
+        let mut clock = self.data.frame_clock.get();
+        clock.advance(time);
+        self.data.frame_clock.set(clock);
+    }
+
+    pub fn frame_info(&self) -> FrameInfo {
+        self.data.frame_clock.get().info()
+    }
+
+    /// Viewport set by the currently applied settings.
+    pub fn viewport(&self) -> ViewportSetting {
+        ViewportSetting::read_cached(&self.data.settings_cache.borrow())
+    }
+
+    /// Pixel store set by the currently applied settings.
+    pub fn pixel_store(&self) -> PixelStore {
+        PixelStore::read_cached(&self.data.settings_cache.borrow())
+    }
+
+    /// Warn in debug builds about textures blended with straight alpha while their content
+    /// is premultiplied, or the reverse.
+    pub(crate) fn check_blended_alpha(&self, textures: &[Option<Texture>]) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        if let Some(premultiplied) = self.data.settings_cache.borrow().blended_alpha() {
+            for texture in textures.iter().flatten() {
+                if texture.is_premultiplied() != premultiplied {
+                    trace_event!(
+                        WARN,
+                        texture = %texture,
+                        premultiplied = texture.is_premultiplied(),
+                        "texture alpha doesn't match the blend function"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Program of the two shaders, see [Gl::program_builder] for defines, attribute locations
+    /// and other options.
     pub fn program(&self, fragment: &str, vertex: &str) -> Result<Program, GlError> {
-        Program::new(self.clone(), fragment, vertex)
+        self.program_builder()
+            .vertex(vertex)
+            .fragment(fragment)
+            .build()
+    }
+
+    pub fn program_builder(&self) -> ProgramBuilder {
+        ProgramBuilder::new(self.clone())
     }
 
     pub fn items_buffer<I>(&self, data: &[I], usage: BufferUsage) -> Result<ItemsBuffer<I>, GlError>
@@ -147,6 +1133,20 @@ impl Gl {
         ItemsBuffer::new(self.clone(), data, usage)
     }
 
+    pub fn raw_items_buffer(
+        &self,
+        layout: DynamicLayout,
+        data: &[f32],
+        usage: BufferUsage,
+    ) -> Result<RawItemsBuffer, GlError> {
+        RawItemsBuffer::new(self.clone(), layout, data, usage)
+    }
+
+    /// Streaming buffer for the per-frame vertex data, `capacity` is in bytes.
+    pub fn frame_arena(&self, capacity: usize) -> Result<FrameArena, GlError> {
+        FrameArena::new(self.clone(), capacity)
+    }
+
     pub fn elements_buffer(
         &self,
         data: &[u32],
@@ -155,17 +1155,84 @@ impl Gl {
         ElementsBuffer::new(self.clone(), data, usage)
     }
 
+    /// Clear the buffers listed in `params` with a single call,
+    /// clear values are set through the settings cache only for the duration of the call.
+    /// Write masks of the cleared buffers are enabled too, since they also apply to clearing.
+    pub fn clear(&self, params: ClearParams) {
+        let (color, depth, stencil, color_mask, depth_mask, stencil_mask) = {
+            let cache = self.data.settings_cache.borrow();
+            (
+                params
+                    .color
+                    .unwrap_or_else(|| ClearColorSetting::read_cached(&cache).color),
+                params
+                    .depth
+                    .unwrap_or_else(|| ClearDepthSetting::read_cached(&cache).value),
+                params
+                    .stencil
+                    .unwrap_or_else(|| ClearStencilSetting::read_cached(&cache).value),
+                match params.color {
+                    Some(_) => ColorMask::default(),
+                    None => ColorMask::read_cached(&cache),
+                },
+                match params.depth {
+                    Some(_) => DepthMask::default(),
+                    None => DepthMask::read_cached(&cache),
+                },
+                match params.stencil {
+                    Some(_) => StencilMask::default(),
+                    None => StencilMask::read_cached(&cache),
+                },
+            )
+        };
+        let mask = params.mask();
+        if mask != 0 {
+            self.apply(
+                Gl::settings()
+                    .clear_color(color[0], color[1], color[2], color[3])
+                    .clear_depth(depth)
+                    .clear_stencil(stencil)
+                    .color_mask(color_mask.0, color_mask.1, color_mask.2, color_mask.3)
+                    .depth_mask(depth_mask.0)
+                    .stencil_mask(stencil_mask.0),
+                || self.execute(Command::Clear { mask }),
+            );
+        }
+    }
+
+    /// Submit the queued commands to the GPU without waiting for them to complete.
+    /// Browsers flush automatically when control returns to the event loop, so it's needed
+    /// only when rendering into several canvases or kicking off work before long CPU tasks.
+    pub fn flush(&self) {
+        self.execute(Command::Flush {});
+    }
+
+    /// Block until all the queued commands are completed.
+    /// It stalls the pipeline, readbacks already wait for the data they need,
+    /// so use it only for measurements or synchronizing with non-WebGL consumers.
+    pub fn finish(&self) {
+        self.execute(Command::Finish {});
+    }
+
+    #[deprecated(note = "use Gl::clear instead")]
     pub fn clear_color_buffer(&self) {
-        self.context().clear(Context::COLOR_BUFFER_BIT);
+        self.execute(Command::Clear {
+            mask: Context::COLOR_BUFFER_BIT,
+        });
     }
 
+    #[deprecated(note = "use Gl::clear instead")]
     pub fn clear_depth_buffer(&self) {
-        self.context().clear(Context::DEPTH_BUFFER_BIT);
+        self.execute(Command::Clear {
+            mask: Context::DEPTH_BUFFER_BIT,
+        });
     }
 
+    #[deprecated(note = "use Gl::clear instead")]
     pub fn clear_buffers(&self) {
-        self.context()
-            .clear(Context::COLOR_BUFFER_BIT | Context::DEPTH_BUFFER_BIT);
+        self.execute(Command::Clear {
+            mask: Context::COLOR_BUFFER_BIT | Context::DEPTH_BUFFER_BIT,
+        });
     }
 
     pub fn texture(
@@ -179,17 +1246,70 @@ impl Gl {
         Texture::new(self.clone(), width, height, data_type, format, data)
     }
 
-    pub fn depth_buffer(&self, width: u32, height: u32) -> Result<DepthBuffer, GlError> {
-        DepthBuffer::new(self.clone(), width, height)
+    /// RGBA texture of the bitmap size, see [decode_image](crate::decode_image).
+    pub fn image_bitmap_texture(&self, bitmap: &ImageBitmap) -> Result<Texture, GlError> {
+        self.texture(
+            bitmap.width(),
+            bitmap.height(),
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::ImageBitmap(bitmap.clone()),
+        )
+    }
+
+    /// Create a cube map with uninitialized faces, see [CubeMap::new].
+    pub fn cube_map(
+        &self,
+        size: u32,
+        data_type: TextureType,
+        format: TextureFormat,
+    ) -> Result<CubeMap, GlError> {
+        CubeMap::new(self.clone(), size, data_type, format)
+    }
+
+    pub fn depth_buffer(
+        &self,
+        width: u32,
+        height: u32,
+        format: DepthBufferFormat,
+    ) -> Result<DepthBuffer, GlError> {
+        DepthBuffer::new(self.clone(), width, height, format)
+    }
+
+    pub fn depth_reader(&self) -> Result<DepthReader, GlError> {
+        DepthReader::new(self.clone())
+    }
+
+    pub fn shadow_map(&self, width: u32, height: u32) -> Result<ShadowMap, GlError> {
+        ShadowMap::new(self.clone(), width, height)
     }
 
     pub fn frame_buffer(&self) -> Result<FrameBuffer, GlError> {
         FrameBuffer::new(self.clone())
     }
 
+    /// RGBA texture of the best [render precision](Gl::render_precision),
+    /// check the returned precision to know whether the values should be encoded.
+    pub fn precision_texture(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<(Texture, RenderPrecision), GlError> {
+        let precision = self.render_precision();
+        let texture = self.texture(
+            width,
+            height,
+            precision.texture_type(),
+            TextureFormat::Rgba,
+            TextureContent::None,
+        )?;
+        Ok((texture, precision))
+    }
+
     pub fn frame_buffer_with_color(&self, texture: Texture) -> Result<FrameBuffer, GlError> {
         let mut result = FrameBuffer::new(self.clone())?;
-        result.set_color_buffer(Some(texture));
+        result.set_color_buffer(Some(texture))?;
+        result.check_color_renderable()?;
         Ok(result)
     }
 
@@ -199,8 +1319,23 @@ impl Gl {
         depth_buffer: DepthBuffer,
     ) -> Result<FrameBuffer, GlError> {
         let mut result = FrameBuffer::new(self.clone())?;
-        result.set_color_buffer(Some(texture));
-        result.set_depth_buffer(Some(depth_buffer));
+        result.set_color_buffer(Some(texture))?;
+        result.set_depth_buffer(Some(depth_buffer))?;
+        result.check_color_renderable()?;
+        Ok(result)
+    }
+
+    /// Create a frame buffer rendering depth into a texture, so it can be sampled later.
+    /// The depth texture should have `Depth` or `DepthStencil` format.
+    pub fn frame_buffer_with_depth_texture(
+        &self,
+        color: Texture,
+        depth: Texture,
+    ) -> Result<FrameBuffer, GlError> {
+        let mut result = FrameBuffer::new(self.clone())?;
+        result.set_color_buffer(Some(color))?;
+        result.set_depth_texture(Some(depth))?;
+        result.check_color_renderable()?;
         Ok(result)
     }
 }
@@ -0,0 +1,132 @@
+//! Several contexts on one page, see [GlRegistry].
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::Gl;
+
+type Preprocessor = Rc<dyn Fn(&str) -> String>;
+
+/// Shader sources shared by the contexts of a registry.
+#[derive(Default)]
+pub(crate) struct SharedSources {
+    preprocessor: RefCell<Option<Preprocessor>>,
+    sources: RefCell<HashMap<String, Rc<str>>>,
+}
+
+impl SharedSources {
+    /// Preprocessed source, each unique source is preprocessed once for all the contexts.
+    pub fn source(&self, source: &str) -> Rc<str> {
+        if let Some(result) = self.sources.borrow().get(source) {
+            return result.clone();
+        }
+        let preprocessor = self.preprocessor.borrow().clone();
+        let result: Rc<str> = match preprocessor {
+            Some(preprocessor) => preprocessor(source).into(),
+            None => source.into(),
+        };
+        self.sources
+            .borrow_mut()
+            .insert(source.into(), result.clone());
+        result
+    }
+}
+
+impl fmt::Debug for SharedSources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedSources")
+            .field("preprocessor", &self.preprocessor.borrow().is_some())
+            .field("sources", &self.sources.borrow().len())
+            .finish()
+    }
+}
+
+/// Named contexts of a page with several canvases.
+///
+/// Each canvas has its own [Gl], GL objects can't be shared between them,
+/// but the shader source preprocessing is done once and cached for all of them.
+/// Draws and frame buffer attachments with resources of another context
+/// fail with [GlError::WrongContext](crate::GlError::WrongContext).
+#[derive(Clone, Debug, Default)]
+pub struct GlRegistry {
+    contexts: Rc<RefCell<Vec<(String, Gl)>>>,
+    sources: Rc<SharedSources>,
+}
+
+impl GlRegistry {
+    pub fn new() -> GlRegistry {
+        Default::default()
+    }
+
+    /// Add the context under the name, replacing the previous one with the same name.
+    /// Programs created with the context from now on use the shared sources.
+    pub fn add(&self, name: &str, gl: Gl) -> Gl {
+        gl.set_shared_sources(Some(self.sources.clone()));
+        let mut contexts = self.contexts.borrow_mut();
+        contexts.retain(|(existing, _)| existing != name);
+        contexts.push((name.into(), gl.clone()));
+        gl
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Gl> {
+        let mut contexts = self.contexts.borrow_mut();
+        let index = contexts.iter().position(|(existing, _)| existing == name)?;
+        let (_, gl) = contexts.remove(index);
+        gl.set_shared_sources(None);
+        Some(gl)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Gl> {
+        self.contexts
+            .borrow()
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, gl)| gl.clone())
+    }
+
+    /// Name of the context in the registry.
+    pub fn name_of(&self, gl: &Gl) -> Option<String> {
+        self.contexts
+            .borrow()
+            .iter()
+            .find(|(_, existing)| existing == gl)
+            .map(|(name, _)| name.clone())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.contexts
+            .borrow()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contexts.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contexts.borrow().is_empty()
+    }
+
+    /// Transformation applied to the shader sources before compilation, like includes
+    /// or a common header. Cached sources are dropped, since they're preprocessed differently.
+    pub fn set_preprocessor(&self, preprocessor: impl Fn(&str) -> String + 'static) {
+        self.sources
+            .preprocessor
+            .replace(Some(Rc::new(preprocessor)));
+        self.sources.sources.borrow_mut().clear();
+    }
+
+    /// Preprocessed source, as it's passed to the compiler.
+    pub fn source(&self, source: &str) -> Rc<str> {
+        self.sources.source(source)
+    }
+
+    /// Number of the unique sources in the cache.
+    pub fn cached_sources(&self) -> usize {
+        self.sources.sources.borrow().len()
+    }
+}
@@ -0,0 +1,36 @@
+/// Shader source loaded by `load_glsl!("file.glsl", manifest)` with the files it's built from,
+/// so hot-reload tooling and asset pipelines know which files to watch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GlslManifest {
+    pub source: &'static str,
+    /// The loaded file first, then the included ones in the order of inclusion
+    pub files: &'static [GlslFile],
+}
+
+/// File of a [GlslManifest].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GlslFile {
+    /// Path relative to the `glsl` folder, with `/` separators
+    pub path: &'static str,
+    /// FNV-1a hash of the file content
+    pub hash: u64,
+}
+
+impl GlslManifest {
+    pub fn contains(&self, path: &str) -> bool {
+        self.files.iter().any(|file| file.path == path)
+    }
+
+    /// Hash of all the files, changes when any of them does.
+    pub fn hash(&self) -> u64 {
+        self.files.iter().fold(0xcbf2_9ce4_8422_2325, |hash, file| {
+            (hash ^ file.hash).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+}
+
+impl From<GlslManifest> for &'static str {
+    fn from(manifest: GlslManifest) -> &'static str {
+        manifest.source
+    }
+}
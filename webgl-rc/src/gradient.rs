@@ -0,0 +1,96 @@
+use crate::{Color, Gl, GlError, Texture, TextureContent, TextureFormat, TextureType};
+
+/// Color ramp from a list of stops, for heatmaps and transfer functions.
+///
+/// Colors are interpolated in the linear space, so a ramp between saturated colors
+/// doesn't get darker in the middle like the interpolation of sRGB values does.
+/// Positions before the first stop and after the last one get the color of the nearest stop.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Gradient {
+    stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+    pub fn new() -> Gradient {
+        Default::default()
+    }
+
+    /// Colors evenly spread over `[0, 1]`.
+    pub fn from_colors(colors: &[Color]) -> Gradient {
+        let last = colors.len().saturating_sub(1).max(1) as f32;
+        colors
+            .iter()
+            .enumerate()
+            .fold(Gradient::new(), |gradient, (i, color)| {
+                gradient.with_stop(i as f32 / last, *color)
+            })
+    }
+
+    /// Add a stop, stops at the same position make a sharp transition.
+    pub fn add_stop(&mut self, position: f32, color: Color) {
+        let index = self.stops.partition_point(|(stop, _)| *stop <= position);
+        self.stops.insert(index, (position, color));
+    }
+
+    pub fn with_stop(mut self, position: f32, color: Color) -> Self {
+        self.add_stop(position, color);
+        self
+    }
+
+    /// Stops ordered by position.
+    pub fn stops(&self) -> &[(f32, Color)] {
+        &self.stops
+    }
+
+    /// Color at the position, transparent if there are no stops.
+    pub fn sample(&self, position: f32) -> Color {
+        let next = self.stops.partition_point(|(stop, _)| *stop <= position);
+        let previous = next.checked_sub(1).map(|i| self.stops[i]);
+        match (previous, self.stops.get(next).copied()) {
+            (None, None) => Color::TRANSPARENT,
+            (Some((_, color)), None) | (None, Some((_, color))) => color,
+            (Some((start, from)), Some((end, to))) => {
+                let t = (position - start) / (end - start);
+                let mix = |a: f32, b: f32| a + (b - a) * t;
+                Color::linear(
+                    mix(from.r, to.r),
+                    mix(from.g, to.g),
+                    mix(from.b, to.b),
+                    mix(from.a, to.a),
+                )
+            }
+        }
+    }
+
+    /// Colors of the texel centers of a `width × 1` ramp, so sampling it with
+    /// the linear filter at `u` matches [Gradient::sample] at `u`.
+    fn texels(&self, width: u32) -> impl Iterator<Item = Color> + '_ {
+        (0..width).map(move |i| self.sample((i as f32 + 0.5) / width as f32))
+    }
+
+    /// `width × 1` RGBA byte texture of sRGB colors with straight alpha,
+    /// convert the sampled values with `srgb_to_linear` of [COLOR_GLSL](crate::COLOR_GLSL).
+    pub fn texture(&self, gl: &Gl, width: u32) -> Result<Texture, GlError> {
+        gl.texture(
+            width,
+            1,
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::Bytes(self.texels(width).flat_map(Color::to_srgb8).collect()),
+        )
+    }
+
+    /// `width × 1` RGBA half-float texture of linear colors, sampled values need no conversion.
+    pub fn linear_texture(&self, gl: &Gl, width: u32) -> Result<Texture, GlError> {
+        let texture = gl.texture(
+            width,
+            1,
+            TextureType::HalfFloat,
+            TextureFormat::Rgba,
+            TextureContent::None,
+        )?;
+        let values: Vec<f32> = self.texels(width).flat_map(Color::to_linear).collect();
+        texture.write_floats(&values)?;
+        Ok(texture)
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+
+use crate::data_buffer::{AttributeFormat, Writable};
+use crate::types::{f32_to_f16_bits, DataType, TypeMark};
+
+/// IEEE 754 half precision float for vertex attributes, halves the buffer size
+/// of the data which doesn't need the full precision, like normals or texture coordinates.
+///
+/// Components are packed in pairs into the buffer floats, a single value or a `vec3`
+/// is padded with zeros. Half-float vertex data is a part of WebGL2,
+/// WebGL1 accepts `HALF_FLOAT_OES` attributes only on some implementations.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct f16(u16);
+
+impl f16 {
+    pub const ZERO: f16 = f16(0);
+    pub const ONE: f16 = f16(0x3c00);
+
+    pub fn from_f32(value: f32) -> f16 {
+        f16(f32_to_f16_bits(value))
+    }
+
+    pub fn from_bits(bits: u16) -> f16 {
+        f16(bits)
+    }
+
+    pub fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let mantissa = (self.0 & 0x03ff) as u32;
+        let bits = match exponent {
+            0 if mantissa == 0 => sign,
+            0 => {
+                // subnormal, normalized for f32
+                let shift = mantissa.leading_zeros() - 21;
+                let mantissa = (mantissa << shift) & 0x03ff;
+                sign | ((127 - 15 + 1 - shift) << 23) | (mantissa << 13)
+            }
+            0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+            _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+        };
+        f32::from_bits(bits)
+    }
+}
+
+impl From<f32> for f16 {
+    fn from(value: f32) -> Self {
+        f16::from_f32(value)
+    }
+}
+
+impl From<f16> for f32 {
+    fn from(value: f16) -> Self {
+        value.to_f32()
+    }
+}
+
+impl fmt::Debug for f16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_f32(), f)
+    }
+}
+
+impl fmt::Display for f16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f32(), f)
+    }
+}
+
+/// Two halves in a float, the first one in the low bytes, as the buffer is little endian.
+fn write_halves(values: &[f16], output: &mut Vec<f32>) {
+    for pair in values.chunks(2) {
+        let low = pair[0].0 as u32;
+        let high = pair.get(1).map_or(0, |value| value.0 as u32);
+        output.push(f32::from_bits(low | (high << 16)));
+    }
+}
+
+macro_rules! impl_half {
+    ($type:ty, $data_type:expr, $stride:expr, $this:ident => $values:expr) => {
+        impl TypeMark for $type {
+            const DATA_TYPE: Option<DataType> = Some($data_type);
+        }
+
+        impl Writable for $type {
+            fn write(&self, output: &mut Vec<f32>) {
+                let $this = self;
+                write_halves($values, output);
+            }
+            fn stride() -> usize {
+                $stride
+            }
+            fn format() -> AttributeFormat {
+                AttributeFormat::HalfFloat
+            }
+        }
+    };
+}
+
+impl_half!(f16, DataType::Float, 1, value => std::slice::from_ref(value));
+impl_half!([f16; 2], DataType::Vec2, 1, values => values);
+impl_half!([f16; 3], DataType::Vec3, 2, values => values);
+impl_half!([f16; 4], DataType::Vec4, 2, values => values);
+
+/// Signed normalized `vec4` packed as `INT_2_10_10_10_REV`, 4 bytes instead of 16,
+/// for normals and tangents. `xyz` get 10 bits, `w` gets 2 bits, enough for
+/// the tangent handedness. Requires WebGL2, the attribute is skipped on WebGL1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PackedNormal(u32);
+
+impl PackedNormal {
+    /// Pack a normal with `w = 0`, components are clamped to `[-1, 1]`.
+    pub fn from_normal(normal: [f32; 3]) -> PackedNormal {
+        PackedNormal::from_vec4([normal[0], normal[1], normal[2], 0.0])
+    }
+
+    /// Pack a tangent with its handedness in `w`, components are clamped to `[-1, 1]`.
+    pub fn from_vec4(value: [f32; 4]) -> PackedNormal {
+        let pack = |value: f32, max: f32, bits: u32| {
+            let value = (value.clamp(-1.0, 1.0) * max).round() as i32;
+            (value as u32) & ((1 << bits) - 1)
+        };
+        PackedNormal(
+            pack(value[0], 511.0, 10)
+                | (pack(value[1], 511.0, 10) << 10)
+                | (pack(value[2], 511.0, 10) << 20)
+                | (pack(value[3], 1.0, 2) << 30),
+        )
+    }
+
+    /// Unpacked value, as the shader gets it.
+    pub fn to_vec4(self) -> [f32; 4] {
+        let unpack = |shift: u32, bits: u32, max: f32| {
+            let value = ((self.0 << (32 - shift - bits)) as i32) >> (32 - bits);
+            (value as f32 / max).max(-1.0)
+        };
+        [
+            unpack(0, 10, 511.0),
+            unpack(10, 10, 511.0),
+            unpack(20, 10, 511.0),
+            unpack(30, 2, 1.0),
+        ]
+    }
+
+    pub fn from_bits(bits: u32) -> PackedNormal {
+        PackedNormal(bits)
+    }
+
+    pub fn to_bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<[f32; 3]> for PackedNormal {
+    fn from(normal: [f32; 3]) -> Self {
+        PackedNormal::from_normal(normal)
+    }
+}
+
+impl TypeMark for PackedNormal {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec4);
+}
+
+impl Writable for PackedNormal {
+    fn write(&self, output: &mut Vec<f32>) {
+        output.push(f32::from_bits(self.0));
+    }
+    fn stride() -> usize {
+        1
+    }
+    fn format() -> AttributeFormat {
+        AttributeFormat::Int2_10_10_10Rev
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_known_bits() {
+        assert_eq!(f16::from_bits(0x3c00).to_f32(), 1.0);
+        assert_eq!(f16::from_bits(0xc000).to_f32(), -2.0);
+        assert_eq!(f16::from_bits(0x7bff).to_f32(), 65504.0);
+        assert_eq!(f16::from_bits(0x0400).to_f32(), 2f32.powi(-14));
+        assert_eq!(
+            f16::from_bits(0x8000).to_f32().to_bits(),
+            (-0.0f32).to_bits()
+        );
+    }
+
+    #[test]
+    fn decode_subnormals() {
+        assert_eq!(f16::from_bits(0x0001).to_f32(), 2f32.powi(-24));
+        assert_eq!(f16::from_bits(0x0200).to_f32(), 2f32.powi(-15));
+        assert_eq!(f16::from_bits(0x03ff).to_f32(), 1023.0 * 2f32.powi(-24));
+        assert_eq!(f16::from_bits(0x8001).to_f32(), -(2f32.powi(-24)));
+    }
+
+    #[test]
+    fn decode_infinity_and_nan() {
+        assert_eq!(f16::from_bits(0x7c00).to_f32(), f32::INFINITY);
+        assert_eq!(f16::from_bits(0xfc00).to_f32(), f32::NEG_INFINITY);
+        assert!(f16::from_bits(0x7e00).to_f32().is_nan());
+        assert!(f16::from_bits(0x7c01).to_f32().is_nan());
+    }
+
+    #[test]
+    fn every_finite_half_survives_round_trip() {
+        for bits in 0..=u16::MAX {
+            let value = f16::from_bits(bits);
+            if value.to_f32().is_nan() {
+                assert!(f16::from_f32(value.to_f32()).to_f32().is_nan());
+            } else {
+                assert_eq!(f16::from_f32(value.to_f32()), value, "{:#06x}", bits);
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Radiance `.hdr` images, the common format of environment maps for image based lighting.
+
+use crate::features::Feature;
+use crate::gl::{Gl, GlError};
+use crate::texture::{Texture, TextureContent, TextureFilter, TextureFormat, TextureType};
+
+/// Largest finite half-float, brighter values would turn into infinities
+const HALF_FLOAT_MAX: f32 = 65504.0;
+
+/// Image decoded from a Radiance `.hdr` (RGBE) file into linear floats.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    /// `width × height` colors, rows from top to bottom
+    pub data: Vec<[f32; 3]>,
+}
+
+impl HdrImage {
+    /// Decode a `.hdr` file with the `32-bit_rle_rgbe` format, flat and run-length encoded
+    /// scanlines are supported. `EXPOSURE` is ignored, values are kept as stored.
+    pub fn parse(bytes: &[u8]) -> Result<HdrImage, GlError> {
+        let mut rest = bytes;
+        let mut next_line = || -> Result<&str, GlError> {
+            let end = rest
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .ok_or_else(|| error("unexpected end of the header"))?;
+            let line = std::str::from_utf8(&rest[..end]).map_err(|_| error("invalid header"))?;
+            rest = &rest[end + 1..];
+            Ok(line.trim_end_matches('\r'))
+        };
+
+        let signature = next_line()?;
+        if signature != "#?RADIANCE" && signature != "#?RGBE" {
+            return Err(error("missing #?RADIANCE signature"));
+        }
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(error(&format!("unsupported format {}", format)));
+                }
+            }
+        }
+        let resolution = next_line()?;
+        let (height, width, top_down) = match resolution.split_whitespace().collect::<Vec<_>>()[..]
+        {
+            ["-Y", height, "+X", width] => (height, width, true),
+            ["+Y", height, "+X", width] => (height, width, false),
+            _ => return Err(error(&format!("unsupported resolution {:?}", resolution))),
+        };
+        let parse_size = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| error(&format!("invalid size {:?}", value)))
+        };
+        let (width, height) = (parse_size(width)?, parse_size(height)?);
+
+        let mut reader = Reader { bytes: rest };
+        let mut rows = Vec::with_capacity(height as usize);
+        for _ in 0..height {
+            rows.push(reader.scanline(width as usize)?);
+        }
+        if !top_down {
+            rows.reverse();
+        }
+        Ok(HdrImage {
+            width,
+            height,
+            data: rows
+                .iter()
+                .flatten()
+                .map(|rgbe| decode_rgbe(*rgbe))
+                .collect(),
+        })
+    }
+
+    /// Upload into an RGB half-float texture, values are clamped to the half-float range.
+    /// The texture is filtered linearly when the context supports it.
+    pub fn to_texture(&self, gl: &Gl) -> Result<Texture, GlError> {
+        if !gl.supports(Feature::HalfFloatTexture) {
+            return Err(GlError::FeatureNotSupported(Feature::HalfFloatTexture));
+        }
+        let texture = gl.texture(
+            self.width,
+            self.height,
+            TextureType::HalfFloat,
+            TextureFormat::Rgb,
+            TextureContent::None,
+        )?;
+        if !gl.supports(Feature::HalfFloatLinearFiltering) {
+            texture.set_filter(TextureFilter::Nearest);
+        }
+        let values: Vec<f32> = self
+            .data
+            .iter()
+            .flatten()
+            .map(|value| value.min(HALF_FLOAT_MAX))
+            .collect();
+        texture.write_floats(&values)?;
+        Ok(texture)
+    }
+}
+
+fn error(message: &str) -> GlError {
+    GlError::ParseError(format!(".hdr: {}", message))
+}
+
+/// Shared exponent encoding, the mantissas are centered in their intervals like in Radiance.
+fn decode_rgbe([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    [
+        (r as f32 + 0.5) * scale,
+        (g as f32 + 0.5) * scale,
+        (b as f32 + 0.5) * scale,
+    ]
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], GlError> {
+        if self.bytes.len() < count {
+            return Err(error("unexpected end of the pixel data"));
+        }
+        let (result, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(result)
+    }
+
+    fn pixel(&mut self) -> Result<[u8; 4], GlError> {
+        let bytes = self.take(4)?;
+        Ok([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn scanline(&mut self, width: usize) -> Result<Vec<[u8; 4]>, GlError> {
+        let is_rle = (8..0x8000).contains(&width)
+            && self.bytes.len() >= 4
+            && self.bytes[0] == 2
+            && self.bytes[1] == 2
+            && self.bytes[2] & 0x80 == 0;
+        if is_rle {
+            self.rle_scanline(width)
+        } else {
+            self.flat_scanline(width)
+        }
+    }
+
+    /// Channels stored one after another, each one run-length encoded.
+    fn rle_scanline(&mut self, width: usize) -> Result<Vec<[u8; 4]>, GlError> {
+        let header = self.pixel()?;
+        if ((header[2] as usize) << 8 | header[3] as usize) != width {
+            return Err(error("scanline width mismatch"));
+        }
+        let mut result = vec![[0u8; 4]; width];
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = self.take(1)?[0] as usize;
+                let (count, run) = match count {
+                    count if count > 128 => (count - 128, true),
+                    count => (count, false),
+                };
+                if count == 0 || x + count > width {
+                    return Err(error("invalid run length"));
+                }
+                if run {
+                    let value = self.take(1)?[0];
+                    result[x..x + count]
+                        .iter_mut()
+                        .for_each(|pixel| pixel[channel] = value);
+                } else {
+                    let values = self.take(count)?;
+                    for (pixel, value) in result[x..x + count].iter_mut().zip(values) {
+                        pixel[channel] = *value;
+                    }
+                }
+                x += count;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Plain pixels, with the old style runs repeating the previous pixel.
+    fn flat_scanline(&mut self, width: usize) -> Result<Vec<[u8; 4]>, GlError> {
+        let mut result: Vec<[u8; 4]> = Vec::with_capacity(width);
+        let mut shift = 0;
+        while result.len() < width {
+            let pixel = self.pixel()?;
+            match (pixel, result.last().copied()) {
+                ([1, 1, 1, count], Some(previous)) => {
+                    let count = (count as usize)
+                        .checked_shl(shift)
+                        .filter(|count| *count > 0 && result.len() + count <= width)
+                        .ok_or_else(|| error("invalid run length"))?;
+                    result.extend(std::iter::repeat_n(previous, count));
+                    shift += 8;
+                }
+                _ => {
+                    result.push(pixel);
+                    shift = 0;
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(resolution: &str, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\nEXPOSURE=1.0\n\n{}\n",
+            resolution
+        )
+        .into_bytes();
+        bytes.extend_from_slice(pixels);
+        bytes
+    }
+
+    fn is_parse_error(bytes: &[u8]) -> bool {
+        matches!(HdrImage::parse(bytes), Err(GlError::ParseError(_)))
+    }
+
+    #[test]
+    fn decode_flat_scanlines() {
+        let image = HdrImage::parse(&file(
+            "-Y 2 +X 2",
+            &[
+                128, 64, 0, 129, 0, 0, 0, 0, 255, 255, 255, 128, 1, 2, 3, 136,
+            ],
+        ))
+        .unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(
+            image.data,
+            vec![
+                [128.5 / 128.0, 64.5 / 128.0, 0.5 / 128.0],
+                [0.0; 3],
+                [255.5 / 256.0; 3],
+                [1.5, 2.5, 3.5],
+            ]
+        );
+    }
+
+    #[test]
+    fn bottom_up_rows_are_flipped() {
+        let image = HdrImage::parse(&file("+Y 2 +X 1", &[1, 1, 1, 136, 2, 2, 2, 136])).unwrap();
+        assert_eq!(image.data, vec![[2.5; 3], [1.5; 3]]);
+    }
+
+    #[test]
+    fn decode_old_style_runs() {
+        let image = HdrImage::parse(&file("-Y 1 +X 3", &[1, 2, 3, 136, 1, 1, 1, 2])).unwrap();
+        assert_eq!(image.data, vec![[1.5, 2.5, 3.5]; 3]);
+    }
+
+    #[test]
+    fn decode_rle_scanlines() {
+        let mut pixels = vec![2, 2, 0, 8];
+        // Red run, green literals, blue run, shared exponent run
+        pixels.extend_from_slice(&[128 + 8, 7]);
+        pixels.extend_from_slice(&[8, 0, 1, 2, 3, 4, 5, 6, 7]);
+        pixels.extend_from_slice(&[128 + 3, 0, 5, 1, 1, 1, 1, 1]);
+        pixels.extend_from_slice(&[128 + 8, 136]);
+        let image = HdrImage::parse(&file("-Y 1 +X 8", &pixels)).unwrap();
+        let expected: Vec<[f32; 3]> = (0..8)
+            .map(|x| [7.5, x as f32 + 0.5, if x < 3 { 0.5 } else { 1.5 }])
+            .collect();
+        assert_eq!(image.data, expected);
+    }
+
+    #[test]
+    fn invalid_rle_scanlines_are_rejected() {
+        // Width in the scanline header differs from the resolution
+        assert!(is_parse_error(&file(
+            "-Y 1 +X 8",
+            &[2, 2, 0, 9, 128 + 8, 0]
+        )));
+        // Run longer than the scanline
+        assert!(is_parse_error(&file(
+            "-Y 1 +X 8",
+            &[2, 2, 0, 8, 128 + 9, 0]
+        )));
+        // Zero run length
+        assert!(is_parse_error(&file("-Y 1 +X 8", &[2, 2, 0, 8, 0])));
+        // Channels missing
+        assert!(is_parse_error(&file(
+            "-Y 1 +X 8",
+            &[2, 2, 0, 8, 128 + 8, 0]
+        )));
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        assert!(is_parse_error(b"#?PNG\n\n-Y 1 +X 1\n\x01\x01\x01\x80"));
+        assert!(is_parse_error(
+            b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\x01\x01\x01\x80"
+        ));
+        assert!(is_parse_error(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n"));
+        assert!(is_parse_error(b"#?RADIANCE"));
+        assert!(is_parse_error(&file("+X 1 -Y 1", &[1, 1, 1, 128])));
+        assert!(is_parse_error(&file("-Y 0 +X 1", &[])));
+        assert!(is_parse_error(&file("-Y 1 +X one", &[1, 1, 1, 128])));
+    }
+
+    #[test]
+    fn truncated_pixel_data_is_rejected() {
+        assert!(is_parse_error(&file(
+            "-Y 2 +X 2",
+            &[1, 1, 1, 128, 2, 2, 2, 128, 3]
+        )));
+        assert!(is_parse_error(&file("-Y 1 +X 2", &[])));
+    }
+}
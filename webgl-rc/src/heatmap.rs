@@ -0,0 +1,196 @@
+use crate::post::ScreenPass;
+use crate::{
+    Feature, Gl, GlError, Gradient, Texture, TextureContent, TextureFilter, TextureFormat,
+    TextureType, Uniforms, WebGlVersion,
+};
+
+const FRAGMENT_SHADER: &str = r#"
+    precision highp float;
+    uniform sampler2D u_grid;
+    uniform sampler2D u_ramp;
+    uniform vec2 u_grid_size;
+    uniform vec2 u_range;
+    uniform bool u_bilinear;
+    varying vec2 v_uv;
+
+    float value(vec2 cell) {
+        return texture2D(u_grid, (clamp(cell, vec2(0.0), u_grid_size - 1.0) + 0.5) / u_grid_size).r;
+    }
+
+    void main() {
+        // Rows go from the top, cell centers are at the integer positions
+        vec2 position = vec2(v_uv.x, 1.0 - v_uv.y) * u_grid_size - 0.5;
+        float result;
+        if (u_bilinear) {
+            vec2 cell = floor(position);
+            vec2 t = position - cell;
+            result = mix(
+                mix(value(cell), value(cell + vec2(1.0, 0.0)), t.x),
+                mix(value(cell + vec2(0.0, 1.0)), value(cell + vec2(1.0, 1.0)), t.x),
+                t.y
+            );
+        } else {
+            result = value(floor(position + 0.5));
+        }
+        float x = clamp((result - u_range.x) / max(u_range.y - u_range.x, 0.000001), 0.0, 1.0);
+        gl_FragColor = texture2D(u_ramp, vec2(x, 0.5));
+    }
+"#;
+
+/// Texels of the color ramp.
+const RAMP_SIZE: u32 = 256;
+
+#[derive(Clone, Uniforms)]
+struct HeatmapUniforms {
+    grid: Texture,
+    ramp: Texture,
+    grid_size: [f32; 2],
+    range: [f32; 2],
+    bilinear: bool,
+}
+
+/// Grid of values colored with a [Gradient], filling the current viewport.
+///
+/// Values are stored in a float texture, or a half-float one without float textures support,
+/// and mapped from the value range to the gradient positions `[0, 1]`.
+/// Rows are ordered from the top. The bilinear interpolation is done in the shader,
+/// so it doesn't depend on the float linear filtering extensions.
+#[derive(Debug)]
+pub struct Heatmap {
+    gl: Gl,
+    pass: ScreenPass,
+    grid: Texture,
+    ramp: Texture,
+    range: [f32; 2],
+    filter: TextureFilter,
+}
+
+impl Heatmap {
+    /// Heatmap of `columns × rows` values, the range is set to the minimum and maximum of them.
+    pub fn new(
+        gl: Gl,
+        columns: u32,
+        rows: u32,
+        values: &[f32],
+        gradient: &Gradient,
+    ) -> Result<Heatmap, GlError> {
+        let data_type = if gl.supports(Feature::FloatTexture) {
+            TextureType::Float
+        } else if gl.supports(Feature::HalfFloatTexture) {
+            TextureType::HalfFloat
+        } else {
+            return Err(GlError::FeatureNotSupported(Feature::HalfFloatTexture));
+        };
+        // WebGL2 has sized float formats only for RGB and RGBA
+        let format = match gl.version() {
+            WebGlVersion::WebGl1 => TextureFormat::Luminance,
+            WebGlVersion::WebGl2 => TextureFormat::Rgba,
+        };
+        let grid = gl.texture(columns, rows, data_type, format, TextureContent::None)?;
+        grid.set_filter(TextureFilter::Nearest);
+        let heatmap = Heatmap {
+            pass: ScreenPass::new(gl.clone(), FRAGMENT_SHADER)?,
+            grid,
+            ramp: gradient.texture(&gl, RAMP_SIZE)?,
+            range: value_range(values),
+            filter: TextureFilter::Nearest,
+            gl,
+        };
+        heatmap.set_values(values)?;
+        Ok(heatmap)
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.gl.clone()
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.grid.width()
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.grid.height()
+    }
+
+    /// Replace the values row by row, the length should match the grid size.
+    pub fn set_values(&self, values: &[f32]) -> Result<(), GlError> {
+        let expected = (self.columns() * self.rows()) as usize;
+        if values.len() != expected {
+            return Err(GlError::InvalidBufferSize {
+                expected: expected as u32,
+                received: values.len() as u32,
+            });
+        }
+        match self.grid.format() {
+            TextureFormat::Rgba => {
+                let texels: Vec<f32> = values
+                    .iter()
+                    .flat_map(|value| [*value, 0.0, 0.0, 1.0])
+                    .collect();
+                self.grid.write_floats(&texels)
+            }
+            _ => self.grid.write_floats(values),
+        }
+    }
+
+    pub fn set_gradient(&mut self, gradient: &Gradient) -> Result<(), GlError> {
+        self.ramp = gradient.texture(&self.gl, RAMP_SIZE)?;
+        Ok(())
+    }
+
+    pub fn range(&self) -> [f32; 2] {
+        self.range
+    }
+
+    /// Values mapped to the start and the end of the gradient, the ones outside are clamped.
+    pub fn set_range(&mut self, min: f32, max: f32) {
+        self.range = [min, max];
+    }
+
+    pub fn with_range(mut self, min: f32, max: f32) -> Self {
+        self.set_range(min, max);
+        self
+    }
+
+    pub fn filter(&self) -> TextureFilter {
+        self.filter
+    }
+
+    /// `Nearest` shows the cells as squares, `Linear` interpolates the values between them.
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        self.filter = filter;
+    }
+
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.set_filter(filter);
+        self
+    }
+
+    /// Draw into the current frame buffer and viewport.
+    pub fn draw(&self) -> Result<(), GlError> {
+        self.pass.draw(
+            None,
+            &HeatmapUniforms {
+                grid: self.grid.clone(),
+                ramp: self.ramp.clone(),
+                grid_size: [self.columns() as f32, self.rows() as f32],
+                range: self.range,
+                bilinear: self.filter == TextureFilter::Linear,
+            },
+        )
+    }
+}
+
+/// Minimum and maximum of the finite values, `[0, 1]` if there are none.
+fn value_range(values: &[f32]) -> [f32; 2] {
+    values
+        .iter()
+        .filter(|value| value.is_finite())
+        .fold(None, |range: Option<[f32; 2]>, value| {
+            Some(match range {
+                Some([min, max]) => [min.min(*value), max.max(*value)],
+                None => [*value, *value],
+            })
+        })
+        .unwrap_or([0.0, 1.0])
+}
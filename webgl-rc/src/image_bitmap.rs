@@ -0,0 +1,78 @@
+//! Image decoding off the main thread with `createImageBitmap`.
+
+use js_sys::{JsString, Promise};
+use wasm_bindgen::JsCast;
+use web_sys::{
+    Blob, ImageBitmapOptions, ImageOrientation, PremultiplyAlpha, ResizeQuality, Window,
+    WorkerGlobalScope,
+};
+
+use crate::gl::GlError;
+
+/// Options applied by the browser while decoding, pixel store flags like
+/// `UNPACK_FLIP_Y_WEBGL` are ignored for bitmaps, so flipping and premultiplying happen here.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageDecodeOptions {
+    /// Width and height of the decoded image, like a power of two size for mipmaps on WebGL 1
+    pub resize: Option<(u32, u32)>,
+    pub resize_quality: ResizeQuality,
+    pub premultiply_alpha: bool,
+    pub flip_y: bool,
+}
+
+impl Default for ImageDecodeOptions {
+    fn default() -> Self {
+        ImageDecodeOptions {
+            resize: None,
+            resize_quality: ResizeQuality::Low,
+            premultiply_alpha: false,
+            flip_y: false,
+        }
+    }
+}
+
+impl ImageDecodeOptions {
+    fn to_js(&self) -> ImageBitmapOptions {
+        let options = ImageBitmapOptions::new();
+        if let Some((width, height)) = self.resize {
+            options.set_resize_width(width);
+            options.set_resize_height(height);
+            options.set_resize_quality(self.resize_quality);
+        }
+        options.set_premultiply_alpha(if self.premultiply_alpha {
+            PremultiplyAlpha::Premultiply
+        } else {
+            PremultiplyAlpha::None
+        });
+        options.set_image_orientation(if self.flip_y {
+            ImageOrientation::FlipY
+        } else {
+            ImageOrientation::FromImage
+        });
+        options
+    }
+}
+
+/// Decode an encoded image, like a fetched `.png`, the promise resolves to an [ImageBitmap](web_sys::ImageBitmap).
+///
+/// Browsers decode bitmaps in the background, so large textures don't block the frame.
+/// Works in workers as well, the bitmap is transferable and can be posted to the rendering thread.
+///
+/// ```ignore
+/// let bitmap: ImageBitmap = JsFuture::from(decode_image(&blob, &options)?).await?.into();
+/// let texture = gl.image_bitmap_texture(&bitmap)?;
+/// ```
+pub fn decode_image(blob: &Blob, options: &ImageDecodeOptions) -> Result<Promise, GlError> {
+    let global = js_sys::global();
+    let options = options.to_js();
+    let result = if let Some(window) = global.dyn_ref::<Window>() {
+        window.create_image_bitmap_with_blob_and_image_bitmap_options(blob, &options)
+    } else if let Some(worker) = global.dyn_ref::<WorkerGlobalScope>() {
+        worker.create_image_bitmap_with_blob_and_image_bitmap_options(blob, &options)
+    } else {
+        return Err(GlError::UnknownError(Some(
+            "createImageBitmap isn't available".into(),
+        )));
+    };
+    result.map_err(|e| GlError::UnknownError(Some(JsString::from(e).into())))
+}
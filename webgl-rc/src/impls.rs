@@ -1,7 +1,92 @@
+use std::collections::HashMap;
+
+use super::cube_map::CubeMap;
 use super::data_buffer::Writable;
 use super::texture::Texture;
 use super::types::{DataType, TypeMark};
-use crate::uniforms::{IntoUniform, UniformValue};
+use crate::uniforms::{Field, IntoUniform, UniformValue, Uniforms};
+
+// ()
+
+/// No uniforms, for programs which don't declare any or keep the previous values
+impl Uniforms for () {
+    fn uniforms(&self) -> Vec<Field> {
+        Vec::new()
+    }
+}
+
+// Tuples of uniforms, fields are set in order, so the later values win
+
+impl<A: Uniforms, B: Uniforms> Uniforms for (A, B) {
+    fn uniforms(&self) -> Vec<Field> {
+        let mut result = self.0.uniforms();
+        result.extend(self.1.uniforms());
+        result
+    }
+}
+
+impl<A: Uniforms, B: Uniforms, C: Uniforms> Uniforms for (A, B, C) {
+    fn uniforms(&self) -> Vec<Field> {
+        let mut result = self.0.uniforms();
+        result.extend(self.1.uniforms());
+        result.extend(self.2.uniforms());
+        result
+    }
+}
+
+impl<A: Uniforms, B: Uniforms, C: Uniforms, D: Uniforms> Uniforms for (A, B, C, D) {
+    fn uniforms(&self) -> Vec<Field> {
+        let mut result = self.0.uniforms();
+        result.extend(self.1.uniforms());
+        result.extend(self.2.uniforms());
+        result.extend(self.3.uniforms());
+        result
+    }
+}
+
+/// References allow combining uniforms owned elsewhere, like `(&camera, &material)`
+impl<U: Uniforms + ?Sized> Uniforms for &U {
+    fn uniforms(&self) -> Vec<Field> {
+        (*self).uniforms()
+    }
+}
+
+// Dynamic uniforms with names known only at runtime, names are used as is
+
+impl Uniforms for [(&str, UniformValue)] {
+    fn uniforms(&self) -> Vec<Field> {
+        self.iter()
+            .map(|(name, value)| Field {
+                name: name.to_string().into(),
+                value: value.clone(),
+            })
+            .collect()
+    }
+}
+
+impl<const N: usize> Uniforms for [(&str, UniformValue); N] {
+    fn uniforms(&self) -> Vec<Field> {
+        self[..].uniforms()
+    }
+}
+
+/// Fields collected from other uniforms, like the ones queued in a [CommandBuffer](crate::CommandBuffer)
+impl Uniforms for [Field] {
+    fn uniforms(&self) -> Vec<Field> {
+        self.to_vec()
+    }
+}
+
+impl Uniforms for HashMap<String, UniformValue> {
+    fn uniforms(&self) -> Vec<Field> {
+        self.iter()
+            .map(|(name, value)| Field {
+                name: name.clone().into(),
+                value: value.clone(),
+            })
+            .collect()
+    }
+}
 
 // f32
 
@@ -15,26 +100,160 @@ impl Writable for f32 {
 }
 
 impl TypeMark for f32 {
-    fn data_type() -> DataType {
-        DataType::Float
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Float);
 }
 
 impl IntoUniform for f32 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Float(*self)
     }
 }
 
+// f64, converted to f32 at the boundary
+
+impl Writable for f64 {
+    fn write(&self, output: &mut Vec<f32>) {
+        output.push(*self as f32);
+    }
+    fn stride() -> usize {
+        1
+    }
+}
+
+impl TypeMark for f64 {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Float);
+}
+
+impl IntoUniform for f64 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::Float(*self as f32)
+    }
+}
+
+// (f64, f64)
+
+impl TypeMark for (f64, f64) {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec2);
+}
+
+impl IntoUniform for (f64, f64) {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::Vec2([self.0 as f32, self.1 as f32])
+    }
+}
+
+impl Writable for (f64, f64) {
+    fn write(&self, output: &mut Vec<f32>) {
+        output.push(self.0 as f32);
+        output.push(self.1 as f32);
+    }
+    fn stride() -> usize {
+        2
+    }
+}
+
+// i32
+
+impl TypeMark for i32 {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Int);
+}
+
+impl From<i32> for UniformValue {
+    fn from(value: i32) -> Self {
+        UniformValue::Int(value)
+    }
+}
+
+impl IntoUniform for i32 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::Int(*self)
+    }
+}
+
+// u32, GLSL ES 1.0 has no unsigned type, so the bits are passed as `int`
+// and values above `i32::MAX` wrap, which keeps bit flags intact
+
+impl TypeMark for u32 {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Int);
+}
+
+impl IntoUniform for u32 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::Int(*self as i32)
+    }
+}
+
+// [i32; N]
+
+impl TypeMark for [i32; 2] {
+    const DATA_TYPE: Option<DataType> = Some(DataType::IVec2);
+}
+
+impl IntoUniform for [i32; 2] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::IVec2(*self)
+    }
+}
+
+impl TypeMark for [i32; 3] {
+    const DATA_TYPE: Option<DataType> = Some(DataType::IVec3);
+}
+
+impl IntoUniform for [i32; 3] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::IVec3(*self)
+    }
+}
+
+impl TypeMark for [i32; 4] {
+    const DATA_TYPE: Option<DataType> = Some(DataType::IVec4);
+}
+
+impl IntoUniform for [i32; 4] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::IVec4(*self)
+    }
+}
+
+// (i32, i32)
+
+impl TypeMark for (i32, i32) {
+    const DATA_TYPE: Option<DataType> = Some(DataType::IVec2);
+}
+
+impl IntoUniform for (i32, i32) {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::IVec2([self.0, self.1])
+    }
+}
+
 // Texture
 
 impl TypeMark for Texture {
-    fn data_type() -> DataType {
-        DataType::Sampler
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Sampler);
 }
 
 impl IntoUniform for Texture {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Texture(self.clone())
     }
@@ -43,12 +262,12 @@ impl IntoUniform for Texture {
 // Option<Texture>
 
 impl TypeMark for Option<Texture> {
-    fn data_type() -> DataType {
-        DataType::Sampler
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Sampler);
 }
 
 impl IntoUniform for Option<Texture> {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         self.as_ref()
             .map(|texture| UniformValue::Texture(texture.clone()))
@@ -56,12 +275,40 @@ impl IntoUniform for Option<Texture> {
     }
 }
 
+// CubeMap
+
+impl TypeMark for CubeMap {
+    const DATA_TYPE: Option<DataType> = Some(DataType::SamplerCube);
+}
+
+impl IntoUniform for CubeMap {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::CubeMap(self.clone())
+    }
+}
+
+// Option<CubeMap>
+
+impl TypeMark for Option<CubeMap> {
+    const DATA_TYPE: Option<DataType> = Some(DataType::SamplerCube);
+}
+
+impl IntoUniform for Option<CubeMap> {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        self.as_ref()
+            .map(|cube_map| UniformValue::CubeMap(cube_map.clone()))
+            .unwrap_or(UniformValue::None)
+    }
+}
+
 // Boolean
 
 impl TypeMark for bool {
-    fn data_type() -> DataType {
-        DataType::Boolean
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Boolean);
 }
 
 impl From<bool> for UniformValue {
@@ -71,6 +318,8 @@ impl From<bool> for UniformValue {
 }
 
 impl IntoUniform for bool {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Boolean(*self)
     }
@@ -79,12 +328,12 @@ impl IntoUniform for bool {
 // [f32;2]
 
 impl TypeMark for [f32; 2] {
-    fn data_type() -> DataType {
-        DataType::Vec2
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec2);
 }
 
 impl IntoUniform for [f32; 2] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Vec2(*self)
     }
@@ -104,12 +353,12 @@ impl Writable for [f32; 2] {
 // [f32;3]
 
 impl TypeMark for [f32; 3] {
-    fn data_type() -> DataType {
-        DataType::Vec3
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec3);
 }
 
 impl IntoUniform for [f32; 3] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Vec3(*self)
     }
@@ -129,12 +378,12 @@ impl Writable for [f32; 3] {
 // [f32;4]
 
 impl TypeMark for [f32; 4] {
-    fn data_type() -> DataType {
-        DataType::Vec4
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec4);
 }
 
 impl IntoUniform for [f32; 4] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Vec4(*self)
     }
@@ -151,15 +400,62 @@ impl Writable for [f32; 4] {
     }
 }
 
+// Matrices as arrays of columns, `m[column][row]` like in GLSL
+
+impl TypeMark for [[f32; 2]; 2] {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Mat2);
+}
+
+impl IntoUniform for [[f32; 2]; 2] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        let [a, b] = *self;
+        UniformValue::Mat2([a[0], a[1], b[0], b[1]])
+    }
+}
+
+impl TypeMark for [[f32; 3]; 3] {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Mat3);
+}
+
+impl IntoUniform for [[f32; 3]; 3] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        let mut result = [0.0; 9];
+        for (column, values) in self.iter().enumerate() {
+            result[column * 3..column * 3 + 3].copy_from_slice(values);
+        }
+        UniformValue::Mat3(result)
+    }
+}
+
+impl TypeMark for [[f32; 4]; 4] {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Mat4);
+}
+
+impl IntoUniform for [[f32; 4]; 4] {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        let mut result = [0.0; 16];
+        for (column, values) in self.iter().enumerate() {
+            result[column * 4..column * 4 + 4].copy_from_slice(values);
+        }
+        UniformValue::Mat4(result)
+    }
+}
+
 // (f32, f32)
 
 impl TypeMark for (f32, f32) {
-    fn data_type() -> DataType {
-        DataType::Vec2
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec2);
 }
 
 impl IntoUniform for (f32, f32) {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Vec2([self.0, self.1])
     }
@@ -178,12 +474,12 @@ impl Writable for (f32, f32) {
 // (f32, f32, f32)
 
 impl TypeMark for (f32, f32, f32) {
-    fn data_type() -> DataType {
-        DataType::Vec3
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec3);
 }
 
 impl IntoUniform for (f32, f32, f32) {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Vec3([self.0, self.1, self.2])
     }
@@ -203,12 +499,12 @@ impl Writable for (f32, f32, f32) {
 // (f32, f32, f32, f32)
 
 impl TypeMark for (f32, f32, f32, f32) {
-    fn data_type() -> DataType {
-        DataType::Vec4
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec4);
 }
 
 impl IntoUniform for (f32, f32, f32, f32) {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Vec4([self.0, self.1, self.2, self.3])
     }
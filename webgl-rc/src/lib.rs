@@ -1,32 +1,117 @@
 #[cfg(feature = "nalgebra-glm")]
 extern crate nalgebra_glm as glm;
 
+// Derive macros generate `webgl_rc::` paths, this makes them usable inside the crate
+extern crate self as webgl_rc;
+
 #[cfg(feature = "nalgebra-glm")]
 mod nalgebra;
 
+#[macro_use]
+mod trace;
+
+mod backend;
+mod matrix;
+#[cfg(feature = "mock")]
+mod mock_backend;
+mod webgl_backend;
+
+pub mod analysis;
+pub mod auto_uniforms;
+pub mod batch;
 pub mod buffer_usage;
+pub mod camera;
+pub mod color;
+pub mod command_buffer;
+pub mod cube_map;
+pub mod cube_target;
 pub mod data_buffer;
 pub mod depth_buffer;
+pub mod depth_reader;
 pub mod element_buffer;
+pub mod extensions;
+pub mod features;
+pub mod frame_arena;
 pub mod frame_buffer;
 pub mod gl;
+pub mod gl_registry;
+pub mod glsl_manifest;
+pub mod gradient;
+pub mod half;
+pub mod hdr;
+pub mod heatmap;
+pub mod image_bitmap;
 pub mod impls;
+pub mod markers;
+pub mod mask;
+pub mod polyline;
+pub mod post;
+pub mod precision;
+pub mod presets;
 pub mod program;
+pub mod program_check;
+pub mod recorder;
+pub mod registry;
 pub mod settings;
+pub mod shadow_map;
+pub mod simulation;
+pub mod skybox;
+pub mod state;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod texture;
+pub mod texture_region;
+pub mod tilemap;
 pub mod types;
 pub mod uniforms;
+pub mod video_texture;
+pub mod worker;
 
+pub use analysis::*;
+pub use auto_uniforms::FrameInfo;
+pub use batch::*;
 pub use buffer_usage::*;
+pub use camera::*;
+pub use color::*;
+pub use command_buffer::*;
+pub use cube_map::*;
+pub use cube_target::*;
 pub use data_buffer::*;
 pub use depth_buffer::*;
+pub use depth_reader::*;
 pub use element_buffer::*;
+pub use extensions::*;
+pub use features::*;
+pub use frame_arena::*;
 pub use frame_buffer::*;
 pub use gl::*;
+pub use gl_registry::*;
+pub use glsl_manifest::*;
+pub use gradient::*;
+pub use half::*;
+pub use hdr::*;
+pub use heatmap::*;
+pub use image_bitmap::*;
+pub use markers::*;
+pub use mask::*;
+pub use polyline::*;
+pub use post::*;
+pub use precision::*;
+pub use presets::*;
 pub use program::*;
+pub use recorder::*;
+pub use registry::*;
 pub use settings::*;
+pub use shadow_map::*;
+pub use simulation::*;
+pub use skybox::*;
+pub use state::*;
 pub use texture::*;
+pub use texture_region::*;
+pub use tilemap::*;
 pub use types::{DataType, TypeMark};
 pub use uniforms::{IntoUniform, UniformValue};
+pub use video_texture::*;
+pub use worker::*;
 
 pub use webgl_rc_macro::*;
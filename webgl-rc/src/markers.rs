@@ -0,0 +1,192 @@
+use crate::{
+    Attributes, BufferUsage, Color, Gl, GlError, Instances, ItemsBuffer, PrimitiveType, Program,
+    Texture, TextureRegion, Uniforms,
+};
+
+const VERTEX_SHADER: &str = r#"
+    attribute vec2 a_corner;
+    attribute vec2 i_position;
+    attribute float i_size;
+    attribute vec4 i_color;
+    uniform vec2 u_view_size;
+    uniform vec4 u_region;
+    varying vec2 v_corner;
+    varying vec2 v_uv;
+    varying float v_size;
+    varying vec4 v_color;
+    void main() {
+        vec2 position = (i_position + a_corner * i_size * 0.5) / u_view_size;
+        gl_Position = vec4(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0, 0.0, 1.0);
+        v_corner = a_corner;
+        v_uv = u_region.xy + (a_corner * 0.5 + 0.5) * u_region.zw;
+        v_size = i_size;
+        v_color = i_color;
+    }
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform int u_shape;
+    uniform sampler2D u_glyph;
+    varying vec2 v_corner;
+    varying vec2 v_uv;
+    varying float v_size;
+    varying vec4 v_color;
+
+    const int CIRCLE = 0;
+    const int SQUARE = 1;
+
+    void main() {
+        if (u_shape == CIRCLE) {
+            // The edge is smoothed over a view unit
+            float alpha = clamp((1.0 - length(v_corner)) * v_size * 0.5, 0.0, 1.0);
+            if (alpha <= 0.0) {
+                discard;
+            }
+            gl_FragColor = vec4(v_color.rgb, v_color.a * alpha);
+        } else if (u_shape == SQUARE) {
+            gl_FragColor = v_color;
+        } else {
+            gl_FragColor = texture2D(u_glyph, v_uv) * v_color;
+        }
+    }
+"#;
+
+/// Point of a [Markers] set. The color is passed as sRGB bytes and written as is.
+#[derive(Clone, Copy, Debug, PartialEq, Instances)]
+pub struct Marker {
+    pub position: [f32; 2],
+    /// Diameter of circles, side of squares and glyphs
+    pub size: f32,
+    pub color: Color,
+}
+
+impl Marker {
+    pub fn new(position: [f32; 2], size: f32, color: Color) -> Marker {
+        Marker {
+            position,
+            size,
+            color,
+        }
+    }
+}
+
+/// Shape drawn for every marker.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum MarkerShape {
+    /// Circle with a smoothed edge
+    #[default]
+    Circle,
+    Square,
+    /// Region of a texture, like an icon of an atlas, multiplied by the marker color
+    Glyph(TextureRegion),
+}
+
+impl MarkerShape {
+    fn index(&self) -> i32 {
+        match self {
+            MarkerShape::Circle => 0,
+            MarkerShape::Square => 1,
+            MarkerShape::Glyph(_) => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Attributes)]
+struct MarkerCorner {
+    corner: [f32; 2],
+}
+
+#[derive(Clone, Uniforms)]
+struct MarkersUniforms {
+    view_size: [f32; 2],
+    shape: i32,
+    glyph: Option<Texture>,
+    region: [f32; 4],
+}
+
+/// Scatter plot of markers, each one is an instance of a quad, so millions of points
+/// are drawn with a single draw call.
+///
+/// Positions and sizes are in view units, the view shows `view_size` units
+/// from the top left corner, like [Tilemap](crate::Tilemap). Markers are drawn in order,
+/// enable blending for translucent colors and smooth circle edges.
+#[derive(Debug)]
+pub struct Markers {
+    gl: Gl,
+    program: Program,
+    quad: ItemsBuffer<MarkerCorner>,
+    markers: ItemsBuffer<Marker>,
+    shape: MarkerShape,
+}
+
+impl Markers {
+    pub fn new(gl: Gl, markers: &[Marker]) -> Result<Markers, GlError> {
+        let quad = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]]
+            .map(|corner| MarkerCorner { corner });
+        Ok(Markers {
+            program: gl.program(FRAGMENT_SHADER, VERTEX_SHADER)?,
+            quad: gl.items_buffer(&quad, BufferUsage::Static)?,
+            markers: gl.items_buffer(markers, BufferUsage::Static)?,
+            gl,
+            shape: MarkerShape::default(),
+        })
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.gl.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.markers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Buffer of the markers, update a part of them with [ItemsBuffer::update].
+    pub fn markers(&self) -> &ItemsBuffer<Marker> {
+        &self.markers
+    }
+
+    /// Replace all the markers, `usage` hints how often they're going to change.
+    pub fn set_markers(&self, markers: &[Marker], usage: BufferUsage) {
+        self.markers.set_content(markers, usage);
+    }
+
+    pub fn shape(&self) -> &MarkerShape {
+        &self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: MarkerShape) {
+        self.shape = shape;
+    }
+
+    pub fn with_shape(mut self, shape: MarkerShape) -> Self {
+        self.set_shape(shape);
+        self
+    }
+
+    /// Draw into the current viewport showing `view_size` units.
+    pub fn draw(&self, view_size: [f32; 2]) -> Result<(), GlError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let (glyph, region) = match &self.shape {
+            MarkerShape::Glyph(region) => (Some(region.texture().clone()), region.rect()),
+            _ => (None, [0.0, 0.0, 1.0, 1.0]),
+        };
+        self.program.draw_instances(
+            PrimitiveType::TriangleStrip,
+            &MarkersUniforms {
+                view_size,
+                shape: self.shape.index(),
+                glyph,
+                region,
+            },
+            &self.quad,
+            &self.markers,
+        )
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{ClearParams, DepthFunction, Gl, Settings, StencilOperation};
+
+/// Stencil masking, drawing content clipped to arbitrary shapes.
+///
+/// The mask shape is drawn into the stencil buffer first, then the content is drawn
+/// only where the shape was (or wasn't) drawn:
+///
+/// ```ignore
+/// let mask = Mask::new(gl.clone());
+/// mask.begin_define(|| draw_mask_shape());
+/// mask.draw_masked(|| draw_content());
+/// ```
+///
+/// The target needs a stencil buffer. Canvas contexts are created without one, so render
+/// into a frame buffer with a [DepthBufferFormat](crate::DepthBufferFormat) that has stencil.
+#[derive(Clone, Debug)]
+pub struct Mask {
+    gl: Gl,
+    reference: i32,
+}
+
+impl Mask {
+    pub fn new(gl: Gl) -> Mask {
+        Mask { gl, reference: 1 }
+    }
+
+    /// Stencil value written for the mask shape, it allows to keep several masks
+    /// in the same buffer when defined with [Mask::add_define].
+    pub fn with_reference(self, reference: i32) -> Mask {
+        Mask { reference, ..self }
+    }
+
+    pub fn reference(&self) -> i32 {
+        self.reference
+    }
+
+    /// Clear the stencil buffer and draw the mask shape into it. Color and depth are not written.
+    pub fn begin_define<R>(&self, callback: impl FnOnce() -> R) -> R {
+        self.gl.apply(Gl::settings().stencil_mask(!0), || {
+            self.gl.clear(ClearParams {
+                stencil: Some(0),
+                ..Default::default()
+            })
+        });
+        self.add_define(callback)
+    }
+
+    /// Draw the mask shape without clearing the stencil buffer, extending the existing mask.
+    pub fn add_define<R>(&self, callback: impl FnOnce() -> R) -> R {
+        self.gl.apply(
+            Gl::settings()
+                .stencil_test(true)
+                .stencil_function(DepthFunction::Always, self.reference, !0)
+                .stencil_operation(
+                    StencilOperation::Keep,
+                    StencilOperation::Keep,
+                    StencilOperation::Replace,
+                )
+                .stencil_mask(!0)
+                .color_mask(false, false, false, false)
+                .depth_mask(false),
+            callback,
+        )
+    }
+
+    /// Draw only inside the mask shape.
+    pub fn draw_masked<R>(&self, callback: impl FnOnce() -> R) -> R {
+        self.draw(DepthFunction::Equal, callback)
+    }
+
+    /// Draw only outside the mask shape.
+    pub fn draw_outside<R>(&self, callback: impl FnOnce() -> R) -> R {
+        self.draw(DepthFunction::NotEqual, callback)
+    }
+
+    fn draw<R>(&self, function: DepthFunction, callback: impl FnOnce() -> R) -> R {
+        self.gl.apply(
+            Gl::settings()
+                .stencil_test(true)
+                .stencil_function(function, self.reference, !0)
+                .stencil_operation(
+                    StencilOperation::Keep,
+                    StencilOperation::Keep,
+                    StencilOperation::Keep,
+                )
+                .stencil_mask(0),
+            callback,
+        )
+    }
+}
@@ -0,0 +1,282 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use js_sys::Uint8Array;
+use web_sys::WebGlRenderingContext as Context;
+
+use crate::backend::{
+    AttributeInfo, Backend, LinkOptions, PixelData, ProgramLayout, TextureSource, UniformInfo,
+};
+use crate::features::Feature;
+use crate::recorder::Command;
+use crate::types::DataType;
+use crate::{GlError, WebGlVersion};
+
+/// Backend without a GL context, commands are only recorded by [Gl](crate::Gl).
+///
+/// Programs are "linked" by scanning the shader sources for attribute and uniform declarations,
+/// so drawing code sets the same attributes and uniforms it would with a real context.
+/// Readbacks return zeros.
+#[derive(Debug, Default)]
+pub(crate) struct MockBackend {
+    shaders: RefCell<HashMap<u64, String>>,
+    lost: Cell<bool>,
+    /// Extensions reported as unsupported
+    missing_extensions: Vec<String>,
+}
+
+impl MockBackend {
+    pub(crate) fn without_extensions(extensions: &[&str]) -> MockBackend {
+        MockBackend {
+            missing_extensions: extensions.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Remove `//` and `/* */` comments.
+fn strip_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix("//") {
+            rest = tail.find('\n').map(|i| &tail[i..]).unwrap_or("");
+        } else if let Some(tail) = rest.strip_prefix("/*") {
+            rest = tail.find("*/").map(|i| &tail[i + 2..]).unwrap_or("");
+            result.push(' ');
+        } else {
+            let c = rest.chars().next().unwrap();
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    result
+}
+
+/// Declarations like `uniform highp vec2 u_a, u_b;` with the given qualifier.
+fn declarations(source: &str, qualifier: &str) -> Vec<(String, DataType)> {
+    let mut result = Vec::new();
+    // Preprocessor directives aren't terminated with a semicolon
+    let source = strip_comments(source)
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    for statement in source.split([';', '{', '}']) {
+        let mut tokens = statement.split_whitespace();
+        if tokens.next() != Some(qualifier) {
+            continue;
+        }
+        let mut tokens = tokens.skip_while(|token| matches!(*token, "lowp" | "mediump" | "highp"));
+        let data_type = match tokens.next().and_then(glsl_type) {
+            Some(data_type) => data_type,
+            None => continue,
+        };
+        let names: String = tokens.collect();
+        for name in names.split(',').filter(|name| !name.is_empty()) {
+            // Arrays are not supported
+            if !name.contains('[') {
+                result.push((name.to_string(), data_type));
+            }
+        }
+    }
+    result
+}
+
+fn glsl_type(name: &str) -> Option<DataType> {
+    [
+        DataType::Boolean,
+        DataType::Int,
+        DataType::IVec2,
+        DataType::IVec3,
+        DataType::IVec4,
+        DataType::Float,
+        DataType::Vec2,
+        DataType::Vec3,
+        DataType::Vec4,
+        DataType::Mat2,
+        DataType::Mat3,
+        DataType::Mat4,
+        DataType::Sampler,
+        DataType::SamplerCube,
+    ]
+    .iter()
+    .copied()
+    .find(|data_type| <&str>::from(*data_type) == name)
+}
+
+impl Backend for MockBackend {
+    fn execute(&self, command: &Command) -> Result<(), GlError> {
+        match command {
+            Command::DeleteShader { id } => {
+                self.shaders.borrow_mut().remove(id);
+            }
+            Command::VertexAttribDivisor { .. }
+                if !self.extension_supported("ANGLE_instanced_arrays") =>
+            {
+                return Err(GlError::ExtensionNotFound(
+                    "ANGLE_instanced_arrays".into(),
+                ))
+            }
+            Command::CreateShader { id, .. } | Command::CreateProgram { id, .. } => {
+                return Err(GlError::UnknownError(Some(format!(
+                    "Object #{} can't be created from a command",
+                    id
+                ))))
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn compile_shader(&self, id: u64, _shader_type: u32, source: &str) -> Result<(), GlError> {
+        self.shaders.borrow_mut().insert(id, source.into());
+        Ok(())
+    }
+
+    fn link_program(
+        &self,
+        _id: u64,
+        vertex_shader: u64,
+        fragment_shader: u64,
+        options: &LinkOptions,
+    ) -> Result<ProgramLayout, GlError> {
+        if options.transform_feedback.is_some() {
+            return Err(GlError::FeatureNotSupported(Feature::WebGl2));
+        }
+        let shaders = self.shaders.borrow();
+        let vertex = shaders
+            .get(&vertex_shader)
+            .ok_or(GlError::ObjectNotFound(vertex_shader))?;
+        let fragment = shaders
+            .get(&fragment_shader)
+            .ok_or(GlError::ObjectNotFound(fragment_shader))?;
+
+        // Unbound attributes take the free locations in the declaration order
+        let bound = &options.attribute_locations;
+        let mut next = 0;
+        let attributes = declarations(vertex, "attribute")
+            .into_iter()
+            .map(|(name, data_type)| {
+                let location = match bound.iter().find(|(bound, _)| *bound == name) {
+                    Some((_, location)) => *location,
+                    None => {
+                        while bound.iter().any(|(_, location)| *location == next) {
+                            next += 1;
+                        }
+                        next += 1;
+                        next - 1
+                    }
+                };
+                AttributeInfo {
+                    name,
+                    location,
+                    data_type,
+                }
+            })
+            .collect();
+
+        let mut uniforms: Vec<UniformInfo> = Vec::new();
+        for (name, data_type) in declarations(vertex, "uniform")
+            .into_iter()
+            .chain(declarations(fragment, "uniform"))
+        {
+            if uniforms.iter().all(|uniform| uniform.name != name) {
+                uniforms.push(UniformInfo { name, data_type });
+            }
+        }
+
+        Ok(ProgramLayout {
+            attributes,
+            uniforms,
+        })
+    }
+
+    fn buffer_data(&self, _target: u32, _data: &[u8], _usage: u32) {}
+
+    fn buffer_sub_data(&self, _target: u32, _offset: usize, _data: &[u8]) {}
+
+    fn tex_image(
+        &self,
+        _target: u32,
+        _format: u32,
+        _width: u32,
+        _height: u32,
+        _data_type: u32,
+        _source: TextureSource,
+    ) -> Result<(), GlError> {
+        Ok(())
+    }
+
+    fn tex_sub_image(
+        &self,
+        _x: i32,
+        _y: i32,
+        _width: u32,
+        _height: u32,
+        _format: u32,
+        _data_type: u32,
+        _data: PixelData,
+    ) -> Result<(), GlError> {
+        Ok(())
+    }
+
+    fn read_pixels(
+        &self,
+        _x: i32,
+        _y: i32,
+        _width: u32,
+        _height: u32,
+        output: &mut [u8],
+    ) -> Result<(), GlError> {
+        output.iter_mut().for_each(|value| *value = 0);
+        Ok(())
+    }
+
+    fn read_pixels_into_buffer(
+        &self,
+        _x: i32,
+        _y: i32,
+        _width: u32,
+        _height: u32,
+        _output: &Uint8Array,
+    ) -> Result<(), GlError> {
+        Err(GlError::ReadPixelsError(Some(
+            "JS buffers aren't available without a context".into(),
+        )))
+    }
+
+    fn frame_buffer_status(&self) -> u32 {
+        Context::FRAMEBUFFER_COMPLETE
+    }
+
+    fn depth_texture_supported(&self) -> bool {
+        true
+    }
+
+    fn element_index_uint_supported(&self) -> bool {
+        true
+    }
+
+    fn extension_supported(&self, name: &str) -> bool {
+        !self.missing_extensions.iter().any(|missing| missing == name)
+    }
+
+    fn version(&self) -> WebGlVersion {
+        WebGlVersion::WebGl1
+    }
+
+    /// Size of a canvas without width and height attributes
+    fn drawing_buffer_size(&self) -> (i32, i32) {
+        (300, 150)
+    }
+
+    fn is_context_lost(&self) -> bool {
+        self.lost.get()
+    }
+
+    fn simulate_context_loss(&self, lost: bool) -> Result<(), GlError> {
+        self.lost.set(lost);
+        Ok(())
+    }
+}
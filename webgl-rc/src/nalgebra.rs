@@ -3,48 +3,48 @@ use crate::{DataType, IntoUniform, TypeMark, UniformValue, Writable};
 use glm::{Vec1, Vec2, Vec3, Vec4};
 
 impl TypeMark for Vec1 {
-    fn data_type() -> DataType {
-        DataType::Float
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Float);
 }
 
 impl TypeMark for Vec2 {
-    fn data_type() -> DataType {
-        DataType::Vec2
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec2);
 }
 
 impl TypeMark for Vec3 {
-    fn data_type() -> DataType {
-        DataType::Vec3
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec3);
 }
 
 impl TypeMark for Vec4 {
-    fn data_type() -> DataType {
-        DataType::Vec4
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Vec4);
 }
 
 impl IntoUniform for Vec1 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         self.x.into_uniform()
     }
 }
 
 impl IntoUniform for Vec2 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         (self.x, self.y).into_uniform()
     }
 }
 
 impl IntoUniform for Vec3 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         (self.x, self.y, self.z).into_uniform()
     }
 }
 
 impl IntoUniform for Vec4 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         (self.x, self.y, self.z, self.w).into_uniform()
     }
@@ -99,30 +99,28 @@ impl Writable for Vec4 {
 use glm::{Mat2, Mat3, Mat4};
 
 impl TypeMark for Mat2 {
-    fn data_type() -> DataType {
-        DataType::Mat2
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Mat2);
 }
 
 impl TypeMark for Mat3 {
-    fn data_type() -> DataType {
-        DataType::Mat3
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Mat3);
 }
 
 impl TypeMark for Mat4 {
-    fn data_type() -> DataType {
-        DataType::Mat4
-    }
+    const DATA_TYPE: Option<DataType> = Some(DataType::Mat4);
 }
 
 impl IntoUniform for Mat2 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Mat2([self.m11, self.m12, self.m21, self.m22])
     }
 }
 
 impl IntoUniform for Mat3 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Mat3([
             self.m11, self.m12, self.m13, self.m21, self.m22, self.m23, self.m31, self.m32,
@@ -132,6 +130,8 @@ impl IntoUniform for Mat3 {
 }
 
 impl IntoUniform for Mat4 {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
     fn into_uniform(&self) -> UniformValue {
         UniformValue::Mat4([
             self.m11, self.m12, self.m13, self.m14, self.m21, self.m22, self.m23, self.m24,
@@ -0,0 +1,357 @@
+use crate::color::COLOR_GLSL;
+use crate::{
+    Attributes, BufferUsage, Color, Gl, GlError, Instances, IntoUniform, ItemsBuffer,
+    PrimitiveType, Program, Uniforms,
+};
+
+const VERTEX_SHADER: &str = r#"
+    attribute vec3 a_vertex;
+    attribute vec2 i_previous;
+    attribute vec2 i_start;
+    attribute vec2 i_end;
+    attribute vec2 i_next;
+    uniform vec2 u_view_size;
+    uniform float u_width;
+    uniform int u_join;
+    uniform int u_cap;
+    uniform float u_miter_limit;
+    varying vec2 v_local;
+    varying float v_length;
+    varying vec2 v_round;
+
+    const int MITER = 0;
+    const int BEVEL = 1;
+    const int ROUND = 2;
+    const int BUTT = 0;
+
+    vec2 normal(vec2 direction) {
+        return vec2(-direction.y, direction.x);
+    }
+
+    void main() {
+        float half_width = u_width * 0.5;
+        vec2 segment = i_end - i_start;
+        float segment_length = length(segment);
+        vec2 direction = segment_length > 0.0 ? segment / segment_length : vec2(1.0, 0.0);
+        vec2 side = normal(direction);
+
+        bool at_end = a_vertex.x > 0.5;
+        vec2 point = at_end ? i_end : i_start;
+        bool joined = at_end ? i_next != i_end : i_previous != i_start;
+        // Direction of the adjacent segment, both segments compute the same miter
+        vec2 adjacent = joined ? normalize(at_end ? i_next - i_end : i_start - i_previous) : direction;
+        vec2 miter = normalize(side + normal(adjacent));
+        float miter_scale = 1.0 / max(dot(miter, side), 0.0001);
+        bool mitered = joined && u_join == MITER && miter_scale <= u_miter_limit;
+
+        vec2 position;
+        if (a_vertex.z > 0.5) {
+            // Triangle filling the outer side of a bevel join at the start
+            bool beveled = joined && !at_end && (u_join == BEVEL || u_join == MITER && !mitered);
+            float turn = adjacent.x * direction.y - adjacent.y * direction.x;
+            float outer = turn > 0.0 ? -1.0 : 1.0;
+            vec2 corner = a_vertex.y < -0.5 ? normal(adjacent) : side;
+            position = point + (beveled ? corner * outer * half_width * abs(a_vertex.y) : vec2(0.0));
+            v_local = vec2(0.0);
+        } else {
+            vec2 offset = mitered ? miter * miter_scale * half_width : side * half_width;
+            // Round joins and square or round caps extend the segment by the half of the width
+            bool extended = joined ? u_join == ROUND : u_cap != BUTT;
+            float extension = extended ? (at_end ? half_width : -half_width) : 0.0;
+            position = point + offset * a_vertex.y + direction * extension;
+            v_local = vec2((at_end ? segment_length : 0.0) + extension, a_vertex.y * half_width);
+        }
+        v_length = segment_length;
+        v_round = vec2(
+            i_previous != i_start ? float(u_join == ROUND) : float(u_cap == ROUND),
+            i_next != i_end ? float(u_join == ROUND) : float(u_cap == ROUND)
+        );
+
+        vec2 clip = position / u_view_size;
+        gl_Position = vec4(clip.x * 2.0 - 1.0, 1.0 - clip.y * 2.0, 0.0, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform vec4 u_color;
+    uniform float u_width;
+    varying vec2 v_local;
+    varying float v_length;
+    varying vec2 v_round;
+
+    void main() {
+        float half_width = u_width * 0.5;
+        if (v_local.x < 0.0 && v_round.x > 0.5 && length(v_local) > half_width) {
+            discard;
+        }
+        if (v_local.x > v_length && v_round.y > 0.5
+            && length(v_local - vec2(v_length, 0.0)) > half_width) {
+            discard;
+        }
+        gl_FragColor = linear_to_srgb(u_color);
+    }
+"#;
+
+/// Shape of the corners between segments.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, IntoUniform)]
+pub enum LineJoin {
+    /// Sharp corners, beveled when the miter is longer than the miter limit
+    #[default]
+    Miter = 0,
+    Bevel = 1,
+    Round = 2,
+}
+
+/// Shape of the ends of open polylines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, IntoUniform)]
+pub enum LineCap {
+    /// The line ends at the end points
+    #[default]
+    Butt = 0,
+    /// The line is extended by the half of the width
+    Square = 1,
+    Round = 2,
+}
+
+#[derive(Clone, Copy, Debug, Attributes)]
+struct LineVertex {
+    /// End of the segment, side of the line and whether it's a join vertex
+    vertex: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, Instances)]
+struct LineSegment {
+    previous: [f32; 2],
+    start: [f32; 2],
+    end: [f32; 2],
+    next: [f32; 2],
+}
+
+#[derive(Clone, Uniforms)]
+struct PolylineUniforms {
+    view_size: [f32; 2],
+    width: f32,
+    join: LineJoin,
+    cap: LineCap,
+    miter_limit: f32,
+    color: Color,
+}
+
+/// Line strip of any width, since `lineWidth` above 1 isn't supported on most platforms.
+///
+/// Each segment is an instance expanded into a quad with a triangle filling bevel joins,
+/// all the segments are drawn with a single instanced draw call.
+/// Points and the width are in view units, the view shows `view_size` units
+/// from the top left corner, like [Tilemap](crate::Tilemap).
+/// Round joins overlap, so translucent lines are blended twice at the corners.
+#[derive(Debug)]
+pub struct Polyline {
+    gl: Gl,
+    program: Program,
+    vertices: ItemsBuffer<LineVertex>,
+    segments: ItemsBuffer<LineSegment>,
+    points: Vec<[f32; 2]>,
+    closed: bool,
+    width: f32,
+    color: Color,
+    join: LineJoin,
+    cap: LineCap,
+    miter_limit: f32,
+}
+
+impl Polyline {
+    pub fn new(gl: Gl) -> Result<Polyline, GlError> {
+        let vertices = [
+            [0.0, -1.0, 0.0],
+            [1.0, -1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, -1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, -1.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ]
+        .map(|vertex| LineVertex { vertex });
+        Ok(Polyline {
+            program: gl.program(&format!("{}{}", COLOR_GLSL, FRAGMENT_SHADER), VERTEX_SHADER)?,
+            vertices: gl.items_buffer(&vertices, BufferUsage::Static)?,
+            segments: gl.items_buffer(&[], BufferUsage::Dynamic)?,
+            gl,
+            points: Vec::new(),
+            closed: false,
+            width: 1.0,
+            color: Color::WHITE,
+            join: LineJoin::default(),
+            cap: LineCap::default(),
+            miter_limit: 4.0,
+        })
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.gl.clone()
+    }
+
+    pub fn points(&self) -> &[[f32; 2]] {
+        &self.points
+    }
+
+    /// Replace the points, repeated consecutive points are skipped.
+    pub fn set_points(&mut self, points: &[[f32; 2]]) {
+        self.points.clear();
+        for point in points {
+            if self.points.last() != Some(point) {
+                self.points.push(*point);
+            }
+        }
+        self.upload();
+    }
+
+    pub fn with_points(mut self, points: &[[f32; 2]]) -> Self {
+        self.set_points(points);
+        self
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Connect the last point with the first one, closed polylines have no caps.
+    pub fn set_closed(&mut self, closed: bool) {
+        self.closed = closed;
+        self.upload();
+    }
+
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.set_closed(closed);
+        self
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width;
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.set_width(width);
+        self
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.set_color(color);
+        self
+    }
+
+    pub fn join(&self) -> LineJoin {
+        self.join
+    }
+
+    pub fn set_join(&mut self, join: LineJoin) {
+        self.join = join;
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.set_join(join);
+        self
+    }
+
+    pub fn cap(&self) -> LineCap {
+        self.cap
+    }
+
+    pub fn set_cap(&mut self, cap: LineCap) {
+        self.cap = cap;
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.set_cap(cap);
+        self
+    }
+
+    pub fn miter_limit(&self) -> f32 {
+        self.miter_limit
+    }
+
+    /// Longest miter relative to the half of the width, sharper corners are beveled.
+    pub fn set_miter_limit(&mut self, limit: f32) {
+        self.miter_limit = limit;
+    }
+
+    pub fn with_miter_limit(mut self, limit: f32) -> Self {
+        self.set_miter_limit(limit);
+        self
+    }
+
+    /// Segments with their neighbour points, an end point is its own neighbour.
+    fn build_segments(&self) -> Vec<LineSegment> {
+        let points = &self.points;
+        let mut count = points.len();
+        // The first point repeated at the end is the same as closing the line
+        if self.closed && count > 1 && points[0] == points[count - 1] {
+            count -= 1;
+        }
+        let closed = self.closed && count > 2;
+        let segments = match count {
+            0 | 1 => 0,
+            _ if closed => count,
+            _ => count - 1,
+        };
+        (0..segments)
+            .map(|i| {
+                let start = points[i];
+                let end = points[(i + 1) % count];
+                LineSegment {
+                    previous: if i > 0 || closed {
+                        points[(i + count - 1) % count]
+                    } else {
+                        start
+                    },
+                    start,
+                    end,
+                    next: if i + 2 < count || closed {
+                        points[(i + 2) % count]
+                    } else {
+                        end
+                    },
+                }
+            })
+            .collect()
+    }
+
+    fn upload(&self) {
+        self.segments
+            .set_content(&self.build_segments(), BufferUsage::Dynamic);
+    }
+
+    /// Draw into the current viewport showing `view_size` units.
+    pub fn draw(&self, view_size: [f32; 2]) -> Result<(), GlError> {
+        if self.points.len() < 2 {
+            return Ok(());
+        }
+        self.program.draw_instances(
+            PrimitiveType::Triangles,
+            &PolylineUniforms {
+                view_size,
+                width: self.width,
+                join: self.join,
+                cap: self.cap,
+                miter_limit: self.miter_limit,
+                color: self.color,
+            },
+            &self.vertices,
+            &self.segments,
+        )
+    }
+}
@@ -0,0 +1,223 @@
+//! Post-processing passes drawing full screen effects over rendered textures.
+//!
+//! Effects implement [PostEffect] and can be combined with an [EffectChain],
+//! which renders them one after another through intermediate textures.
+
+mod bloom;
+mod blur;
+mod fxaa;
+mod lut;
+mod tonemap;
+
+pub use bloom::*;
+pub use blur::*;
+pub use fxaa::*;
+pub use lut::*;
+pub use tonemap::*;
+
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::uniforms::Uniforms;
+use crate::{
+    Attributes, BufferUsage, FrameBuffer, Gl, GlError, ItemsBuffer, PrimitiveType, Program,
+    Settings, Texture, TextureContent, TextureFormat, TextureType,
+};
+
+const VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    varying vec2 v_uv;
+    void main() {
+        v_uv = a_position * 0.5 + 0.5;
+        gl_Position = vec4(a_position, 0.0, 1.0);
+    }
+"#;
+
+#[derive(Clone, Copy, Debug, Attributes)]
+struct ScreenAttributes {
+    position: [f32; 2],
+}
+
+/// Full screen quad drawn with a custom fragment shader.
+///
+/// The fragment shader receives texture coordinates of the screen in `varying vec2 v_uv`.
+#[derive(Clone, Debug)]
+pub struct ScreenPass {
+    gl: Gl,
+    program: Program,
+    quad: ItemsBuffer<ScreenAttributes>,
+}
+
+impl ScreenPass {
+    pub fn new(gl: Gl, fragment: &str) -> Result<ScreenPass, GlError> {
+        Ok(ScreenPass {
+            program: gl.program(fragment, VERTEX_SHADER)?,
+            quad: gl.items_buffer(
+                &[
+                    ScreenAttributes {
+                        position: [-1.0, -1.0],
+                    },
+                    ScreenAttributes {
+                        position: [1.0, -1.0],
+                    },
+                    ScreenAttributes {
+                        position: [-1.0, 1.0],
+                    },
+                    ScreenAttributes {
+                        position: [1.0, 1.0],
+                    },
+                ],
+                BufferUsage::Static,
+            )?,
+            gl,
+        })
+    }
+
+    pub fn program(&self) -> Program {
+        self.program.clone()
+    }
+
+    /// Draw into the whole color texture of the target,
+    /// or into the current frame buffer and viewport when it's `None`.
+    pub fn draw<U: Uniforms + ?Sized>(
+        &self,
+        target: Option<&FrameBuffer>,
+        uniforms: &U,
+    ) -> Result<(), GlError> {
+        let settings = Gl::settings()
+            .blend(false)
+            .depth_test(false)
+            .color_mask(true, true, true, true);
+        let draw = || {
+            self.program
+                .draw_arrays(PrimitiveType::TriangleStrip, uniforms, &self.quad)
+        };
+        match target {
+            Some(target) => {
+                let (width, height) = target.color_size().unwrap_or_default();
+                self.gl.apply(
+                    settings.frame_buffer(target.clone()).viewport(
+                        0,
+                        0,
+                        width as i32,
+                        height as i32,
+                    ),
+                    draw,
+                )
+            }
+            None => self.gl.apply(settings, draw),
+        }
+    }
+}
+
+/// Full screen effect processing a texture.
+pub trait PostEffect: fmt::Debug {
+    /// Draw the `source` processed by the effect into the target,
+    /// or into the current frame buffer and viewport when it's `None`.
+    fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError>;
+}
+
+/// Frame buffer with an RGBA color texture, recreated when the requested size or type changes.
+#[derive(Debug, Default)]
+pub struct RenderTarget {
+    target: RefCell<Option<(FrameBuffer, Texture)>>,
+}
+
+impl RenderTarget {
+    pub fn new() -> RenderTarget {
+        Default::default()
+    }
+
+    /// Frame buffer and its color texture of the requested size and type.
+    pub fn get(
+        &self,
+        gl: &Gl,
+        width: u32,
+        height: u32,
+        data_type: TextureType,
+    ) -> Result<(FrameBuffer, Texture), GlError> {
+        let mut target = self.target.borrow_mut();
+        match target.as_ref() {
+            Some((buffer, texture))
+                if texture.size() == (width, height) && texture.data_type() == data_type =>
+            {
+                Ok((buffer.clone(), texture.clone()))
+            }
+            _ => {
+                let texture = gl.texture(
+                    width,
+                    height,
+                    data_type,
+                    TextureFormat::Rgba,
+                    TextureContent::None,
+                )?;
+                let buffer = gl.frame_buffer_with_color(texture.clone())?;
+                *target = Some((buffer.clone(), texture.clone()));
+                Ok((buffer, texture))
+            }
+        }
+    }
+}
+
+/// Sequence of effects, each one processes the output of the previous one.
+///
+/// Intermediate textures have the size and type of the source texture.
+/// An empty chain doesn't draw anything.
+#[derive(Debug)]
+pub struct EffectChain {
+    gl: Gl,
+    effects: Vec<Box<dyn PostEffect>>,
+    targets: [RenderTarget; 2],
+}
+
+impl EffectChain {
+    pub fn new(gl: Gl) -> EffectChain {
+        EffectChain {
+            gl,
+            effects: Vec::new(),
+            targets: Default::default(),
+        }
+    }
+
+    pub fn push(&mut self, effect: impl PostEffect + 'static) {
+        self.effects.push(Box::new(effect));
+    }
+
+    pub fn with(mut self, effect: impl PostEffect + 'static) -> Self {
+        self.push(effect);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError> {
+        let mut input = source.clone();
+        for (i, effect) in self.effects.iter().enumerate() {
+            if i + 1 == self.effects.len() {
+                effect.render(&input, target)?;
+            } else {
+                let (buffer, texture) = self.targets[i % 2].get(
+                    &self.gl,
+                    source.width(),
+                    source.height(),
+                    source.data_type(),
+                )?;
+                effect.render(&input, Some(&buffer))?;
+                input = texture;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PostEffect for EffectChain {
+    fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError> {
+        EffectChain::render(self, source, target)
+    }
+}
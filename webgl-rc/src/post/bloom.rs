@@ -0,0 +1,148 @@
+use std::cell::Cell;
+
+use super::{BlurQuality, GaussianBlur, PostEffect, RenderTarget, ScreenPass};
+use crate::{FrameBuffer, Gl, GlError, Settings, Texture, TextureFilter, Uniforms};
+
+const THRESHOLD_SHADER: &str = r#"
+    precision mediump float;
+    uniform sampler2D u_source;
+    uniform float u_threshold;
+    varying vec2 v_uv;
+    void main() {
+        vec3 color = texture2D(u_source, v_uv).rgb;
+        float brightness = max(color.r, max(color.g, color.b));
+        gl_FragColor = vec4(color * max(brightness - u_threshold, 0.0) / max(brightness, 0.0001), 1.0);
+    }
+"#;
+
+const COMPOSITE_SHADER: &str = r#"
+    precision mediump float;
+    uniform sampler2D u_source;
+    uniform sampler2D u_bloom;
+    uniform float u_intensity;
+    varying vec2 v_uv;
+    void main() {
+        vec4 color = texture2D(u_source, v_uv);
+        gl_FragColor = vec4(color.rgb + texture2D(u_bloom, v_uv).rgb * u_intensity, color.a);
+    }
+"#;
+
+#[derive(Clone, Uniforms)]
+struct ThresholdUniforms {
+    source: Texture,
+    threshold: f32,
+}
+
+#[derive(Clone, Uniforms)]
+struct CompositeUniforms {
+    source: Texture,
+    bloom: Texture,
+    intensity: f32,
+}
+
+/// Glow around bright areas: pixels brighter than the threshold are blurred at half
+/// resolution and added on top of the source.
+///
+/// Use a `HalfFloat` or `Float` source to bloom HDR values above `1.0`.
+#[derive(Debug)]
+pub struct Bloom {
+    gl: Gl,
+    threshold_pass: ScreenPass,
+    composite_pass: ScreenPass,
+    blur: GaussianBlur,
+    threshold: Cell<f32>,
+    intensity: Cell<f32>,
+    bright: RenderTarget,
+    blurred: RenderTarget,
+}
+
+impl Bloom {
+    pub fn new(gl: Gl, quality: BlurQuality) -> Result<Bloom, GlError> {
+        Ok(Bloom {
+            threshold_pass: ScreenPass::new(gl.clone(), THRESHOLD_SHADER)?,
+            composite_pass: ScreenPass::new(gl.clone(), COMPOSITE_SHADER)?,
+            blur: GaussianBlur::new(gl.clone(), quality)?,
+            gl,
+            threshold: Cell::new(0.8),
+            intensity: Cell::new(1.0),
+            bright: RenderTarget::new(),
+            blurred: RenderTarget::new(),
+        })
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold.get()
+    }
+
+    /// Brightness of the color's largest channel where the bloom starts.
+    pub fn set_threshold(&self, threshold: f32) {
+        self.threshold.set(threshold);
+    }
+
+    pub fn with_threshold(self, threshold: f32) -> Self {
+        self.set_threshold(threshold);
+        self
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity.get()
+    }
+
+    pub fn set_intensity(&self, intensity: f32) {
+        self.intensity.set(intensity);
+    }
+
+    pub fn with_intensity(self, intensity: f32) -> Self {
+        self.set_intensity(intensity);
+        self
+    }
+
+    /// Blur radius in pixels of the half resolution bright pass.
+    pub fn radius(&self) -> f32 {
+        self.blur.radius()
+    }
+
+    pub fn set_radius(&self, radius: f32) {
+        self.blur.set_radius(radius);
+    }
+
+    pub fn with_radius(self, radius: f32) -> Self {
+        self.set_radius(radius);
+        self
+    }
+}
+
+impl PostEffect for Bloom {
+    fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError> {
+        let width = (source.width() / 2).max(1);
+        let height = (source.height() / 2).max(1);
+        let (bright_buffer, bright) =
+            self.bright
+                .get(&self.gl, width, height, source.data_type())?;
+        let (blurred_buffer, blurred) =
+            self.blurred
+                .get(&self.gl, width, height, source.data_type())?;
+
+        self.gl.apply(
+            Gl::settings().texture_filter(source.clone(), TextureFilter::Linear),
+            || {
+                self.threshold_pass.draw(
+                    Some(&bright_buffer),
+                    &ThresholdUniforms {
+                        source: source.clone(),
+                        threshold: self.threshold(),
+                    },
+                )
+            },
+        )?;
+        self.blur.render(&bright, Some(&blurred_buffer))?;
+        self.composite_pass.draw(
+            target,
+            &CompositeUniforms {
+                source: source.clone(),
+                bloom: blurred,
+                intensity: self.intensity(),
+            },
+        )
+    }
+}
@@ -0,0 +1,123 @@
+use std::cell::Cell;
+
+use super::{PostEffect, RenderTarget, ScreenPass};
+use crate::{FrameBuffer, Gl, GlError, Settings, Texture, TextureFilter, Uniforms};
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform sampler2D u_source;
+    uniform vec2 u_step;
+    varying vec2 v_uv;
+    void main() {
+        vec4 sum = vec4(0.0);
+        float total = 0.0;
+        for (int i = -RADIUS; i <= RADIUS; i++) {
+            float x = float(i) / float(RADIUS);
+            float weight = exp(-2.0 * x * x);
+            sum += texture2D(u_source, v_uv + u_step * float(i)) * weight;
+            total += weight;
+        }
+        gl_FragColor = sum / total;
+    }
+"#;
+
+#[derive(Clone, Uniforms)]
+struct BlurUniforms {
+    source: Texture,
+    step: [f32; 2],
+}
+
+/// Number of texture samples per blur direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlurQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl BlurQuality {
+    pub fn samples(self) -> u32 {
+        match self {
+            BlurQuality::Low => 5,
+            BlurQuality::Medium => 9,
+            BlurQuality::High => 15,
+        }
+    }
+}
+
+/// Separable Gaussian blur, drawn as a horizontal and a vertical pass.
+///
+/// The radius is in pixels of the source texture, samples are spread over it
+/// with linear filtering, so large radii with low quality get blocky.
+#[derive(Debug)]
+pub struct GaussianBlur {
+    gl: Gl,
+    pass: ScreenPass,
+    quality: BlurQuality,
+    radius: Cell<f32>,
+    horizontal: RenderTarget,
+}
+
+impl GaussianBlur {
+    pub fn new(gl: Gl, quality: BlurQuality) -> Result<GaussianBlur, GlError> {
+        let radius = quality.samples() / 2;
+        let fragment = format!("#define RADIUS {}\n{}", radius, FRAGMENT_SHADER);
+        Ok(GaussianBlur {
+            pass: ScreenPass::new(gl.clone(), &fragment)?,
+            gl,
+            quality,
+            radius: Cell::new(radius as f32),
+            horizontal: RenderTarget::new(),
+        })
+    }
+
+    pub fn quality(&self) -> BlurQuality {
+        self.quality
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius.get()
+    }
+
+    pub fn set_radius(&self, radius: f32) {
+        self.radius.set(radius);
+    }
+
+    pub fn with_radius(self, radius: f32) -> Self {
+        self.set_radius(radius);
+        self
+    }
+
+    fn step(&self) -> f32 {
+        self.radius() / (self.quality.samples() / 2) as f32
+    }
+}
+
+impl PostEffect for GaussianBlur {
+    fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError> {
+        let (width, height) = source.size();
+        let (buffer, horizontal) =
+            self.horizontal
+                .get(&self.gl, width, height, source.data_type())?;
+        self.gl.apply(
+            Gl::settings().texture_filter(source.clone(), TextureFilter::Linear),
+            || {
+                self.pass.draw(
+                    Some(&buffer),
+                    &BlurUniforms {
+                        source: source.clone(),
+                        step: [self.step() / width as f32, 0.0],
+                    },
+                )
+            },
+        )?;
+        self.pass.draw(
+            target,
+            &BlurUniforms {
+                source: horizontal,
+                step: [0.0, self.step() / height as f32],
+            },
+        )
+    }
+}
@@ -0,0 +1,100 @@
+use super::{PostEffect, ScreenPass};
+use crate::{FrameBuffer, Gl, GlError, Settings, Texture, TextureFilter, Uniforms};
+
+// Simplified FXAA by Timothy Lottes: blur along the local edge direction,
+// falling back to a shorter blur when the longer one crosses the edge.
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform sampler2D u_source;
+    uniform vec2 u_texel;
+    uniform float u_span_max;
+    varying vec2 v_uv;
+
+    const float REDUCE_MIN = 1.0 / 128.0;
+    const float REDUCE_MUL = 1.0 / 8.0;
+    const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+    void main() {
+        float luma_nw = dot(texture2D(u_source, v_uv + vec2(-1.0, -1.0) * u_texel).rgb, LUMA);
+        float luma_ne = dot(texture2D(u_source, v_uv + vec2(1.0, -1.0) * u_texel).rgb, LUMA);
+        float luma_sw = dot(texture2D(u_source, v_uv + vec2(-1.0, 1.0) * u_texel).rgb, LUMA);
+        float luma_se = dot(texture2D(u_source, v_uv + vec2(1.0, 1.0) * u_texel).rgb, LUMA);
+        vec4 center = texture2D(u_source, v_uv);
+        float luma_m = dot(center.rgb, LUMA);
+        float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+        float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+        vec2 direction = vec2(
+            -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+            (luma_nw + luma_sw) - (luma_ne + luma_se)
+        );
+        float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+        float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+        direction = clamp(direction * scale, -u_span_max, u_span_max) * u_texel;
+
+        vec3 near = 0.5 * (
+            texture2D(u_source, v_uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+            texture2D(u_source, v_uv + direction * (2.0 / 3.0 - 0.5)).rgb
+        );
+        vec3 far = near * 0.5 + 0.25 * (
+            texture2D(u_source, v_uv - direction * 0.5).rgb +
+            texture2D(u_source, v_uv + direction * 0.5).rgb
+        );
+        float luma_far = dot(far, LUMA);
+        vec3 color = luma_far < luma_min || luma_far > luma_max ? near : far;
+        gl_FragColor = vec4(color, center.a);
+    }
+"#;
+
+#[derive(Clone, Uniforms)]
+struct FxaaUniforms {
+    source: Texture,
+    texel: [f32; 2],
+    span_max: f32,
+}
+
+/// Fast approximate antialiasing, smooths edges of offscreen targets
+/// which don't get the context multisampling.
+///
+/// Expects colors in display (gamma) space, so apply it after tonemapping.
+#[derive(Clone, Debug)]
+pub struct Fxaa {
+    pass: ScreenPass,
+    span_max: f32,
+}
+
+impl Fxaa {
+    pub fn new(gl: Gl) -> Result<Fxaa, GlError> {
+        Ok(Fxaa {
+            pass: ScreenPass::new(gl, FRAGMENT_SHADER)?,
+            span_max: 8.0,
+        })
+    }
+
+    pub fn span_max(&self) -> f32 {
+        self.span_max
+    }
+
+    /// Longest blur along an edge in pixels, `8.0` by default.
+    pub fn with_span_max(self, span_max: f32) -> Self {
+        Fxaa { span_max, ..self }
+    }
+}
+
+impl PostEffect for Fxaa {
+    fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError> {
+        source.gl().apply(
+            Gl::settings().texture_filter(source.clone(), TextureFilter::Linear),
+            || {
+                self.pass.draw(
+                    target,
+                    &FxaaUniforms {
+                        source: source.clone(),
+                        texel: [1.0 / source.width() as f32, 1.0 / source.height() as f32],
+                        span_max: self.span_max,
+                    },
+                )
+            },
+        )
+    }
+}
@@ -0,0 +1,301 @@
+use std::cell::Cell;
+
+use super::{PostEffect, ScreenPass};
+use crate::{
+    FrameBuffer, Gl, GlError, Settings, Texture, TextureContent, TextureFilter, TextureFormat,
+    TextureType, Uniforms,
+};
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform sampler2D u_source;
+    uniform sampler2D u_lut;
+    uniform float u_size;
+    uniform vec3 u_domain_min;
+    uniform vec3 u_domain_max;
+    uniform float u_intensity;
+    varying vec2 v_uv;
+
+    vec3 lookup(vec3 color) {
+        color = clamp((color - u_domain_min) / (u_domain_max - u_domain_min), 0.0, 1.0);
+        float blue = color.b * (u_size - 1.0);
+        float slice = floor(blue);
+        float next = min(slice + 1.0, u_size - 1.0);
+        vec2 uv = (color.rg * (u_size - 1.0) + 0.5) / vec2(u_size * u_size, u_size);
+        vec3 a = texture2D(u_lut, uv + vec2(slice / u_size, 0.0)).rgb;
+        vec3 b = texture2D(u_lut, uv + vec2(next / u_size, 0.0)).rgb;
+        return mix(a, b, blue - slice);
+    }
+
+    void main() {
+        vec4 source = texture2D(u_source, v_uv);
+        gl_FragColor = vec4(mix(source.rgb, lookup(source.rgb), u_intensity), source.a);
+    }
+"#;
+
+#[derive(Clone, Uniforms)]
+struct LutUniforms {
+    source: Texture,
+    lut: Texture,
+    size: f32,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    intensity: f32,
+}
+
+/// 3D color lookup table parsed from an Adobe / Resolve `.cube` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CubeLut {
+    pub title: Option<String>,
+    pub size: u32,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    /// `size³` colors, red changes fastest, then green, then blue
+    pub data: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    /// Parse a `.cube` file, only 3D tables are supported.
+    pub fn parse(source: &str) -> Result<CubeLut, GlError> {
+        let error = |line: usize, message: &str| {
+            GlError::ParseError(format!(".cube line {}: {}", line + 1, message))
+        };
+        let mut lut = CubeLut {
+            title: None,
+            size: 0,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data: Vec::new(),
+        };
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            match keyword {
+                "TITLE" => lut.title = Some(rest.trim_matches('"').into()),
+                "LUT_3D_SIZE" => {
+                    lut.size = rest
+                        .parse()
+                        .ok()
+                        .filter(|size| (2..=256).contains(size))
+                        .ok_or_else(|| error(i, "invalid LUT_3D_SIZE"))?;
+                }
+                "LUT_1D_SIZE" => return Err(error(i, "1D tables are not supported")),
+                "DOMAIN_MIN" => {
+                    lut.domain_min =
+                        parse_triple(rest).ok_or_else(|| error(i, "invalid DOMAIN_MIN"))?
+                }
+                "DOMAIN_MAX" => {
+                    lut.domain_max =
+                        parse_triple(rest).ok_or_else(|| error(i, "invalid DOMAIN_MAX"))?
+                }
+                _ => lut
+                    .data
+                    .push(parse_triple(line).ok_or_else(|| error(i, "invalid color"))?),
+            }
+        }
+        let expected = lut.size.pow(3) as usize;
+        if lut.size == 0 || lut.data.len() != expected {
+            return Err(GlError::ParseError(format!(
+                ".cube: {} colors expected, {} found",
+                expected,
+                lut.data.len()
+            )));
+        }
+        Ok(lut)
+    }
+
+    /// Upload as a 2D-packed LUT, see [ColorLut].
+    pub fn to_lut(&self, gl: &Gl) -> Result<ColorLut, GlError> {
+        let size = self.size as usize;
+        let mut bytes = vec![0u8; size * size * size * 4];
+        for (i, color) in self.data.iter().enumerate() {
+            let (r, g, b) = (i % size, i / size % size, i / (size * size));
+            let offset = (g * size * size + b * size + r) * 4;
+            for (channel, value) in color.iter().enumerate() {
+                bytes[offset + channel] = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+            bytes[offset + 3] = 255;
+        }
+        let texture = gl.texture(
+            self.size * self.size,
+            self.size,
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::Bytes(bytes),
+        )?;
+        Ok(ColorLut::from_texture(texture)?.with_domain(self.domain_min, self.domain_max))
+    }
+}
+
+fn parse_triple(value: &str) -> Option<[f32; 3]> {
+    let mut parts = value.split_whitespace().map(|part| part.parse::<f32>());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(a)), Some(Ok(b)), Some(Ok(c)), None) => Some([a, b, c]),
+        _ => None,
+    }
+}
+
+/// Color lookup table packed into a 2D texture.
+///
+/// A LUT of size `N` is a `N² × N` strip of `N` square slices, one per blue value,
+/// each slice has red along the x axis and green along the y axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorLut {
+    texture: Texture,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+}
+
+impl ColorLut {
+    pub fn from_texture(texture: Texture) -> Result<ColorLut, GlError> {
+        let size = texture.height();
+        if size < 2 || texture.width() != size * size {
+            return Err(GlError::UnsupportedType(Some(format!(
+                "{}: LUT strip of N² × N pixels expected, {}x{} found",
+                texture,
+                texture.width(),
+                texture.height()
+            ))));
+        }
+        Ok(ColorLut {
+            texture,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+        })
+    }
+
+    /// Input range mapped to the table, `[0, 1]` by default.
+    pub fn with_domain(self, min: [f32; 3], max: [f32; 3]) -> Self {
+        ColorLut {
+            domain_min: min,
+            domain_max: max,
+            ..self
+        }
+    }
+
+    pub fn texture(&self) -> Texture {
+        self.texture.clone()
+    }
+
+    pub fn size(&self) -> u32 {
+        self.texture.height()
+    }
+}
+
+/// Color grading with a lookup table, blended with the source by the intensity.
+///
+/// Expects colors in display space, so apply it after tonemapping.
+#[derive(Debug)]
+pub struct ColorGrading {
+    pass: ScreenPass,
+    lut: ColorLut,
+    intensity: Cell<f32>,
+}
+
+impl ColorGrading {
+    pub fn new(gl: Gl, lut: ColorLut) -> Result<ColorGrading, GlError> {
+        Ok(ColorGrading {
+            pass: ScreenPass::new(gl, FRAGMENT_SHADER)?,
+            lut,
+            intensity: Cell::new(1.0),
+        })
+    }
+
+    pub fn lut(&self) -> &ColorLut {
+        &self.lut
+    }
+
+    pub fn set_lut(&mut self, lut: ColorLut) {
+        self.lut = lut;
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity.get()
+    }
+
+    pub fn set_intensity(&self, intensity: f32) {
+        self.intensity.set(intensity);
+    }
+
+    pub fn with_intensity(self, intensity: f32) -> Self {
+        self.set_intensity(intensity);
+        self
+    }
+}
+
+impl PostEffect for ColorGrading {
+    fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError> {
+        let lut = &self.lut;
+        source.gl().apply(
+            Gl::settings().texture_filter(lut.texture(), TextureFilter::Linear),
+            || {
+                self.pass.draw(
+                    target,
+                    &LutUniforms {
+                        source: source.clone(),
+                        lut: lut.texture(),
+                        size: lut.size() as f32,
+                        domain_min: lut.domain_min,
+                        domain_max: lut.domain_max,
+                        intensity: self.intensity(),
+                    },
+                )
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: &str = "# Created by hand\nTITLE \"Identity\"\n\nLUT_3D_SIZE 2\n\
+        0 0 0\n1 0 0\n0 1 0\n1 1 0\n# blue slice\n0 0 1\n1 0 1\n0 1 1\n  1 1 1  \n";
+
+    fn is_parse_error(source: &str) -> bool {
+        matches!(CubeLut::parse(source), Err(GlError::ParseError(_)))
+    }
+
+    #[test]
+    fn parse_table() {
+        let lut = CubeLut::parse(IDENTITY).unwrap();
+        assert_eq!(lut.title.as_deref(), Some("Identity"));
+        assert_eq!(lut.size, 2);
+        assert_eq!((lut.domain_min, lut.domain_max), ([0.0; 3], [1.0; 3]));
+        assert_eq!(lut.data.len(), 8);
+        assert_eq!(lut.data[1], [1.0, 0.0, 0.0]);
+        assert_eq!(lut.data[6], [0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn parse_domain() {
+        let source = format!("DOMAIN_MIN -0.5 0 0.25\nDOMAIN_MAX 2 2 2.5\n{}", IDENTITY);
+        let lut = CubeLut::parse(&source).unwrap();
+        assert_eq!(lut.domain_min, [-0.5, 0.0, 0.25]);
+        assert_eq!(lut.domain_max, [2.0, 2.0, 2.5]);
+        assert!(is_parse_error(&format!("DOMAIN_MIN 0 0\n{}", IDENTITY)));
+        assert!(is_parse_error(&format!("DOMAIN_MAX 1 1 1 1\n{}", IDENTITY)));
+    }
+
+    #[test]
+    fn invalid_size_is_rejected() {
+        assert!(is_parse_error("LUT_3D_SIZE 1\n0 0 0\n"));
+        assert!(is_parse_error("LUT_3D_SIZE 257\n"));
+        assert!(is_parse_error("LUT_3D_SIZE two\n"));
+        assert!(is_parse_error("LUT_1D_SIZE 2\n0 0 0\n1 1 1\n"));
+        assert!(is_parse_error("0 0 0\n"));
+    }
+
+    #[test]
+    fn wrong_color_count_is_rejected() {
+        let missing = IDENTITY.trim_end().trim_end_matches("1 1 1");
+        assert!(is_parse_error(missing));
+        assert!(is_parse_error(&format!("{}0.5 0.5 0.5\n", IDENTITY)));
+        assert!(is_parse_error(&IDENTITY.replace("1 0 1", "1 0")));
+        assert!(is_parse_error(&IDENTITY.replace("1 0 1", "1 0 x")));
+    }
+}
@@ -0,0 +1,131 @@
+use std::cell::Cell;
+
+use super::{PostEffect, ScreenPass};
+use crate::{FrameBuffer, Gl, GlError, Texture, Uniforms};
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform sampler2D u_source;
+    uniform float u_operator;
+    uniform float u_exposure;
+    uniform bool u_srgb;
+    varying vec2 v_uv;
+
+    vec3 aces(vec3 x) {
+        // Narkowicz fit of the ACES filmic curve
+        return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+    }
+
+    vec3 to_srgb(vec3 linear) {
+        vec3 low = linear * 12.92;
+        vec3 high = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+        return mix(low, high, step(vec3(0.0031308), linear));
+    }
+
+    void main() {
+        vec4 source = texture2D(u_source, v_uv);
+        vec3 color = max(source.rgb * u_exposure, 0.0);
+        if (u_operator > 1.5) {
+            color = aces(color);
+        } else if (u_operator > 0.5) {
+            color = color / (1.0 + color);
+        } else {
+            color = clamp(color, 0.0, 1.0);
+        }
+        if (u_srgb) {
+            color = to_srgb(color);
+        }
+        gl_FragColor = vec4(color, source.a);
+    }
+"#;
+
+#[derive(Clone, Uniforms)]
+struct TonemapUniforms {
+    source: Texture,
+    operator: f32,
+    exposure: f32,
+    srgb: bool,
+}
+
+/// Curve mapping HDR colors into the displayable `[0, 1]` range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TonemapOperator {
+    /// Colors are clamped
+    None,
+    /// `c / (1 + c)`, keeps hue and never fully saturates
+    Reinhard,
+    /// Filmic curve approximating ACES, with more contrast and saturated highlights
+    #[default]
+    Aces,
+}
+
+/// Final output pass converting a linear, possibly HDR, frame for display:
+/// exposure, tonemapping and linear to sRGB conversion.
+#[derive(Debug)]
+pub struct Tonemap {
+    pass: ScreenPass,
+    operator: Cell<TonemapOperator>,
+    exposure: Cell<f32>,
+    srgb: Cell<bool>,
+}
+
+impl Tonemap {
+    pub fn new(gl: Gl, operator: TonemapOperator) -> Result<Tonemap, GlError> {
+        Ok(Tonemap {
+            pass: ScreenPass::new(gl, FRAGMENT_SHADER)?,
+            operator: Cell::new(operator),
+            exposure: Cell::new(1.0),
+            srgb: Cell::new(true),
+        })
+    }
+
+    pub fn operator(&self) -> TonemapOperator {
+        self.operator.get()
+    }
+
+    pub fn set_operator(&self, operator: TonemapOperator) {
+        self.operator.set(operator);
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure.get()
+    }
+
+    /// Multiplier applied to colors before tonemapping.
+    pub fn set_exposure(&self, exposure: f32) {
+        self.exposure.set(exposure);
+    }
+
+    pub fn with_exposure(self, exposure: f32) -> Self {
+        self.set_exposure(exposure);
+        self
+    }
+
+    pub fn srgb(&self) -> bool {
+        self.srgb.get()
+    }
+
+    /// Convert the result from linear to sRGB, enabled by default.
+    pub fn set_srgb(&self, srgb: bool) {
+        self.srgb.set(srgb);
+    }
+
+    pub fn with_srgb(self, srgb: bool) -> Self {
+        self.set_srgb(srgb);
+        self
+    }
+}
+
+impl PostEffect for Tonemap {
+    fn render(&self, source: &Texture, target: Option<&FrameBuffer>) -> Result<(), GlError> {
+        self.pass.draw(
+            target,
+            &TonemapUniforms {
+                source: source.clone(),
+                operator: self.operator() as u32 as f32,
+                exposure: self.exposure(),
+                srgb: self.srgb(),
+            },
+        )
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    FrameBuffer, Gl, GlError, Settings, Texture, TextureContent, TextureFormat, TextureType,
+};
+use web_sys::WebGlRenderingContext;
+
+/// GLSL helpers storing a value from `[0, 1)` in the four bytes of an RGBA texel,
+/// for render targets with [RenderPrecision::EncodedByte].
+pub const ENCODE_FLOAT_GLSL: &str = r#"
+    vec4 encode_float(float value) {
+        vec4 result = fract(clamp(value, 0.0, 0.999999) * vec4(1.0, 255.0, 65025.0, 16581375.0));
+        return result - result.yzww * vec4(1.0 / 255.0, 1.0 / 255.0, 1.0 / 255.0, 0.0);
+    }
+    float decode_float(vec4 rgba) {
+        return dot(rgba, vec4(1.0, 1.0 / 255.0, 1.0 / 65025.0, 1.0 / 16581375.0));
+    }
+"#;
+
+/// Best render target type available, from the most precise one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RenderPrecision {
+    Float,
+    HalfFloat,
+    /// Byte textures, values should be encoded with [ENCODE_FLOAT_GLSL]
+    EncodedByte,
+}
+
+impl RenderPrecision {
+    pub fn texture_type(self) -> TextureType {
+        match self {
+            RenderPrecision::Float => TextureType::Float,
+            RenderPrecision::HalfFloat => TextureType::HalfFloat,
+            RenderPrecision::EncodedByte => TextureType::Byte,
+        }
+    }
+
+    pub fn is_encoded(self) -> bool {
+        self == RenderPrecision::EncodedByte
+    }
+
+    /// Preprocessor define for the shaders, like `#define RENDER_PRECISION_FLOAT`,
+    /// so a shader can switch to the encoding where it's needed.
+    pub fn glsl_define(self) -> &'static str {
+        match self {
+            RenderPrecision::Float => "#define RENDER_PRECISION_FLOAT\n",
+            RenderPrecision::HalfFloat => "#define RENDER_PRECISION_HALF_FLOAT\n",
+            RenderPrecision::EncodedByte => "#define RENDER_PRECISION_ENCODED_BYTE\n",
+        }
+    }
+}
+
+/// Render into a small texture of the type, the frame buffer status tells
+/// whether the extensions it requires are available.
+pub(crate) fn probe(gl: &Gl, data_type: TextureType) -> bool {
+    let result = (|| -> Result<bool, GlError> {
+        let texture = Texture::new(
+            gl.clone(),
+            1,
+            1,
+            data_type,
+            TextureFormat::Rgba,
+            TextureContent::None,
+        )?;
+        let mut frame_buffer = FrameBuffer::new(gl.clone())?;
+        frame_buffer.set_color_buffer(Some(texture))?;
+        let status = gl.apply(Gl::settings().frame_buffer(frame_buffer), || {
+            gl.frame_buffer_status()
+        });
+        Ok(status == WebGlRenderingContext::FRAMEBUFFER_COMPLETE)
+    })();
+    let supported = result.unwrap_or(false);
+    trace_event!(DEBUG, ?data_type, supported, "render target type probed");
+    supported
+}
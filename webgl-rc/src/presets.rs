@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::gl::Gl;
+use crate::settings::{Settings, SettingsCache};
+
+/// Object-safe [Settings], so settings of different types can be registered together.
+trait DynSettings: fmt::Debug {
+    fn apply_dyn(&self, gl: &Gl, cache: &RefCell<SettingsCache>, callback: &mut dyn FnMut());
+}
+
+impl<S: Settings> DynSettings for S {
+    fn apply_dyn(&self, gl: &Gl, cache: &RefCell<SettingsCache>, callback: &mut dyn FnMut()) {
+        self.apply(gl, cache, callback)
+    }
+}
+
+/// Settings registered by name with [Gl::register_preset], like `"opaque"` or `"transparent"`.
+/// It's a [Settings] itself, so it can be composed with other settings.
+#[derive(Clone)]
+pub struct Preset {
+    name: Rc<str>,
+    settings: Rc<dyn DynSettings>,
+}
+
+impl Preset {
+    pub fn new(name: &str, settings: impl Settings + 'static) -> Preset {
+        Preset {
+            name: name.into(),
+            settings: Rc::new(settings),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl PartialEq for Preset {
+    fn eq(&self, other: &Preset) -> bool {
+        Rc::ptr_eq(&self.settings, &other.settings)
+    }
+}
+
+impl fmt::Debug for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Preset")
+            .field("name", &self.name)
+            .field("settings", &self.settings)
+            .finish()
+    }
+}
+
+impl Settings for Preset {
+    fn apply<R, F: FnOnce() -> R>(
+        &self,
+        gl: &Gl,
+        cache: &RefCell<SettingsCache>,
+        callback: F,
+    ) -> R {
+        let mut callback = Some(callback);
+        let mut result = None;
+        self.settings.apply_dyn(gl, cache, &mut || {
+            result = callback.take().map(|callback| callback());
+        });
+        result.expect("preset settings should call the callback")
+    }
+}
@@ -1,17 +1,25 @@
 use crate::ElementsBuffer;
-use core::convert::TryFrom;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use std::convert::TryInto;
-use std::rc::Rc;
-use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlUniformLocation};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Range;
+use std::rc::{Rc, Weak};
+use web_sys::{WebGl2RenderingContext, WebGlRenderingContext};
 
-use super::data_buffer::{Item, ItemsBuffer};
-use super::gl::Gl;
+use super::auto_uniforms::auto_uniform;
+use super::backend::{AttributeInfo, LinkOptions, UniformInfo};
+use super::cube_map::CubeMap;
+use super::data_buffer::{clamp_range, VertexSource};
+use super::features::{Feature, Features};
 use super::gl::GlError;
+use super::gl::{Gl, WebGlVersion};
 use super::settings::Settings;
 use super::texture::{Texture, TEXTURES_COUNT};
 use super::types::DataType;
-use crate::uniforms::{UniformValue, Uniforms};
+use crate::recorder::Command;
+use crate::registry::{write_name, Resource, ResourceInfo, ResourceKind};
+use crate::uniforms::{Field, UniformValue, Uniforms};
 
 #[repr(u32)]
 #[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
@@ -25,83 +33,169 @@ pub enum PrimitiveType {
     Triangles = WebGlRenderingContext::TRIANGLES,
 }
 
-#[derive(Clone, Debug)]
-struct AttributeInfo {
-    name: String,
-    location: u32,
-    data_type: DataType,
+/// Parameters of [Program::draw]. Fields can be set directly or with the builder methods,
+/// `DrawParams { range: Some(0..10), ..DrawParams::new(primitive, &uniforms, &vertices) }`.
+#[derive(Clone)]
+pub struct DrawParams<'a> {
+    pub primitive: PrimitiveType,
+    pub uniforms: &'a dyn Uniforms,
+    pub attributes: &'a dyn VertexSource,
+    /// Per instance attributes, the draw is instanced when they're set
+    pub instances: Option<&'a dyn VertexSource>,
+    /// Indices of the vertices to draw, all vertices are drawn in order without them
+    pub elements: Option<&'a ElementsBuffer>,
+    /// Instances to draw, clamped to the instances length. All instances are drawn without it,
+    /// it's ignored by draws without instances.
+    pub range: Option<Range<usize>>,
+    /// Indices to draw, clamped to the elements length, like one of the meshes packed
+    /// into a shared elements buffer. It's ignored by draws without elements.
+    pub element_range: Option<Range<usize>>,
 }
 
-#[derive(Clone, Debug)]
-struct UniformInfo {
-    name: String,
-    location: WebGlUniformLocation,
-    data_type: DataType,
+impl<'a> DrawParams<'a> {
+    pub fn new(
+        primitive: PrimitiveType,
+        uniforms: &'a dyn Uniforms,
+        attributes: &'a dyn VertexSource,
+    ) -> DrawParams<'a> {
+        DrawParams {
+            primitive,
+            uniforms,
+            attributes,
+            instances: None,
+            elements: None,
+            range: None,
+            element_range: None,
+        }
+    }
+
+    pub fn instances(mut self, instances: &'a dyn VertexSource) -> DrawParams<'a> {
+        self.instances = Some(instances);
+        self
+    }
+
+    pub fn elements(mut self, elements: &'a ElementsBuffer) -> DrawParams<'a> {
+        self.elements = Some(elements);
+        self
+    }
+
+    pub fn range(mut self, range: Range<usize>) -> DrawParams<'a> {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn element_range(mut self, range: Range<usize>) -> DrawParams<'a> {
+        self.element_range = Some(range);
+        self
+    }
 }
 
-#[derive(Clone, Debug)]
-struct Shader {
+/// Compiled shader, programs built from the same source share it, see [Gl::cached_shader].
+pub(crate) struct Shader {
     gl: Gl,
-    handle: WebGlShader,
+    id: u64,
+    shader_type: u32,
     source: String,
 }
 
+impl fmt::Debug for Shader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shader").field("id", &self.id).finish()
+    }
+}
+
 impl PartialEq for Shader {
     fn eq(&self, other: &Shader) -> bool {
-        self.handle == other.handle
+        self.id == other.id
     }
 }
 
 impl Drop for Shader {
     fn drop(&mut self) {
-        self.gl.context().delete_shader(Some(&self.handle));
+        trace_event!(DEBUG, id = self.id, "delete shader");
+        self.gl.uncache_shader(self.shader_type, &self.source);
+        self.gl.delete(Command::DeleteShader { id: self.id });
     }
 }
 
 impl Shader {
-    fn new(gl: Gl, source: &str, shader_type: u32) -> Result<Shader, GlError> {
-        let ctx = gl.context();
-        let handle = ctx
-            .create_shader(shader_type)
-            .ok_or_else(|| GlError::UnknownError(None))?;
-
-        ctx.shader_source(&handle, source);
-        ctx.compile_shader(&handle);
-
-        let status = ctx
-            .get_shader_parameter(&handle, WebGlRenderingContext::COMPILE_STATUS)
-            .as_bool()
-            .ok_or_else(|| GlError::UnknownError(None))?;
-
-        if !status {
-            return Err(GlError::ShaderCompilationError {
-                source: source.into(),
-                info: ctx.get_shader_info_log(&handle),
-            });
+    pub(crate) fn shader_type(&self) -> u32 {
+        self.shader_type
+    }
+
+    pub(crate) fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn new(gl: Gl, source: &str, shader_type: u32) -> Result<Rc<Shader>, GlError> {
+        let source = gl.shader_source(source);
+        let source = source.as_ref();
+        if let Some(shader) = gl.cached_shader(shader_type, source) {
+            trace_event!(DEBUG, id = shader.id, shader_type, "reuse shader");
+            return Ok(shader);
         }
+        let id = gl.compile_shader(shader_type, source)?;
+        trace_event!(DEBUG, id, shader_type, "create shader");
 
-        return Ok(Shader {
-            gl,
-            handle,
+        let shader = Rc::new(Shader {
+            gl: gl.clone(),
+            id,
+            shader_type,
             source: source.into(),
         });
+        gl.cache_shader(&shader);
+        Ok(shader)
     }
 }
 
-#[derive(Debug, Clone)]
+type UniformNames = Rc<dyn Fn(&str) -> String>;
+
+#[derive(Clone)]
 struct ProgramData {
     gl: Gl,
-    handle: WebGlProgram,
-    vertex_shader: Shader,
-    fragment_shader: Shader,
+    id: u64,
+    vertex_shader: Rc<Shader>,
+    fragment_shader: Rc<Shader>,
     attributes: Vec<AttributeInfo>,
     uniforms: Vec<UniformInfo>,
+    label: RefCell<Option<String>>,
+    /// Maps names of [Uniforms] fields to the names used in the shaders
+    uniform_names: RefCell<Option<UniformNames>>,
+    /// Values of the uniforms missing in the [Uniforms] passed to a draw, by the shader names
+    default_uniforms: RefCell<Vec<Field>>,
+}
+
+impl fmt::Debug for ProgramData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgramData")
+            .field("id", &self.id)
+            .field("vertex_shader", &self.vertex_shader)
+            .field("fragment_shader", &self.fragment_shader)
+            .field("attributes", &self.attributes)
+            .field("uniforms", &self.uniforms)
+            .field("label", &self.label)
+            .field("uniform_names", &self.uniform_names.borrow().is_some())
+            .field("default_uniforms", &self.default_uniforms)
+            .finish()
+    }
 }
 
 impl Drop for ProgramData {
     fn drop(&mut self) {
-        let wgl = self.gl.context();
-        wgl.delete_program(Some(&self.handle));
+        trace_event!(DEBUG, id = self.id, "delete program");
+        self.gl.unregister(self.id);
+        self.gl.delete(Command::DeleteProgram { id: self.id });
+    }
+}
+
+impl Resource for ProgramData {
+    fn info(&self) -> ResourceInfo {
+        ResourceInfo {
+            id: self.id,
+            kind: ResourceKind::Program,
+            size: 0,
+            label: self.label.borrow().clone(),
+        }
     }
 }
 
@@ -112,93 +206,57 @@ pub struct Program {
 
 impl PartialEq for Program {
     fn eq(&self, other: &Program) -> bool {
-        self.data.handle == other.data.handle
+        self.data.id == other.data.id
     }
 }
 
 impl Eq for Program {}
 
-impl Program {
-    fn collect_attributes(
-        ctx: &WebGlRenderingContext,
-        program: &WebGlProgram,
-    ) -> Result<Vec<AttributeInfo>, GlError> {
-        let attributes_count = ctx
-            .get_program_parameter(&program, WebGlRenderingContext::ACTIVE_ATTRIBUTES)
-            .as_f64()
-            .ok_or_else(|| GlError::UnknownError(Some("Failed to get attributes count".into())))?
-            as u32;
-
-        let mut result = Vec::with_capacity(attributes_count as usize);
-
-        for i in 0..attributes_count {
-            let info = ctx.get_active_attrib(&program, i).ok_or_else(|| {
-                GlError::UnknownError(Some("Failed to get attribute info".into()))
-            })?;
-
-            // Arrays are not supported
-            if info.size() != 1 {
-                return Err(GlError::UnsupportedType(Some(info.name())));
-            }
-
-            let location = ctx.get_attrib_location(&program, &info.name());
-            result.push(AttributeInfo {
-                name: info.name(),
-                data_type: DataType::try_from(info.type_())
-                    .map_err(|_| GlError::UnsupportedType(Some(info.name())))?,
-                location: location.try_into().map_err(|_| {
-                    GlError::UnknownError(Some("Negative attribute location".to_string()))
-                })?,
-            });
-        }
-        return Ok(result);
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.data.label.borrow();
+        write_name(f, ResourceKind::Program, self.data.id, label.as_deref())
     }
+}
 
-    fn collect_uniforms(
-        ctx: &WebGlRenderingContext,
-        program: &WebGlProgram,
-    ) -> Result<Vec<UniformInfo>, GlError> {
-        let uniforms_count = ctx
-            .get_program_parameter(&program, WebGlRenderingContext::ACTIVE_UNIFORMS)
-            .as_f64()
-            .ok_or_else(|| GlError::UnknownError(Some("Failed to get uniforms count".into())))?
-            as u32;
+/// Program handle which doesn't keep the program alive, like for the keys of material caches.
+#[derive(Clone, Debug)]
+pub struct ProgramWeak {
+    id: u64,
+    data: Weak<ProgramData>,
+}
 
-        let mut result = Vec::with_capacity(uniforms_count as usize);
+impl PartialEq for ProgramWeak {
+    fn eq(&self, other: &ProgramWeak) -> bool {
+        self.id == other.id
+    }
+}
 
-        for i in 0..uniforms_count {
-            let info = ctx
-                .get_active_uniform(&program, i)
-                .ok_or_else(|| GlError::UnknownError(Some("Failed to get uniform info".into())))?;
+impl Eq for ProgramWeak {}
 
-            // Arrays are not supported
-            if info.size() != 1 {
-                return Err(GlError::UnsupportedType(Some(info.name())));
-            }
+impl ProgramWeak {
+    /// Id of the program, it stays valid after the program is dropped.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
 
-            let location = ctx
-                .get_uniform_location(&program, &info.name())
-                .ok_or_else(|| {
-                    GlError::UnknownError(Some("Failed to get uniform location".into()))
-                })?;
-            result.push(UniformInfo {
-                name: info.name(),
-                data_type: DataType::try_from(info.type_())
-                    .map_err(|_| GlError::UnsupportedType(Some(info.name())))?,
-                location,
-            });
-        }
+    /// The program, `None` if all its strong handles are dropped.
+    pub fn upgrade(&self) -> Option<Program> {
+        self.data.upgrade().map(|data| Program { data })
+    }
 
-        return Ok(result);
+    pub fn is_alive(&self) -> bool {
+        self.data.strong_count() > 0
     }
+}
 
+impl Program {
     pub(crate) fn new(
         gl: Gl,
         fragment_shader_source: &str,
         vertex_shader_source: &str,
+        options: &LinkOptions,
     ) -> Result<Self, GlError> {
-        let ctx: &WebGlRenderingContext = gl.context();
-
         let vertex_shader = Shader::new(
             gl.clone(),
             vertex_shader_source,
@@ -210,72 +268,168 @@ impl Program {
             WebGlRenderingContext::FRAGMENT_SHADER,
         )?;
 
-        let program = ctx.create_program().unwrap();
-        ctx.attach_shader(&program, &vertex_shader.handle);
-        ctx.attach_shader(&program, &fragment_shader.handle);
-        ctx.link_program(&program);
-
-        let link_status = ctx
-            .get_program_parameter(&program, WebGlRenderingContext::LINK_STATUS)
-            .as_bool()
-            .ok_or_else(|| GlError::UnknownError(Some("Failed to get linking status".into())))?;
-
-        if !link_status {
-            return Err(GlError::ProgramLinkingError {
-                vertex: vertex_shader_source.into(),
-                fragment: fragment_shader_source.into(),
-                info: ctx.get_program_info_log(&program),
-            });
+        let (id, layout) = gl
+            .link_program(vertex_shader.id, fragment_shader.id, options)
+            .map_err(|error| match error {
+                GlError::ProgramLinkingError { info, .. } => GlError::ProgramLinkingError {
+                    vertex: vertex_shader_source.into(),
+                    fragment: fragment_shader_source.into(),
+                    info,
+                },
+                error => error,
+            })?;
+        trace_event!(
+            DEBUG,
+            id,
+            vertex_shader = vertex_shader.id,
+            fragment_shader = fragment_shader.id,
+            "create program"
+        );
+
+        let data = Rc::new(ProgramData {
+            gl: gl.clone(),
+            id,
+            vertex_shader,
+            fragment_shader,
+            attributes: layout.attributes,
+            uniforms: layout.uniforms,
+            label: Default::default(),
+            uniform_names: Default::default(),
+            default_uniforms: Default::default(),
+        });
+        gl.register(id, &data);
+
+        Ok(Program { data })
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.data.gl.clone()
+    }
+
+    pub fn id(&self) -> u64 {
+        self.data.id
+    }
+
+    /// Weak handle of the same object.
+    pub fn downgrade(&self) -> ProgramWeak {
+        ProgramWeak {
+            id: self.data.id,
+            data: Rc::downgrade(&self.data),
         }
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.label.borrow().clone()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.data.label.replace(Some(label.into()));
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
+    /// Map names of the [Uniforms] fields, `u_color` for the derived `color` field,
+    /// to the names declared in the shaders. See [unprefixed](crate::uniforms::unprefixed)
+    /// and [camel_case](crate::uniforms::camel_case) for common conventions.
+    pub fn set_uniform_names(&self, mapping: impl Fn(&str) -> String + 'static) {
+        self.data.uniform_names.replace(Some(Rc::new(mapping)));
+    }
+
+    pub fn with_uniform_names(self, mapping: impl Fn(&str) -> String + 'static) -> Self {
+        self.set_uniform_names(mapping);
+        self
+    }
 
-        return Ok(Program {
-            data: Rc::new(ProgramData {
-                gl: gl.clone(),
-                handle: program.clone(),
-                vertex_shader,
-                fragment_shader,
-                attributes: Program::collect_attributes(&ctx, &program)?,
-                uniforms: Program::collect_uniforms(&ctx, &program)?,
+    /// Value for the uniform, like an identity matrix or a white texture, used when
+    /// the [Uniforms] of a draw don't provide it. The name is the one declared in the shaders.
+    pub fn set_default_uniform(&self, name: &str, value: UniformValue) {
+        let mut defaults = self.data.default_uniforms.borrow_mut();
+        match defaults.iter_mut().find(|field| field.name == name) {
+            Some(field) => field.value = value,
+            None => defaults.push(Field {
+                name: Cow::Owned(name.into()),
+                value,
             }),
-        });
+        }
     }
 
-    pub(crate) fn handle(&self) -> WebGlProgram {
-        self.data.handle.clone()
+    pub fn with_default_uniform(self, name: &str, value: UniformValue) -> Self {
+        self.set_default_uniform(name, value);
+        self
     }
 
-    pub(self) fn set_attributes<T: Item>(&self, buffer: &ItemsBuffer<T>, divisor: u32) {
-        let gl: &WebGlRenderingContext = self.data.gl.context();
-        let instanced = self.data.gl.instanced_arrays();
-        let mut offset: usize = 0;
+    pub fn remove_default_uniform(&self, name: &str) {
+        self.data
+            .default_uniforms
+            .borrow_mut()
+            .retain(|field| field.name != name);
+    }
+
+    /// Point the program attributes to the source, skipping the `skip` first vertices
+    /// or instances after the source [first](VertexSource::first) item.
+    pub(self) fn set_attributes(
+        &self,
+        source: &(impl VertexSource + ?Sized),
+        divisor: u32,
+        skip: usize,
+    ) {
+        let layout = source.layout();
+        let stride = (layout.stride() * 4) as i32;
+        let version = self.data.gl.version();
 
         self.data.gl.apply(
             Gl::settings()
-                .items_buffer((*buffer).clone())
+                .array_buffer(source.array_buffer().clone())
                 .program(self.clone()),
             || {
-                for item in T::layout() {
-                    (&self.data.attributes)
+                for attribute in layout.attributes() {
+                    let info = self
+                        .data
+                        .attributes
                         .iter()
-                        .find(|i| i.name == item.name)
-                        .map(|info| {
-                            gl.vertex_attrib_pointer_with_i32(
-                                info.location,
-                                item.data_type.size_in_floats().unwrap().try_into().unwrap(),
-                                WebGlRenderingContext::FLOAT,
-                                false,
-                                (T::stride() * 4).try_into().unwrap(),
-                                (offset * 4).try_into().unwrap(),
-                            );
-                            instanced.vertex_attrib_divisor_angle(info.location, divisor)
+                        .find(|i| i.name == attribute.name);
+                    // Layouts are built from float fields, other types can't be bound
+                    let size = attribute
+                        .data_type
+                        .size_in_floats()
+                        .filter(|_| attribute.format.is_supported(version));
+                    if let (Some(info), Some(size)) = (info, size) {
+                        // Instance attributes with a divisor advance slower
+                        let (divisor, skip) = match divisor {
+                            0 => (0, skip),
+                            divisor => (
+                                divisor * attribute.divisor,
+                                skip / attribute.divisor as usize,
+                            ),
+                        };
+                        let start = (source.first() + skip) * layout.stride();
+                        self.data.gl.execute(Command::VertexAttribPointer {
+                            location: info.location,
+                            size: size as i32,
+                            data_type: attribute.format.gl_type(),
+                            normalized: attribute.format.is_normalized(),
+                            stride,
+                            offset: ((start + attribute.offset) * 4) as i32,
                         });
-                    offset += item.data_type.size_in_floats().unwrap();
+                        self.data.gl.set_attribute_divisor(info.location, divisor);
+                    } else if info.is_some() {
+                        trace_event!(
+                            WARN,
+                            attribute = %attribute.name,
+                            data_type = ?attribute.data_type,
+                            format = ?attribute.format,
+                            "attribute type can't be bound"
+                        );
+                    }
                 }
             },
         );
     }
 
-    pub(self) fn enable_attributes<R, F: FnOnce() -> R>(&self, callback: F) -> R {
+    pub(crate) fn enable_attributes<R, F: FnOnce() -> R>(&self, callback: F) -> R {
         let attributes: Vec<u32> = (&self.data.attributes).iter().map(|v| v.location).collect();
         self.data
             .gl
@@ -284,186 +438,389 @@ impl Program {
 
     pub(self) fn set_uniforms<R, F: FnOnce() -> R>(
         &self,
-        uniforms: &impl Uniforms,
+        uniforms: &(impl Uniforms + ?Sized),
         callback: F,
-    ) -> R {
+    ) -> Result<R, GlError> {
         let items = uniforms.uniforms();
         let info = &self.data.uniforms;
         let gl = &self.data.gl;
-        let context: &WebGlRenderingContext = gl.context();
+        let names = self.data.uniform_names.borrow().clone();
+        let defaults = self.data.default_uniforms.borrow();
+        let mut provided = vec![false; info.len()];
+        // Both lists have an entry per texture unit, so 2D textures and cube maps don't overlap
+        let mut textures: Vec<Option<Texture>> = Vec::with_capacity(TEXTURES_COUNT as usize);
+        let mut cube_maps: Vec<Option<CubeMap>> = Vec::new();
 
-        let mut textures: Vec<Texture> = Vec::with_capacity(TEXTURES_COUNT.try_into().unwrap());
+        for value in items
+            .iter()
+            .chain(defaults.iter())
+            .map(|field| &field.value)
+        {
+            match value {
+                UniformValue::Texture(texture) => gl.check_own(&texture.gl(), texture)?,
+                UniformValue::CubeMap(cube_map) => gl.check_own(&cube_map.gl(), cube_map)?,
+                _ => {}
+            }
+        }
+
+        // Matched before any command, so too many samplers fail without setting units past the last
+        let mut values: Vec<(&UniformInfo, Cow<UniformValue>)> = Vec::with_capacity(info.len());
+        for i in items.iter() {
+            let name = match &names {
+                Some(mapping) => Cow::Owned(mapping(&i.name)),
+                None => Cow::Borrowed(i.name.as_ref()),
+            };
+            if let Some(index) = info.iter().position(|info| info.name == name) {
+                values.push((&info[index], Cow::Borrowed(&i.value)));
+                provided[index] = true;
+            }
+        }
+        let frame = gl.auto_uniforms().then(|| gl.frame_info());
+        for (info, _) in info
+            .iter()
+            .zip(&provided)
+            .filter(|(_, provided)| !**provided)
+        {
+            if let Some(field) = defaults.iter().find(|field| field.name == info.name) {
+                values.push((info, Cow::Borrowed(&field.value)));
+            } else if let Some(value) = frame
+                .and_then(|frame| auto_uniform(&info.name, info.data_type, frame, gl.viewport()))
+            {
+                values.push((info, Cow::Owned(value)));
+            }
+        }
+
+        let required = values
+            .iter()
+            .filter(|(_, value)| {
+                matches!(**value, UniformValue::Texture(_) | UniformValue::CubeMap(_))
+            })
+            .count();
+        let available = gl.texture_units() as usize;
+        if required > available {
+            return Err(GlError::TooManyTextures {
+                required,
+                available,
+            }
+            .with_context(format!("drawing with {}", self)));
+        }
 
         gl.apply(Gl::settings().program(self.clone()), || {
-            for i in items.iter() {
-                info.iter().find(|info| info.name == i.name).map(|info| {
-                    let location = Some(&info.location);
-                    match &i.value {
-                        UniformValue::None => match info.data_type {
-                            DataType::Boolean => context.uniform1i(location, 0),
-                            DataType::Float => context.uniform1f(location, 0.0),
-                            DataType::Vec2 => {
-                                context.uniform2f(location, 0.0, 0.0);
-                            }
-                            DataType::Vec3 => {
-                                context.uniform3f(location, 0.0, 0.0, 0.0);
-                            }
-                            DataType::Vec4 => {
-                                context.uniform4f(location, 0.0, 0.0, 0.0, 0.0);
-                            }
-                            DataType::Mat2 => {
-                                let mat = [0.0; 4];
-                                context.uniform_matrix2fv_with_f32_array(location, false, &mat)
-                            }
-                            DataType::Mat3 => {
-                                let mat = [0.0; 9];
-                                context.uniform_matrix3fv_with_f32_array(location, false, &mat)
-                            }
-                            DataType::Mat4 => {
-                                let mat = [0.0; 16];
-                                context.uniform_matrix4fv_with_f32_array(location, false, &mat)
-                            }
-                            DataType::Sampler => {
-                                context.uniform1i(location, -1);
-                            }
-                        },
-                        UniformValue::Boolean(value) => {
-                            context.uniform1i(location, if *value { 1 } else { 0 })
-                        }
-                        UniformValue::Float(value) => context.uniform1f(location, *value),
-                        UniformValue::Vec2(value) => {
-                            context.uniform2fv_with_f32_array(location, value)
-                        }
-                        UniformValue::Vec3(value) => {
-                            context.uniform3fv_with_f32_array(location, value)
-                        }
-                        UniformValue::Vec4(value) => {
-                            context.uniform4fv_with_f32_array(location, value)
-                        }
-                        UniformValue::Mat2(value) => {
-                            context.uniform_matrix2fv_with_f32_array(location, false, value)
-                        }
-                        UniformValue::Mat3(value) => {
-                            context.uniform_matrix3fv_with_f32_array(location, false, value)
-                        }
-                        UniformValue::Mat4(value) => {
-                            context.uniform_matrix4fv_with_f32_array(location, false, value)
-                        }
-                        UniformValue::Texture(value) => {
-                            context.uniform1i(location, textures.len().try_into().unwrap());
-                            textures.push(value.clone())
-                        }
+            for (info, value) in &values {
+                gl.execute(if info.data_type.is_integer() {
+                    Command::UniformInt {
+                        name: info.name.clone(),
+                        data_type: info.data_type.into(),
+                        values: int_uniform_values(info.data_type, value, textures.len()),
+                    }
+                } else {
+                    Command::Uniform {
+                        name: info.name.clone(),
+                        data_type: info.data_type.into(),
+                        values: uniform_values(info.data_type, value, textures.len()),
                     }
                 });
+                match value.as_ref() {
+                    UniformValue::Texture(value) => {
+                        textures.push(Some(value.clone()));
+                        cube_maps.push(None);
+                    }
+                    UniformValue::CubeMap(value) => {
+                        textures.push(None);
+                        cube_maps.push(Some(value.clone()));
+                    }
+                    _ => {}
+                }
             }
         });
 
-        gl.apply(Gl::settings().texture_list(textures), callback)
+        gl.check_blended_alpha(&textures);
+        Ok(gl.apply(
+            Gl::settings()
+                .texture_list(textures)
+                .cube_map_list(cube_maps),
+            callback,
+        ))
     }
 
-    pub fn draw_arrays<T: Item, U: Uniforms>(
+    /// Buffers of another context can't be drawn, see [Gl::check_own].
+    pub(self) fn check_own_sources(
+        &self,
+        attributes: &(impl VertexSource + ?Sized),
+        elements: Option<&ElementsBuffer>,
+        instances: Option<&dyn VertexSource>,
+    ) -> Result<(), GlError> {
+        let gl = &self.data.gl;
+        let buffers = std::iter::once(attributes.array_buffer())
+            .chain(instances.map(|instances| instances.array_buffer()));
+        for buffer in buffers {
+            gl.check_own(&buffer.gl(), buffer)?;
+        }
+        if let Some(elements) = elements {
+            gl.check_own(&elements.gl(), elements)?;
+        }
+        Ok(())
+    }
+
+    /// Check the draw parameters, mistakes like indices past the end of the vertex buffer
+    /// render garbage or nothing without any GL error. The maximum index is checked in debug
+    /// builds only, the other checks are cheap and run always.
+    pub(self) fn validate_draw(
         &self,
         primitive_type: PrimitiveType,
-        uniforms: &U,
-        attributes: &ItemsBuffer<T>,
-    ) {
+        attributes: &(impl VertexSource + ?Sized),
+        elements: Option<(&ElementsBuffer, &Range<usize>)>,
+        instances: Option<&dyn VertexSource>,
+    ) -> Result<(), GlError> {
+        let error = |message: String| {
+            let message = format!("{}: {}", self, message);
+            trace_event!(ERROR, %message, "invalid draw");
+            Err(GlError::InvalidDraw(message))
+        };
+
+        let (count, what) = match elements {
+            Some((_, range)) => (range.len(), "indices"),
+            None => (attributes.len(), "vertices"),
+        };
+        let expected = match primitive_type {
+            PrimitiveType::Triangles if !count.is_multiple_of(3) => Some("a multiple of 3"),
+            PrimitiveType::Lines if !count.is_multiple_of(2) => Some("a multiple of 2"),
+            PrimitiveType::TriangleStrip | PrimitiveType::TriangleFan if count > 0 && count < 3 => {
+                Some("at least 3")
+            }
+            PrimitiveType::LineStrip | PrimitiveType::LineLoop if count == 1 => Some("at least 2"),
+            _ => None,
+        };
+        if let Some(expected) = expected {
+            return error(format!(
+                "{:?} drawn with {} {}, it should be {}",
+                primitive_type, count, what, expected
+            ));
+        }
+
+        if let Some(instances) = instances.filter(|instances| instances.is_empty()) {
+            return error(format!(
+                "instanced draw with empty instances buffer {}",
+                instances.array_buffer()
+            ));
+        }
+
+        // The maximum is known for the whole buffer only, ranges may use other vertex buffers
+        let whole_elements = elements
+            .filter(|(elements, range)| range.len() == elements.len())
+            .map(|(elements, _)| elements);
+        let whole_elements = whole_elements.filter(|elements| !elements.is_empty());
+        // Scanning the indices is left to debug builds
+        if let Some(elements) = whole_elements.filter(|_| cfg!(debug_assertions)) {
+            if elements.max_index() as usize >= attributes.len() {
+                return error(format!(
+                    "index {} of {} is out of {} vertices of {}",
+                    elements.max_index(),
+                    elements,
+                    attributes.len(),
+                    attributes.array_buffer()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw with the parameters, the other draw methods are shortcuts for it.
+    /// Counts which don't fit the primitive and empty instances fail with
+    /// [GlError::InvalidDraw], indices past the vertices fail in debug builds only.
+    ///
+    /// ```ignore
+    /// program.draw(
+    ///     DrawParams::new(PrimitiveType::Triangles, &uniforms, &vertices)
+    ///         .elements(&indices)
+    ///         .instances(&instances),
+    /// )?;
+    /// ```
+    pub fn draw(&self, params: DrawParams) -> Result<(), GlError> {
+        let DrawParams {
+            primitive,
+            uniforms,
+            attributes,
+            instances,
+            elements,
+            range,
+            element_range,
+        } = params;
+        self.data.gl.check_context()?;
+        self.check_own_sources(attributes, elements, instances)?;
+        let element_range = elements.map(|elements| {
+            clamp_range(element_range.unwrap_or(0..elements.len()), elements.len())
+        });
+        self.validate_draw(
+            primitive,
+            attributes,
+            elements.zip(element_range.as_ref()),
+            instances,
+        )?;
+        let range = instances
+            .map(|instances| clamp_range(range.unwrap_or(0..instances.len()), instances.len()));
+        let _span = trace_span!(
+            DEBUG,
+            "draw",
+            program = self.id(),
+            ?primitive,
+            attributes = attributes.array_buffer().id(),
+            count = attributes.len(),
+            elements = ?elements.map(ElementsBuffer::id),
+            elements_range = ?element_range,
+            instances = ?instances.map(|instances| instances.array_buffer().id()),
+            instances_range = ?range
+        );
         let gl = &self.data.gl;
-        gl.apply(Gl::settings().program(self.clone()), || {
+        let mode = primitive.into();
+        // Offset into the elements buffer is in bytes
+        let (element_offset, element_count) = match (elements, &element_range) {
+            (Some(elements), Some(range)) => (
+                (range.start * elements.index_type().size()) as i32,
+                range.len() as i32,
+            ),
+            _ => (0, 0),
+        };
+        let draw = || {
             self.enable_attributes(|| {
                 self.set_uniforms(uniforms, || {
-                    self.set_attributes(attributes, 0);
-                    gl.context().draw_arrays(
-                        primitive_type.into(),
-                        0,
-                        attributes.len().try_into().unwrap(),
-                    )
-                });
-            });
-        });
+                    self.set_attributes(attributes, 0, 0);
+                    if let (Some(instances), Some(range)) = (instances, &range) {
+                        self.set_attributes(instances, 1, range.start);
+                    }
+                    gl.execute(match (elements, &range) {
+                        (None, None) => Command::DrawArrays {
+                            mode,
+                            first: 0,
+                            count: attributes.len() as i32,
+                        },
+                        (None, Some(range)) => Command::DrawArraysInstanced {
+                            mode,
+                            first: 0,
+                            count: attributes.len() as i32,
+                            instances: range.len() as i32,
+                        },
+                        (Some(elements), None) => Command::DrawElements {
+                            mode,
+                            count: element_count,
+                            data_type: elements.index_type().into(),
+                            offset: element_offset,
+                        },
+                        (Some(elements), Some(range)) => Command::DrawElementsInstanced {
+                            mode,
+                            count: element_count,
+                            data_type: elements.index_type().into(),
+                            offset: element_offset,
+                            instances: range.len() as i32,
+                        },
+                    });
+                })
+            })
+        };
+        match elements {
+            Some(elements) => gl.apply(
+                Gl::settings()
+                    .program(self.clone())
+                    .element_buffer(elements.clone()),
+                draw,
+            ),
+            None => gl.apply(Gl::settings().program(self.clone()), draw),
+        }
     }
 
-    pub fn draw_instances<T: Item, I: Item, U: Uniforms>(
+    pub fn draw_arrays<A: VertexSource, U: Uniforms + ?Sized>(
         &self,
         primitive_type: PrimitiveType,
         uniforms: &U,
-        attributes: &ItemsBuffer<T>,
-        instances: &ItemsBuffer<I>,
-    ) {
-        let gl = &self.data.gl;
-        gl.apply(Gl::settings().program(self.clone()), || {
-            self.enable_attributes(|| {
-                self.set_uniforms(uniforms, || {
-                    self.set_attributes(attributes, 0);
-                    self.set_attributes(instances, 1);
-                    gl.instanced_arrays().draw_arrays_instanced_angle(
-                        primitive_type.into(),
-                        0,
-                        attributes.len().try_into().unwrap(),
-                        instances.len().try_into().unwrap(),
-                    );
-                });
-            });
-        });
+        attributes: &A,
+    ) -> Result<(), GlError> {
+        self.draw(DrawParams::new(primitive_type, &uniforms, attributes))
     }
 
-    pub fn draw_element_arrays<T: Item, U: Uniforms>(
+    pub fn draw_instances<A: VertexSource, I: VertexSource, U: Uniforms + ?Sized>(
         &self,
         primitive_type: PrimitiveType,
         uniforms: &U,
-        attributes: &ItemsBuffer<T>,
+        attributes: &A,
+        instances: &I,
+    ) -> Result<(), GlError> {
+        self.draw(DrawParams::new(primitive_type, &uniforms, attributes).instances(instances))
+    }
+
+    /// Draw only the instances in the range, it's clamped to the buffer length.
+    pub fn draw_instances_range<A: VertexSource, I: VertexSource, U: Uniforms + ?Sized>(
+        &self,
+        primitive_type: PrimitiveType,
+        uniforms: &U,
+        attributes: &A,
+        instances: &I,
+        range: Range<usize>,
+    ) -> Result<(), GlError> {
+        self.draw(
+            DrawParams::new(primitive_type, &uniforms, attributes)
+                .instances(instances)
+                .range(range),
+        )
+    }
+
+    pub fn draw_element_arrays<A: VertexSource, U: Uniforms + ?Sized>(
+        &self,
+        primitive_type: PrimitiveType,
+        uniforms: &U,
+        attributes: &A,
         elements: &ElementsBuffer,
-    ) {
-        let gl = &self.data.gl;
-        gl.apply(
-            Gl::settings()
-                .program(self.clone())
-                .element_buffer(elements.clone()),
-            || {
-                self.enable_attributes(|| {
-                    self.set_uniforms(uniforms, || {
-                        self.set_attributes(attributes, 0);
-                        gl.context().draw_elements_with_i32(
-                            primitive_type.into(),
-                            elements.len() as i32,
-                            WebGlRenderingContext::UNSIGNED_INT,
-                            0,
-                        )
-                    });
-                });
-            },
-        );
+    ) -> Result<(), GlError> {
+        self.draw(DrawParams::new(primitive_type, &uniforms, attributes).elements(elements))
     }
 
-    pub fn draw_element_instances<T: Item, I: Item, U: Uniforms>(
+    /// Draw only the indices in the range, it's clamped to the buffer length.
+    /// Meshes packed into one elements buffer can be drawn separately this way.
+    pub fn draw_element_range<A: VertexSource, U: Uniforms + ?Sized>(
         &self,
         primitive_type: PrimitiveType,
         uniforms: &U,
-        attributes: &ItemsBuffer<T>,
+        attributes: &A,
         elements: &ElementsBuffer,
-        instances: &ItemsBuffer<I>,
-    ) {
-        let gl = &self.data.gl;
-        gl.apply(
-            Gl::settings()
-                .program(self.clone())
-                .element_buffer(elements.clone()),
-            || {
-                self.enable_attributes(|| {
-                    self.set_uniforms(uniforms, || {
-                        self.set_attributes(attributes, 0);
-                        self.set_attributes(instances, 1);
-                        gl.instanced_arrays()
-                            .draw_elements_instanced_angle_with_i32(
-                                primitive_type.into(),
-                                elements.len() as i32,
-                                WebGlRenderingContext::UNSIGNED_INT,
-                                0,
-                                instances.len() as i32,
-                            );
-                    });
-                });
-            },
-        );
+        range: Range<usize>,
+    ) -> Result<(), GlError> {
+        self.draw(
+            DrawParams::new(primitive_type, &uniforms, attributes)
+                .elements(elements)
+                .element_range(range),
+        )
+    }
+
+    pub fn draw_element_instances<A: VertexSource, I: VertexSource, U: Uniforms + ?Sized>(
+        &self,
+        primitive_type: PrimitiveType,
+        uniforms: &U,
+        attributes: &A,
+        elements: &ElementsBuffer,
+        instances: &I,
+    ) -> Result<(), GlError> {
+        self.draw(
+            DrawParams::new(primitive_type, &uniforms, attributes)
+                .elements(elements)
+                .instances(instances),
+        )
+    }
+
+    /// Draw only the instances in the range, it's clamped to the buffer length.
+    pub fn draw_element_instances_range<A: VertexSource, I: VertexSource, U: Uniforms + ?Sized>(
+        &self,
+        primitive_type: PrimitiveType,
+        uniforms: &U,
+        attributes: &A,
+        elements: &ElementsBuffer,
+        instances: &I,
+        range: Range<usize>,
+    ) -> Result<(), GlError> {
+        self.draw(
+            DrawParams::new(primitive_type, &uniforms, attributes)
+                .elements(elements)
+                .instances(instances)
+                .range(range),
+        )
     }
 
     pub fn vertex_source(&self) -> &String {
@@ -473,4 +830,224 @@ impl Program {
     pub fn fragment_source(&self) -> &String {
         &self.data.fragment_shader.source
     }
+
+    /// Type of the active attribute, `None` if the program doesn't use it.
+    pub(crate) fn attribute_type(&self, name: &str) -> Option<DataType> {
+        self.data
+            .attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| attribute.data_type)
+    }
+}
+
+/// How transform feedback writes the captured varyings.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Hash)]
+pub enum TransformFeedbackMode {
+    /// All the varyings into a single buffer
+    Interleaved = WebGl2RenderingContext::INTERLEAVED_ATTRIBS,
+    /// Each varying into its own buffer
+    Separate = WebGl2RenderingContext::SEPARATE_ATTRIBS,
+}
+
+/// Program with options beyond the two shader sources, see [Gl::program_builder].
+#[derive(Clone, Debug)]
+pub struct ProgramBuilder {
+    gl: Gl,
+    vertex: String,
+    fragment: String,
+    defines: Vec<(String, String)>,
+    features: Features,
+    options: LinkOptions,
+    label: Option<String>,
+}
+
+impl ProgramBuilder {
+    pub(crate) fn new(gl: Gl) -> ProgramBuilder {
+        ProgramBuilder {
+            gl,
+            vertex: Default::default(),
+            fragment: Default::default(),
+            defines: Default::default(),
+            features: Features::empty(),
+            options: Default::default(),
+            label: None,
+        }
+    }
+
+    pub fn vertex(mut self, source: &str) -> Self {
+        self.vertex = source.into();
+        self
+    }
+
+    pub fn fragment(mut self, source: &str) -> Self {
+        self.fragment = source.into();
+        self
+    }
+
+    /// Add `#define name` to both shaders, after the `#version` directive if there is one.
+    pub fn define(self, name: &str) -> Self {
+        self.define_value(name, "")
+    }
+
+    /// Add `#define name value` to both shaders.
+    pub fn define_value(mut self, name: &str, value: impl fmt::Display) -> Self {
+        self.defines.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Fail the build if the context lacks the features. Shader extensions, like
+    /// [Feature::StandardDerivatives], are enabled in WebGL1 fragment shaders by inserting
+    /// their `#extension` directive, `#version 300 es` shaders have them built in.
+    pub fn require(mut self, features: impl Into<Features>) -> Self {
+        self.features |= features;
+        self
+    }
+
+    /// Bind the attribute to the location before linking, so programs sharing
+    /// a vertex layout agree on it.
+    pub fn attribute_location(mut self, name: &str, location: u32) -> Self {
+        self.options
+            .attribute_locations
+            .push((name.into(), location));
+        self
+    }
+
+    /// Varyings captured by transform feedback, requires WebGL2.
+    pub fn transform_feedback_varyings(
+        mut self,
+        varyings: &[&str],
+        mode: TransformFeedbackMode,
+    ) -> Self {
+        self.options.transform_feedback = Some((
+            varyings.iter().map(|name| name.to_string()).collect(),
+            mode.into(),
+        ));
+        self
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Program, GlError> {
+        if self.options.transform_feedback.is_some() && !self.gl.supports(Feature::WebGl2) {
+            return Err(GlError::FeatureNotSupported(Feature::WebGl2));
+        }
+        if let Some(feature) = self
+            .features
+            .iter()
+            .find(|feature| !self.gl.supports(*feature))
+        {
+            return Err(GlError::FeatureNotSupported(feature).with_context(format!(
+                "building program {}",
+                self.label.as_deref().unwrap_or("without a label")
+            )));
+        }
+        let directives: Vec<String> = if is_glsl3(&self.fragment) {
+            Vec::new()
+        } else {
+            self.features
+                .iter()
+                .filter_map(Feature::shader_directive)
+                .filter(|_| self.gl.version() == WebGlVersion::WebGl1)
+                .map(|name| format!("#extension {} : enable", name))
+                .collect()
+        };
+        let defines: Vec<String> = self
+            .defines
+            .iter()
+            .map(|(name, value)| {
+                if value.is_empty() {
+                    format!("#define {}", name)
+                } else {
+                    format!("#define {} {}", name, value)
+                }
+            })
+            .collect();
+        let fragment_preamble: Vec<String> =
+            directives.into_iter().chain(defines.clone()).collect();
+        let program = Program::new(
+            self.gl.clone(),
+            &with_preamble(&self.fragment, &fragment_preamble),
+            &with_preamble(&self.vertex, &defines),
+            &self.options,
+        )?;
+        if let Some(label) = self.label {
+            program.set_label(&label);
+        }
+        Ok(program)
+    }
+}
+
+fn is_glsl3(source: &str) -> bool {
+    source.trim_start().starts_with("#version 300")
+}
+
+/// Insert the lines after the `#version` directive, which should be the first line.
+fn with_preamble<'a>(source: &'a str, lines: &[String]) -> Cow<'a, str> {
+    if lines.is_empty() {
+        return Cow::Borrowed(source);
+    }
+    let split = if source.trim_start().starts_with("#version") {
+        source.find('\n').map_or(source.len(), |index| index + 1)
+    } else {
+        0
+    };
+    let (version, body) = source.split_at(split);
+    let mut result = String::from(version);
+    if !version.is_empty() && !version.ends_with('\n') {
+        result.push('\n');
+    }
+    for line in lines {
+        result.push_str(line);
+        result.push('\n');
+    }
+    result.push_str(body);
+    Cow::Owned(result)
+}
+
+/// Uniform value as it's written into a recording, textures are replaced with their units.
+fn uniform_values(data_type: DataType, value: &UniformValue, texture_unit: usize) -> Vec<f32> {
+    match value {
+        UniformValue::None => match data_type {
+            DataType::Sampler | DataType::SamplerCube => vec![-1.0],
+            data_type => vec![0.0; data_type.size_in_floats().unwrap_or(1)],
+        },
+        UniformValue::Boolean(value) => vec![if *value { 1.0 } else { 0.0 }],
+        UniformValue::Int(value) => vec![*value as f32],
+        UniformValue::IVec2(value) => value.iter().map(|v| *v as f32).collect(),
+        UniformValue::IVec3(value) => value.iter().map(|v| *v as f32).collect(),
+        UniformValue::IVec4(value) => value.iter().map(|v| *v as f32).collect(),
+        UniformValue::Float(value) => vec![*value],
+        UniformValue::Vec2(value) => value.to_vec(),
+        UniformValue::Vec3(value) => value.to_vec(),
+        UniformValue::Vec4(value) => value.to_vec(),
+        UniformValue::Mat2(value) => value.to_vec(),
+        UniformValue::Mat3(value) => value.to_vec(),
+        UniformValue::Mat4(value) => value.to_vec(),
+        UniformValue::Texture(_) | UniformValue::CubeMap(_) => vec![texture_unit as f32],
+    }
+}
+
+/// Values of an `int` or `ivec` uniform, float values are truncated.
+fn int_uniform_values(data_type: DataType, value: &UniformValue, texture_unit: usize) -> Vec<i32> {
+    match value {
+        UniformValue::None => match data_type {
+            DataType::IVec2 => vec![0; 2],
+            DataType::IVec3 => vec![0; 3],
+            DataType::IVec4 => vec![0; 4],
+            _ => vec![0],
+        },
+        UniformValue::Int(value) => vec![*value],
+        UniformValue::IVec2(value) => value.to_vec(),
+        UniformValue::IVec3(value) => value.to_vec(),
+        UniformValue::IVec4(value) => value.to_vec(),
+        value => uniform_values(data_type, value, texture_unit)
+            .into_iter()
+            .map(|value| value as i32)
+            .collect(),
+    }
 }
@@ -0,0 +1,197 @@
+use crate::auto_uniforms::{FRAME, RESOLUTION, TIME, TIME_DELTA};
+use crate::types::DataType;
+
+/// Uniform or attribute of a shader or of a derive struct, compared by `check_program!`
+/// while the crate is compiled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Declaration {
+    pub name: &'static str,
+    /// `None` for GLSL arrays, structures and other types without a [DataType], only their names are checked
+    pub data_type: Option<DataType>,
+}
+
+/// Uniforms of a struct, implemented by `derive(Uniforms)`.
+pub trait DeclaredUniforms {
+    const UNIFORMS: &'static [Declaration];
+}
+
+/// Attributes of a struct, implemented by `derive(Attributes)` and `derive(Instances)`.
+pub trait DeclaredAttributes {
+    const ATTRIBUTES: &'static [Declaration];
+}
+
+const AUTO_UNIFORMS: [&str; 4] = [TIME, TIME_DELTA, FRAME, RESOLUTION];
+
+/// Fail the build if the uniforms struct and the shaders don't match. Every field should be declared
+/// with the same type, and every declared uniform should be a field, an automatic uniform,
+/// or one of the `defaults`.
+pub const fn check_uniforms(
+    declared: &[Declaration],
+    uniforms: &[Declaration],
+    defaults: &[&str],
+    struct_name: &str,
+    shaders: &str,
+) {
+    check_fields("uniform", declared, uniforms, struct_name, shaders);
+    let mut i = 0;
+    while i < declared.len() {
+        let name = declared[i].name;
+        if find(uniforms, name).is_none()
+            && !contains(&AUTO_UNIFORMS, name)
+            && !contains(defaults, name)
+        {
+            Message::new()
+                .push("uniform `")
+                .push(name)
+                .push("` of ")
+                .push(shaders)
+                .push(" isn't provided by ")
+                .push(struct_name)
+                .panic();
+        }
+        i += 1;
+    }
+}
+
+/// Fail the build if the attributes and instances structs and the vertex shader don't match,
+/// every field should be declared with the same type, and every declared attribute should be a field.
+pub const fn check_attributes(
+    declared: &[Declaration],
+    attributes: &[Declaration],
+    instances: &[Declaration],
+    structs: &str,
+    shader: &str,
+) {
+    check_fields("attribute", declared, attributes, structs, shader);
+    check_fields("attribute", declared, instances, structs, shader);
+    let mut i = 0;
+    while i < declared.len() {
+        let name = declared[i].name;
+        if find(attributes, name).is_none() && find(instances, name).is_none() {
+            Message::new()
+                .push("attribute `")
+                .push(name)
+                .push("` of ")
+                .push(shader)
+                .push(" isn't provided by ")
+                .push(structs)
+                .panic();
+        }
+        i += 1;
+    }
+}
+
+const fn check_fields(
+    kind: &str,
+    declared: &[Declaration],
+    fields: &[Declaration],
+    structs: &str,
+    shaders: &str,
+) {
+    let mut i = 0;
+    while i < fields.len() {
+        let field = fields[i];
+        match find(declared, field.name) {
+            None => Message::new()
+                .push(kind)
+                .push(" `")
+                .push(field.name)
+                .push("` of ")
+                .push(structs)
+                .push(" isn't declared in ")
+                .push(shaders)
+                .panic(),
+            Some(declaration) => {
+                if let (Some(expected), Some(actual)) = (declaration.data_type, field.data_type) {
+                    if expected as u8 != actual as u8 {
+                        Message::new()
+                            .push(kind)
+                            .push(" `")
+                            .push(field.name)
+                            .push("` is `")
+                            .push(actual.glsl_name())
+                            .push("` in ")
+                            .push(structs)
+                            .push(" but `")
+                            .push(expected.glsl_name())
+                            .push("` in ")
+                            .push(shaders)
+                            .panic();
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+}
+
+const fn find(declarations: &[Declaration], name: &str) -> Option<Declaration> {
+    let mut i = 0;
+    while i < declarations.len() {
+        if str_eq(declarations[i].name, name) {
+            return Some(declarations[i]);
+        }
+        i += 1;
+    }
+    None
+}
+
+const fn contains(names: &[&str], name: &str) -> bool {
+    let mut i = 0;
+    while i < names.len() {
+        if str_eq(names[i], name) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Error message built in a const context, where panics can't format their arguments.
+struct Message {
+    bytes: [u8; 512],
+    length: usize,
+}
+
+impl Message {
+    const fn new() -> Message {
+        Message {
+            bytes: [0; 512],
+            length: 0,
+        }
+    }
+
+    const fn push(mut self, text: &str) -> Message {
+        let text = text.as_bytes();
+        let mut i = 0;
+        while i < text.len() && self.length < self.bytes.len() {
+            self.bytes[self.length] = text[i];
+            self.length += 1;
+            i += 1;
+        }
+        self
+    }
+
+    const fn panic(&self) -> ! {
+        let (message, _) = self.bytes.split_at(self.length);
+        match std::str::from_utf8(message) {
+            Ok(message) => panic!("{}", message),
+            Err(_) => panic!("shader inputs don't match the structs"),
+        }
+    }
+}
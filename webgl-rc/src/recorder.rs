@@ -0,0 +1,577 @@
+//! Recording of the GL commands issued through [Gl].
+//!
+//! A [Recording] is a plain list of commands referencing GL objects by their ids,
+//! it can be saved as text, compared with a reference log and replayed.
+//! [Recording::to_json] exports it as a trace for external tools.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use crate::{Gl, GlError};
+
+/// Value which can be written into a command log and parsed back.
+trait LogValue: Sized {
+    fn write(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result;
+    fn parse(value: &str) -> Option<Self>;
+    fn write_json(&self, output: &mut String) -> fmt::Result;
+}
+
+macro_rules! log_value_from_str {
+    ($($type:ty),*) => {
+        $(
+            impl LogValue for $type {
+                fn write(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(formatter, "{}", self)
+                }
+                fn parse(value: &str) -> Option<Self> {
+                    value.parse().ok()
+                }
+                fn write_json(&self, output: &mut String) -> fmt::Result {
+                    write!(output, "{}", self)
+                }
+            }
+        )*
+    };
+}
+
+log_value_from_str!(bool, i32, u32, u64, usize);
+
+impl LogValue for f32 {
+    fn write(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self)
+    }
+    fn parse(value: &str) -> Option<Self> {
+        value.parse().ok()
+    }
+    fn write_json(&self, output: &mut String) -> fmt::Result {
+        if self.is_finite() {
+            write!(output, "{}", self)
+        } else {
+            // JSON has no NaN and infinities
+            write!(output, "\"{}\"", self)
+        }
+    }
+}
+
+/// Whitespace separates the fields, so it's written percent-encoded along with `%` itself.
+impl LogValue for String {
+    fn write(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.chars() {
+            if c.is_whitespace() || c == '%' {
+                for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                    write!(formatter, "%{:02X}", byte)?;
+                }
+            } else {
+                formatter.write_char(c)?;
+            }
+        }
+        Ok(())
+    }
+    fn parse(value: &str) -> Option<Self> {
+        let mut bytes = Vec::with_capacity(value.len());
+        let mut rest = value.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            if byte == b'%' {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            } else {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+        String::from_utf8(bytes).ok()
+    }
+    fn write_json(&self, output: &mut String) -> fmt::Result {
+        write_json_string(output, self)
+    }
+}
+
+fn write_json_string(output: &mut String, value: &str) -> fmt::Result {
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(output, "\\u{:04x}", c as u32)?,
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    Ok(())
+}
+
+impl<T: LogValue> LogValue for Option<T> {
+    fn write(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(value) => value.write(formatter),
+            None => formatter.write_str("-"),
+        }
+    }
+    fn parse(value: &str) -> Option<Self> {
+        if value == "-" {
+            Some(None)
+        } else {
+            T::parse(value).map(Some)
+        }
+    }
+    fn write_json(&self, output: &mut String) -> fmt::Result {
+        match self {
+            Some(value) => value.write_json(output),
+            None => {
+                output.push_str("null");
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<T: LogValue> LogValue for Vec<T> {
+    fn write(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, value) in self.iter().enumerate() {
+            if i != 0 {
+                formatter.write_str(",")?;
+            }
+            value.write(formatter)?;
+        }
+        Ok(())
+    }
+    fn parse(value: &str) -> Option<Self> {
+        if value.is_empty() {
+            Some(Vec::new())
+        } else {
+            value.split(',').map(T::parse).collect()
+        }
+    }
+    fn write_json(&self, output: &mut String) -> fmt::Result {
+        output.push('[');
+        for (i, value) in self.iter().enumerate() {
+            if i != 0 {
+                output.push(',');
+            }
+            value.write_json(output)?;
+        }
+        output.push(']');
+        Ok(())
+    }
+}
+
+macro_rules! commands {
+    ($($(#[$meta:meta])* $name:ident { $($field:ident: $type:ty),* $(,)? }),* $(,)?) => {
+        /// GL command, objects are referenced by their ids, enum values are GL constants.
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum Command {
+            $($(#[$meta])* $name { $($field: $type),* },)*
+        }
+
+        impl Command {
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Command::$name { .. } => stringify!($name),)*
+                }
+            }
+
+            /// JSON object with the command name and its arguments,
+            /// absent object ids are `null`.
+            pub fn to_json(&self) -> String {
+                let mut output = String::new();
+                // Writing into a String doesn't fail
+                let _ = self.write_json(&mut output);
+                output
+            }
+
+            fn write_json(&self, output: &mut String) -> fmt::Result {
+                match self {
+                    $(Command::$name { $($field),* } => {
+                        output.push_str(concat!("{\"name\":\"", stringify!($name), "\",\"args\":{"));
+                        let mut _first = true;
+                        $(
+                            if !_first {
+                                output.push(',');
+                            }
+                            _first = false;
+                            output.push_str(concat!("\"", stringify!($field), "\":"));
+                            LogValue::write_json($field, output)?;
+                        )*
+                        output.push_str("}}");
+                        Ok(())
+                    })*
+                }
+            }
+        }
+
+        impl fmt::Display for Command {
+            fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    $(Command::$name { $($field),* } => {
+                        formatter.write_str(stringify!($name))?;
+                        $(
+                            write!(formatter, " {}=", stringify!($field))?;
+                            LogValue::write($field, formatter)?;
+                        )*
+                        Ok(())
+                    })*
+                }
+            }
+        }
+
+        impl FromStr for Command {
+            type Err = GlError;
+
+            fn from_str(line: &str) -> Result<Command, GlError> {
+                let mut parts = line.split_whitespace();
+                let name = parts
+                    .next()
+                    .ok_or_else(|| GlError::InvalidRecording("Empty command".into()))?;
+                let fields = parts
+                    .map(|part| {
+                        part.split_once('=').ok_or_else(|| {
+                            GlError::InvalidRecording(format!("Invalid field {:?}", part))
+                        })
+                    })
+                    .collect::<Result<HashMap<&str, &str>, GlError>>()?;
+                match name {
+                    $(stringify!($name) => Ok(Command::$name {
+                        $($field: parse_field(&fields, stringify!($field))?,)*
+                    }),)*
+                    _ => Err(GlError::InvalidRecording(format!("Unknown command {:?}", name))),
+                }
+            }
+        }
+    };
+}
+
+fn parse_field<T: LogValue>(fields: &HashMap<&str, &str>, name: &str) -> Result<T, GlError> {
+    fields
+        .get(name)
+        .and_then(|value| T::parse(value))
+        .ok_or_else(|| GlError::InvalidRecording(format!("Invalid or missing field {:?}", name)))
+}
+
+commands! {
+    CreateBuffer { id: u64 },
+    DeleteBuffer { id: u64 },
+    BindBuffer { target: u32, buffer: Option<u64> },
+    /// Buffer content isn't recorded, only its size in bytes
+    BufferData { target: u32, size: usize, usage: u32 },
+    /// Buffer content isn't recorded, only the changed range in bytes
+    BufferSubData { target: u32, offset: usize, size: usize },
+    CreateTexture { id: u64 },
+    DeleteTexture { id: u64 },
+    ActiveTexture { unit: u32 },
+    BindTexture { target: u32, texture: Option<u64> },
+    TexParameter { target: u32, parameter: u32, value: i32 },
+    PixelStore { parameter: u32, value: i32 },
+    /// Texture content isn't recorded
+    TexImage2d { target: u32, level: i32, format: u32, width: u32, height: u32, data_type: u32 },
+    /// Texture content isn't recorded
+    TexSubImage2d { target: u32, level: i32, x: i32, y: i32, width: u32, height: u32, format: u32, data_type: u32 },
+    GenerateMipmap { target: u32 },
+    CreateFramebuffer { id: u64 },
+    DeleteFramebuffer { id: u64 },
+    BindFramebuffer { framebuffer: Option<u64> },
+    FramebufferTexture2d { attachment: u32, target: u32, texture: Option<u64> },
+    FramebufferRenderbuffer { attachment: u32, renderbuffer: Option<u64> },
+    CreateRenderbuffer { id: u64 },
+    DeleteRenderbuffer { id: u64 },
+    BindRenderbuffer { renderbuffer: Option<u64> },
+    RenderbufferStorage { format: u32, width: u32, height: u32 },
+    CreateShader { id: u64, shader_type: u32 },
+    DeleteShader { id: u64 },
+    CreateProgram { id: u64, vertex_shader: u64, fragment_shader: u64 },
+    DeleteProgram { id: u64 },
+    UseProgram { program: Option<u64> },
+    Enable { capability: u32 },
+    Disable { capability: u32 },
+    BlendEquation { color: u32, alpha: u32 },
+    BlendFunction { src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32 },
+    DepthFunction { function: u32 },
+    CullFace { face: u32 },
+    ColorMask { r: bool, g: bool, b: bool, a: bool },
+    DepthMask { value: bool },
+    StencilFunction { function: u32, reference: i32, mask: u32 },
+    StencilOperation { fail: u32, depth_fail: u32, pass: u32 },
+    StencilMask { mask: u32 },
+    ClearColor { r: f32, g: f32, b: f32, a: f32 },
+    ClearDepth { value: f32 },
+    ClearStencil { value: i32 },
+    Viewport { x: i32, y: i32, width: i32, height: i32 },
+    Scissor { x: i32, y: i32, width: i32, height: i32 },
+    Clear { mask: u32 },
+    EnableVertexAttribArray { location: u32 },
+    DisableVertexAttribArray { location: u32 },
+    VertexAttribDivisor { location: u32, divisor: u32 },
+    VertexAttribPointer { location: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32 },
+    /// Uniform of the current program, `data_type` is the GL type of the uniform
+    Uniform { name: String, data_type: u32, values: Vec<f32> },
+    /// Uniform of the `int` or `ivec` type, integers are passed as is
+    UniformInt { name: String, data_type: u32, values: Vec<i32> },
+    DrawArrays { mode: u32, first: i32, count: i32 },
+    DrawElements { mode: u32, count: i32, data_type: u32, offset: i32 },
+    DrawArraysInstanced { mode: u32, first: i32, count: i32, instances: i32 },
+    DrawElementsInstanced { mode: u32, count: i32, data_type: u32, offset: i32, instances: i32 },
+    ReadPixels { x: i32, y: i32, width: i32, height: i32, format: u32, data_type: u32 },
+    Flush {},
+    Finish {},
+    /// Start of the commands of a [Gl::debug_group]
+    PushDebugGroup { name: String },
+    PopDebugGroup {},
+}
+
+/// Commands captured by [Recorder] or [Gl::record].
+///
+/// The text form contains one command per line, so recordings can be stored
+/// as reference files and compared in regression tests.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    commands: Vec<Command>,
+}
+
+impl Recording {
+    pub fn new(commands: Vec<Command>) -> Recording {
+        Recording { commands }
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn into_commands(self) -> Vec<Command> {
+        self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Trace for external tools: a JSON object with the `commands` array, see [Command::to_json].
+    ///
+    /// Objects are referenced by the same ids as in the resource registry,
+    /// enum values are GL constants.
+    pub fn to_json(&self) -> String {
+        let mut output = String::from("{\"commands\":[");
+        for (i, command) in self.commands.iter().enumerate() {
+            if i != 0 {
+                output.push(',');
+            }
+            output.push_str("\n  ");
+            // Writing into a String doesn't fail
+            let _ = command.write_json(&mut output);
+        }
+        output.push_str("\n]}\n");
+        output
+    }
+
+    /// Issue the recorded commands again.
+    ///
+    /// Referenced objects should be still alive in the `gl` context.
+    /// Object creation, deletion, uploads and readbacks are skipped,
+    /// since their data isn't a part of the recording.
+    pub fn replay(&self, gl: &Gl) -> Result<(), GlError> {
+        for command in &self.commands {
+            match command {
+                Command::CreateBuffer { .. }
+                | Command::DeleteBuffer { .. }
+                | Command::BufferData { .. }
+                | Command::BufferSubData { .. }
+                | Command::CreateTexture { .. }
+                | Command::DeleteTexture { .. }
+                | Command::TexImage2d { .. }
+                | Command::TexSubImage2d { .. }
+                | Command::CreateFramebuffer { .. }
+                | Command::DeleteFramebuffer { .. }
+                | Command::CreateRenderbuffer { .. }
+                | Command::DeleteRenderbuffer { .. }
+                | Command::RenderbufferStorage { .. }
+                | Command::CreateShader { .. }
+                | Command::DeleteShader { .. }
+                | Command::CreateProgram { .. }
+                | Command::DeleteProgram { .. }
+                | Command::ReadPixels { .. } => {}
+                // Through the divisor cache, so later draws reset the replayed divisors
+                Command::VertexAttribDivisor { location, divisor } => {
+                    gl.set_attribute_divisor(*location, *divisor)
+                }
+                command => gl
+                    .try_execute(command.clone())
+                    .map_err(|error| match error {
+                        GlError::ObjectNotFound(id) => {
+                            GlError::InvalidRecording(format!("Object #{} not found", id))
+                        }
+                        error => error,
+                    })?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Commands of debug groups are indented, the indentation is ignored when parsed.
+impl fmt::Display for Recording {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut depth: usize = 0;
+        for command in &self.commands {
+            if let Command::PopDebugGroup {} = command {
+                depth = depth.saturating_sub(1);
+            }
+            writeln!(formatter, "{:indent$}{}", "", command, indent = depth * 2)?;
+            if let Command::PushDebugGroup { .. } = command {
+                depth += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recording {
+    type Err = GlError;
+
+    fn from_str(source: &str) -> Result<Recording, GlError> {
+        Ok(Recording {
+            commands: source
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+/// Captures the commands issued through [Gl] until it's finished or dropped.
+///
+/// Recorders can be nested, commands captured by the inner recorder
+/// are also added to the outer one.
+#[derive(Debug)]
+pub struct Recorder {
+    gl: Gl,
+    previous: Option<Option<Vec<Command>>>,
+}
+
+impl Recorder {
+    pub fn start(gl: &Gl) -> Recorder {
+        Recorder {
+            gl: gl.clone(),
+            previous: Some(gl.swap_recording(Some(Vec::new()))),
+        }
+    }
+
+    pub fn finish(mut self) -> Recording {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Recording {
+        let commands = match self.previous.take() {
+            Some(previous) => self.gl.swap_recording(previous).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        for command in &commands {
+            self.gl.record_command(|| command.clone());
+        }
+        Recording { commands }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        let recording: Recording = "BindTexture target=3553 texture=-\n\nUniform name=u_color data_type=35666 values=1,0.5,0,1\n  Viewport x=0 y=0 width=300 height=150\nFlush"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            recording.commands(),
+            &[
+                Command::BindTexture {
+                    target: 3553,
+                    texture: None,
+                },
+                Command::Uniform {
+                    name: "u_color".into(),
+                    data_type: 35666,
+                    values: vec![1.0, 0.5, 0.0, 1.0],
+                },
+                Command::Viewport {
+                    x: 0,
+                    y: 0,
+                    width: 300,
+                    height: 150,
+                },
+                Command::Flush {},
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let recording = Recording::new(vec![
+            Command::PushDebugGroup {
+                name: "shadow pass 100% é".into(),
+            },
+            Command::BindFramebuffer {
+                framebuffer: Some(7),
+            },
+            Command::ColorMask {
+                r: true,
+                g: false,
+                b: true,
+                a: false,
+            },
+            Command::UniformInt {
+                name: "u_layers".into(),
+                data_type: 5124,
+                values: vec![],
+            },
+            Command::ClearColor {
+                r: 0.25,
+                g: -1.5,
+                b: 1e-7,
+                a: 1.0,
+            },
+            Command::PopDebugGroup {},
+        ]);
+        let text = recording.to_string();
+        assert!(text.contains("name=shadow%20pass%20100%25%20é"));
+        assert!(text.contains("\n  BindFramebuffer framebuffer=7\n"));
+        assert_eq!(text.parse::<Recording>(), Ok(recording));
+    }
+
+    #[test]
+    fn malformed_recordings_are_rejected() {
+        for source in [
+            "Frobnicate x=1",
+            "Clear",
+            "Clear mask",
+            "Clear mask=red",
+            "Clear mask=-1",
+            "BindTexture target=3553 texture=",
+            "PushDebugGroup name=truncated%2",
+            "PushDebugGroup name=%zz",
+        ] {
+            assert!(
+                matches!(
+                    source.parse::<Recording>(),
+                    Err(GlError::InvalidRecording(_))
+                ),
+                "{:?} is parsed",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Registry of the live GL resources, see [Gl::resources](crate::Gl::resources).
+//!
+//! Resources are tracked with weak references, so the registry doesn't keep them alive
+//! and anything listed is still referenced somewhere in the application.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use crate::buffer_usage::BufferUsage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ResourceKind {
+    ArrayBuffer,
+    ElementsBuffer,
+    Texture,
+    CubeMap,
+    DepthBuffer,
+    FrameBuffer,
+    Program,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 7] = [
+        ResourceKind::ArrayBuffer,
+        ResourceKind::ElementsBuffer,
+        ResourceKind::Texture,
+        ResourceKind::CubeMap,
+        ResourceKind::DepthBuffer,
+        ResourceKind::FrameBuffer,
+        ResourceKind::Program,
+    ];
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceInfo {
+    pub id: u64,
+    pub kind: ResourceKind,
+    /// Estimated size of the GPU storage in bytes, zero for objects without own storage
+    pub size: usize,
+    pub label: Option<String>,
+}
+
+impl fmt::Display for ResourceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_name(f, self.kind, self.id, self.label.as_deref())?;
+        write!(f, " ({} bytes)", self.size)
+    }
+}
+
+/// Name of a resource in messages, like `Texture #3 'albedo'`.
+pub(crate) fn write_name(
+    f: &mut fmt::Formatter<'_>,
+    kind: ResourceKind,
+    id: u64,
+    label: Option<&str>,
+) -> fmt::Result {
+    write!(f, "{:?} #{}", kind, id)?;
+    match label {
+        Some(label) => write!(f, " '{}'", label),
+        None => Ok(()),
+    }
+}
+
+/// Implemented by the shared data of resource handles.
+pub(crate) trait Resource {
+    fn info(&self) -> ResourceInfo;
+
+    /// Usage of the array and elements buffers
+    fn buffer(&self) -> Option<BufferInfo> {
+        None
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Registry {
+    resources: RefCell<BTreeMap<u64, Weak<dyn Resource>>>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("count", &self.resources.borrow().len())
+            .finish()
+    }
+}
+
+impl Registry {
+    pub fn insert(&self, id: u64, resource: Weak<dyn Resource>) {
+        self.resources.borrow_mut().insert(id, resource);
+    }
+
+    pub fn remove(&self, id: u64) {
+        self.resources.borrow_mut().remove(&id);
+    }
+
+    /// Upgrade first, so resources dropped while collecting don't touch the map
+    fn live(&self) -> Vec<Rc<dyn Resource>> {
+        self.resources
+            .borrow()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Resources {
+        Resources {
+            items: self.live().iter().map(|resource| resource.info()).collect(),
+        }
+    }
+
+    pub fn buffer_stats(&self) -> BufferStats {
+        BufferStats {
+            buffers: self
+                .live()
+                .iter()
+                .filter_map(|resource| resource.buffer())
+                .collect(),
+        }
+    }
+}
+
+/// Snapshot of the live resources ordered by creation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Resources {
+    items: Vec<ResourceInfo>,
+}
+
+impl Resources {
+    pub fn items(&self) -> &[ResourceInfo] {
+        &self.items
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ResourceInfo> {
+        self.items.iter()
+    }
+
+    pub fn of_kind(&self, kind: ResourceKind) -> impl Iterator<Item = &ResourceInfo> {
+        self.items.iter().filter(move |item| item.kind == kind)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn count(&self, kind: ResourceKind) -> usize {
+        self.of_kind(kind).count()
+    }
+
+    pub fn size(&self, kind: ResourceKind) -> usize {
+        self.of_kind(kind).map(|item| item.size).sum()
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.items.iter().map(|item| item.size).sum()
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            textures: self.size(ResourceKind::Texture) + self.size(ResourceKind::CubeMap),
+            array_buffers: self.size(ResourceKind::ArrayBuffer),
+            elements_buffers: self.size(ResourceKind::ElementsBuffer),
+            depth_buffers: self.size(ResourceKind::DepthBuffer),
+        }
+    }
+}
+
+impl fmt::Display for Resources {
+    /// Summary with counts and sizes per kind, followed by the list of resources.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for kind in ResourceKind::ALL {
+            let count = self.count(kind);
+            if count > 0 {
+                writeln!(f, "{:?}: {} ({} bytes)", kind, count, self.size(kind))?;
+            }
+        }
+        for item in &self.items {
+            writeln!(f, "  {}", item)?;
+        }
+        Ok(())
+    }
+}
+
+/// Estimated GPU memory in bytes by category, see [Gl::memory_usage](crate::Gl::memory_usage).
+///
+/// Sizes are computed from dimensions and formats, drivers may pad or compress the storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub textures: usize,
+    pub array_buffers: usize,
+    pub elements_buffers: usize,
+    pub depth_buffers: usize,
+}
+
+impl MemoryUsage {
+    pub fn buffers(&self) -> usize {
+        self.array_buffers + self.elements_buffers
+    }
+
+    pub fn total(&self) -> usize {
+        self.textures + self.buffers() + self.depth_buffers
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes (textures: {}, array buffers: {}, elements buffers: {}, depth buffers: {})",
+            self.total(),
+            self.textures,
+            self.array_buffers,
+            self.elements_buffers,
+            self.depth_buffers
+        )
+    }
+}
+
+/// How a buffer is used, see [Gl::buffer_stats](crate::Gl::buffer_stats).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferInfo {
+    pub id: u64,
+    pub kind: ResourceKind,
+    /// Usage of the last upload
+    pub usage: BufferUsage,
+    /// Usage the buffer was created with
+    pub initial_usage: BufferUsage,
+    /// Size of the GPU storage in bytes
+    pub capacity_bytes: usize,
+    /// Number of uploads including the initial one, partial updates included
+    pub uploads: usize,
+    pub label: Option<String>,
+}
+
+impl fmt::Display for BufferInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_name(f, self.kind, self.id, self.label.as_deref())?;
+        write!(
+            f,
+            " {:?}, {} bytes, {} uploads",
+            self.usage, self.capacity_bytes, self.uploads
+        )?;
+        if self.usage != self.initial_usage {
+            write!(f, " (created as {:?})", self.initial_usage)?;
+        }
+        Ok(())
+    }
+}
+
+/// Snapshot of the live array and elements buffers ordered by creation.
+///
+/// Useful to find buffers created with a wrong usage hint, like `Static` buffers
+/// uploaded every frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferStats {
+    buffers: Vec<BufferInfo>,
+}
+
+impl BufferStats {
+    pub fn buffers(&self) -> &[BufferInfo] {
+        &self.buffers
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BufferInfo> {
+        self.buffers.iter()
+    }
+
+    pub fn with_usage(&self, usage: BufferUsage) -> impl Iterator<Item = &BufferInfo> {
+        self.buffers
+            .iter()
+            .filter(move |buffer| buffer.usage == usage)
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    pub fn count(&self, usage: BufferUsage) -> usize {
+        self.with_usage(usage).count()
+    }
+
+    /// Bytes of the buffers with the usage
+    pub fn size(&self, usage: BufferUsage) -> usize {
+        self.with_usage(usage)
+            .map(|buffer| buffer.capacity_bytes)
+            .sum()
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.buffers
+            .iter()
+            .map(|buffer| buffer.capacity_bytes)
+            .sum()
+    }
+
+    pub fn uploads(&self) -> usize {
+        self.buffers.iter().map(|buffer| buffer.uploads).sum()
+    }
+}
+
+impl fmt::Display for BufferStats {
+    /// Summary per usage, followed by the list of buffers.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for usage in [
+            BufferUsage::Static,
+            BufferUsage::Dynamic,
+            BufferUsage::Stream,
+        ] {
+            let count = self.count(usage);
+            if count > 0 {
+                writeln!(f, "{:?}: {} ({} bytes)", usage, count, self.size(usage))?;
+            }
+        }
+        for buffer in &self.buffers {
+            writeln!(f, "  {}", buffer)?;
+        }
+        Ok(())
+    }
+}
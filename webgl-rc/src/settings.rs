@@ -1,18 +1,19 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use std::cell::RefCell;
-use std::convert::TryInto;
 use std::fmt::Debug;
 use std::ops::Deref;
 use std::ops::DerefMut;
-use web_sys::{AngleInstancedArrays, WebGlRenderingContext as Context};
+use web_sys::WebGlRenderingContext as Context;
 
+use super::cube_map::CubeMap;
 use super::data_buffer::{ArrayBuffer, Item, ItemsBuffer};
 use super::gl::Gl;
 use super::program::Program;
 use super::texture::Texture;
 use super::texture::TextureFilter;
 use crate::depth_buffer::DepthBuffer;
-use crate::{ElementsBuffer, FrameBuffer};
+use crate::state::{CachedParameter, StateValue};
+use crate::{Command, ElementsBuffer, FrameBuffer};
 
 #[repr(u32)]
 #[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
@@ -78,7 +79,12 @@ impl Default for DepthFunction {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct ColorMask(bool, bool, bool, bool);
+pub struct ColorMask(
+    pub(crate) bool,
+    pub(crate) bool,
+    pub(crate) bool,
+    pub(crate) bool,
+);
 
 impl Default for ColorMask {
     fn default() -> Self {
@@ -86,20 +92,105 @@ impl Default for ColorMask {
     }
 }
 
+/// Depth buffer writes, transparent objects are usually drawn with depth test but without writes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DepthMask(pub(crate) bool);
+
+impl Default for DepthMask {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// Stencil test function, the test passes when `function(reference & mask, stencil & mask)` holds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StencilFunction {
+    pub function: DepthFunction,
+    pub reference: i32,
+    pub mask: u32,
+}
+
+impl Default for StencilFunction {
+    fn default() -> Self {
+        Self {
+            function: DepthFunction::Always,
+            reference: 0,
+            mask: !0,
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
+pub enum StencilOperation {
+    #[default]
+    Keep = Context::KEEP,
+    Zero = Context::ZERO,
+    Replace = Context::REPLACE,
+    Increment = Context::INCR,
+    IncrementWrap = Context::INCR_WRAP,
+    Decrement = Context::DECR,
+    DecrementWrap = Context::DECR_WRAP,
+    Invert = Context::INVERT,
+}
+
+/// Stencil buffer updates when the stencil test fails, the depth test fails, or both pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StencilOperationSetting {
+    pub fail: StencilOperation,
+    pub depth_fail: StencilOperation,
+    pub pass: StencilOperation,
+}
+
+/// Unpacking of the uploaded images, videos and bytes, image bitmaps ignore it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PixelStore {
+    /// Flip the rows, so the top row of an image is at the bottom
+    pub flip_y: bool,
+    /// Multiply the colors by alpha, see [Texture::is_premultiplied]
+    pub premultiply_alpha: bool,
+}
+
+/// Row alignment of the uploaded bytes, 1, 2, 4 or 8, rows of tightly packed data
+/// with widths not divisible by the alignment need 1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnpackAlignment(pub(crate) i32);
+
+impl Default for UnpackAlignment {
+    fn default() -> Self {
+        Self(4)
+    }
+}
+
+/// Bits of the stencil buffer that can be written.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StencilMask(pub(crate) u32);
+
+impl Default for StencilMask {
+    fn default() -> Self {
+        Self(!0)
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct SettingsCache {
     blend: BlendSetting,
     depth: DepthTestSetting,
+    cull: CullSetting,
+    stencil: StencilTestSetting,
     array_buffer: ArrayBufferSetting,
     element_buffer: ElementBufferSetting,
     active_texture: ActiveTextureSetting,
     textures: [Option<Texture>; 16],
+    cube_maps: [Option<CubeMap>; 16],
     enabled_attributes: EnabledAttributesSetting,
     instanced_attributes: InstancedAttributesSetting,
     program: ProgramSetting,
     clear_color: ClearColorSetting,
     clear_depth: ClearDepthSetting,
+    clear_stencil: ClearStencilSetting,
     viewport: ViewportSetting,
+    scissor: ScissorSetting,
     depth_buffer: DepthBufferSetting,
     frame_buffer: FrameBufferSetting,
     blend_equation: BlendEquationSetting,
@@ -107,6 +198,241 @@ pub struct SettingsCache {
     depth_function: DepthFunction,
     cull_face: CullFace,
     color_mask: ColorMask,
+    depth_mask: DepthMask,
+    stencil_function: StencilFunction,
+    stencil_operation: StencilOperationSetting,
+    stencil_mask: StencilMask,
+    pixel_store: PixelStore,
+    unpack_alignment: UnpackAlignment,
+}
+
+impl SettingsCache {
+    /// Whether blending expects premultiplied colors, `None` if blending is disabled
+    /// or the function isn't one of [BlendFunctionSetting::alpha].
+    pub(crate) fn blended_alpha(&self) -> Option<bool> {
+        if !self.blend.0 {
+            None
+        } else if self.blend_function == BlendFunctionSetting::alpha(true) {
+            Some(true)
+        } else if self.blend_function == BlendFunctionSetting::alpha(false) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Cached values in the form of `getParameter` results.
+    pub(crate) fn parameters(&self) -> Vec<CachedParameter> {
+        use StateValue::*;
+        let mut result = vec![
+            CachedParameter::new("BLEND", Context::BLEND, Bool(self.blend.0)),
+            CachedParameter::new("DEPTH_TEST", Context::DEPTH_TEST, Bool(self.depth.0)),
+            CachedParameter::new("CULL_FACE", Context::CULL_FACE, Bool(self.cull.0)),
+            CachedParameter::new("STENCIL_TEST", Context::STENCIL_TEST, Bool(self.stencil.0)),
+            CachedParameter::new(
+                "SCISSOR_TEST",
+                Context::SCISSOR_TEST,
+                Bool(self.scissor.enabled),
+            ),
+            CachedParameter::new(
+                "ARRAY_BUFFER_BINDING",
+                Context::ARRAY_BUFFER_BINDING,
+                Object(self.array_buffer.0.as_ref().map(|v| v.id())),
+            ),
+            CachedParameter::new(
+                "ELEMENT_ARRAY_BUFFER_BINDING",
+                Context::ELEMENT_ARRAY_BUFFER_BINDING,
+                Object(self.element_buffer.0.as_ref().map(|v| v.id())),
+            ),
+            CachedParameter::new(
+                "CURRENT_PROGRAM",
+                Context::CURRENT_PROGRAM,
+                Object(self.program.program.as_ref().map(|v| v.id())),
+            ),
+            CachedParameter::new(
+                "FRAMEBUFFER_BINDING",
+                Context::FRAMEBUFFER_BINDING,
+                Object(self.frame_buffer.buffer.as_ref().map(|v| v.id())),
+            ),
+            CachedParameter::new(
+                "RENDERBUFFER_BINDING",
+                Context::RENDERBUFFER_BINDING,
+                Object(self.depth_buffer.buffer.as_ref().map(|v| v.id())),
+            ),
+            CachedParameter::new(
+                "ACTIVE_TEXTURE",
+                Context::ACTIVE_TEXTURE,
+                Enum(Context::TEXTURE0 + self.active_texture.0),
+            ),
+        ];
+        for (unit, (texture, cube_map)) in self.textures.iter().zip(&self.cube_maps).enumerate() {
+            result.push(CachedParameter {
+                texture_unit: Some(unit as u32),
+                ..CachedParameter::new(
+                    &format!("TEXTURE_BINDING_2D[{}]", unit),
+                    Context::TEXTURE_BINDING_2D,
+                    Object(texture.as_ref().map(|v| v.id())),
+                )
+            });
+            result.push(CachedParameter {
+                texture_unit: Some(unit as u32),
+                ..CachedParameter::new(
+                    &format!("TEXTURE_BINDING_CUBE_MAP[{}]", unit),
+                    Context::TEXTURE_BINDING_CUBE_MAP,
+                    Object(cube_map.as_ref().map(|v| v.id())),
+                )
+            });
+        }
+        for (name, items) in [
+            ("enabled attributes", &self.enabled_attributes.items),
+            ("instanced attributes", &self.instanced_attributes.items),
+        ] {
+            result.push(CachedParameter {
+                name: name.into(),
+                parameter: None,
+                texture_unit: None,
+                value: Ints(items.iter().map(|&v| v as i32).collect()),
+            });
+        }
+        let ColorMask(r, g, b, a) = self.color_mask;
+        let viewport = self.viewport;
+        result.extend([
+            CachedParameter::new(
+                "COLOR_CLEAR_VALUE",
+                Context::COLOR_CLEAR_VALUE,
+                Floats(self.clear_color.color.to_vec()),
+            ),
+            CachedParameter::new(
+                "DEPTH_CLEAR_VALUE",
+                Context::DEPTH_CLEAR_VALUE,
+                Float(self.clear_depth.value),
+            ),
+            CachedParameter::new(
+                "STENCIL_CLEAR_VALUE",
+                Context::STENCIL_CLEAR_VALUE,
+                Int(self.clear_stencil.value.into()),
+            ),
+            CachedParameter::new(
+                "VIEWPORT",
+                Context::VIEWPORT,
+                Ints(vec![
+                    viewport.x,
+                    viewport.y,
+                    viewport.width,
+                    viewport.height,
+                ]),
+            ),
+            CachedParameter::new(
+                "BLEND_EQUATION_RGB",
+                Context::BLEND_EQUATION_RGB,
+                Enum(self.blend_equation.color.into()),
+            ),
+            CachedParameter::new(
+                "BLEND_EQUATION_ALPHA",
+                Context::BLEND_EQUATION_ALPHA,
+                Enum(self.blend_equation.alpha.into()),
+            ),
+            CachedParameter::new(
+                "BLEND_SRC_RGB",
+                Context::BLEND_SRC_RGB,
+                Enum(self.blend_function.src_rgb.into()),
+            ),
+            CachedParameter::new(
+                "BLEND_DST_RGB",
+                Context::BLEND_DST_RGB,
+                Enum(self.blend_function.dst_rgb.into()),
+            ),
+            CachedParameter::new(
+                "BLEND_SRC_ALPHA",
+                Context::BLEND_SRC_ALPHA,
+                Enum(self.blend_function.src_alpha.into()),
+            ),
+            CachedParameter::new(
+                "BLEND_DST_ALPHA",
+                Context::BLEND_DST_ALPHA,
+                Enum(self.blend_function.dst_alpha.into()),
+            ),
+            CachedParameter::new(
+                "DEPTH_FUNC",
+                Context::DEPTH_FUNC,
+                Enum(self.depth_function.into()),
+            ),
+            CachedParameter::new(
+                "CULL_FACE_MODE",
+                Context::CULL_FACE_MODE,
+                Enum(self.cull_face.into()),
+            ),
+            CachedParameter::new(
+                "COLOR_WRITEMASK",
+                Context::COLOR_WRITEMASK,
+                Bools(vec![r, g, b, a]),
+            ),
+            CachedParameter::new(
+                "DEPTH_WRITEMASK",
+                Context::DEPTH_WRITEMASK,
+                Bool(self.depth_mask.0),
+            ),
+            CachedParameter::new(
+                "STENCIL_FUNC",
+                Context::STENCIL_FUNC,
+                Enum(self.stencil_function.function.into()),
+            ),
+            CachedParameter::new(
+                "STENCIL_REF",
+                Context::STENCIL_REF,
+                Int(self.stencil_function.reference.into()),
+            ),
+            CachedParameter::new(
+                "STENCIL_VALUE_MASK",
+                Context::STENCIL_VALUE_MASK,
+                Int(self.stencil_function.mask.into()),
+            ),
+            CachedParameter::new(
+                "STENCIL_FAIL",
+                Context::STENCIL_FAIL,
+                Enum(self.stencil_operation.fail.into()),
+            ),
+            CachedParameter::new(
+                "STENCIL_PASS_DEPTH_FAIL",
+                Context::STENCIL_PASS_DEPTH_FAIL,
+                Enum(self.stencil_operation.depth_fail.into()),
+            ),
+            CachedParameter::new(
+                "STENCIL_PASS_DEPTH_PASS",
+                Context::STENCIL_PASS_DEPTH_PASS,
+                Enum(self.stencil_operation.pass.into()),
+            ),
+            CachedParameter::new(
+                "STENCIL_WRITEMASK",
+                Context::STENCIL_WRITEMASK,
+                Int(self.stencil_mask.0.into()),
+            ),
+            CachedParameter::new(
+                "UNPACK_FLIP_Y_WEBGL",
+                Context::UNPACK_FLIP_Y_WEBGL,
+                Bool(self.pixel_store.flip_y),
+            ),
+            CachedParameter::new(
+                "UNPACK_PREMULTIPLY_ALPHA_WEBGL",
+                Context::UNPACK_PREMULTIPLY_ALPHA_WEBGL,
+                Bool(self.pixel_store.premultiply_alpha),
+            ),
+            CachedParameter::new(
+                "UNPACK_ALIGNMENT",
+                Context::UNPACK_ALIGNMENT,
+                Int(self.unpack_alignment.0.into()),
+            ),
+        ]);
+        if self.scissor.enabled {
+            let scissor = self.scissor;
+            result.push(CachedParameter::new(
+                "SCISSOR_BOX",
+                Context::SCISSOR_BOX,
+                Ints(vec![scissor.x, scissor.y, scissor.width, scissor.height]),
+            ));
+        }
+        result
+    }
 }
 
 pub trait Settings
@@ -122,6 +448,11 @@ where
         ComposedSetting(self, DepthTestSetting(value))
     }
 
+    /// Enable face culling, the culled faces are selected with [Settings::cull_face].
+    fn cull(self, value: bool) -> ComposedSetting<Self, CullSetting> {
+        ComposedSetting(self, CullSetting(value))
+    }
+
     fn blend(self, value: bool) -> ComposedSetting<Self, BlendSetting> {
         ComposedSetting(self, BlendSetting(value))
     }
@@ -152,6 +483,25 @@ where
         )
     }
 
+    /// Enable blending of colors over the background, with straight or premultiplied alpha.
+    fn blend_alpha(
+        self,
+        premultiplied: bool,
+    ) -> ComposedSetting<ComposedSetting<Self, BlendSetting>, BlendFunctionSetting> {
+        ComposedSetting(
+            ComposedSetting(self, BlendSetting(true)),
+            BlendFunctionSetting::alpha(premultiplied),
+        )
+    }
+
+    /// Blending matching the alpha of the texture content, see [Texture::is_premultiplied].
+    fn blend_texture(
+        self,
+        texture: &Texture,
+    ) -> ComposedSetting<ComposedSetting<Self, BlendSetting>, BlendFunctionSetting> {
+        self.blend_alpha(texture.is_premultiplied())
+    }
+
     fn depth_function(self, function: DepthFunction) -> ComposedSetting<Self, DepthFunction> {
         ComposedSetting(self, function)
     }
@@ -170,17 +520,40 @@ where
         )
     }
 
-    fn texture_list<T: IntoIterator<Item = Texture>>(
+    /// Textures bound to the units in order, `None` leaves the unit without a texture.
+    fn texture_list<T: IntoIterator<Item = I>, I: Into<Option<Texture>>>(
         self,
         textures: T,
     ) -> ComposedSetting<Self, TextureListSetting> {
         let mut setting: [Option<Texture>; 16] = Default::default();
         for (i, texture) in textures.into_iter().enumerate() {
-            setting[i] = Some(texture);
+            setting[i] = texture.into();
         }
         ComposedSetting(self, TextureListSetting { textures: setting })
     }
 
+    fn cube_map(self, index: u32, cube_map: CubeMap) -> ComposedSetting<Self, CubeMapSetting> {
+        ComposedSetting(
+            self,
+            CubeMapSetting {
+                index,
+                cube_map: Some(cube_map),
+            },
+        )
+    }
+
+    /// Cube maps bound to the units in order, `None` leaves the unit without a cube map.
+    fn cube_map_list<T: IntoIterator<Item = I>, I: Into<Option<CubeMap>>>(
+        self,
+        cube_maps: T,
+    ) -> ComposedSetting<Self, CubeMapListSetting> {
+        let mut setting: [Option<CubeMap>; 16] = Default::default();
+        for (i, cube_map) in cube_maps.into_iter().enumerate() {
+            setting[i] = cube_map.into();
+        }
+        ComposedSetting(self, CubeMapListSetting { cube_maps: setting })
+    }
+
     fn texture_filter(
         self,
         texture: Texture,
@@ -247,6 +620,10 @@ where
         ComposedSetting(self, ClearDepthSetting { value })
     }
 
+    fn clear_stencil(self, value: i32) -> ComposedSetting<Self, ClearStencilSetting> {
+        ComposedSetting(self, ClearStencilSetting { value })
+    }
+
     fn viewport(
         self,
         x: i32,
@@ -265,6 +642,51 @@ where
         )
     }
 
+    /// Viewport covering a part of the current viewport, coordinates are fractions
+    /// of its size with the origin at the bottom left corner, like in GL.
+    /// The current viewport is the one set by an enclosing [Settings::viewport].
+    fn viewport_fraction(
+        self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> ComposedSetting<Self, ViewportFraction> {
+        ComposedSetting(
+            self,
+            ViewportFraction {
+                x,
+                y,
+                width,
+                height,
+            },
+        )
+    }
+
+    /// Enable scissor test limiting drawing and clearing to the area
+    fn scissor(
+        self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> ComposedSetting<Self, ScissorSetting> {
+        ComposedSetting(
+            self,
+            ScissorSetting {
+                enabled: true,
+                x,
+                y,
+                width,
+                height,
+            },
+        )
+    }
+
+    fn no_scissor(self) -> ComposedSetting<Self, ScissorSetting> {
+        ComposedSetting(self, ScissorSetting::default())
+    }
+
     fn depth_buffer(self, buffer: DepthBuffer) -> ComposedSetting<Self, DepthBufferSetting> {
         ComposedSetting(
             self,
@@ -290,6 +712,86 @@ where
     fn color_mask(self, r: bool, g: bool, b: bool, a: bool) -> ComposedSetting<Self, ColorMask> {
         ComposedSetting(self, ColorMask(r, g, b, a))
     }
+
+    fn depth_mask(self, value: bool) -> ComposedSetting<Self, DepthMask> {
+        ComposedSetting(self, DepthMask(value))
+    }
+
+    fn stencil_test(self, value: bool) -> ComposedSetting<Self, StencilTestSetting> {
+        ComposedSetting(self, StencilTestSetting(value))
+    }
+
+    fn stencil_function(
+        self,
+        function: DepthFunction,
+        reference: i32,
+        mask: u32,
+    ) -> ComposedSetting<Self, StencilFunction> {
+        ComposedSetting(
+            self,
+            StencilFunction {
+                function,
+                reference,
+                mask,
+            },
+        )
+    }
+
+    fn stencil_operation(
+        self,
+        fail: StencilOperation,
+        depth_fail: StencilOperation,
+        pass: StencilOperation,
+    ) -> ComposedSetting<Self, StencilOperationSetting> {
+        ComposedSetting(
+            self,
+            StencilOperationSetting {
+                fail,
+                depth_fail,
+                pass,
+            },
+        )
+    }
+
+    fn stencil_mask(self, mask: u32) -> ComposedSetting<Self, StencilMask> {
+        ComposedSetting(self, StencilMask(mask))
+    }
+
+    /// Unpacking of the images, videos and bytes uploaded into textures.
+    fn pixel_store(
+        self,
+        flip_y: bool,
+        premultiply_alpha: bool,
+    ) -> ComposedSetting<Self, PixelStore> {
+        ComposedSetting(
+            self,
+            PixelStore {
+                flip_y,
+                premultiply_alpha,
+            },
+        )
+    }
+
+    fn unpack_alignment(self, alignment: i32) -> ComposedSetting<Self, UnpackAlignment> {
+        ComposedSetting(self, UnpackAlignment(alignment))
+    }
+}
+
+/// What happens to the settings after the [Gl::apply] callback, see [Gl::set_apply_strategy].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ApplyStrategy {
+    /// Roll the previous values back, so the code after a nested `apply` sees the outer settings
+    #[default]
+    Restore,
+    /// Keep the values, the next `apply` changes only the ones which differ. It saves the rollback
+    /// calls in frames of many sequential scopes, but the code after a nested `apply` sees its settings,
+    /// so each draw should apply all the settings it depends on.
+    Diff,
+}
+
+/// Whether the settings should be rolled back after the callback.
+fn restores(gl: &Gl) -> bool {
+    gl.apply_strategy() == ApplyStrategy::Restore
 }
 
 pub trait CachedSettings {
@@ -315,11 +817,14 @@ where
         return if self == &old_value {
             callback()
         } else {
+            trace_event!(TRACE, value = ?self, previous = ?old_value, "apply setting");
             Self::write_cached(&mut cache.borrow_mut(), self);
             Self::set(gl, self);
             let result = callback();
-            Self::set(gl, &old_value);
-            Self::write_cached(&mut cache.borrow_mut(), &old_value);
+            if restores(gl) {
+                Self::set(gl, &old_value);
+                Self::write_cached(&mut cache.borrow_mut(), &old_value);
+            }
             result
         };
     }
@@ -350,17 +855,17 @@ impl<S1: Settings, S2: Settings> Settings for ComposedSetting<S1, S2> {
 }
 #[derive(Default, PartialEq, Debug, Clone, Copy)]
 pub struct ClearColorSetting {
-    color: [f32; 4],
+    pub color: [f32; 4],
 }
 
 impl CachedSettings for ClearColorSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().clear_color(
-            value.color[0],
-            value.color[1],
-            value.color[2],
-            value.color[3],
-        );
+        gl.execute(Command::ClearColor {
+            r: value.color[0],
+            g: value.color[1],
+            b: value.color[2],
+            a: value.color[3],
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -372,14 +877,20 @@ impl CachedSettings for ClearColorSetting {
     }
 }
 
-#[derive(Default, PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct ClearDepthSetting {
-    value: f32,
+    pub value: f32,
+}
+
+impl Default for ClearDepthSetting {
+    fn default() -> Self {
+        ClearDepthSetting { value: 1.0 }
+    }
 }
 
 impl CachedSettings for ClearDepthSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().clear_depth(value.value);
+        gl.execute(Command::ClearDepth { value: value.value });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -391,6 +902,25 @@ impl CachedSettings for ClearDepthSetting {
     }
 }
 
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ClearStencilSetting {
+    pub value: i32,
+}
+
+impl CachedSettings for ClearStencilSetting {
+    fn set(gl: &Gl, value: &Self) {
+        gl.execute(Command::ClearStencil { value: value.value });
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.clear_stencil
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.clear_stencil = *value;
+    }
+}
+
 #[derive(Default, PartialEq, Debug, Clone, Copy)]
 pub struct ViewportSetting {
     pub x: i32,
@@ -401,8 +931,12 @@ pub struct ViewportSetting {
 
 impl CachedSettings for ViewportSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context()
-            .viewport(value.x, value.y, value.width, value.height);
+        gl.execute(Command::Viewport {
+            x: value.x,
+            y: value.y,
+            width: value.width,
+            height: value.height,
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -414,12 +948,105 @@ impl CachedSettings for ViewportSetting {
     }
 }
 
+impl ViewportSetting {
+    /// Part of the viewport, coordinates are fractions of its size from the bottom left corner.
+    /// Edges are rounded, so adjacent fractions share edges without gaps or overlaps.
+    pub fn fraction(&self, x: f32, y: f32, width: f32, height: f32) -> ViewportSetting {
+        let left = self.x + (x * self.width as f32).round() as i32;
+        let right = self.x + ((x + width) * self.width as f32).round() as i32;
+        let bottom = self.y + (y * self.height as f32).round() as i32;
+        let top = self.y + ((y + height) * self.height as f32).round() as i32;
+        ViewportSetting {
+            x: left,
+            y: bottom,
+            width: right - left,
+            height: top - bottom,
+        }
+    }
+
+    /// Split the viewport into a grid of cells, ordered by rows from the top left corner.
+    pub fn split(&self, columns: u32, rows: u32) -> Vec<ViewportSetting> {
+        let (cell_width, cell_height) = (1.0 / columns as f32, 1.0 / rows as f32);
+        (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| {
+                    self.fraction(
+                        column as f32 * cell_width,
+                        (rows - row - 1) as f32 * cell_height,
+                        cell_width,
+                        cell_height,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Viewport relative to the current one, see [Settings::viewport_fraction].
+#[derive(Default, PartialEq, Debug, Clone, Copy)]
+pub struct ViewportFraction {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Settings for ViewportFraction {
+    fn apply<R, F: FnOnce() -> R>(
+        &self,
+        gl: &Gl,
+        cache: &RefCell<SettingsCache>,
+        callback: F,
+    ) -> R {
+        let viewport = ViewportSetting::read_cached(&cache.borrow()).fraction(
+            self.x,
+            self.y,
+            self.width,
+            self.height,
+        );
+        viewport.apply(gl, cache, callback)
+    }
+}
+
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ScissorSetting {
+    pub enabled: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl CachedSettings for ScissorSetting {
+    fn set(gl: &Gl, value: &Self) {
+        set_capability(gl, Context::SCISSOR_TEST, value.enabled);
+        if value.enabled {
+            gl.execute(Command::Scissor {
+                x: value.x,
+                y: value.y,
+                width: value.width,
+                height: value.height,
+            });
+        }
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.scissor
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.scissor = *value;
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct ActiveTextureSetting(u32);
 
 impl CachedSettings for ActiveTextureSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().active_texture(value.0 + Context::TEXTURE0);
+        gl.execute(Command::ActiveTexture {
+            unit: value.0 + Context::TEXTURE0,
+        });
     }
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
         cache.active_texture
@@ -434,10 +1061,14 @@ pub struct ArrayBufferSetting(Option<ArrayBuffer>);
 
 impl CachedSettings for ArrayBufferSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().bind_buffer(
-            Context::ARRAY_BUFFER,
-            value.0.as_ref().map(|v| v.handle()).as_ref(),
-        );
+        gl.execute(Command::BindBuffer {
+            target: Context::ARRAY_BUFFER,
+            buffer: value
+                .0
+                .as_ref()
+                .filter(|v| gl.is_own(&v.gl(), *v))
+                .map(|v| v.id()),
+        });
     }
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
         cache.array_buffer.clone()
@@ -452,10 +1083,14 @@ pub struct ElementBufferSetting(Option<ElementsBuffer>);
 
 impl CachedSettings for ElementBufferSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().bind_buffer(
-            Context::ELEMENT_ARRAY_BUFFER,
-            value.0.as_ref().map(|v| v.handle()).as_ref(),
-        );
+        gl.execute(Command::BindBuffer {
+            target: Context::ELEMENT_ARRAY_BUFFER,
+            buffer: value
+                .0
+                .as_ref()
+                .filter(|v| gl.is_own(&v.gl(), *v))
+                .map(|v| v.id()),
+        });
     }
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
         cache.element_buffer.clone()
@@ -470,11 +1105,7 @@ pub struct BlendSetting(bool);
 
 impl CachedSettings for BlendSetting {
     fn set(gl: &Gl, value: &Self) {
-        if value.0 {
-            gl.context().enable(Context::BLEND)
-        } else {
-            gl.context().disable(Context::BLEND)
-        }
+        set_capability(gl, Context::BLEND, value.0);
     }
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
         cache.blend
@@ -489,11 +1120,7 @@ pub struct DepthTestSetting(bool);
 
 impl CachedSettings for DepthTestSetting {
     fn set(gl: &Gl, value: &Self) {
-        if value.0 {
-            gl.context().enable(Context::DEPTH_TEST)
-        } else {
-            gl.context().disable(Context::DEPTH_TEST)
-        }
+        set_capability(gl, Context::DEPTH_TEST, value.0);
     }
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
         cache.depth
@@ -503,6 +1130,36 @@ impl CachedSettings for DepthTestSetting {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullSetting(bool);
+
+impl CachedSettings for CullSetting {
+    fn set(gl: &Gl, value: &Self) {
+        set_capability(gl, Context::CULL_FACE, value.0);
+    }
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.cull
+    }
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.cull = *value;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StencilTestSetting(bool);
+
+impl CachedSettings for StencilTestSetting {
+    fn set(gl: &Gl, value: &Self) {
+        set_capability(gl, Context::STENCIL_TEST, value.0);
+    }
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.stencil
+    }
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.stencil = *value;
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct TextureSetting {
     index: u32,
@@ -512,8 +1169,12 @@ pub struct TextureSetting {
 impl TextureSetting {
     pub(self) fn set_texture(gl: &Gl, index: u32, texture: Option<&Texture>) {
         gl.apply(Gl::settings().active_texture(index), || {
-            gl.context()
-                .bind_texture(Context::TEXTURE_2D, texture.map(|texture| texture.handle()));
+            gl.execute(Command::BindTexture {
+                target: Context::TEXTURE_2D,
+                texture: texture
+                    .filter(|texture| gl.is_own(&texture.gl(), *texture))
+                    .map(|texture| texture.id()),
+            });
         })
     }
 }
@@ -526,11 +1187,21 @@ impl Settings for TextureSetting {
         callback: F,
     ) -> R {
         let previous = cache.borrow().textures[self.index as usize].clone();
+        trace_event!(
+            TRACE,
+            index = self.index,
+            texture = ?self.texture.as_ref().map(|texture| texture.id()),
+            "apply texture setting"
+        );
         cache.borrow_mut().textures[self.index as usize] = self.texture.clone();
-        Self::set_texture(gl, self.index, self.texture.as_ref());
+        if restores(gl) || previous != self.texture {
+            Self::set_texture(gl, self.index, self.texture.as_ref());
+        }
         let result = callback();
-        Self::set_texture(gl, self.index, previous.as_ref());
-        cache.borrow_mut().textures[self.index as usize] = previous;
+        if restores(gl) {
+            Self::set_texture(gl, self.index, previous.as_ref());
+            cache.borrow_mut().textures[self.index as usize] = previous;
+        }
         return result;
     }
 }
@@ -548,7 +1219,7 @@ impl TextureListSetting {
     ) {
         for i in 0..16 {
             if current[i] != target[i] {
-                TextureSetting::set_texture(gl, i.try_into().unwrap(), target[i].as_ref());
+                TextureSetting::set_texture(gl, i as u32, target[i].as_ref());
             }
         }
     }
@@ -562,19 +1233,112 @@ impl Settings for TextureListSetting {
         callback: F,
     ) -> R {
         let previous = cache.borrow().textures.clone();
+        trace_event!(
+            TRACE,
+            textures = ?self.textures.iter().map(|texture| texture.as_ref().map(|texture| texture.id())).collect::<Vec<_>>(),
+            "apply texture list setting"
+        );
 
         cache.borrow_mut().textures = self.textures.clone();
         TextureListSetting::set_textures(gl, &previous, &self.textures);
 
         let result = callback();
 
-        TextureListSetting::set_textures(gl, &self.textures, &previous);
-        cache.borrow_mut().textures = previous;
+        if restores(gl) {
+            TextureListSetting::set_textures(gl, &self.textures, &previous);
+            cache.borrow_mut().textures = previous;
+        }
 
         return result;
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CubeMapSetting {
+    index: u32,
+    cube_map: Option<CubeMap>,
+}
+
+impl CubeMapSetting {
+    pub(self) fn set_cube_map(gl: &Gl, index: u32, cube_map: Option<&CubeMap>) {
+        gl.apply(Gl::settings().active_texture(index), || {
+            gl.execute(Command::BindTexture {
+                target: Context::TEXTURE_CUBE_MAP,
+                texture: cube_map
+                    .filter(|cube_map| gl.is_own(&cube_map.gl(), *cube_map))
+                    .map(|cube_map| cube_map.id()),
+            });
+        })
+    }
+}
+
+impl Settings for CubeMapSetting {
+    fn apply<R, F: FnOnce() -> R>(
+        &self,
+        gl: &Gl,
+        cache: &RefCell<SettingsCache>,
+        callback: F,
+    ) -> R {
+        let previous = cache.borrow().cube_maps[self.index as usize].clone();
+        trace_event!(
+            TRACE,
+            index = self.index,
+            cube_map = ?self.cube_map.as_ref().map(|cube_map| cube_map.id()),
+            "apply cube map setting"
+        );
+        cache.borrow_mut().cube_maps[self.index as usize] = self.cube_map.clone();
+        if restores(gl) || previous != self.cube_map {
+            Self::set_cube_map(gl, self.index, self.cube_map.as_ref());
+        }
+        let result = callback();
+        if restores(gl) {
+            Self::set_cube_map(gl, self.index, previous.as_ref());
+            cache.borrow_mut().cube_maps[self.index as usize] = previous;
+        }
+        result
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CubeMapListSetting {
+    cube_maps: [Option<CubeMap>; 16],
+}
+
+impl CubeMapListSetting {
+    pub(self) fn set_cube_maps(
+        gl: &Gl,
+        current: &[Option<CubeMap>; 16],
+        target: &[Option<CubeMap>; 16],
+    ) {
+        for (i, (current, target)) in current.iter().zip(target.iter()).enumerate() {
+            if current != target {
+                CubeMapSetting::set_cube_map(gl, i as u32, target.as_ref());
+            }
+        }
+    }
+}
+
+impl Settings for CubeMapListSetting {
+    fn apply<R, F: FnOnce() -> R>(
+        &self,
+        gl: &Gl,
+        cache: &RefCell<SettingsCache>,
+        callback: F,
+    ) -> R {
+        let previous = cache.borrow().cube_maps.clone();
+        cache.borrow_mut().cube_maps = self.cube_maps.clone();
+        CubeMapListSetting::set_cube_maps(gl, &previous, &self.cube_maps);
+
+        let result = callback();
+
+        if restores(gl) {
+            CubeMapListSetting::set_cube_maps(gl, &self.cube_maps, &previous);
+            cache.borrow_mut().cube_maps = previous;
+        }
+        result
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TextureFilterSetting {
     texture: Texture,
@@ -592,6 +1356,26 @@ impl Settings for TextureFilterSetting {
     }
 }
 
+fn set_capability(gl: &Gl, capability: u32, enabled: bool) {
+    if enabled {
+        gl.execute(Command::Enable { capability })
+    } else {
+        gl.execute(Command::Disable { capability })
+    }
+}
+
+fn set_attribute_array(gl: &Gl, location: u32, enabled: bool) {
+    if enabled {
+        gl.execute(Command::EnableVertexAttribArray { location })
+    } else {
+        gl.execute(Command::DisableVertexAttribArray { location })
+    }
+}
+
+fn set_attribute_divisor(gl: &Gl, location: u32, divisor: u32) {
+    gl.set_attribute_divisor(location, divisor)
+}
+
 fn array_diff<'a, T: PartialEq>(v1: &'a Vec<T>, v2: &'a Vec<T>) -> impl Iterator<Item = &'a T> {
     v1.iter().filter(move |i| !v2.contains(i))
 }
@@ -608,9 +1392,9 @@ impl Settings for EnabledAttributesSetting {
         cache: &RefCell<SettingsCache>,
         callback: F,
     ) -> R {
-        let context: &Context = gl.context();
         // get old value
         let previous = { cache.borrow().enabled_attributes.clone() };
+        trace_event!(TRACE, attributes = ?self.items, "apply enabled attributes setting");
 
         // set current value
         {
@@ -619,27 +1403,27 @@ impl Settings for EnabledAttributesSetting {
 
         // disable extra attributes
         array_diff(&previous.items, &self.items).for_each(|i| {
-            context.disable_vertex_attrib_array(*i);
+            set_attribute_array(gl, *i, false);
         });
 
         // enable disabled attributes
         array_diff(&self.items, &previous.items).for_each(|i| {
-            context.enable_vertex_attrib_array(*i);
+            set_attribute_array(gl, *i, true);
         });
 
         // do the stuff
         let result = callback();
 
         // rollback changes
-        array_diff(&previous.items, &self.items).for_each(|i| {
-            context.enable_vertex_attrib_array(*i);
-        });
+        if restores(gl) {
+            array_diff(&previous.items, &self.items).for_each(|i| {
+                set_attribute_array(gl, *i, true);
+            });
 
-        array_diff(&self.items, &previous.items).for_each(|i| {
-            context.disable_vertex_attrib_array(*i);
-        });
+            array_diff(&self.items, &previous.items).for_each(|i| {
+                set_attribute_array(gl, *i, false);
+            });
 
-        {
             cache.borrow_mut().enabled_attributes = previous;
         }
 
@@ -659,9 +1443,9 @@ impl Settings for InstancedAttributesSetting {
         cache: &RefCell<SettingsCache>,
         callback: F,
     ) -> R {
-        let context: &AngleInstancedArrays = gl.instanced_arrays();
         // get old value
         let previous = { cache.borrow().instanced_attributes.clone() };
+        trace_event!(TRACE, attributes = ?self.items, "apply instanced attributes setting");
 
         // set current value
         {
@@ -670,27 +1454,27 @@ impl Settings for InstancedAttributesSetting {
 
         // disable instancing
         array_diff(&previous.items, &self.items).for_each(|i| {
-            context.vertex_attrib_divisor_angle(*i, 0);
+            set_attribute_divisor(gl, *i, 0);
         });
 
         // enable instancing
         array_diff(&self.items, &previous.items).for_each(|i| {
-            context.vertex_attrib_divisor_angle(*i, 1);
+            set_attribute_divisor(gl, *i, 1);
         });
 
         // do the stuff
         let result = callback();
 
         // rollback changes
-        array_diff(&previous.items, &self.items).for_each(|i| {
-            context.vertex_attrib_divisor_angle(*i, 1);
-        });
+        if restores(gl) {
+            array_diff(&previous.items, &self.items).for_each(|i| {
+                set_attribute_divisor(gl, *i, 1);
+            });
 
-        array_diff(&self.items, &previous.items).for_each(|i| {
-            context.vertex_attrib_divisor_angle(*i, 0);
-        });
+            array_diff(&self.items, &previous.items).for_each(|i| {
+                set_attribute_divisor(gl, *i, 0);
+            });
 
-        {
             cache.borrow_mut().instanced_attributes = previous;
         }
 
@@ -705,13 +1489,13 @@ pub struct ProgramSetting {
 
 impl CachedSettings for ProgramSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().use_program(
-            value
+        gl.execute(Command::UseProgram {
+            program: value
                 .program
                 .as_ref()
-                .map(|program| program.handle())
-                .as_ref(),
-        );
+                .filter(|program| gl.is_own(&program.gl(), *program))
+                .map(|program| program.id()),
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -730,10 +1514,13 @@ pub struct DepthBufferSetting {
 
 impl CachedSettings for DepthBufferSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().bind_renderbuffer(
-            Context::RENDERBUFFER,
-            value.buffer.as_ref().map(|v| v.handle()),
-        );
+        gl.execute(Command::BindRenderbuffer {
+            renderbuffer: value
+                .buffer
+                .as_ref()
+                .filter(|v| gl.is_own(&v.gl(), *v))
+                .map(|v| v.id()),
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -752,10 +1539,13 @@ pub struct FrameBufferSetting {
 
 impl CachedSettings for FrameBufferSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().bind_framebuffer(
-            Context::FRAMEBUFFER,
-            value.buffer.as_ref().map(|v| v.handle()),
-        );
+        gl.execute(Command::BindFramebuffer {
+            framebuffer: value
+                .buffer
+                .as_ref()
+                .filter(|v| gl.is_own(&v.gl(), *v))
+                .map(|v| v.id()),
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -775,8 +1565,10 @@ pub struct BlendEquationSetting {
 
 impl CachedSettings for BlendEquationSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context()
-            .blend_equation_separate(value.color.into(), value.alpha.into());
+        gl.execute(Command::BlendEquation {
+            color: value.color.into(),
+            alpha: value.alpha.into(),
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -807,14 +1599,31 @@ impl Default for BlendFunctionSetting {
     }
 }
 
+impl BlendFunctionSetting {
+    /// Colors blended over the background, premultiplied colors are added without
+    /// multiplying by alpha again.
+    pub fn alpha(premultiplied: bool) -> BlendFunctionSetting {
+        BlendFunctionSetting {
+            src_rgb: if premultiplied {
+                BlendFunction::One
+            } else {
+                BlendFunction::SrcAlpha
+            },
+            dst_rgb: BlendFunction::OneMinusSrcAlpha,
+            src_alpha: BlendFunction::One,
+            dst_alpha: BlendFunction::OneMinusSrcAlpha,
+        }
+    }
+}
+
 impl CachedSettings for BlendFunctionSetting {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().blend_func_separate(
-            value.src_rgb.into(),
-            value.dst_rgb.into(),
-            value.src_alpha.into(),
-            value.dst_alpha.into(),
-        );
+        gl.execute(Command::BlendFunction {
+            src_rgb: value.src_rgb.into(),
+            dst_rgb: value.dst_rgb.into(),
+            src_alpha: value.src_alpha.into(),
+            dst_alpha: value.dst_alpha.into(),
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -828,7 +1637,9 @@ impl CachedSettings for BlendFunctionSetting {
 
 impl CachedSettings for DepthFunction {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().depth_func((*value).into());
+        gl.execute(Command::DepthFunction {
+            function: (*value).into(),
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -842,7 +1653,9 @@ impl CachedSettings for DepthFunction {
 
 impl CachedSettings for CullFace {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().cull_face((*value).into());
+        gl.execute(Command::CullFace {
+            face: (*value).into(),
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -856,7 +1669,12 @@ impl CachedSettings for CullFace {
 
 impl CachedSettings for ColorMask {
     fn set(gl: &Gl, value: &Self) {
-        gl.context().color_mask(value.0, value.1, value.2, value.3);
+        gl.execute(Command::ColorMask {
+            r: value.0,
+            g: value.1,
+            b: value.2,
+            a: value.3,
+        });
     }
 
     fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
@@ -867,3 +1685,105 @@ impl CachedSettings for ColorMask {
         cache.color_mask = *value;
     }
 }
+
+impl CachedSettings for DepthMask {
+    fn set(gl: &Gl, value: &Self) {
+        gl.execute(Command::DepthMask { value: value.0 });
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.depth_mask
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.depth_mask = *value;
+    }
+}
+
+impl CachedSettings for StencilFunction {
+    fn set(gl: &Gl, value: &Self) {
+        gl.execute(Command::StencilFunction {
+            function: value.function.into(),
+            reference: value.reference,
+            mask: value.mask,
+        });
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.stencil_function
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.stencil_function = *value;
+    }
+}
+
+impl CachedSettings for StencilOperationSetting {
+    fn set(gl: &Gl, value: &Self) {
+        gl.execute(Command::StencilOperation {
+            fail: value.fail.into(),
+            depth_fail: value.depth_fail.into(),
+            pass: value.pass.into(),
+        });
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.stencil_operation
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.stencil_operation = *value;
+    }
+}
+
+impl CachedSettings for StencilMask {
+    fn set(gl: &Gl, value: &Self) {
+        gl.execute(Command::StencilMask { mask: value.0 });
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.stencil_mask
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.stencil_mask = *value;
+    }
+}
+
+impl CachedSettings for PixelStore {
+    fn set(gl: &Gl, value: &Self) {
+        gl.execute(Command::PixelStore {
+            parameter: Context::UNPACK_FLIP_Y_WEBGL,
+            value: value.flip_y.into(),
+        });
+        gl.execute(Command::PixelStore {
+            parameter: Context::UNPACK_PREMULTIPLY_ALPHA_WEBGL,
+            value: value.premultiply_alpha.into(),
+        });
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.pixel_store
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.pixel_store = *value;
+    }
+}
+
+impl CachedSettings for UnpackAlignment {
+    fn set(gl: &Gl, value: &Self) {
+        gl.execute(Command::PixelStore {
+            parameter: Context::UNPACK_ALIGNMENT,
+            value: value.0,
+        });
+    }
+
+    fn read_cached(cache: &impl Deref<Target = SettingsCache>) -> Self {
+        cache.unpack_alignment
+    }
+
+    fn write_cached(cache: &mut impl DerefMut<Target = SettingsCache>, value: &Self) {
+        cache.unpack_alignment = *value;
+    }
+}
@@ -0,0 +1,155 @@
+use crate::matrix::{multiply, IDENTITY};
+use crate::uniforms::{Field, Uniforms};
+use crate::{
+    ClearParams, DepthFunction, FrameBuffer, Gl, GlError, IntoUniform, Settings, Texture,
+    TextureContent, TextureFilter, TextureFormat, TextureType, UniformValue,
+};
+
+/// Maps clip space coordinates from `[-1, 1]` to texture coordinates and depth in `[0, 1]`.
+pub const SHADOW_BIAS_MATRIX: [f32; 16] = [
+    0.5, 0.0, 0.0, 0.0, //
+    0.0, 0.5, 0.0, 0.0, //
+    0.0, 0.0, 0.5, 0.0, //
+    0.5, 0.5, 0.5, 1.0,
+];
+
+/// Depth render target for shadow mapping.
+///
+/// The scene is drawn from the light point of view inside [ShadowMap::depth_pass],
+/// then the main pass samples the depth texture using [ShadowMap::uniforms]:
+///
+/// * `u_light_matrix: mat4` - light view projection, for the depth pass vertex shader
+/// * `u_shadow_matrix: mat4` - light view projection followed by the bias matrix,
+///   maps world positions to shadow map coordinates and depth
+/// * `u_shadow_map: sampler2D` - depth texture
+/// * `u_shadow_map_size: vec2` - size of the depth texture in pixels
+///
+/// Matrices are column-major, like GLSL ones. Requires depth texture support.
+#[derive(Clone, Debug)]
+pub struct ShadowMap {
+    gl: Gl,
+    frame_buffer: FrameBuffer,
+    depth: Texture,
+    light_matrix: [f32; 16],
+}
+
+impl ShadowMap {
+    pub fn new(gl: Gl, width: u32, height: u32) -> Result<ShadowMap, GlError> {
+        // WebGL1 frame buffers need a color attachment to be complete
+        let color = gl.texture(
+            width,
+            height,
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::None,
+        )?;
+        let depth = gl.texture(
+            width,
+            height,
+            TextureType::Int,
+            TextureFormat::Depth,
+            TextureContent::None,
+        )?;
+        depth.set_filter(TextureFilter::Nearest);
+        let frame_buffer = gl.frame_buffer_with_depth_texture(color, depth.clone())?;
+        Ok(ShadowMap {
+            gl,
+            frame_buffer,
+            depth,
+            light_matrix: IDENTITY,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.depth.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.depth.height()
+    }
+
+    pub fn depth_texture(&self) -> Texture {
+        self.depth.clone()
+    }
+
+    pub fn frame_buffer(&self) -> FrameBuffer {
+        self.frame_buffer.clone()
+    }
+
+    pub fn light_matrix(&self) -> [f32; 16] {
+        self.light_matrix
+    }
+
+    /// Light view projection matrix, column-major.
+    pub fn set_light_matrix(&mut self, matrix: [f32; 16]) {
+        self.light_matrix = matrix;
+    }
+
+    /// Light view projection followed by the bias matrix.
+    pub fn shadow_matrix(&self) -> [f32; 16] {
+        multiply(&SHADOW_BIAS_MATRIX, &self.light_matrix)
+    }
+
+    /// Clear the depth texture and draw into it, only depth is written.
+    pub fn depth_pass<R>(&self, callback: impl FnOnce() -> R) -> R {
+        let gl = &self.gl;
+        gl.apply(
+            Gl::settings()
+                .frame_buffer(self.frame_buffer.clone())
+                .viewport(0, 0, self.width() as i32, self.height() as i32)
+                .depth_test(true)
+                .depth_function(DepthFunction::Less)
+                .depth_mask(true)
+                .color_mask(false, false, false, false),
+            || {
+                gl.clear(ClearParams {
+                    depth: Some(1.0),
+                    ..Default::default()
+                });
+                callback()
+            },
+        )
+    }
+
+    pub fn uniforms(&self) -> ShadowMapUniforms {
+        ShadowMapUniforms {
+            light_matrix: self.light_matrix,
+            shadow_matrix: self.shadow_matrix(),
+            shadow_map: self.depth.clone(),
+        }
+    }
+}
+
+/// Uniforms of a [ShadowMap], combine them with the scene ones using a tuple.
+#[derive(Clone, Debug)]
+pub struct ShadowMapUniforms {
+    pub light_matrix: [f32; 16],
+    pub shadow_matrix: [f32; 16],
+    pub shadow_map: Texture,
+}
+
+impl Uniforms for ShadowMapUniforms {
+    fn uniforms(&self) -> Vec<Field> {
+        vec![
+            Field {
+                name: "u_light_matrix".into(),
+                value: UniformValue::Mat4(self.light_matrix),
+            },
+            Field {
+                name: "u_shadow_matrix".into(),
+                value: UniformValue::Mat4(self.shadow_matrix),
+            },
+            Field {
+                name: "u_shadow_map".into(),
+                value: self.shadow_map.into_uniform(),
+            },
+            Field {
+                name: "u_shadow_map_size".into(),
+                value: UniformValue::Vec2([
+                    self.shadow_map.width() as f32,
+                    self.shadow_map.height() as f32,
+                ]),
+            },
+        ]
+    }
+}
@@ -0,0 +1,114 @@
+use std::cell::Cell;
+
+use crate::post::ScreenPass;
+use crate::uniforms::Uniforms;
+use crate::{
+    FrameBuffer, Gl, GlError, Texture, TextureContent, TextureFilter, TextureFormat, TextureType,
+    Uniforms,
+};
+
+#[derive(Clone, Uniforms)]
+struct StateUniforms {
+    state: Texture,
+    state_size: [f32; 2],
+}
+
+/// State of a simulation updated on the GPU, like cellular automata, particles or fluids.
+///
+/// The state is a pair of RGBA textures, each step draws the step shader into one of them
+/// reading the other one, then they're swapped. The step shader is a fragment shader
+/// of a [ScreenPass] receiving the current state in `uniform sampler2D u_state`,
+/// its size in `uniform vec2 u_state_size` and the texel coordinates in `varying vec2 v_uv`.
+/// Textures are sampled with the nearest filter and clamped, wrap the coordinates
+/// in the shader for a toroidal world.
+#[derive(Debug)]
+pub struct IterativeSimulation {
+    gl: Gl,
+    pass: ScreenPass,
+    states: [(FrameBuffer, Texture); 2],
+    current: Cell<usize>,
+    iterations: Cell<u64>,
+}
+
+impl IterativeSimulation {
+    /// Simulation of `width × height` texels, the state is filled with zeros.
+    /// The data type should be color renderable, see [Gl::render_precision].
+    pub fn new(
+        gl: Gl,
+        width: u32,
+        height: u32,
+        data_type: TextureType,
+        step_shader: &str,
+    ) -> Result<IterativeSimulation, GlError> {
+        let state = || -> Result<(FrameBuffer, Texture), GlError> {
+            let texture = gl.texture(
+                width,
+                height,
+                data_type,
+                TextureFormat::Rgba,
+                TextureContent::None,
+            )?;
+            texture.set_filter(TextureFilter::Nearest);
+            Ok((gl.frame_buffer_with_color(texture.clone())?, texture))
+        };
+        Ok(IterativeSimulation {
+            pass: ScreenPass::new(gl.clone(), step_shader)?,
+            states: [state()?, state()?],
+            current: Cell::new(0),
+            iterations: Cell::new(0),
+            gl,
+        })
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.gl.clone()
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.texture().size()
+    }
+
+    /// Texture of the current state, write into it to set the initial state.
+    pub fn texture(&self) -> Texture {
+        self.states[self.current.get()].1.clone()
+    }
+
+    /// Number of steps done since the creation or the last [reset](IterativeSimulation::reset).
+    pub fn iterations(&self) -> u64 {
+        self.iterations.get()
+    }
+
+    /// Zero the iterations counter, the state is kept.
+    pub fn reset(&self) {
+        self.iterations.set(0);
+    }
+
+    /// Advance the state by one step, `uniforms` are passed to the step shader
+    /// in addition to the state ones.
+    pub fn step<U: Uniforms + ?Sized>(&self, uniforms: &U) -> Result<(), GlError> {
+        let current = self.current.get();
+        let (width, height) = self.size();
+        let (target, _) = &self.states[1 - current];
+        self.pass.draw(
+            Some(target),
+            &(
+                StateUniforms {
+                    state: self.texture(),
+                    state_size: [width as f32, height as f32],
+                },
+                uniforms,
+            ),
+        )?;
+        self.current.set(1 - current);
+        self.iterations.set(self.iterations.get() + 1);
+        Ok(())
+    }
+
+    /// Advance the state by `count` steps with the same uniforms.
+    pub fn steps<U: Uniforms + ?Sized>(&self, count: u32, uniforms: &U) -> Result<(), GlError> {
+        for _ in 0..count {
+            self.step(uniforms)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use crate::matrix::{inverse, multiply, rotation, Mat4};
+use crate::uniforms::{Field, IntoUniform, UniformValue, Uniforms};
+use crate::{
+    Attributes, BufferUsage, CubeFace, CubeMap, DepthFunction, Gl, GlError, ItemsBuffer,
+    PrimitiveType, Program, Settings, Texture, TextureFilter, TextureFormat,
+};
+
+const VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    uniform mat4 u_inverse_view_projection;
+    varying vec3 v_direction;
+    void main() {
+        vec4 far = u_inverse_view_projection * vec4(a_position, 1.0, 1.0);
+        v_direction = far.xyz / far.w;
+        gl_Position = vec4(a_position, 1.0, 1.0);
+    }
+"#;
+
+const CUBE_MAP_SHADER: &str = r#"
+    precision mediump float;
+    uniform samplerCube u_environment;
+    varying vec3 v_direction;
+    void main() {
+        gl_FragColor = textureCube(u_environment, v_direction);
+    }
+"#;
+
+// Shared by the skybox and the cube map conversion, so both show the same image
+const EQUIRECTANGULAR_UV: &str = r#"
+    vec2 equirectangular_uv(vec3 direction) {
+        direction = normalize(direction);
+        return vec2(
+            atan(direction.z, direction.x) / (2.0 * 3.14159265) + 0.5,
+            asin(clamp(direction.y, -1.0, 1.0)) / 3.14159265 + 0.5
+        );
+    }
+"#;
+
+const EQUIRECTANGULAR_SHADER: &str = r#"
+    uniform sampler2D u_environment;
+    varying vec3 v_direction;
+    void main() {
+        gl_FragColor = texture2D(u_environment, equirectangular_uv(v_direction));
+    }
+"#;
+
+const FACE_VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    uniform vec3 u_forward;
+    uniform vec3 u_right;
+    uniform vec3 u_up;
+    varying vec3 v_direction;
+    void main() {
+        v_direction = u_forward + a_position.x * u_right + a_position.y * u_up;
+        gl_Position = vec4(a_position, 0.0, 1.0);
+    }
+"#;
+
+fn equirectangular_shader(main: &str) -> String {
+    format!("precision highp float;\n{}{}", EQUIRECTANGULAR_UV, main)
+}
+
+#[derive(Clone, Copy, Debug, Attributes)]
+struct SkyboxAttributes {
+    position: [f32; 2],
+}
+
+fn quad(gl: &Gl) -> Result<ItemsBuffer<SkyboxAttributes>, GlError> {
+    gl.items_buffer(
+        &[
+            SkyboxAttributes {
+                position: [-1.0, -1.0],
+            },
+            SkyboxAttributes {
+                position: [1.0, -1.0],
+            },
+            SkyboxAttributes {
+                position: [-1.0, 1.0],
+            },
+            SkyboxAttributes {
+                position: [1.0, 1.0],
+            },
+        ],
+        BufferUsage::Static,
+    )
+}
+
+#[derive(Clone, crate::Uniforms)]
+struct FaceUniforms {
+    environment: Texture,
+    forward: [f32; 3],
+    right: [f32; 3],
+    up: [f32; 3],
+}
+
+/// Texture drawn as the background around the scene.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Environment {
+    CubeMap(CubeMap),
+    /// Longitude along the x axis and latitude along the y axis, `+Y` is up
+    Equirectangular(Texture),
+}
+
+impl From<CubeMap> for Environment {
+    fn from(cube_map: CubeMap) -> Self {
+        Environment::CubeMap(cube_map)
+    }
+}
+
+impl From<Texture> for Environment {
+    fn from(texture: Texture) -> Self {
+        Environment::Equirectangular(texture)
+    }
+}
+
+struct SkyboxUniforms<'a> {
+    inverse_view_projection: Mat4,
+    environment: &'a Environment,
+}
+
+impl Uniforms for SkyboxUniforms<'_> {
+    fn uniforms(&self) -> Vec<Field> {
+        vec![
+            Field {
+                name: "u_inverse_view_projection".into(),
+                value: UniformValue::Mat4(self.inverse_view_projection),
+            },
+            Field {
+                name: "u_environment".into(),
+                value: match self.environment {
+                    Environment::CubeMap(cube_map) => cube_map.into_uniform(),
+                    Environment::Equirectangular(texture) => texture.into_uniform(),
+                },
+            },
+        ]
+    }
+}
+
+/// Environment background drawn with a full screen quad at the maximum depth.
+///
+/// Draw it after the opaque geometry, so it's covered by the scene and only the
+/// visible pixels are shaded. Matrices are column-major, like GLSL ones,
+/// the translation of the view matrix is ignored.
+#[derive(Clone, Debug)]
+pub struct Skybox {
+    gl: Gl,
+    cube_map_program: Program,
+    equirectangular_program: Program,
+    quad: ItemsBuffer<SkyboxAttributes>,
+    environment: Environment,
+}
+
+impl Skybox {
+    pub fn new(gl: Gl, environment: impl Into<Environment>) -> Result<Skybox, GlError> {
+        Ok(Skybox {
+            cube_map_program: gl.program(CUBE_MAP_SHADER, VERTEX_SHADER)?,
+            equirectangular_program: gl.program(
+                &equirectangular_shader(EQUIRECTANGULAR_SHADER),
+                VERTEX_SHADER,
+            )?,
+            quad: quad(&gl)?,
+            gl,
+            environment: environment.into(),
+        })
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn set_environment(&mut self, environment: impl Into<Environment>) {
+        self.environment = environment.into();
+    }
+
+    /// Draw into the current frame buffer, passes where the depth buffer is still cleared.
+    pub fn draw(&self, view: &[f32; 16], projection: &[f32; 16]) -> Result<(), GlError> {
+        let inverse_view_projection =
+            inverse(&multiply(projection, &rotation(view))).ok_or_else(|| {
+                GlError::UnknownError(Some("Skybox view projection is not invertible".into()))
+            })?;
+        let program = match self.environment {
+            Environment::CubeMap(_) => &self.cube_map_program,
+            Environment::Equirectangular(_) => &self.equirectangular_program,
+        };
+        self.gl.apply(
+            Gl::settings()
+                .depth_test(true)
+                .depth_function(DepthFunction::LEqual)
+                .depth_mask(false)
+                .blend(false)
+                .cull(false),
+            || {
+                program.draw_arrays(
+                    PrimitiveType::TriangleStrip,
+                    &SkyboxUniforms {
+                        inverse_view_projection,
+                        environment: &self.environment,
+                    },
+                    &self.quad,
+                )
+            },
+        )
+    }
+}
+
+impl CubeMap {
+    /// Render an equirectangular environment into the faces of a new cube map,
+    /// with the same orientation as [Skybox] uses for equirectangular textures.
+    ///
+    /// The cube map has RGBA format and the data type of the source, so float and
+    /// half-float sources need color buffer float support to be rendered into.
+    pub fn from_equirectangular(source: &Texture, size: u32) -> Result<CubeMap, GlError> {
+        let gl = source.gl();
+        let cube_map = gl.cube_map(size, source.data_type(), TextureFormat::Rgba)?;
+        let program = gl.program(
+            &equirectangular_shader(EQUIRECTANGULAR_SHADER),
+            FACE_VERTEX_SHADER,
+        )?;
+        let quad = quad(&gl)?;
+        let mut frame_buffer = gl.frame_buffer()?;
+        for face in CubeFace::ALL {
+            frame_buffer.set_color_cube_face(cube_map.clone(), face)?;
+            frame_buffer.check_status()?;
+            let [forward, right, up] = face.axes();
+            gl.apply(
+                Gl::settings()
+                    .frame_buffer(frame_buffer.clone())
+                    .viewport(0, 0, size as i32, size as i32)
+                    .depth_test(false)
+                    .blend(false)
+                    .cull(false)
+                    .color_mask(true, true, true, true)
+                    .texture_filter(source.clone(), TextureFilter::Linear),
+                || {
+                    program.draw_arrays(
+                        PrimitiveType::TriangleStrip,
+                        &FaceUniforms {
+                            environment: source.clone(),
+                            forward,
+                            right,
+                            up,
+                        },
+                        &quad,
+                    )
+                },
+            )?;
+        }
+        Ok(cube_map)
+    }
+}
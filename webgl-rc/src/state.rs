@@ -0,0 +1,129 @@
+//! Diagnostics of the GL state, see [Gl::dump_state](crate::Gl::dump_state).
+
+use std::fmt;
+
+/// Value of a GL state parameter, enum values are GL constants,
+/// bound objects are referenced by their ids.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StateValue {
+    Bool(bool),
+    Int(i64),
+    Enum(u32),
+    Float(f32),
+    Bools(Vec<bool>),
+    Ints(Vec<i32>),
+    Floats(Vec<f32>),
+    Object(Option<u64>),
+}
+
+impl fmt::Display for StateValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateValue::Bool(value) => write!(f, "{}", value),
+            StateValue::Int(value) => write!(f, "{}", value),
+            StateValue::Enum(value) => write!(f, "0x{:04X}", value),
+            StateValue::Float(value) => write!(f, "{}", value),
+            StateValue::Bools(values) => write!(f, "{:?}", values),
+            StateValue::Ints(values) => write!(f, "{:?}", values),
+            StateValue::Floats(values) => write!(f, "{:?}", values),
+            StateValue::Object(Some(id)) => write!(f, "#{}", id),
+            StateValue::Object(None) => f.write_str("null"),
+        }
+    }
+}
+
+/// Cached value of a state parameter, and the value queried from GL if it's available.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateEntry {
+    /// GL parameter name, with the texture unit for texture bindings
+    pub name: String,
+    pub cached: StateValue,
+    /// `None` if the backend can't query the parameter,
+    /// or the bound object isn't created by this [Gl](crate::Gl)
+    pub actual: Option<StateValue>,
+}
+
+impl StateEntry {
+    pub fn is_mismatch(&self) -> bool {
+        matches!(&self.actual, Some(actual) if *actual != self.cached)
+    }
+}
+
+/// Snapshot of the settings cache compared with the real GL state.
+///
+/// Displayed as a table, mismatching parameters are marked with `!`
+/// and the ones which couldn't be queried with `?`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateDump {
+    pub entries: Vec<StateEntry>,
+}
+
+impl StateDump {
+    pub fn mismatches(&self) -> impl Iterator<Item = &StateEntry> {
+        self.entries.iter().filter(|entry| entry.is_mismatch())
+    }
+
+    pub fn has_mismatches(&self) -> bool {
+        self.mismatches().next().is_some()
+    }
+}
+
+impl fmt::Display for StateDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .entries
+            .iter()
+            .map(|entry| entry.name.len())
+            .max()
+            .unwrap_or(0);
+        for entry in &self.entries {
+            match &entry.actual {
+                Some(actual) if entry.is_mismatch() => writeln!(
+                    f,
+                    "! {:width$}  {} (actual {})",
+                    entry.name,
+                    entry.cached,
+                    actual,
+                    width = width
+                )?,
+                Some(_) => writeln!(
+                    f,
+                    "  {:width$}  {}",
+                    entry.name,
+                    entry.cached,
+                    width = width
+                )?,
+                None => writeln!(
+                    f,
+                    "? {:width$}  {}",
+                    entry.name,
+                    entry.cached,
+                    width = width
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parameter of the settings cache to compare with the GL state.
+#[derive(Clone, Debug)]
+pub(crate) struct CachedParameter {
+    pub name: String,
+    /// `getParameter` name, `None` for the state which can't be queried this way
+    pub parameter: Option<u32>,
+    /// Texture unit to make active before the query
+    pub texture_unit: Option<u32>,
+    pub value: StateValue,
+}
+
+impl CachedParameter {
+    pub fn new(name: &str, parameter: u32, value: StateValue) -> CachedParameter {
+        CachedParameter {
+            name: name.into(),
+            parameter: Some(parameter),
+            texture_unit: None,
+            value,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Helpers for testing rendering code in a browser with `wasm-bindgen-test`.
+//!
+//! Render the scene into an off-screen target and compare the result with a reference image:
+//!
+//! ```ignore
+//! use wasm_bindgen_test::*;
+//! use webgl_rc::testing::{self, Snapshot};
+//! use webgl_rc::ClearParams;
+//!
+//! wasm_bindgen_test_configure!(run_in_browser);
+//!
+//! #[wasm_bindgen_test]
+//! fn clear_red() {
+//!     let gl = testing::context(2, 2).unwrap();
+//!     let snapshot = testing::render(&gl, 2, 2, || {
+//!         gl.clear(ClearParams { color: Some([1.0, 0.0, 0.0, 1.0]), ..Default::default() });
+//!     })
+//!     .unwrap();
+//!     snapshot.assert_matches(&Snapshot::filled(2, 2, [255, 0, 0, 255]), 0);
+//! }
+//! ```
+
+use std::fmt;
+
+use js_sys::JsString;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, HtmlImageElement};
+
+use crate::{Gl, GlError, Settings, TextureContent, TextureFormat, TextureType};
+
+/// Create a canvas which isn't attached to the document.
+pub fn canvas(width: u32, height: u32) -> Result<HtmlCanvasElement, GlError> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| GlError::UnknownError(Some("Document isn't available".into())))?;
+    let canvas: HtmlCanvasElement = document
+        .create_element("canvas")
+        .map_err(|e| GlError::UnknownError(Some(JsString::from(e).into())))?
+        .dyn_into()
+        .map_err(|_| GlError::UnknownError(Some("Canvas creation failed".into())))?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    Ok(canvas)
+}
+
+/// Create a context on a hidden canvas.
+pub fn context(width: u32, height: u32) -> Result<Gl, GlError> {
+    Gl::new(&canvas(width, height)?)
+}
+
+/// Render into an off-screen RGBA target and read the result.
+pub fn render(
+    gl: &Gl,
+    width: u32,
+    height: u32,
+    callback: impl FnOnce(),
+) -> Result<Snapshot, GlError> {
+    let target = gl.texture(
+        width,
+        height,
+        TextureType::Byte,
+        TextureFormat::Rgba,
+        TextureContent::None,
+    )?;
+    gl.apply(
+        Gl::settings()
+            .frame_buffer(gl.frame_buffer_with_color(target.clone())?)
+            .viewport(0, 0, width as i32, height as i32),
+        callback,
+    );
+
+    // GL rows go bottom to top, snapshots are stored top to bottom like images
+    let pixels = target.read_pixels_array()?;
+    let row = width as usize * 4;
+    Snapshot::new(
+        width,
+        height,
+        pixels.chunks(row).rev().flatten().copied().collect(),
+    )
+}
+
+/// RGBA 8-bit image, rows are ordered top to bottom.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<Snapshot, GlError> {
+        let size = width * height * 4;
+        if pixels.len() as u32 != size {
+            return Err(GlError::InvalidBufferSize {
+                expected: size,
+                received: pixels.len() as u32,
+            });
+        }
+        Ok(Snapshot {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn filled(width: u32, height: u32, color: [u8; 4]) -> Snapshot {
+        Snapshot {
+            width,
+            height,
+            pixels: color.repeat((width * height) as usize),
+        }
+    }
+
+    /// Read a reference image, it should be completely loaded.
+    pub fn from_image(gl: &Gl, image: &HtmlImageElement) -> Result<Snapshot, GlError> {
+        let texture = gl.texture(
+            image.natural_width(),
+            image.natural_height(),
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::Image(image.clone()),
+        )?;
+        Snapshot::new(
+            texture.width(),
+            texture.height(),
+            texture.read_pixels_array()?,
+        )
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Compare with the expected image, channels may differ by `tolerance`.
+    pub fn compare(&self, expected: &Snapshot, tolerance: u8) -> Result<(), SnapshotMismatch> {
+        if (self.width, self.height) != (expected.width, expected.height) {
+            return Err(SnapshotMismatch::Size {
+                expected: (expected.width, expected.height),
+                actual: (self.width, self.height),
+            });
+        }
+
+        let mut count = 0;
+        let mut max_difference = 0;
+        let mut first = None;
+        for (i, (actual, expected)) in self
+            .pixels
+            .chunks(4)
+            .zip(expected.pixels.chunks(4))
+            .enumerate()
+        {
+            let difference = actual
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or(0);
+            if difference > tolerance {
+                count += 1;
+                max_difference = max_difference.max(difference);
+                first.get_or_insert((i as u32 % self.width, i as u32 / self.width));
+            }
+        }
+
+        match first {
+            None => Ok(()),
+            Some(first) => Err(SnapshotMismatch::Pixels {
+                count,
+                max_difference,
+                first,
+            }),
+        }
+    }
+
+    /// Panics with the mismatch description if the snapshots differ.
+    pub fn assert_matches(&self, expected: &Snapshot, tolerance: u8) {
+        if let Err(mismatch) = self.compare(expected, tolerance) {
+            panic!("{}", mismatch);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotMismatch {
+    Size {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    Pixels {
+        count: usize,
+        max_difference: u8,
+        /// Coordinates of the first mismatched pixel
+        first: (u32, u32),
+    },
+}
+
+impl fmt::Display for SnapshotMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotMismatch::Size { expected, actual } => write!(
+                f,
+                "Snapshot size {}x{} doesn't match the expected {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            SnapshotMismatch::Pixels {
+                count,
+                max_difference,
+                first,
+            } => write!(
+                f,
+                "{} pixels differ, max difference {}, first at ({}, {})",
+                count, max_difference, first.0, first.1
+            ),
+        }
+    }
+}
@@ -1,15 +1,21 @@
-use std::cell::Cell;
-use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::{Rc, Weak};
 
-use js_sys::{Error, JsString, Uint8Array};
+use js_sys::Uint8Array;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use web_sys::{
-    HtmlImageElement, OesTextureHalfFloat, WebGlRenderingContext as Context, WebGlTexture,
+    HtmlImageElement, HtmlVideoElement, ImageBitmap, OesTextureHalfFloat,
+    WebGlRenderingContext as Context, WebglDepthTexture,
 };
 
+use super::backend::{PixelData, TextureSource};
 use super::gl::Gl;
-use super::gl::GlError;
+use super::gl::{ErrorContext, GlError};
+use super::registry::{write_name, Resource, ResourceInfo, ResourceKind};
 use super::settings::Settings;
+use crate::types::f32_to_f16_bits;
+use crate::{ClearParams, Command, DepthReader, Feature, Luminance, TextureAnalyzer, WebGlVersion};
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
@@ -24,12 +30,52 @@ impl Default for TextureFilter {
     }
 }
 
+impl TextureFilter {
+    /// Minification filter of a texture, `mipmaps` selects the mipmapped variant.
+    fn min_filter(self, mipmaps: bool) -> i32 {
+        match (self, mipmaps) {
+            (filter, false) => filter.into(),
+            (TextureFilter::Nearest, true) => Context::NEAREST_MIPMAP_NEAREST as i32,
+            (TextureFilter::Linear, true) => Context::LINEAR_MIPMAP_LINEAR as i32,
+        }
+    }
+}
+
+/// Sampling of the coordinates outside of `[0, 1]`.
+///
+/// WebGL1 supports only `ClampToEdge` for non-power-of-two textures.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, Default, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
+pub enum TextureWrap {
+    #[default]
+    ClampToEdge = Context::CLAMP_TO_EDGE as i32,
+    Repeat = Context::REPEAT as i32,
+    MirroredRepeat = Context::MIRRORED_REPEAT as i32,
+}
+
 #[repr(u32)]
 #[derive(Clone, Copy, Debug, TryFromPrimitive, IntoPrimitive, PartialEq, Eq)]
 pub enum TextureType {
     Byte = Context::UNSIGNED_BYTE,
     Float = Context::FLOAT,
     HalfFloat = OesTextureHalfFloat::HALF_FLOAT_OES,
+    /// 16-bit depth, used with `Depth` format
+    Short = Context::UNSIGNED_SHORT,
+    /// 32-bit depth, used with `Depth` format
+    Int = Context::UNSIGNED_INT,
+    /// Packed depth and stencil, used with `DepthStencil` format
+    Int24x8 = WebglDepthTexture::UNSIGNED_INT_24_8_WEBGL,
+}
+
+impl TextureType {
+    /// Bytes per channel, packed depth and stencil counts as a single channel.
+    pub fn size(self) -> u32 {
+        match self {
+            TextureType::Byte => 1,
+            TextureType::HalfFloat | TextureType::Short => 2,
+            TextureType::Float | TextureType::Int | TextureType::Int24x8 => 4,
+        }
+    }
 }
 
 #[repr(u32)]
@@ -40,6 +86,8 @@ pub enum TextureFormat {
     LuminanceAlpha = Context::LUMINANCE_ALPHA,
     Rgb = Context::RGB,
     Rgba = Context::RGBA,
+    Depth = Context::DEPTH_COMPONENT,
+    DepthStencil = Context::DEPTH_STENCIL,
 }
 
 impl TextureFormat {
@@ -50,6 +98,52 @@ impl TextureFormat {
             TextureFormat::LuminanceAlpha => 2,
             TextureFormat::Rgb => 3,
             TextureFormat::Rgba => 4,
+            TextureFormat::Depth => 1,
+            TextureFormat::DepthStencil => 1,
+        }
+    }
+
+    pub fn is_depth(self) -> bool {
+        self == TextureFormat::Depth || self == TextureFormat::DepthStencil
+    }
+
+    /// Check that textures of the format can hold the data type in the WebGL version.
+    pub fn validate(self, data_type: TextureType, version: WebGlVersion) -> Result<(), GlError> {
+        use TextureType::*;
+        let valid = match self {
+            TextureFormat::Depth => matches!(data_type, Short | Int),
+            TextureFormat::DepthStencil => data_type == Int24x8,
+            // WebGL2 has sized float formats only for RGB and RGBA
+            TextureFormat::Rgb | TextureFormat::Rgba => {
+                matches!(data_type, Byte | Float | HalfFloat)
+            }
+            TextureFormat::Alpha | TextureFormat::Luminance | TextureFormat::LuminanceAlpha => {
+                data_type == Byte
+                    || version == WebGlVersion::WebGl1 && matches!(data_type, Float | HalfFloat)
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(GlError::UnsupportedType(Some(format!(
+                "{:?} texture format with {:?} data type isn't supported in {:?}",
+                self, data_type, version
+            ))))
+        }
+    }
+
+    /// Can be used as a frame buffer color attachment.
+    ///
+    /// Float RGBA targets also depend on the color buffer float extensions,
+    /// check [FrameBuffer::check_status](crate::FrameBuffer::check_status) for them.
+    pub fn is_color_renderable(self, data_type: TextureType) -> bool {
+        match self {
+            TextureFormat::Rgba => matches!(
+                data_type,
+                TextureType::Byte | TextureType::Float | TextureType::HalfFloat
+            ),
+            TextureFormat::Rgb => data_type == TextureType::Byte,
+            _ => false,
         }
     }
 }
@@ -58,33 +152,75 @@ impl TextureFormat {
 pub enum TextureContent {
     None,
     Image(HtmlImageElement),
+    /// Decoded image, see [decode_image](crate::decode_image)
+    ImageBitmap(ImageBitmap),
     Bytes(Vec<u8>),
 }
 
 pub const TEXTURES_COUNT: u32 = 16;
 
-#[derive(Debug)]
 struct TextureInfo {
     gl: Gl,
-    handle: WebGlTexture,
+    id: u64,
     width: u32,
     height: u32,
     data_type: TextureType,
     format: TextureFormat,
     filter: Cell<TextureFilter>,
+    wrap: Cell<TextureWrap>,
+    mipmaps: Cell<bool>,
+    premultiplied: Cell<bool>,
+    label: RefCell<Option<String>>,
 }
 
 impl PartialEq<TextureInfo> for TextureInfo {
     fn eq(&self, other: &TextureInfo) -> bool {
-        self.handle == other.handle
+        self.id == other.id
     }
 }
 
 impl Eq for TextureInfo {}
 
+impl fmt::Debug for TextureInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextureInfo")
+            .field("id", &self.id)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("data_type", &self.data_type)
+            .field("format", &self.format)
+            .field("filter", &self.filter)
+            .field("wrap", &self.wrap)
+            .field("mipmaps", &self.mipmaps)
+            .field("premultiplied", &self.premultiplied)
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
 impl Drop for TextureInfo {
     fn drop(&mut self) {
-        self.gl.context().delete_texture(Some(&self.handle))
+        trace_event!(DEBUG, id = self.id, "delete texture");
+        self.gl.unregister(self.id);
+        self.gl.delete(Command::DeleteTexture { id: self.id });
+    }
+}
+
+impl Resource for TextureInfo {
+    fn info(&self) -> ResourceInfo {
+        let size =
+            (self.width * self.height * self.format.channels() * self.data_type.size()) as usize;
+        ResourceInfo {
+            id: self.id,
+            kind: ResourceKind::Texture,
+            // The mip chain takes a third of the base level
+            size: if self.mipmaps.get() {
+                size * 4 / 3
+            } else {
+                size
+            },
+            label: self.label.borrow().clone(),
+        }
     }
 }
 
@@ -93,6 +229,45 @@ pub struct Texture {
     data: Rc<TextureInfo>,
 }
 
+impl fmt::Display for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.data.label.borrow();
+        write_name(f, ResourceKind::Texture, self.data.id, label.as_deref())
+    }
+}
+
+/// Handle which doesn't keep the texture alive, so caches and registries built on top
+/// of the crate don't hold GPU memory forever. [Texture::downgrade] creates it.
+#[derive(Clone, Debug)]
+pub struct TextureWeak {
+    id: u64,
+    data: Weak<TextureInfo>,
+}
+
+impl PartialEq for TextureWeak {
+    fn eq(&self, other: &TextureWeak) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for TextureWeak {}
+
+impl TextureWeak {
+    /// Id of the texture, it stays valid after the texture is dropped.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The texture, `None` if all its strong handles are dropped.
+    pub fn upgrade(&self) -> Option<Texture> {
+        self.data.upgrade().map(|data| Texture { data })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.data.strong_count() > 0
+    }
+}
+
 impl Texture {
     pub fn new(
         gl: Gl,
@@ -102,43 +277,53 @@ impl Texture {
         format: TextureFormat,
         data: TextureContent,
     ) -> Result<Texture, GlError> {
-        let handle = gl
-            .context()
-            .create_texture()
-            .ok_or_else(|| GlError::UnknownError(Some("Texture creation failed".into())))?;
+        format.validate(data_type, gl.version())?;
+        if format.is_depth() && !gl.depth_texture_supported() {
+            return Err(GlError::ExtensionNotFound("WEBGL_depth_texture".into()));
+        }
+
+        let id = gl
+            .create_object(|id| Command::CreateTexture { id })
+            .map_err(|_| GlError::UnknownError(Some("Texture creation failed".into())))?;
+        trace_event!(
+            DEBUG,
+            id,
+            width,
+            height,
+            ?data_type,
+            ?format,
+            "create texture"
+        );
 
         let result = Texture {
             data: Rc::new(TextureInfo {
                 gl: gl.clone(),
-                handle: handle.clone(),
+                id,
                 filter: Default::default(),
+                wrap: Default::default(),
+                mipmaps: Default::default(),
+                premultiplied: Default::default(),
                 width,
                 height,
                 data_type,
                 format,
+                label: Default::default(),
             }),
         };
+        gl.register(id, &result.data);
 
         gl.apply(
             Gl::settings().active_texture(0).texture(0, result.clone()),
             || {
-                gl.context().tex_parameteri(
-                    Context::TEXTURE_2D,
-                    Context::TEXTURE_WRAP_S,
-                    Context::CLAMP_TO_EDGE as i32,
-                );
-                gl.context().tex_parameteri(
-                    Context::TEXTURE_2D,
-                    Context::TEXTURE_WRAP_T,
-                    Context::CLAMP_TO_EDGE as i32,
-                );
-                gl.context().tex_parameteri(
-                    Context::TEXTURE_2D,
+                Texture::set_parameter(&gl, Context::TEXTURE_WRAP_S, TextureWrap::default().into());
+                Texture::set_parameter(&gl, Context::TEXTURE_WRAP_T, TextureWrap::default().into());
+                Texture::set_parameter(
+                    &gl,
                     Context::TEXTURE_MAG_FILTER,
                     TextureFilter::default().into(),
                 );
-                gl.context().tex_parameteri(
-                    Context::TEXTURE_2D,
+                Texture::set_parameter(
+                    &gl,
                     Context::TEXTURE_MIN_FILTER,
                     TextureFilter::default().into(),
                 );
@@ -148,16 +333,52 @@ impl Texture {
         match data {
             TextureContent::None => result.init_buffer()?,
             TextureContent::Image(image) => result.write_image(&image)?,
+            TextureContent::ImageBitmap(bitmap) => result.write_image_bitmap(&bitmap)?,
             TextureContent::Bytes(bytes) => result.write_bytes(&bytes)?,
         }
 
         Ok(result)
     }
 
+    fn set_parameter(gl: &Gl, parameter: u32, value: i32) {
+        gl.execute(Command::TexParameter {
+            target: Context::TEXTURE_2D,
+            parameter,
+            value,
+        });
+    }
+
     pub fn gl(&self) -> Gl {
         self.data.gl.clone()
     }
 
+    pub fn id(&self) -> u64 {
+        self.data.id
+    }
+
+    /// Weak handle of the same object.
+    pub fn downgrade(&self) -> TextureWeak {
+        TextureWeak {
+            id: self.data.id,
+            data: Rc::downgrade(&self.data),
+        }
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.data.label.borrow().clone()
+    }
+
+    /// Name the object for debugging, the label is a part of error messages
+    /// and [Gl::resources] entries.
+    pub fn set_label(&self, label: &str) {
+        self.data.label.replace(Some(label.into()));
+    }
+
+    pub fn with_label(self, label: &str) -> Self {
+        self.set_label(label);
+        self
+    }
+
     pub fn width(&self) -> u32 {
         self.data.width
     }
@@ -171,10 +392,6 @@ impl Texture {
         self.data.format
     }
 
-    pub(crate) fn handle(&self) -> &WebGlTexture {
-        &self.data.handle
-    }
-
     pub fn size(&self) -> (u32, u32) {
         (self.width(), self.height())
     }
@@ -186,19 +403,14 @@ impl Texture {
     pub fn set_filter(&self, filter: TextureFilter) {
         if self.filter() != filter {
             let ref gl = self.data.gl;
-            let context = gl.context();
             gl.apply(
                 Gl::settings().texture(0, self.clone()).active_texture(0),
                 || {
-                    context.tex_parameteri(
-                        Context::TEXTURE_2D,
-                        Context::TEXTURE_MAG_FILTER,
-                        filter.into(),
-                    );
-                    context.tex_parameteri(
-                        Context::TEXTURE_2D,
+                    Texture::set_parameter(gl, Context::TEXTURE_MAG_FILTER, filter.into());
+                    Texture::set_parameter(
+                        gl,
                         Context::TEXTURE_MIN_FILTER,
-                        filter.into(),
+                        filter.min_filter(self.has_mipmaps()),
                     );
                     self.data.filter.set(filter);
                 },
@@ -206,78 +418,139 @@ impl Texture {
         }
     }
 
-    pub fn write_image(&self, image: &HtmlImageElement) -> Result<(), GlError> {
-        let gl = self.gl();
-        let format: u32 = self.format().into();
+    pub fn is_power_of_two(&self) -> bool {
+        self.width().is_power_of_two() && self.height().is_power_of_two()
+    }
 
-        gl.apply(
-            Gl::settings().active_texture(0).texture(0, self.clone()),
-            || {
-                gl.context()
-                    .tex_image_2d_with_u32_and_u32_and_image(
-                        Context::TEXTURE_2D,
-                        0,
-                        format as i32,
-                        format,
-                        self.data_type().into(),
-                        image,
-                    )
-                    .map_err(|e| GlError::WritePixelsError(Some(JsString::from(e).into())))
-            },
-        )?;
+    /// WebGL1 samples non-power-of-two textures with mipmaps or repeated wrapping as black,
+    /// report it instead.
+    fn check_power_of_two(&self, operation: &str) -> Result<(), GlError> {
+        if self.data.gl.version() == WebGlVersion::WebGl1 && !self.is_power_of_two() {
+            Err(
+                GlError::FeatureNotSupported(Feature::WebGl2).with_context(format!(
+                    "{} of {} with {}x{} size, WebGL1 supports it only for power-of-two textures",
+                    operation,
+                    self,
+                    self.width(),
+                    self.height()
+                )),
+            )
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn wrap(&self) -> TextureWrap {
+        self.data.wrap.get()
+    }
 
+    /// Wrapping of both coordinates. Fails for non-power-of-two textures in WebGL1,
+    /// unless it's `ClampToEdge`.
+    pub fn set_wrap(&self, wrap: TextureWrap) -> Result<(), GlError> {
+        if wrap != TextureWrap::ClampToEdge {
+            self.check_power_of_two(&format!("{:?} wrapping", wrap))?;
+        }
+        if self.wrap() != wrap {
+            let ref gl = self.data.gl;
+            gl.apply(
+                Gl::settings().texture(0, self.clone()).active_texture(0),
+                || {
+                    Texture::set_parameter(gl, Context::TEXTURE_WRAP_S, wrap.into());
+                    Texture::set_parameter(gl, Context::TEXTURE_WRAP_T, wrap.into());
+                    self.data.wrap.set(wrap);
+                },
+            );
+        }
         Ok(())
     }
 
-    pub fn write_bytes(&self, bytes: &Vec<u8>) -> Result<(), GlError> {
-        let gl = self.gl();
-        let format: u32 = self.format().into();
+    pub fn has_mipmaps(&self) -> bool {
+        self.data.mipmaps.get()
+    }
 
+    /// Build the mipmaps from the current content and sample them with the current filter.
+    /// Call it again after the content changes, the levels aren't updated by writes.
+    /// Fails for non-power-of-two textures in WebGL1.
+    pub fn generate_mipmaps(&self) -> Result<(), GlError> {
+        self.check_power_of_two("generating mipmaps")?;
+        let ref gl = self.data.gl;
+        gl.check_context()?;
         gl.apply(
-            Gl::settings().active_texture(0).texture(0, self.clone()),
+            Gl::settings().texture(0, self.clone()).active_texture(0),
             || {
-                gl.context()
-                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-                        Context::TEXTURE_2D,
-                        0,
-                        format as i32,
-                        self.width() as i32,
-                        self.height() as i32,
-                        0,
-                        format,
-                        self.data_type().into(),
-                        Some(bytes),
-                    )
-                    .map_err(|e| GlError::WritePixelsError(Some(JsString::from(e).into())))
+                gl.execute(Command::GenerateMipmap {
+                    target: Context::TEXTURE_2D,
+                });
+                Texture::set_parameter(
+                    gl,
+                    Context::TEXTURE_MIN_FILTER,
+                    self.filter().min_filter(true),
+                );
             },
-        )?;
-
+        );
+        self.data.mipmaps.set(true);
         Ok(())
     }
 
+    /// Whether the colors are multiplied by alpha. Uploads of images, videos and bytes
+    /// take it from [Gl::pixel_store], set it for bitmaps decoded with premultiplied alpha
+    /// and for rendered content.
+    pub fn is_premultiplied(&self) -> bool {
+        self.data.premultiplied.get()
+    }
+
+    pub fn set_premultiplied(&self, premultiplied: bool) {
+        self.data.premultiplied.set(premultiplied);
+    }
+
+    pub fn write_image(&self, image: &HtmlImageElement) -> Result<(), GlError> {
+        self.write(TextureSource::Image(image))
+    }
+
+    /// Upload a decoded image, the texture size should match the bitmap one.
+    /// Pixel store flags don't apply to bitmaps, flip and premultiply them while decoding.
+    pub fn write_image_bitmap(&self, bitmap: &ImageBitmap) -> Result<(), GlError> {
+        self.write(TextureSource::ImageBitmap(bitmap))
+    }
+
+    /// Upload the current frame, the texture size should match the video one.
+    pub fn write_video(&self, video: &HtmlVideoElement) -> Result<(), GlError> {
+        self.write(TextureSource::Video(video))
+    }
+
+    pub fn write_bytes(&self, bytes: &Vec<u8>) -> Result<(), GlError> {
+        self.write(TextureSource::Bytes(bytes))
+    }
+
     fn init_buffer(&self) -> Result<(), GlError> {
-        let gl = self.gl();
-        let format: u32 = self.format().into();
+        self.write(TextureSource::None)
+    }
 
+    fn write(&self, source: TextureSource) -> Result<(), GlError> {
+        let gl = self.gl();
+        // Set once the upload succeeds, a failed one keeps the previous content
+        let premultiplied = match source {
+            TextureSource::None => Some(false),
+            TextureSource::ImageBitmap(_) => None,
+            _ => Some(gl.pixel_store().premultiply_alpha),
+        };
         gl.apply(
             Gl::settings().active_texture(0).texture(0, self.clone()),
             || {
-                gl.context()
-                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-                        Context::TEXTURE_2D,
-                        0,
-                        format as i32,
-                        self.width() as i32,
-                        self.height() as i32,
-                        0,
-                        format,
-                        self.data_type().into(),
-                        None,
-                    )
-                    .map_err(|e| GlError::InitTextureBufferError(Some(JsString::from(e).into())))
+                gl.tex_image(
+                    Context::TEXTURE_2D,
+                    self.format().into(),
+                    self.width(),
+                    self.height(),
+                    self.data_type().into(),
+                    source,
+                )
             },
-        )?;
-
+        )
+        .with_context(|| format!("uploading {}", self))?;
+        if let Some(premultiplied) = premultiplied {
+            self.set_premultiplied(premultiplied);
+        }
         Ok(())
     }
 
@@ -294,23 +567,9 @@ impl Texture {
 
             gl.apply(
                 Gl::settings().frame_buffer(gl.frame_buffer_with_color(self.clone())?),
-                || {
-                    gl.context()
-                        .read_pixels_with_opt_u8_array(
-                            0,
-                            0,
-                            self.width() as i32,
-                            self.height() as i32,
-                            TextureFormat::Rgba.into(),
-                            TextureType::Byte.into(),
-                            Some(array),
-                        )
-                        .map_err(|error_object| {
-                            let error: Error = error_object.into();
-                            GlError::ReadPixelsError(Some(error.message().into()))
-                        })
-                },
-            )?;
+                || gl.read_pixels(0, 0, self.width(), self.height(), array),
+            )
+            .with_context(|| format!("reading {}", self))?;
             Ok(())
         }
     }
@@ -319,7 +578,8 @@ impl Texture {
     pub fn read_pixels_into_buffer(&self, buffer: &Uint8Array) -> Result<(), GlError> {
         if self.data_type() != TextureType::Byte {
             Err(GlError::ReadPixelsError(Some(format!(
-                "Invalid texture data type {:?}",
+                "{}: invalid texture data type {:?}",
+                self,
                 self.data_type()
             ))))
         } else if buffer.length() != self.width() * self.height() * self.format().channels() {
@@ -332,29 +592,15 @@ impl Texture {
 
             gl.apply(
                 Gl::settings().frame_buffer(gl.frame_buffer_with_color(self.clone())?),
-                || {
-                    gl.context()
-                        .read_pixels_with_opt_array_buffer_view(
-                            0,
-                            0,
-                            self.width() as i32,
-                            self.height() as i32,
-                            TextureFormat::Rgba.into(),
-                            TextureType::Byte.into(),
-                            Some(buffer),
-                        )
-                        .map_err(|error_object| {
-                            let error: Error = error_object.into();
-                            GlError::ReadPixelsError(Some(error.message().into()))
-                        })
-                },
-            )?;
+                || gl.read_pixels_into_buffer(0, 0, self.width(), self.height(), buffer),
+            )
+            .with_context(|| format!("reading {}", self))?;
             Ok(())
         }
     }
 
     pub fn read_pixels_array(&self) -> Result<Vec<u8>, GlError> {
-        let mut result = Vec::with_capacity((self.width() * self.height() * 4) as usize);
+        let mut result = vec![0; (self.width() * self.height() * 4) as usize];
         self.read_pixels_into_array(&mut result)?;
         return Ok(result);
     }
@@ -365,17 +611,188 @@ impl Texture {
         return Ok(result);
     }
 
+    /// Read depth values of the region from a depth texture, see [DepthReader].
+    /// The reader program is compiled on every call, so prefer a persistent [DepthReader]
+    /// for frequent reads.
+    pub fn read_depth(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Vec<f32>, GlError> {
+        DepthReader::new(self.gl())?.read(self, x, y, width, height)
+    }
+
+    /// Average and maximum luminance, see [TextureAnalyzer]. The analyzer programs are
+    /// compiled on every call, so prefer a persistent [TextureAnalyzer] for every frame analysis.
+    pub fn analyze(&self) -> Result<Luminance, GlError> {
+        TextureAnalyzer::new(self.gl())?.luminance(self)
+    }
+
+    /// Luminance histogram over `[min, max]`, see [TextureAnalyzer::histogram].
+    pub fn histogram(&self, bins: u32, min: f32, max: f32) -> Result<Vec<f32>, GlError> {
+        TextureAnalyzer::new(self.gl())?.histogram(self, bins, min, max)
+    }
+
     pub fn clear(&self, r: f32, g: f32, b: f32, a: f32) -> Result<(), GlError> {
+        self.clear_region(0, 0, self.width(), self.height(), r, g, b, a)
+    }
+
+    /// Clear the region of the texture. Renderable textures are cleared with scissor test,
+    /// the rest of the color textures are overwritten with `texSubImage2D`.
+    /// Depth textures are cleared with the `r` value.
+    #[allow(clippy::too_many_arguments)]
+    pub fn clear_region(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        r: f32,
+        g: f32,
+        b: f32,
+        a: f32,
+    ) -> Result<(), GlError> {
+        if x.checked_add(width)
+            .is_none_or(|right| right > self.width())
+            || y.checked_add(height).is_none_or(|top| top > self.height())
+        {
+            return Err(GlError::WritePixelsError(Some(format!(
+                "{}: region {}x{} at ({}, {}) is out of the texture bounds {}x{}",
+                self,
+                width,
+                height,
+                x,
+                y,
+                self.width(),
+                self.height()
+            ))));
+        }
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
         let gl = self.gl();
+        let frame_buffer = if self.format().is_depth() {
+            let mut frame_buffer = gl.frame_buffer()?;
+            frame_buffer.set_depth_texture(Some(self.clone()))?;
+            frame_buffer
+        } else {
+            gl.frame_buffer_with_color(self.clone())?
+        };
 
-        gl.apply(
-            Gl::settings()
-                .clear_color(r, g, b, a)
-                .viewport(0, 0, self.width() as i32, self.height() as i32)
-                .frame_buffer(gl.frame_buffer_with_color(self.clone())?),
-            || gl.clear_color_buffer(),
-        );
+        match frame_buffer.check_status() {
+            Ok(()) => {
+                gl.apply(
+                    Gl::settings()
+                        .frame_buffer(frame_buffer)
+                        .viewport(0, 0, self.width() as i32, self.height() as i32)
+                        .scissor(x as i32, y as i32, width as i32, height as i32)
+                        .color_mask(true, true, true, true)
+                        .depth_mask(true),
+                    || {
+                        gl.clear(if self.format().is_depth() {
+                            ClearParams {
+                                depth: Some(r),
+                                ..Default::default()
+                            }
+                        } else {
+                            ClearParams {
+                                color: Some([r, g, b, a]),
+                                ..Default::default()
+                            }
+                        })
+                    },
+                );
+                Ok(())
+            }
+            Err(error) if self.format().is_depth() => Err(error),
+            Err(_) => self.fill_region(x, y, width, height, [r, g, b, a]),
+        }
+    }
 
-        Ok(())
+    /// Overwrite the region with the same value, used for non-renderable textures
+    fn fill_region(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        [r, g, b, a]: [f32; 4],
+    ) -> Result<(), GlError> {
+        let pixel: Vec<f32> = match self.format() {
+            TextureFormat::Alpha => vec![a],
+            TextureFormat::Luminance => vec![r],
+            TextureFormat::LuminanceAlpha => vec![r, a],
+            TextureFormat::Rgb => vec![r, g, b],
+            TextureFormat::Rgba => vec![r, g, b, a],
+            TextureFormat::Depth | TextureFormat::DepthStencil => {
+                return Err(GlError::WritePixelsError(Some(format!(
+                    "{}: depth textures can't be filled with pixel data",
+                    self
+                ))))
+            }
+        };
+        let values = pixel
+            .iter()
+            .cycle()
+            .take(pixel.len() * width as usize * height as usize);
+        // Rows of 1 or 3 byte texels aren't 4 byte aligned
+        let gl = self.gl();
+        gl.apply(Gl::settings().unpack_alignment(1), || {
+            self.write_region_values(x, y, width, height, values)
+        })
+    }
+
+    /// Upload float values for all the texels, converted to the texture data type.
+    /// Byte textures get values clamped to `[0, 1]`.
+    pub fn write_floats(&self, values: &[f32]) -> Result<(), GlError> {
+        let expected = (self.width() * self.height() * self.format().channels()) as usize;
+        if values.len() != expected {
+            return Err(GlError::InvalidBufferSize {
+                expected: expected as u32,
+                received: values.len() as u32,
+            });
+        }
+        self.write_region_values(0, 0, self.width(), self.height(), values.iter())
+            .with_context(|| format!("uploading {}", self))
+    }
+
+    fn write_region_values<'a>(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        values: impl Iterator<Item = &'a f32>,
+    ) -> Result<(), GlError> {
+        let gl = self.gl();
+        let write = |data: PixelData| {
+            gl.apply(
+                Gl::settings().active_texture(0).texture(0, self.clone()),
+                || {
+                    gl.tex_sub_image(
+                        x as i32,
+                        y as i32,
+                        width,
+                        height,
+                        self.format().into(),
+                        self.data_type().into(),
+                        data,
+                    )
+                },
+            )
+        };
+
+        match self.data_type() {
+            TextureType::Byte => write(PixelData::Bytes(
+                &values
+                    .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .collect::<Vec<_>>(),
+            )),
+            TextureType::Float => write(PixelData::Floats(&values.copied().collect::<Vec<_>>())),
+            TextureType::HalfFloat => write(PixelData::Shorts(
+                &values.map(|v| f32_to_f16_bits(*v)).collect::<Vec<_>>(),
+            )),
+            data_type => Err(GlError::WritePixelsError(Some(format!(
+                "{}: unsupported texture data type {:?}",
+                self, data_type
+            )))),
+        }
     }
 }
@@ -0,0 +1,123 @@
+use std::borrow::Cow;
+
+use crate::types::{DataType, TypeMark};
+use crate::uniforms::{Field, IntoUniform, UniformValue, Uniforms};
+use crate::Texture;
+
+/// GLSL helper mapping `[0, 1]` coordinates into the region passed as `u_region`.
+pub const TEXTURE_REGION_GLSL: &str = r#"
+    vec2 region_uv(vec4 region, vec2 uv) {
+        return region.xy + uv * region.zw;
+    }
+"#;
+
+/// Rectangle of a texture, like a sprite or a glyph in an atlas.
+///
+/// The rectangle is `[x, y, width, height]` in texture coordinates.
+/// As a uniform field the region is passed as the texture sampler, as [Uniforms]
+/// it sets `u_texture` and `u_region`, so it can be combined with other uniforms
+/// in a tuple, like `(&sprite, &uniforms)`, and used with [TEXTURE_REGION_GLSL].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureRegion {
+    texture: Texture,
+    rect: [f32; 4],
+}
+
+impl TextureRegion {
+    pub fn new(texture: Texture, rect: [f32; 4]) -> TextureRegion {
+        TextureRegion { texture, rect }
+    }
+
+    pub fn whole(texture: Texture) -> TextureRegion {
+        TextureRegion::new(texture, [0.0, 0.0, 1.0, 1.0])
+    }
+
+    /// Region in pixels from the texture origin.
+    pub fn from_pixels(texture: Texture, x: u32, y: u32, width: u32, height: u32) -> TextureRegion {
+        let (w, h) = (texture.width() as f32, texture.height() as f32);
+        TextureRegion::new(
+            texture,
+            [
+                x as f32 / w,
+                y as f32 / h,
+                width as f32 / w,
+                height as f32 / h,
+            ],
+        )
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn rect(&self) -> [f32; 4] {
+        self.rect
+    }
+
+    /// Size of the region in texture pixels.
+    pub fn pixel_size(&self) -> (f32, f32) {
+        (
+            self.rect[2] * self.texture.width() as f32,
+            self.rect[3] * self.texture.height() as f32,
+        )
+    }
+
+    /// Part of this region, `rect` is relative to it.
+    pub fn sub_region(&self, rect: [f32; 4]) -> TextureRegion {
+        let [x, y, width, height] = self.rect;
+        TextureRegion::new(
+            self.texture.clone(),
+            [
+                x + rect[0] * width,
+                y + rect[1] * height,
+                rect[2] * width,
+                rect[3] * height,
+            ],
+        )
+    }
+
+    /// Cells of a regular grid, row by row from the region origin.
+    pub fn split(&self, columns: u32, rows: u32) -> Vec<TextureRegion> {
+        let (width, height) = (1.0 / columns as f32, 1.0 / rows as f32);
+        (0..rows)
+            .flat_map(|row| {
+                (0..columns)
+                    .map(move |column| [column as f32 * width, row as f32 * height, width, height])
+            })
+            .map(|rect| self.sub_region(rect))
+            .collect()
+    }
+}
+
+impl From<Texture> for TextureRegion {
+    fn from(texture: Texture) -> Self {
+        TextureRegion::whole(texture)
+    }
+}
+
+impl TypeMark for TextureRegion {
+    const DATA_TYPE: Option<DataType> = Some(DataType::Sampler);
+}
+
+impl IntoUniform for TextureRegion {
+    const UNIFORM_TYPE: Option<DataType> = <Self as TypeMark>::DATA_TYPE;
+
+    fn into_uniform(&self) -> UniformValue {
+        self.texture.into_uniform()
+    }
+}
+
+impl Uniforms for TextureRegion {
+    fn uniforms(&self) -> Vec<Field> {
+        vec![
+            Field {
+                name: Cow::Borrowed("u_texture"),
+                value: self.texture.into_uniform(),
+            },
+            Field {
+                name: Cow::Borrowed("u_region"),
+                value: UniformValue::Vec4(self.rect),
+            },
+        ]
+    }
+}
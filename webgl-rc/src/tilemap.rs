@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::{
+    Attributes, BufferUsage, Gl, GlError, Instances, ItemsBuffer, PrimitiveType, Program, Texture,
+    Uniforms,
+};
+
+/// Tile index of the cells which aren't drawn.
+pub const EMPTY_TILE: u32 = u32::MAX;
+
+const VERTEX_SHADER: &str = r#"
+    attribute vec2 a_position;
+    attribute vec2 i_cell;
+    attribute float i_tile;
+    uniform vec2 u_tile_size;
+    uniform vec2 u_scroll;
+    uniform vec2 u_view_size;
+    uniform vec2 u_atlas_size;
+    varying vec2 v_uv;
+    void main() {
+        vec2 position = ((i_cell + a_position) * u_tile_size - u_scroll) / u_view_size;
+        gl_Position = vec4(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0, 0.0, 1.0);
+        vec2 tile = vec2(mod(i_tile, u_atlas_size.x), floor(i_tile / u_atlas_size.x));
+        v_uv = (tile + a_position) / u_atlas_size;
+    }
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    uniform sampler2D u_atlas;
+    varying vec2 v_uv;
+    void main() {
+        gl_FragColor = texture2D(u_atlas, v_uv);
+    }
+"#;
+
+#[derive(Clone, Copy, Debug, Attributes)]
+struct TileVertex {
+    position: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug, Instances)]
+struct TileInstance {
+    cell: [f32; 2],
+    tile: f32,
+}
+
+#[derive(Clone, Uniforms)]
+struct TilemapUniforms {
+    atlas: Texture,
+    tile_size: [f32; 2],
+    scroll: [f32; 2],
+    view_size: [f32; 2],
+    atlas_size: [f32; 2],
+}
+
+/// Tile replaced by the frames in turn, each shown for `frame_duration`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileAnimation {
+    pub frames: Vec<u32>,
+    pub frame_duration: f32,
+}
+
+impl TileAnimation {
+    pub fn new(frames: Vec<u32>, frame_duration: f32) -> TileAnimation {
+        TileAnimation {
+            frames,
+            frame_duration,
+        }
+    }
+
+    /// Frame shown at the time, `None` for empty animations.
+    pub fn frame(&self, time: f32) -> Option<u32> {
+        if self.frames.is_empty() || self.frame_duration <= 0.0 {
+            return self.frames.first().copied();
+        }
+        let index = (time / self.frame_duration).floor() as i64;
+        let index = index.rem_euclid(self.frames.len() as i64) as usize;
+        Some(self.frames[index])
+    }
+}
+
+/// Grid of tiles from an atlas, drawn with a single instanced draw call.
+///
+/// Tiles of the atlas are numbered row by row from its origin, cells of the map
+/// from the top left corner. Positions are in world units, a cell is `tile_size` large,
+/// the view shows `view_size` units starting from the scroll position.
+/// Only the visible cells are uploaded on each draw, so large maps are cheap to scroll.
+#[derive(Debug)]
+pub struct Tilemap {
+    gl: Gl,
+    program: Program,
+    quad: ItemsBuffer<TileVertex>,
+    instances: ItemsBuffer<TileInstance>,
+    atlas: Texture,
+    atlas_size: (u32, u32),
+    columns: u32,
+    rows: u32,
+    tiles: Vec<u32>,
+    tile_size: [f32; 2],
+    scroll: [f32; 2],
+    animations: HashMap<u32, TileAnimation>,
+}
+
+impl Tilemap {
+    /// Empty map of `columns × rows` cells, the atlas is a grid of
+    /// `atlas_columns × atlas_rows` tiles.
+    pub fn new(
+        gl: Gl,
+        atlas: Texture,
+        atlas_columns: u32,
+        atlas_rows: u32,
+        columns: u32,
+        rows: u32,
+    ) -> Result<Tilemap, GlError> {
+        let quad = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]]
+            .map(|position| TileVertex { position });
+        Ok(Tilemap {
+            program: gl.program(FRAGMENT_SHADER, VERTEX_SHADER)?,
+            quad: gl.items_buffer(&quad, BufferUsage::Static)?,
+            instances: gl.items_buffer(&[], BufferUsage::Stream)?,
+            gl,
+            atlas,
+            atlas_size: (atlas_columns.max(1), atlas_rows.max(1)),
+            columns,
+            rows,
+            tiles: vec![EMPTY_TILE; (columns * rows) as usize],
+            tile_size: [1.0, 1.0],
+            scroll: [0.0, 0.0],
+            animations: HashMap::new(),
+        })
+    }
+
+    pub fn gl(&self) -> Gl {
+        self.gl.clone()
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn atlas(&self) -> &Texture {
+        &self.atlas
+    }
+
+    pub fn set_atlas(&mut self, atlas: Texture, columns: u32, rows: u32) {
+        self.atlas = atlas;
+        self.atlas_size = (columns.max(1), rows.max(1));
+    }
+
+    /// Tile of the cell, `None` outside of the map.
+    pub fn tile(&self, column: u32, row: u32) -> Option<u32> {
+        self.index(column, row).map(|index| self.tiles[index])
+    }
+
+    /// Cells outside of the map are ignored.
+    pub fn set_tile(&mut self, column: u32, row: u32, tile: u32) {
+        if let Some(index) = self.index(column, row) {
+            self.tiles[index] = tile;
+        }
+    }
+
+    /// All the cells row by row, the length should match the map size.
+    pub fn set_tiles(&mut self, tiles: &[u32]) -> Result<(), GlError> {
+        if tiles.len() != self.tiles.len() {
+            return Err(GlError::InvalidBufferSize {
+                expected: self.tiles.len() as u32,
+                received: tiles.len() as u32,
+            });
+        }
+        self.tiles.copy_from_slice(tiles);
+        Ok(())
+    }
+
+    pub fn tiles(&self) -> &[u32] {
+        &self.tiles
+    }
+
+    pub fn fill(&mut self, tile: u32) {
+        self.tiles.fill(tile);
+    }
+
+    fn index(&self, column: u32, row: u32) -> Option<usize> {
+        if column < self.columns && row < self.rows {
+            Some((row * self.columns + column) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn tile_size(&self) -> [f32; 2] {
+        self.tile_size
+    }
+
+    pub fn set_tile_size(&mut self, width: f32, height: f32) {
+        self.tile_size = [width, height];
+    }
+
+    /// World position shown at the top left corner of the view.
+    pub fn scroll(&self) -> [f32; 2] {
+        self.scroll
+    }
+
+    pub fn set_scroll(&mut self, x: f32, y: f32) {
+        self.scroll = [x, y];
+    }
+
+    pub fn scroll_by(&mut self, dx: f32, dy: f32) {
+        self.scroll = [self.scroll[0] + dx, self.scroll[1] + dy];
+    }
+
+    /// Animate every cell with the tile.
+    pub fn set_animation(&mut self, tile: u32, animation: TileAnimation) {
+        self.animations.insert(tile, animation);
+    }
+
+    pub fn remove_animation(&mut self, tile: u32) {
+        self.animations.remove(&tile);
+    }
+
+    /// Columns and rows of the cells intersecting the view.
+    pub fn visible_cells(&self, view_size: [f32; 2]) -> (Range<u32>, Range<u32>) {
+        let range = |scroll: f32, size: f32, tile: f32, count: u32| {
+            if tile <= 0.0 {
+                return 0..0;
+            }
+            let start = (scroll / tile).floor().clamp(0.0, count as f32) as u32;
+            let end = ((scroll + size) / tile).ceil().clamp(0.0, count as f32) as u32;
+            start..end.max(start)
+        };
+        (
+            range(
+                self.scroll[0],
+                view_size[0],
+                self.tile_size[0],
+                self.columns,
+            ),
+            range(self.scroll[1], view_size[1], self.tile_size[1], self.rows),
+        )
+    }
+
+    /// Draw the visible cells into the current viewport showing `view_size` world units,
+    /// `time` selects the frames of animated tiles.
+    pub fn draw(&self, view_size: [f32; 2], time: f32) -> Result<(), GlError> {
+        let (columns, rows) = self.visible_cells(view_size);
+        let mut instances = Vec::with_capacity(columns.len() * rows.len());
+        for row in rows {
+            for column in columns.clone() {
+                let tile = self.tiles[(row * self.columns + column) as usize];
+                let tile = match self.animations.get(&tile) {
+                    Some(animation) => animation.frame(time).unwrap_or(EMPTY_TILE),
+                    None => tile,
+                };
+                if tile != EMPTY_TILE {
+                    instances.push(TileInstance {
+                        cell: [column as f32, row as f32],
+                        tile: tile as f32,
+                    });
+                }
+            }
+        }
+        if instances.is_empty() {
+            return Ok(());
+        }
+        self.instances.set_content(&instances, BufferUsage::Stream);
+        self.program.draw_instances(
+            PrimitiveType::TriangleStrip,
+            &TilemapUniforms {
+                atlas: self.atlas.clone(),
+                tile_size: self.tile_size,
+                scroll: self.scroll,
+                view_size,
+                atlas_size: [self.atlas_size.0 as f32, self.atlas_size.1 as f32],
+            },
+            &self.quad,
+            &self.instances,
+        )
+    }
+}
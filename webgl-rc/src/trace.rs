@@ -0,0 +1,28 @@
+//! Internal tracing macros, they expand to nothing without the `trace` feature.
+
+#[cfg(feature = "trace")]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {
+        tracing::event!(target: "webgl_rc", tracing::Level::$level, $($arg)*)
+    };
+}
+
+#[cfg(not(feature = "trace"))]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {};
+}
+
+/// Enter a span, it's active until the returned guard is dropped.
+#[cfg(feature = "trace")]
+macro_rules! trace_span {
+    ($level:ident, $($arg:tt)*) => {
+        tracing::span!(target: "webgl_rc", tracing::Level::$level, $($arg)*).entered()
+    };
+}
+
+#[cfg(not(feature = "trace"))]
+macro_rules! trace_span {
+    ($level:ident, $($arg:tt)*) => {
+        ()
+    };
+}
@@ -8,6 +8,10 @@ use super::gl::GlError;
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum DataType {
     Boolean,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
     Float,
     Vec2,
     Vec3,
@@ -16,12 +20,17 @@ pub enum DataType {
     Mat3,
     Mat4,
     Sampler,
+    SamplerCube,
 }
 
 impl DataType {
     pub(crate) fn size_in_floats(self) -> Option<usize> {
         match self {
             DataType::Boolean => None,
+            DataType::Int => None,
+            DataType::IVec2 => None,
+            DataType::IVec3 => None,
+            DataType::IVec4 => None,
             DataType::Float => Some(1),
             DataType::Vec2 => Some(2),
             DataType::Vec3 => Some(3),
@@ -30,14 +39,18 @@ impl DataType {
             DataType::Mat3 => Some(9),
             DataType::Mat4 => Some(16),
             DataType::Sampler => None,
+            DataType::SamplerCube => None,
         }
     }
-}
 
-impl From<DataType> for &str {
-    fn from(value: DataType) -> &'static str {
-        match value {
+    /// Name of the type in GLSL.
+    pub const fn glsl_name(self) -> &'static str {
+        match self {
             DataType::Boolean => "bool",
+            DataType::Int => "int",
+            DataType::IVec2 => "ivec2",
+            DataType::IVec3 => "ivec3",
+            DataType::IVec4 => "ivec4",
             DataType::Float => "float",
             DataType::Vec2 => "vec2",
             DataType::Vec3 => "vec3",
@@ -46,10 +59,17 @@ impl From<DataType> for &str {
             DataType::Mat3 => "mat3",
             DataType::Mat4 => "mat4",
             DataType::Sampler => "sampler2D",
+            DataType::SamplerCube => "samplerCube",
         }
     }
 }
 
+impl From<DataType> for &str {
+    fn from(value: DataType) -> &'static str {
+        value.glsl_name()
+    }
+}
+
 impl Display for DataType {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         formatter.write_str((*self).into())
@@ -58,7 +78,7 @@ impl Display for DataType {
 
 impl DataType {
     pub fn is_numeric(self) -> bool {
-        self != DataType::Boolean && self != DataType::Sampler
+        self != DataType::Boolean && self != DataType::Sampler && self != DataType::SamplerCube
     }
     pub fn is_vector(self) -> bool {
         self == DataType::Vec2 || self == DataType::Vec3 || self == DataType::Vec4
@@ -66,6 +86,15 @@ impl DataType {
     pub fn is_matrix(self) -> bool {
         self == DataType::Mat2 || self == DataType::Mat3 || self == DataType::Mat4
     }
+    /// `int` or `ivec`, set with integer values
+    pub fn is_integer(self) -> bool {
+        self == DataType::Int || self == DataType::IVec2 || self == DataType::IVec3 || self == DataType::IVec4
+    }
+    /// Types a vertex attribute can have, `float` and `vec`, matrices would take several attribute locations.
+    /// Checked at compile time by `derive(Attributes)`.
+    pub const fn is_attribute(self) -> bool {
+        matches!(self, DataType::Float | DataType::Vec2 | DataType::Vec3 | DataType::Vec4)
+    }
 }
 
 impl TryFrom<u32> for DataType {
@@ -73,6 +102,10 @@ impl TryFrom<u32> for DataType {
     fn try_from(value: u32) -> Result<DataType, GlError> {
         match value {
             Context::BOOL => Ok(DataType::Boolean),
+            Context::INT => Ok(DataType::Int),
+            Context::INT_VEC2 => Ok(DataType::IVec2),
+            Context::INT_VEC3 => Ok(DataType::IVec3),
+            Context::INT_VEC4 => Ok(DataType::IVec4),
             Context::FLOAT => Ok(DataType::Float),
             Context::FLOAT_VEC2 => Ok(DataType::Vec2),
             Context::FLOAT_VEC3 => Ok(DataType::Vec3),
@@ -81,6 +114,7 @@ impl TryFrom<u32> for DataType {
             Context::FLOAT_MAT3 => Ok(DataType::Mat3),
             Context::FLOAT_MAT4 => Ok(DataType::Mat4),
             Context::SAMPLER_2D => Ok(DataType::Sampler),
+            Context::SAMPLER_CUBE => Ok(DataType::SamplerCube),
             _ => Err(GlError::UnsupportedType(None))
         }
     }
@@ -90,6 +124,10 @@ impl From<DataType> for u32 {
     fn from(data_type: DataType) -> Self {
         match data_type {
             DataType::Boolean => Context::BOOL,
+            DataType::Int => Context::INT,
+            DataType::IVec2 => Context::INT_VEC2,
+            DataType::IVec3 => Context::INT_VEC3,
+            DataType::IVec4 => Context::INT_VEC4,
             DataType::Float => Context::FLOAT,
             DataType::Vec2 => Context::FLOAT_VEC2,
             DataType::Vec3 => Context::FLOAT_VEC3,
@@ -98,10 +136,120 @@ impl From<DataType> for u32 {
             DataType::Mat3 => Context::FLOAT_MAT3,
             DataType::Mat4 => Context::FLOAT_MAT4,
             DataType::Sampler => Context::SAMPLER_2D,
+            DataType::SamplerCube => Context::SAMPLER_CUBE,
         }
     }
 }
 
+#[diagnostic::on_unimplemented(
+    message = "type `{Self}` has no GLSL type",
+    label = "doesn't implement TypeMark"
+)]
 pub trait TypeMark {
-    fn data_type() -> DataType;
+    /// GLSL type known at compile time, checked against the shaders by `check_program!`.
+    /// Implementations overriding only [TypeMark::data_type] keep `None` and skip the check.
+    const DATA_TYPE: Option<DataType> = None;
+
+    fn data_type() -> DataType {
+        Self::DATA_TYPE.expect("TypeMark implementations set DATA_TYPE or override data_type")
+    }
+}
+
+/// Convert a value to IEEE 754 half precision bits, rounding to the nearest value, ties to even.
+pub(crate) fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // infinity or NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            sign
+        } else {
+            let mantissa = mantissa | 0x0080_0000;
+            let shift = (14 - exponent) as u32;
+            sign | ((mantissa >> shift) + round_up(mantissa, shift)) as u16
+        }
+    } else {
+        // Carry of the rounding goes into the exponent, up to infinity
+        (((sign as u32) | ((exponent as u32) << 10) | (mantissa >> 13)) + round_up(mantissa, 13))
+            as u16
+    }
+}
+
+/// 1 if the bits dropped by `value >> shift` are over a half, or exactly a half
+/// and the result is odd.
+fn round_up(value: u32, shift: u32) -> u32 {
+    let half = 1 << (shift - 1);
+    let dropped = value & ((1 << shift) - 1);
+    (dropped > half || (dropped == half && (value >> shift) & 1 == 1)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16_bits_of_normal_values() {
+        assert_eq!(f32_to_f16_bits(0.0), 0x0000);
+        assert_eq!(f32_to_f16_bits(-0.0), 0x8000);
+        assert_eq!(f32_to_f16_bits(1.0), 0x3c00);
+        assert_eq!(f32_to_f16_bits(-2.0), 0xc000);
+        assert_eq!(f32_to_f16_bits(0.5), 0x3800);
+        assert_eq!(f32_to_f16_bits(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16_bits(6.1035156e-5), 0x0400);
+    }
+
+    #[test]
+    fn f16_bits_of_subnormal_values() {
+        // 2^-24, the smallest subnormal
+        assert_eq!(f32_to_f16_bits(5.9604645e-8), 0x0001);
+        // 2^-15, the largest power of two below the normal range
+        assert_eq!(f32_to_f16_bits(3.0517578e-5), 0x0200);
+        assert_eq!(f32_to_f16_bits(-3.0517578e-5), 0x8200);
+        // 2^-25 is halfway between zero and 2^-24, below it everything is zero
+        assert_eq!(f32_to_f16_bits(2.9802322e-8), 0x0000);
+        assert_eq!(f32_to_f16_bits(2.9802326e-8), 0x0001);
+        assert_eq!(f32_to_f16_bits(1e-10), 0x0000);
+        assert_eq!(f32_to_f16_bits(-1e-10), 0x8000);
+        // 1.5 × 2^-24 ties to 2 × 2^-24, 2.5 × 2^-24 ties to 2 × 2^-24
+        assert_eq!(f32_to_f16_bits(8.940697e-8), 0x0002);
+        assert_eq!(f32_to_f16_bits(1.4901161e-7), 0x0002);
+    }
+
+    #[test]
+    fn f16_bits_of_infinity_and_nan() {
+        assert_eq!(f32_to_f16_bits(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16_bits(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(f32_to_f16_bits(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16_bits(1e10), 0x7c00);
+        assert_eq!(f32_to_f16_bits(-1e10), 0xfc00);
+        assert_eq!(f32_to_f16_bits(f32::NAN) & 0x7fff, 0x7e00);
+        assert_eq!(f32_to_f16_bits(-f32::NAN), 0xfe00);
+    }
+
+    #[test]
+    fn f16_bits_are_rounded_to_nearest_even() {
+        // 1 + 2^-11 is halfway between 1 and 1 + 2^-10
+        assert_eq!(f32_to_f16_bits(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16_bits(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        let above = 1.0 + 2f32.powi(-11) + 2f32.powi(-20);
+        assert_eq!(f32_to_f16_bits(above), 0x3c01);
+        let below = 1.0 + 2f32.powi(-11) - 2f32.powi(-20);
+        assert_eq!(f32_to_f16_bits(below), 0x3c00);
+        // 65519 rounds down to the largest half
+        assert_eq!(f32_to_f16_bits(65519.0), 0x7bff);
+        // 2047.5 ties to 2048, the mantissa carry goes into the exponent
+        assert_eq!(f32_to_f16_bits(2047.5), 0x6800);
+        assert_eq!(f32_to_f16_bits(0.1), 0x2e66);
+        assert_eq!(f32_to_f16_bits(1.0 / 3.0), 0x3555);
+    }
 }
@@ -1,9 +1,17 @@
+use std::borrow::Cow;
+
+use super::cube_map::CubeMap;
 use super::texture::Texture;
+use super::types::DataType;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum UniformValue {
     None,
     Boolean(bool),
+    Int(i32),
+    IVec2([i32; 2]),
+    IVec3([i32; 3]),
+    IVec4([i32; 4]),
     Float(f32),
     Vec2([f32; 2]),
     Vec3([f32; 3]),
@@ -12,18 +20,49 @@ pub enum UniformValue {
     Mat3([f32; 9]),
     Mat4([f32; 16]),
     Texture(Texture),
+    CubeMap(CubeMap),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Field {
-    pub name: &'static str,
+    /// Derived uniforms use static names, dynamic ones own them
+    pub name: Cow<'static, str>,
     pub value: UniformValue,
 }
 
+#[diagnostic::on_unimplemented(
+    message = "type `{Self}` does not implement IntoUniform",
+    label = "can't be passed as a uniform"
+)]
 pub trait IntoUniform {
+    /// GLSL type of the uniform known at compile time, checked against the shaders
+    /// by `check_program!`, `None` skips the check.
+    const UNIFORM_TYPE: Option<DataType> = None;
+
     fn into_uniform(&self) -> UniformValue;
 }
 
 pub trait Uniforms {
     fn uniforms(&self) -> Vec<Field>;
 }
+
+/// Drop the `u_` prefix added by `derive(Uniforms)`, for shaders with plain uniform names.
+pub fn unprefixed(name: &str) -> String {
+    name.strip_prefix("u_").unwrap_or(name).into()
+}
+
+/// `u_base_color` to `baseColor`, the convention of three.js and most JavaScript engines.
+pub fn camel_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for (i, word) in unprefixed(name).split('_').enumerate() {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if i > 0 => {
+                result.extend(first.to_uppercase());
+                result.push_str(chars.as_str());
+            }
+            _ => result.push_str(word),
+        }
+    }
+    result
+}
@@ -0,0 +1,251 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use js_sys::{Function, Reflect};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::HtmlVideoElement;
+
+use crate::{Gl, GlError, Texture, TextureContent, TextureFormat, TextureType};
+
+const HAVE_CURRENT_DATA: u16 = 2;
+
+/// Receives the time and the frame metadata, the metadata is undefined for animation frames
+type FrameCallback = Closure<dyn FnMut(f64, JsValue)>;
+
+/// Metadata of a presented video frame.
+///
+/// With the `requestAnimationFrame` fallback new frames are detected by the changes
+/// of the current time, `presented_frames` counts the detected ones
+/// and `expected_display_time` is the animation frame time.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VideoFrame {
+    /// Presentation timestamp in seconds
+    pub media_time: f64,
+    pub presented_frames: u32,
+    /// `performance.now()` time in milliseconds when the frame is expected to be displayed
+    pub expected_display_time: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The way new frames are detected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameNotification {
+    VideoFrameCallback,
+    /// Browsers without `requestVideoFrameCallback`
+    AnimationFrame,
+}
+
+fn property(object: &JsValue, name: &str) -> Option<f64> {
+    Reflect::get(object, &JsValue::from_str(name))
+        .ok()
+        .and_then(|value| value.as_f64())
+}
+
+fn method(object: &JsValue, name: &str) -> Option<Function> {
+    Reflect::get(object, &JsValue::from_str(name))
+        .ok()
+        .and_then(|value| value.dyn_into().ok())
+}
+
+/// Keeps a callback scheduled for every frame and stores the newest frame.
+struct FrameWatcher {
+    video: HtmlVideoElement,
+    notification: FrameNotification,
+    pending: Cell<Option<VideoFrame>>,
+    presented_frames: Cell<u32>,
+    last_time: Cell<Option<f64>>,
+    handle: Cell<Option<f64>>,
+    callback: RefCell<Option<FrameCallback>>,
+}
+
+impl FrameWatcher {
+    fn start(video: HtmlVideoElement) -> Rc<FrameWatcher> {
+        let notification = if method(&video, "requestVideoFrameCallback").is_some() {
+            FrameNotification::VideoFrameCallback
+        } else {
+            FrameNotification::AnimationFrame
+        };
+        let watcher = Rc::new(FrameWatcher {
+            video,
+            notification,
+            pending: Default::default(),
+            presented_frames: Default::default(),
+            last_time: Default::default(),
+            handle: Default::default(),
+            callback: Default::default(),
+        });
+        // The closure holds a weak reference, so the watcher is freed with the texture
+        let weak: Weak<FrameWatcher> = Rc::downgrade(&watcher);
+        let callback = Closure::wrap(Box::new(move |now: f64, metadata: JsValue| {
+            if let Some(watcher) = weak.upgrade() {
+                watcher.on_frame(now, &metadata);
+                watcher.schedule();
+            }
+        }) as Box<dyn FnMut(f64, JsValue)>);
+        watcher.callback.replace(Some(callback));
+        watcher.schedule();
+        watcher
+    }
+
+    fn schedule(&self) {
+        let callback = self.callback.borrow();
+        let function: &Function = match callback.as_ref() {
+            Some(callback) => callback.as_ref().unchecked_ref(),
+            None => return,
+        };
+        let handle = match self.notification {
+            FrameNotification::VideoFrameCallback => {
+                method(&self.video, "requestVideoFrameCallback")
+                    .and_then(|request| request.call1(&self.video, function).ok())
+                    .and_then(|handle| handle.as_f64())
+            }
+            FrameNotification::AnimationFrame => web_sys::window()
+                .and_then(|window| window.request_animation_frame(function).ok())
+                .map(f64::from),
+        };
+        if handle.is_none() {
+            trace_event!(WARN, "video frame callback can't be scheduled");
+        }
+        self.handle.set(handle);
+    }
+
+    fn cancel(&self) {
+        if let Some(handle) = self.handle.take() {
+            match self.notification {
+                FrameNotification::VideoFrameCallback => {
+                    if let Some(cancel) = method(&self.video, "cancelVideoFrameCallback") {
+                        let _ = cancel.call1(&self.video, &JsValue::from_f64(handle));
+                    }
+                }
+                FrameNotification::AnimationFrame => {
+                    if let Some(window) = web_sys::window() {
+                        let _ = window.cancel_animation_frame(handle as i32);
+                    }
+                }
+            }
+        }
+        self.callback.replace(None);
+    }
+
+    fn on_frame(&self, now: f64, metadata: &JsValue) {
+        let video = &self.video;
+        let frame = match self.notification {
+            FrameNotification::VideoFrameCallback => VideoFrame {
+                media_time: property(metadata, "mediaTime").unwrap_or_else(|| video.current_time()),
+                presented_frames: property(metadata, "presentedFrames").unwrap_or(0.0) as u32,
+                expected_display_time: property(metadata, "expectedDisplayTime").unwrap_or(now),
+                width: property(metadata, "width")
+                    .map_or_else(|| video.video_width(), |v| v as u32),
+                height: property(metadata, "height")
+                    .map_or_else(|| video.video_height(), |v| v as u32),
+            },
+            FrameNotification::AnimationFrame => {
+                let time = video.current_time();
+                if video.ready_state() < HAVE_CURRENT_DATA || self.last_time.get() == Some(time) {
+                    return;
+                }
+                self.last_time.set(Some(time));
+                self.presented_frames.set(self.presented_frames.get() + 1);
+                VideoFrame {
+                    media_time: time,
+                    presented_frames: self.presented_frames.get(),
+                    expected_display_time: now,
+                    width: video.video_width(),
+                    height: video.video_height(),
+                }
+            }
+        };
+        self.pending.set(Some(frame));
+    }
+}
+
+/// Texture following a video, uploads only the frames which weren't uploaded yet.
+///
+/// New frames are detected with `requestVideoFrameCallback`, or with `requestAnimationFrame`
+/// where it's not supported. Call [VideoTexture::update] before drawing, the texture
+/// is recreated when the video size changes, so get it after the update.
+pub struct VideoTexture {
+    gl: Gl,
+    watcher: Rc<FrameWatcher>,
+    texture: RefCell<Option<Texture>>,
+    frame: Cell<Option<VideoFrame>>,
+}
+
+impl fmt::Debug for VideoTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VideoTexture")
+            .field("notification", &self.watcher.notification)
+            .field("texture", &self.texture)
+            .field("frame", &self.frame)
+            .finish()
+    }
+}
+
+impl VideoTexture {
+    pub fn new(gl: Gl, video: HtmlVideoElement) -> VideoTexture {
+        VideoTexture {
+            gl,
+            watcher: FrameWatcher::start(video),
+            texture: Default::default(),
+            frame: Default::default(),
+        }
+    }
+
+    pub fn video(&self) -> &HtmlVideoElement {
+        &self.watcher.video
+    }
+
+    pub fn notification(&self) -> FrameNotification {
+        self.watcher.notification
+    }
+
+    /// Texture with the last uploaded frame, `None` before the first one.
+    pub fn texture(&self) -> Option<Texture> {
+        self.texture.borrow().clone()
+    }
+
+    /// Metadata of the last uploaded frame.
+    pub fn frame(&self) -> Option<VideoFrame> {
+        self.frame.get()
+    }
+
+    /// A new frame is presented since the last update.
+    pub fn has_new_frame(&self) -> bool {
+        self.watcher.pending.get().is_some()
+    }
+
+    /// Upload the newest frame if there is one, returns whether the texture is changed.
+    pub fn update(&self) -> Result<bool, GlError> {
+        let frame = match self.watcher.pending.take() {
+            Some(frame) if frame.width > 0 && frame.height > 0 => frame,
+            _ => return Ok(false),
+        };
+        let current = self.texture();
+        let texture = match current {
+            Some(texture) if texture.size() == (frame.width, frame.height) => texture,
+            _ => {
+                let texture = self.gl.texture(
+                    frame.width,
+                    frame.height,
+                    TextureType::Byte,
+                    TextureFormat::Rgba,
+                    TextureContent::None,
+                )?;
+                self.texture.replace(Some(texture.clone()));
+                texture
+            }
+        };
+        texture.write_video(&self.watcher.video)?;
+        self.frame.set(Some(frame));
+        Ok(true)
+    }
+}
+
+impl Drop for VideoTexture {
+    fn drop(&mut self) {
+        self.watcher.cancel();
+    }
+}
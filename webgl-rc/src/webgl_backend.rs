@@ -0,0 +1,948 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use js_sys::{Array, Error, Float32Array, Int32Array, JsString, Object, Uint16Array, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    AngleInstancedArrays, OesTextureHalfFloat, WebGl2RenderingContext, WebGlBuffer,
+    WebGlFramebuffer, WebGlProgram, WebGlRenderbuffer, WebGlRenderingContext as Context,
+    WebGlShader, WebGlTexture, WebGlUniformLocation, WebglDepthTexture, WebglLoseContext,
+};
+
+use crate::backend::{
+    AttributeInfo, Backend, LinkOptions, PixelData, ProgramLayout, TextureSource, UniformInfo,
+};
+use crate::extensions::{Extension, ExtensionCache};
+use crate::features::Feature;
+use crate::recorder::Command;
+use crate::state::StateValue;
+use crate::types::DataType;
+use crate::{GlError, WebGlVersion};
+
+/// Parts of the API which differ between WebGL versions.
+#[derive(Debug)]
+enum Api {
+    WebGl1,
+    WebGl2(WebGl2RenderingContext),
+}
+
+/// WebGL context, the calls common for both versions go through the WebGL1 interface.
+#[derive(Debug)]
+pub(crate) struct WebGlBackend {
+    context: Context,
+    api: Api,
+    extensions: ExtensionCache,
+    objects: RefCell<HashMap<u64, JsValue>>,
+    uniforms: RefCell<HashMap<u64, HashMap<String, WebGlUniformLocation>>>,
+    /// Current program, uniform locations are resolved against it
+    program: Cell<Option<u64>>,
+}
+
+fn get_extension<Ex: JsCast>(context: &Context, name: &str) -> Result<Ex, GlError> {
+    context
+        .get_extension(name)
+        .map_err(|_| GlError::ExtensionNotFound(name.into()))
+        .map(|value| match value {
+            Some(extension) => Ok(Ex::unchecked_from_js(extension.into())),
+            None => Err(GlError::ExtensionNotFound(name.into())),
+        })
+        .unwrap_or_else(|error| Err(error))
+}
+
+fn object<T: JsCast>(
+    objects: &HashMap<u64, JsValue>,
+    id: Option<u64>,
+) -> Result<Option<&T>, GlError> {
+    id.map(|id| {
+        objects
+            .get(&id)
+            .map(|handle| handle.unchecked_ref())
+            .ok_or(GlError::ObjectNotFound(id))
+    })
+    .transpose()
+}
+
+fn js_error(error: JsValue) -> Option<String> {
+    Some(Error::from(error).message().into())
+}
+
+impl WebGlBackend {
+    /// Extensions are requested when they're used first, instancing, textures and indices
+    /// enable the ones they require.
+    pub fn webgl1(context: Context) -> Result<WebGlBackend, GlError> {
+        Ok(WebGlBackend::with_api(context, Api::WebGl1))
+    }
+
+    /// Instancing, depth textures, 32-bit indices and half-float textures are a part of WebGL2,
+    /// float render targets still require `EXT_color_buffer_float`.
+    pub fn webgl2(context: WebGl2RenderingContext) -> Result<WebGlBackend, GlError> {
+        let common: Context = context.clone().unchecked_into();
+        Ok(WebGlBackend::with_api(common, Api::WebGl2(context)))
+    }
+
+    fn with_api(context: Context, api: Api) -> WebGlBackend {
+        WebGlBackend {
+            context,
+            api,
+            extensions: Default::default(),
+            objects: Default::default(),
+            uniforms: Default::default(),
+            program: Default::default(),
+        }
+    }
+
+    /// WebGL2 has half floats in the core, with a different constant.
+    fn attribute_type(&self, data_type: u32) -> u32 {
+        match (&self.api, data_type) {
+            (Api::WebGl2(_), OesTextureHalfFloat::HALF_FLOAT_OES) => {
+                WebGl2RenderingContext::HALF_FLOAT
+            }
+            (_, data_type) => data_type,
+        }
+    }
+
+    /// Textures of the type can be sampled with filtering and rendered into where it's supported,
+    /// it's an error to use them before the extensions are requested.
+    fn enable_texture_extensions(&self, data_type: u32) {
+        let extensions: &[Extension] = match (&self.api, data_type) {
+            (Api::WebGl1, OesTextureHalfFloat::HALF_FLOAT_OES) => &[
+                Extension::OesTextureHalfFloat,
+                Extension::OesTextureHalfFloatLinear,
+                Extension::ExtColorBufferHalfFloat,
+            ],
+            (Api::WebGl1, Context::FLOAT) => &[
+                Extension::OesTextureFloat,
+                Extension::OesTextureFloatLinear,
+                Extension::WebglColorBufferFloat,
+            ],
+            (Api::WebGl1, Context::UNSIGNED_SHORT)
+            | (Api::WebGl1, Context::UNSIGNED_INT)
+            | (Api::WebGl1, WebglDepthTexture::UNSIGNED_INT_24_8_WEBGL) => {
+                &[Extension::WebglDepthTexture]
+            }
+            (Api::WebGl2(_), OesTextureHalfFloat::HALF_FLOAT_OES) => {
+                &[Extension::ExtColorBufferFloat]
+            }
+            (Api::WebGl2(_), Context::FLOAT) => &[
+                Extension::ExtColorBufferFloat,
+                Extension::OesTextureFloatLinear,
+            ],
+            _ => &[],
+        };
+        for extension in extensions {
+            self.extension(*extension);
+        }
+    }
+
+    fn angle_instanced_arrays(&self) -> Result<&AngleInstancedArrays, GlError> {
+        self.extension(Extension::AngleInstancedArrays)
+            .map(|extension| extension.unchecked_ref())
+            .ok_or_else(|| {
+                GlError::ExtensionNotFound(Extension::AngleInstancedArrays.name().into())
+            })
+    }
+
+    /// Internal format and data type for texture uploads.
+    /// WebGL2 requires sized internal formats for float and depth textures.
+    fn texture_format(&self, format: u32, data_type: u32) -> Result<(i32, u32), GlError> {
+        self.enable_texture_extensions(data_type);
+        if let Api::WebGl1 = self.api {
+            return Ok((format as i32, data_type));
+        }
+        let data_type = match data_type {
+            OesTextureHalfFloat::HALF_FLOAT_OES => WebGl2RenderingContext::HALF_FLOAT,
+            data_type => data_type,
+        };
+        let internal_format = match (format, data_type) {
+            (format, Context::UNSIGNED_BYTE) => format,
+            (Context::RGBA, Context::FLOAT) => WebGl2RenderingContext::RGBA32F,
+            (Context::RGB, Context::FLOAT) => WebGl2RenderingContext::RGB32F,
+            (Context::RGBA, WebGl2RenderingContext::HALF_FLOAT) => WebGl2RenderingContext::RGBA16F,
+            (Context::RGB, WebGl2RenderingContext::HALF_FLOAT) => WebGl2RenderingContext::RGB16F,
+            (Context::DEPTH_COMPONENT, Context::UNSIGNED_SHORT) => Context::DEPTH_COMPONENT16,
+            (Context::DEPTH_COMPONENT, Context::UNSIGNED_INT) => {
+                WebGl2RenderingContext::DEPTH_COMPONENT24
+            }
+            (Context::DEPTH_STENCIL, WebGl2RenderingContext::UNSIGNED_INT_24_8) => {
+                WebGl2RenderingContext::DEPTH24_STENCIL8
+            }
+            _ => {
+                return Err(GlError::UnsupportedType(Some(format!(
+                    "Texture format {:#x} with data type {:#x} isn't available in WebGL2",
+                    format, data_type
+                ))))
+            }
+        };
+        Ok((internal_format as i32, data_type))
+    }
+
+    fn insert(&self, id: u64, handle: Option<impl Into<JsValue>>) -> Result<(), GlError> {
+        let handle = handle.ok_or_else(|| {
+            GlError::UnknownError(Some(format!("Failed to create object #{}", id)))
+        })?;
+        self.objects.borrow_mut().insert(id, handle.into());
+        Ok(())
+    }
+
+    fn remove<T: JsCast>(&self, id: u64) -> Option<T> {
+        self.objects
+            .borrow_mut()
+            .remove(&id)
+            .map(|handle| handle.unchecked_into())
+    }
+
+    fn collect_attributes(&self, program: &WebGlProgram) -> Result<Vec<AttributeInfo>, GlError> {
+        let ctx = &self.context;
+        let attributes_count = ctx
+            .get_program_parameter(program, Context::ACTIVE_ATTRIBUTES)
+            .as_f64()
+            .ok_or_else(|| GlError::UnknownError(Some("Failed to get attributes count".into())))?
+            as u32;
+
+        let mut result = Vec::with_capacity(attributes_count as usize);
+
+        for i in 0..attributes_count {
+            let info = ctx.get_active_attrib(program, i).ok_or_else(|| {
+                GlError::UnknownError(Some("Failed to get attribute info".into()))
+            })?;
+
+            // Arrays are not supported
+            if info.size() != 1 {
+                return Err(GlError::UnsupportedType(Some(info.name())));
+            }
+
+            let location = ctx.get_attrib_location(program, &info.name());
+            result.push(AttributeInfo {
+                name: info.name(),
+                data_type: DataType::try_from(info.type_())
+                    .map_err(|_| GlError::UnsupportedType(Some(info.name())))?,
+                location: location.try_into().map_err(|_| {
+                    GlError::UnknownError(Some("Negative attribute location".to_string()))
+                })?,
+            });
+        }
+        Ok(result)
+    }
+
+    fn collect_uniforms(
+        &self,
+        program: &WebGlProgram,
+    ) -> Result<(Vec<UniformInfo>, HashMap<String, WebGlUniformLocation>), GlError> {
+        let ctx = &self.context;
+        let uniforms_count = ctx
+            .get_program_parameter(program, Context::ACTIVE_UNIFORMS)
+            .as_f64()
+            .ok_or_else(|| GlError::UnknownError(Some("Failed to get uniforms count".into())))?
+            as u32;
+
+        let mut result = Vec::with_capacity(uniforms_count as usize);
+        let mut locations = HashMap::with_capacity(uniforms_count as usize);
+
+        for i in 0..uniforms_count {
+            let info = ctx
+                .get_active_uniform(program, i)
+                .ok_or_else(|| GlError::UnknownError(Some("Failed to get uniform info".into())))?;
+
+            // Arrays are not supported
+            if info.size() != 1 {
+                return Err(GlError::UnsupportedType(Some(info.name())));
+            }
+
+            let location = ctx
+                .get_uniform_location(program, &info.name())
+                .ok_or_else(|| {
+                    GlError::UnknownError(Some("Failed to get uniform location".into()))
+                })?;
+            result.push(UniformInfo {
+                name: info.name(),
+                data_type: DataType::try_from(info.type_())
+                    .map_err(|_| GlError::UnsupportedType(Some(info.name())))?,
+            });
+            locations.insert(info.name(), location);
+        }
+
+        Ok((result, locations))
+    }
+
+    fn set_uniform(&self, name: &str, data_type: u32, values: &[f32]) {
+        let uniforms = self.uniforms.borrow();
+        let location = self
+            .program
+            .get()
+            .and_then(|program| uniforms.get(&program))
+            .and_then(|locations| locations.get(name));
+        let context = &self.context;
+        match data_type {
+            Context::FLOAT => context.uniform1fv_with_f32_array(location, values),
+            Context::FLOAT_VEC2 => context.uniform2fv_with_f32_array(location, values),
+            Context::FLOAT_VEC3 => context.uniform3fv_with_f32_array(location, values),
+            Context::FLOAT_VEC4 => context.uniform4fv_with_f32_array(location, values),
+            Context::FLOAT_MAT2 => {
+                context.uniform_matrix2fv_with_f32_array(location, false, values)
+            }
+            Context::FLOAT_MAT3 => {
+                context.uniform_matrix3fv_with_f32_array(location, false, values)
+            }
+            Context::FLOAT_MAT4 => {
+                context.uniform_matrix4fv_with_f32_array(location, false, values)
+            }
+            _ => context.uniform1i(location, values.first().copied().unwrap_or(0.0) as i32),
+        }
+    }
+
+    fn set_uniform_int(&self, name: &str, data_type: u32, values: &[i32]) {
+        let uniforms = self.uniforms.borrow();
+        let location = self
+            .program
+            .get()
+            .and_then(|program| uniforms.get(&program))
+            .and_then(|locations| locations.get(name));
+        let context = &self.context;
+        match data_type {
+            Context::INT_VEC2 => context.uniform2iv_with_i32_array(location, values),
+            Context::INT_VEC3 => context.uniform3iv_with_i32_array(location, values),
+            Context::INT_VEC4 => context.uniform4iv_with_i32_array(location, values),
+            _ => context.uniform1iv_with_i32_array(location, values),
+        }
+    }
+}
+
+impl Backend for WebGlBackend {
+    fn execute(&self, command: &Command) -> Result<(), GlError> {
+        let context = &self.context;
+        match command {
+            Command::CreateBuffer { id } => return self.insert(*id, context.create_buffer()),
+            Command::CreateTexture { id } => return self.insert(*id, context.create_texture()),
+            Command::CreateFramebuffer { id } => {
+                return self.insert(*id, context.create_framebuffer())
+            }
+            Command::CreateRenderbuffer { id } => {
+                return self.insert(*id, context.create_renderbuffer())
+            }
+            Command::DeleteBuffer { id } => {
+                context.delete_buffer(self.remove::<WebGlBuffer>(*id).as_ref());
+                return Ok(());
+            }
+            Command::DeleteTexture { id } => {
+                context.delete_texture(self.remove::<WebGlTexture>(*id).as_ref());
+                return Ok(());
+            }
+            Command::DeleteFramebuffer { id } => {
+                context.delete_framebuffer(self.remove::<WebGlFramebuffer>(*id).as_ref());
+                return Ok(());
+            }
+            Command::DeleteRenderbuffer { id } => {
+                context.delete_renderbuffer(self.remove::<WebGlRenderbuffer>(*id).as_ref());
+                return Ok(());
+            }
+            Command::DeleteShader { id } => {
+                context.delete_shader(self.remove::<WebGlShader>(*id).as_ref());
+                return Ok(());
+            }
+            Command::DeleteProgram { id } => {
+                self.uniforms.borrow_mut().remove(id);
+                context.delete_program(self.remove::<WebGlProgram>(*id).as_ref());
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let objects = self.objects.borrow();
+        match command {
+            Command::CreateBuffer { .. }
+            | Command::CreateTexture { .. }
+            | Command::CreateFramebuffer { .. }
+            | Command::CreateRenderbuffer { .. }
+            | Command::DeleteBuffer { .. }
+            | Command::DeleteTexture { .. }
+            | Command::DeleteFramebuffer { .. }
+            | Command::DeleteRenderbuffer { .. }
+            | Command::DeleteShader { .. }
+            | Command::DeleteProgram { .. } => {}
+            // Objects with content are created through the dedicated methods
+            Command::CreateShader { id, .. } | Command::CreateProgram { id, .. } => {
+                return Err(GlError::UnknownError(Some(format!(
+                    "Object #{} can't be created from a command",
+                    id
+                ))))
+            }
+            Command::BufferData { .. }
+            | Command::BufferSubData { .. }
+            | Command::TexImage2d { .. }
+            | Command::TexSubImage2d { .. }
+            | Command::ReadPixels { .. } => {
+                return Err(GlError::UnknownError(Some(format!(
+                    "{} can't be executed without data",
+                    command.name()
+                ))))
+            }
+            Command::BindBuffer { target, buffer } => {
+                context.bind_buffer(*target, object::<WebGlBuffer>(&objects, *buffer)?)
+            }
+            Command::ActiveTexture { unit } => context.active_texture(*unit),
+            Command::BindTexture { target, texture } => {
+                context.bind_texture(*target, object::<WebGlTexture>(&objects, *texture)?)
+            }
+            Command::TexParameter {
+                target,
+                parameter,
+                value,
+            } => context.tex_parameteri(*target, *parameter, *value),
+            Command::PixelStore { parameter, value } => context.pixel_storei(*parameter, *value),
+            Command::BindFramebuffer { framebuffer } => context.bind_framebuffer(
+                Context::FRAMEBUFFER,
+                object::<WebGlFramebuffer>(&objects, *framebuffer)?,
+            ),
+            Command::FramebufferTexture2d {
+                attachment,
+                target,
+                texture,
+            } => context.framebuffer_texture_2d(
+                Context::FRAMEBUFFER,
+                *attachment,
+                *target,
+                object::<WebGlTexture>(&objects, *texture)?,
+                0,
+            ),
+            Command::FramebufferRenderbuffer {
+                attachment,
+                renderbuffer,
+            } => context.framebuffer_renderbuffer(
+                Context::FRAMEBUFFER,
+                *attachment,
+                Context::RENDERBUFFER,
+                object::<WebGlRenderbuffer>(&objects, *renderbuffer)?,
+            ),
+            Command::BindRenderbuffer { renderbuffer } => context.bind_renderbuffer(
+                Context::RENDERBUFFER,
+                object::<WebGlRenderbuffer>(&objects, *renderbuffer)?,
+            ),
+            Command::RenderbufferStorage {
+                format,
+                width,
+                height,
+            } => context.renderbuffer_storage(
+                Context::RENDERBUFFER,
+                *format,
+                *width as i32,
+                *height as i32,
+            ),
+            Command::UseProgram { program } => {
+                context.use_program(object::<WebGlProgram>(&objects, *program)?);
+                self.program.set(*program);
+            }
+            Command::Enable { capability } => context.enable(*capability),
+            Command::Disable { capability } => context.disable(*capability),
+            Command::BlendEquation { color, alpha } => {
+                context.blend_equation_separate(*color, *alpha)
+            }
+            Command::BlendFunction {
+                src_rgb,
+                dst_rgb,
+                src_alpha,
+                dst_alpha,
+            } => context.blend_func_separate(*src_rgb, *dst_rgb, *src_alpha, *dst_alpha),
+            Command::DepthFunction { function } => context.depth_func(*function),
+            Command::CullFace { face } => context.cull_face(*face),
+            Command::ColorMask { r, g, b, a } => context.color_mask(*r, *g, *b, *a),
+            Command::DepthMask { value } => context.depth_mask(*value),
+            Command::StencilFunction {
+                function,
+                reference,
+                mask,
+            } => context.stencil_func(*function, *reference, *mask),
+            Command::StencilOperation {
+                fail,
+                depth_fail,
+                pass,
+            } => context.stencil_op(*fail, *depth_fail, *pass),
+            Command::StencilMask { mask } => context.stencil_mask(*mask),
+            Command::ClearColor { r, g, b, a } => context.clear_color(*r, *g, *b, *a),
+            Command::ClearDepth { value } => context.clear_depth(*value),
+            Command::ClearStencil { value } => context.clear_stencil(*value),
+            Command::Viewport {
+                x,
+                y,
+                width,
+                height,
+            } => context.viewport(*x, *y, *width, *height),
+            Command::Scissor {
+                x,
+                y,
+                width,
+                height,
+            } => context.scissor(*x, *y, *width, *height),
+            Command::Clear { mask } => context.clear(*mask),
+            Command::EnableVertexAttribArray { location } => {
+                context.enable_vertex_attrib_array(*location)
+            }
+            Command::DisableVertexAttribArray { location } => {
+                context.disable_vertex_attrib_array(*location)
+            }
+            Command::VertexAttribDivisor { location, divisor } => match &self.api {
+                Api::WebGl1 => self
+                    .angle_instanced_arrays()?
+                    .vertex_attrib_divisor_angle(*location, *divisor),
+                Api::WebGl2(context) => context.vertex_attrib_divisor(*location, *divisor),
+            },
+            Command::VertexAttribPointer {
+                location,
+                size,
+                data_type,
+                normalized,
+                stride,
+                offset,
+            } => context.vertex_attrib_pointer_with_i32(
+                *location,
+                *size,
+                self.attribute_type(*data_type),
+                *normalized,
+                *stride,
+                *offset,
+            ),
+            Command::Uniform {
+                name,
+                data_type,
+                values,
+            } => self.set_uniform(name, *data_type, values),
+            Command::UniformInt {
+                name,
+                data_type,
+                values,
+            } => self.set_uniform_int(name, *data_type, values),
+            Command::DrawArrays { mode, first, count } => {
+                context.draw_arrays(*mode, *first, *count)
+            }
+            Command::DrawElements {
+                mode,
+                count,
+                data_type,
+                offset,
+            } => context.draw_elements_with_i32(*mode, *count, *data_type, *offset),
+            Command::DrawArraysInstanced {
+                mode,
+                first,
+                count,
+                instances,
+            } => match &self.api {
+                Api::WebGl1 => self
+                    .angle_instanced_arrays()?
+                    .draw_arrays_instanced_angle(*mode, *first, *count, *instances),
+                Api::WebGl2(context) => {
+                    context.draw_arrays_instanced(*mode, *first, *count, *instances)
+                }
+            },
+            Command::DrawElementsInstanced {
+                mode,
+                count,
+                data_type,
+                offset,
+                instances,
+            } => match &self.api {
+                Api::WebGl1 => self
+                    .angle_instanced_arrays()?
+                    .draw_elements_instanced_angle_with_i32(
+                        *mode, *count, *data_type, *offset, *instances,
+                    ),
+                Api::WebGl2(context) => context.draw_elements_instanced_with_i32(
+                    *mode, *count, *data_type, *offset, *instances,
+                ),
+            },
+            Command::GenerateMipmap { target } => context.generate_mipmap(*target),
+            Command::Flush {} => context.flush(),
+            Command::Finish {} => context.finish(),
+            // Groups are only shown in the console of debug builds, WebGL has no markers
+            Command::PushDebugGroup { name } => {
+                if cfg!(debug_assertions) {
+                    web_sys::console::group_1(&JsValue::from_str(name));
+                }
+            }
+            Command::PopDebugGroup {} => {
+                if cfg!(debug_assertions) {
+                    web_sys::console::group_end();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_shader(&self, id: u64, shader_type: u32, source: &str) -> Result<(), GlError> {
+        let ctx = &self.context;
+        let handle = ctx
+            .create_shader(shader_type)
+            .ok_or(GlError::UnknownError(None))?;
+
+        ctx.shader_source(&handle, source);
+        ctx.compile_shader(&handle);
+
+        let status = ctx
+            .get_shader_parameter(&handle, Context::COMPILE_STATUS)
+            .as_bool()
+            .ok_or(GlError::UnknownError(None))?;
+
+        if !status {
+            let info = ctx.get_shader_info_log(&handle);
+            ctx.delete_shader(Some(&handle));
+            return Err(GlError::ShaderCompilationError {
+                source: source.into(),
+                info,
+            });
+        }
+
+        self.insert(id, Some(handle))
+    }
+
+    fn link_program(
+        &self,
+        id: u64,
+        vertex_shader: u64,
+        fragment_shader: u64,
+        options: &LinkOptions,
+    ) -> Result<ProgramLayout, GlError> {
+        let ctx = &self.context;
+        if options.transform_feedback.is_some() && matches!(self.api, Api::WebGl1) {
+            return Err(GlError::FeatureNotSupported(Feature::WebGl2));
+        }
+        let program = ctx
+            .create_program()
+            .ok_or_else(|| GlError::UnknownError(Some("Program creation failed".into())))?;
+        {
+            let objects = self.objects.borrow();
+            if let Some(shader) = object::<WebGlShader>(&objects, Some(vertex_shader))? {
+                ctx.attach_shader(&program, shader);
+            }
+            if let Some(shader) = object::<WebGlShader>(&objects, Some(fragment_shader))? {
+                ctx.attach_shader(&program, shader);
+            }
+        }
+        for (name, location) in options.attribute_locations.iter() {
+            ctx.bind_attrib_location(&program, *location, name);
+        }
+        if let (Api::WebGl2(context), Some((varyings, mode))) =
+            (&self.api, &options.transform_feedback)
+        {
+            let varyings: Array = varyings
+                .iter()
+                .map(|name| JsValue::from_str(name))
+                .collect();
+            context.transform_feedback_varyings(&program, &varyings, *mode);
+        }
+        ctx.link_program(&program);
+
+        let link_status = ctx
+            .get_program_parameter(&program, Context::LINK_STATUS)
+            .as_bool()
+            .ok_or_else(|| GlError::UnknownError(Some("Failed to get linking status".into())))?;
+
+        if !link_status {
+            let info = ctx.get_program_info_log(&program);
+            ctx.delete_program(Some(&program));
+            return Err(GlError::ProgramLinkingError {
+                vertex: String::new(),
+                fragment: String::new(),
+                info,
+            });
+        }
+
+        let attributes = self.collect_attributes(&program)?;
+        let (uniforms, locations) = self.collect_uniforms(&program)?;
+        self.uniforms.borrow_mut().insert(id, locations);
+        self.insert(id, Some(program))?;
+
+        Ok(ProgramLayout {
+            attributes,
+            uniforms,
+        })
+    }
+
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        self.context.buffer_data_with_u8_array(target, data, usage);
+    }
+
+    fn buffer_sub_data(&self, target: u32, offset: usize, data: &[u8]) {
+        self.context
+            .buffer_sub_data_with_i32_and_u8_array(target, offset as i32, data);
+    }
+
+    fn tex_image(
+        &self,
+        target: u32,
+        format: u32,
+        width: u32,
+        height: u32,
+        data_type: u32,
+        source: TextureSource,
+    ) -> Result<(), GlError> {
+        let (internal_format, data_type) = self.texture_format(format, data_type)?;
+        match source {
+            TextureSource::Image(image) => self
+                .context
+                .tex_image_2d_with_u32_and_u32_and_image(
+                    target,
+                    0,
+                    internal_format,
+                    format,
+                    data_type,
+                    image,
+                )
+                .map_err(|e| GlError::WritePixelsError(Some(JsString::from(e).into()))),
+            TextureSource::ImageBitmap(bitmap) => self
+                .context
+                .tex_image_2d_with_u32_and_u32_and_image_bitmap(
+                    target,
+                    0,
+                    internal_format,
+                    format,
+                    data_type,
+                    bitmap,
+                )
+                .map_err(|e| GlError::WritePixelsError(Some(JsString::from(e).into()))),
+            TextureSource::Video(video) => self
+                .context
+                .tex_image_2d_with_u32_and_u32_and_video(
+                    target,
+                    0,
+                    internal_format,
+                    format,
+                    data_type,
+                    video,
+                )
+                .map_err(|e| GlError::WritePixelsError(Some(JsString::from(e).into()))),
+            TextureSource::Bytes(bytes) => self
+                .context
+                .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    target,
+                    0,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                    0,
+                    format,
+                    data_type,
+                    Some(bytes),
+                )
+                .map_err(|e| GlError::WritePixelsError(Some(JsString::from(e).into()))),
+            TextureSource::None => self
+                .context
+                .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    target,
+                    0,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                    0,
+                    format,
+                    data_type,
+                    None,
+                )
+                .map_err(|e| GlError::InitTextureBufferError(Some(JsString::from(e).into()))),
+        }
+    }
+
+    fn tex_sub_image(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        format: u32,
+        data_type: u32,
+        data: PixelData,
+    ) -> Result<(), GlError> {
+        let (_, data_type) = self.texture_format(format, data_type)?;
+        let data: Object = match data {
+            PixelData::Bytes(data) => Uint8Array::from(data).into(),
+            PixelData::Shorts(data) => Uint16Array::from(data).into(),
+            PixelData::Floats(data) => Float32Array::from(data).into(),
+        };
+        self.context
+            .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_array_buffer_view(
+                Context::TEXTURE_2D,
+                0,
+                x,
+                y,
+                width as i32,
+                height as i32,
+                format,
+                data_type,
+                Some(&data),
+            )
+            .map_err(|e| GlError::WritePixelsError(Some(JsString::from(e).into())))
+    }
+
+    fn read_pixels(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        output: &mut [u8],
+    ) -> Result<(), GlError> {
+        self.context
+            .read_pixels_with_opt_u8_array(
+                x,
+                y,
+                width as i32,
+                height as i32,
+                Context::RGBA,
+                Context::UNSIGNED_BYTE,
+                Some(output),
+            )
+            .map_err(|error| GlError::ReadPixelsError(js_error(error)))
+    }
+
+    fn read_pixels_into_buffer(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        output: &Uint8Array,
+    ) -> Result<(), GlError> {
+        self.context
+            .read_pixels_with_opt_array_buffer_view(
+                x,
+                y,
+                width as i32,
+                height as i32,
+                Context::RGBA,
+                Context::UNSIGNED_BYTE,
+                Some(output),
+            )
+            .map_err(|error| GlError::ReadPixelsError(js_error(error)))
+    }
+
+    fn frame_buffer_status(&self) -> u32 {
+        self.context.check_framebuffer_status(Context::FRAMEBUFFER)
+    }
+
+    fn parameter(
+        &self,
+        parameter: u32,
+        texture_unit: Option<u32>,
+        cached: &StateValue,
+    ) -> Option<StateValue> {
+        let context = &self.context;
+        let value = match texture_unit {
+            Some(unit) => {
+                let active = context
+                    .get_parameter(Context::ACTIVE_TEXTURE)
+                    .ok()?
+                    .as_f64()?;
+                context.active_texture(Context::TEXTURE0 + unit);
+                let value = context.get_parameter(parameter);
+                context.active_texture(active as u32);
+                value
+            }
+            None => context.get_parameter(parameter),
+        }
+        .ok()?;
+        Some(match cached {
+            StateValue::Bool(_) => StateValue::Bool(value.as_bool()?),
+            StateValue::Int(_) => StateValue::Int(value.as_f64()? as i64),
+            StateValue::Enum(_) => StateValue::Enum(value.as_f64()? as u32),
+            StateValue::Float(_) => StateValue::Float(value.as_f64()? as f32),
+            StateValue::Bools(_) => StateValue::Bools(
+                value
+                    .dyn_into::<Array>()
+                    .ok()?
+                    .iter()
+                    .map(|v| v.as_bool())
+                    .collect::<Option<_>>()?,
+            ),
+            StateValue::Ints(_) => StateValue::Ints(value.dyn_into::<Int32Array>().ok()?.to_vec()),
+            StateValue::Floats(_) => {
+                StateValue::Floats(value.dyn_into::<Float32Array>().ok()?.to_vec())
+            }
+            StateValue::Object(_) if value.is_null() => StateValue::Object(None),
+            // Objects created outside of `Gl` are reported as unknown
+            StateValue::Object(_) => StateValue::Object(Some(
+                self.objects
+                    .borrow()
+                    .iter()
+                    .find(|(_, object)| Object::is(object, &value))
+                    .map(|(id, _)| *id)?,
+            )),
+        })
+    }
+
+    fn depth_texture_supported(&self) -> bool {
+        match &self.api {
+            Api::WebGl1 => self.extension(Extension::WebglDepthTexture).is_some(),
+            Api::WebGl2(_) => true,
+        }
+    }
+
+    fn extension_supported(&self, name: &str) -> bool {
+        match Extension::ALL
+            .iter()
+            .find(|extension| extension.name() == name)
+        {
+            Some(extension) => self.extension(*extension).is_some(),
+            None => get_extension::<JsValue>(&self.context, name).is_ok(),
+        }
+    }
+
+    fn extension(&self, extension: Extension) -> Option<&JsValue> {
+        self.extensions.get(&self.context, extension)
+    }
+
+    fn element_index_uint_supported(&self) -> bool {
+        match &self.api {
+            Api::WebGl1 => self.extension(Extension::OesElementIndexUint).is_some(),
+            Api::WebGl2(_) => true,
+        }
+    }
+
+    fn version(&self) -> WebGlVersion {
+        match self.api {
+            Api::WebGl1 => WebGlVersion::WebGl1,
+            Api::WebGl2(_) => WebGlVersion::WebGl2,
+        }
+    }
+
+    fn drawing_buffer_size(&self) -> (i32, i32) {
+        (
+            self.context.drawing_buffer_width(),
+            self.context.drawing_buffer_height(),
+        )
+    }
+
+    fn is_context_lost(&self) -> bool {
+        self.context.is_context_lost()
+    }
+
+    /// Objects of the lost context are invalid, the ones created before restoring
+    /// should be recreated by the application.
+    fn simulate_context_loss(&self, lost: bool) -> Result<(), GlError> {
+        let extension: &WebglLoseContext = self
+            .extension(Extension::WebglLoseContext)
+            .map(|extension| extension.unchecked_ref())
+            .ok_or_else(|| GlError::ExtensionNotFound(Extension::WebglLoseContext.name().into()))?;
+        if lost {
+            extension.lose_context();
+        } else {
+            extension.restore_context();
+        }
+        Ok(())
+    }
+
+    fn context(&self) -> Option<&Context> {
+        Some(&self.context)
+    }
+
+    fn webgl2_context(&self) -> Option<&WebGl2RenderingContext> {
+        match &self.api {
+            Api::WebGl1 => None,
+            Api::WebGl2(context) => Some(context),
+        }
+    }
+
+    fn instanced_arrays(&self) -> Option<&AngleInstancedArrays> {
+        match &self.api {
+            Api::WebGl1 => self.angle_instanced_arrays().ok(),
+            Api::WebGl2(_) => None,
+        }
+    }
+}
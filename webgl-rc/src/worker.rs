@@ -0,0 +1,486 @@
+//! Rendering in a worker, see [RenderProxy] and [WorkerRenderer].
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+use js_sys::{Array, Atomics, Float32Array, Int32Array, Object, Reflect, SharedArrayBuffer};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    DedicatedWorkerGlobalScope, HtmlCanvasElement, MessageEvent, OffscreenCanvas, Worker,
+};
+
+use crate::{Gl, GlError, WebGlVersion};
+
+const INIT: &str = "webgl-rc:init";
+const RESIZE: &str = "webgl-rc:resize";
+const UPDATE: &str = "webgl-rc:update";
+const STOP: &str = "webgl-rc:stop";
+const ERROR: &str = "webgl-rc:error";
+
+type MessageCallback = Closure<dyn FnMut(MessageEvent)>;
+type AnimationCallback = Closure<dyn FnMut(f64)>;
+type ErrorCallback = Rc<RefCell<Option<Box<dyn Fn(String)>>>>;
+
+fn js_error(error: JsValue) -> GlError {
+    GlError::UnknownError(error.as_string().or_else(|| {
+        error
+            .dyn_ref::<js_sys::Error>()
+            .map(|error| String::from(error.message()))
+    }))
+}
+
+fn get(object: &JsValue, name: &str) -> JsValue {
+    Reflect::get(object, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn message(kind: &str, fields: &[(&str, JsValue)]) -> Object {
+    let message = Object::new();
+    let _ = Reflect::set(&message, &"type".into(), &kind.into());
+    for (name, value) in fields {
+        let _ = Reflect::set(&message, &JsValue::from_str(name), value);
+    }
+    message
+}
+
+/// Floats shared between the main thread and the worker without messages,
+/// for the values changing every frame, like the pointer position or the camera.
+///
+/// The buffer starts with a version counter, incremented after each write,
+/// so the reader can tell whether anything is changed. Single values are written atomically,
+/// but a reader can see a part of a multi-value write, keep the related values in one `f32`
+/// where it matters or check the version before and after reading.
+#[derive(Clone)]
+pub struct SharedValues {
+    buffer: SharedArrayBuffer,
+    version: Int32Array,
+    values: Float32Array,
+}
+
+impl fmt::Debug for SharedValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedValues")
+            .field("len", &self.len())
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+impl SharedValues {
+    /// Requires cross-origin isolation, `SharedArrayBuffer` isn't available otherwise.
+    pub fn new(len: u32) -> SharedValues {
+        SharedValues::from_buffer(SharedArrayBuffer::new(4 + len * 4))
+    }
+
+    pub fn from_buffer(buffer: SharedArrayBuffer) -> SharedValues {
+        let len = buffer.byte_length().saturating_sub(4) / 4;
+        SharedValues {
+            version: Int32Array::new_with_byte_offset_and_length(&buffer, 0, 1),
+            values: Float32Array::new_with_byte_offset_and_length(&buffer, 4, len),
+            buffer,
+        }
+    }
+
+    pub fn buffer(&self) -> &SharedArrayBuffer {
+        &self.buffer
+    }
+
+    pub fn len(&self) -> u32 {
+        self.values.length()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of the writes so far.
+    pub fn version(&self) -> i32 {
+        Atomics::load(&self.version, 0).unwrap_or(0)
+    }
+
+    /// Values out of the buffer are ignored.
+    pub fn write(&self, offset: u32, values: &[f32]) {
+        let end = (offset as usize + values.len()).min(self.len() as usize);
+        for (index, value) in (offset as usize..end).zip(values) {
+            self.values.set_index(index as u32, *value);
+        }
+        let _ = Atomics::add(&self.version, 0, 1);
+    }
+
+    /// Read into the slice, returns the number of the values read.
+    pub fn read(&self, offset: u32, values: &mut [f32]) -> usize {
+        let end = (offset as usize + values.len()).min(self.len() as usize);
+        let count = end.saturating_sub(offset as usize);
+        for (index, value) in values.iter_mut().enumerate().take(count) {
+            *value = self.values.get_index(offset + index as u32);
+        }
+        count
+    }
+
+    pub fn get(&self, index: u32) -> Option<f32> {
+        if index < self.len() {
+            Some(self.values.get_index(index))
+        } else {
+            None
+        }
+    }
+}
+
+/// Main thread side of the worker rendering.
+///
+/// The canvas control is transferred to the worker, which renders it with [WorkerRenderer].
+/// The scene is updated with messages, any structured-cloneable value, and optionally
+/// with [SharedValues] read by the worker on each frame.
+pub struct RenderProxy {
+    worker: Worker,
+    shared: Option<SharedValues>,
+    on_error: ErrorCallback,
+    listener: MessageCallback,
+}
+
+impl fmt::Debug for RenderProxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderProxy")
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+impl RenderProxy {
+    /// Transfer the canvas to the worker, `shared_values` floats are shared with it
+    /// when it's non-zero. The canvas can't be used on the main thread after that,
+    /// but CSS sizing still applies, send [RenderProxy::resize] when its size changes.
+    pub fn new(
+        worker: Worker,
+        canvas: &HtmlCanvasElement,
+        version: WebGlVersion,
+        shared_values: u32,
+    ) -> Result<RenderProxy, GlError> {
+        let offscreen = canvas.transfer_control_to_offscreen().map_err(js_error)?;
+        let shared = if shared_values > 0 {
+            Some(SharedValues::new(shared_values))
+        } else {
+            None
+        };
+        let init = message(
+            INIT,
+            &[
+                ("canvas", offscreen.clone().into()),
+                (
+                    "version",
+                    match version {
+                        WebGlVersion::WebGl1 => 1.into(),
+                        WebGlVersion::WebGl2 => 2.into(),
+                    },
+                ),
+                (
+                    "shared",
+                    shared
+                        .as_ref()
+                        .map_or(JsValue::UNDEFINED, |shared| shared.buffer().clone().into()),
+                ),
+            ],
+        );
+        worker
+            .post_message_with_transfer(&init, &Array::of1(&offscreen))
+            .map_err(js_error)?;
+
+        let on_error: ErrorCallback = Default::default();
+        let callback = Rc::downgrade(&on_error);
+        let listener = Closure::wrap(Box::new(move |event: MessageEvent| {
+            let data = event.data();
+            if get(&data, "type").as_string().as_deref() != Some(ERROR) {
+                return;
+            }
+            let error = get(&data, "message").as_string().unwrap_or_default();
+            trace_event!(ERROR, %error, "worker rendering failed");
+            if let Some(callback) = callback.upgrade() {
+                if let Some(callback) = callback.borrow().as_ref() {
+                    callback(error);
+                }
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        worker
+            .add_event_listener_with_callback("message", listener.as_ref().unchecked_ref())
+            .map_err(js_error)?;
+
+        Ok(RenderProxy {
+            worker,
+            shared,
+            on_error,
+            listener,
+        })
+    }
+
+    pub fn worker(&self) -> &Worker {
+        &self.worker
+    }
+
+    pub fn shared(&self) -> Option<&SharedValues> {
+        self.shared.as_ref()
+    }
+
+    /// Send the data to [WorkerScene::update].
+    pub fn update(&self, data: &JsValue) -> Result<(), GlError> {
+        self.worker
+            .post_message(&message(UPDATE, &[("data", data.clone())]))
+            .map_err(js_error)
+    }
+
+    /// Send the data moving the transferable objects, like array buffers, into the worker.
+    pub fn update_with_transfer(&self, data: &JsValue, transfer: &Array) -> Result<(), GlError> {
+        self.worker
+            .post_message_with_transfer(&message(UPDATE, &[("data", data.clone())]), transfer)
+            .map_err(js_error)
+    }
+
+    /// Size of the canvas in pixels.
+    pub fn resize(&self, width: u32, height: u32) -> Result<(), GlError> {
+        self.worker
+            .post_message(&message(
+                RESIZE,
+                &[("width", width.into()), ("height", height.into())],
+            ))
+            .map_err(js_error)
+    }
+
+    /// Stop the frame loop of the worker, the worker itself keeps running.
+    pub fn stop(&self) -> Result<(), GlError> {
+        self.worker
+            .post_message(&message(STOP, &[]))
+            .map_err(js_error)
+    }
+
+    /// Called with the errors of the worker scene.
+    pub fn set_on_error(&self, callback: impl Fn(String) + 'static) {
+        self.on_error.replace(Some(Box::new(callback)));
+    }
+}
+
+impl Drop for RenderProxy {
+    fn drop(&mut self) {
+        let _ = self
+            .worker
+            .remove_event_listener_with_callback("message", self.listener.as_ref().unchecked_ref());
+    }
+}
+
+/// Scene rendered by [WorkerRenderer].
+pub trait WorkerScene {
+    /// Data sent with [RenderProxy::update].
+    fn update(&mut self, _gl: &Gl, _data: JsValue) -> Result<(), GlError> {
+        Ok(())
+    }
+
+    /// The canvas is already resized.
+    fn resize(&mut self, _gl: &Gl, _width: u32, _height: u32) -> Result<(), GlError> {
+        Ok(())
+    }
+
+    /// Called on each animation frame of the worker, `time` is in milliseconds.
+    fn render(&mut self, gl: &Gl, shared: Option<&SharedValues>, time: f64) -> Result<(), GlError>;
+}
+
+type SceneFactory<S> = Box<dyn FnOnce(Gl) -> Result<S, GlError>>;
+
+struct RendererState<S> {
+    scope: DedicatedWorkerGlobalScope,
+    factory: Option<SceneFactory<S>>,
+    canvas: Option<OffscreenCanvas>,
+    gl: Option<Gl>,
+    scene: Option<S>,
+    shared: Option<SharedValues>,
+    running: Cell<bool>,
+    on_message: Option<MessageCallback>,
+    on_frame: Option<AnimationCallback>,
+}
+
+/// Worker side of the rendering, drives the scene with the worker animation frames.
+///
+/// The context and the scene are created when the canvas arrives from [RenderProxy].
+/// Errors of the scene are sent back to the proxy. The renderer stops when it's dropped,
+/// so keep it alive as long as the worker renders.
+pub struct WorkerRenderer<S: WorkerScene + 'static> {
+    state: Rc<RefCell<RendererState<S>>>,
+}
+
+impl<S: WorkerScene + 'static> fmt::Debug for WorkerRenderer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("WorkerRenderer")
+            .field("gl", &state.gl)
+            .field("shared", &state.shared)
+            .field("running", &state.running.get())
+            .finish()
+    }
+}
+
+impl<S: WorkerScene + 'static> WorkerRenderer<S> {
+    /// Should be called in a dedicated worker.
+    pub fn start(
+        create: impl FnOnce(Gl) -> Result<S, GlError> + 'static,
+    ) -> Result<WorkerRenderer<S>, GlError> {
+        let scope: DedicatedWorkerGlobalScope = js_sys::global()
+            .dyn_into()
+            .map_err(|_| GlError::UnknownError(Some("not a dedicated worker".into())))?;
+        let state = Rc::new(RefCell::new(RendererState {
+            scope: scope.clone(),
+            factory: Some(Box::new(create)),
+            canvas: None,
+            gl: None,
+            scene: None,
+            shared: None,
+            running: Cell::new(false),
+            on_message: None,
+            on_frame: None,
+        }));
+
+        let weak = Rc::downgrade(&state);
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            if let Some(state) = weak.upgrade() {
+                let result = Self::on_message(&state, event.data());
+                Self::report(&state, result);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let weak = Rc::downgrade(&state);
+        let on_frame = Closure::wrap(Box::new(move |time: f64| {
+            if let Some(state) = weak.upgrade() {
+                let result = Self::on_frame(&state, time);
+                Self::report(&state, result);
+            }
+        }) as Box<dyn FnMut(f64)>);
+
+        scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        {
+            let mut state = state.borrow_mut();
+            state.on_message = Some(on_message);
+            state.on_frame = Some(on_frame);
+        }
+        Ok(WorkerRenderer { state })
+    }
+
+    /// Context of the transferred canvas, `None` before it arrives.
+    pub fn gl(&self) -> Option<Gl> {
+        self.state.borrow().gl.clone()
+    }
+
+    pub fn shared(&self) -> Option<SharedValues> {
+        self.state.borrow().shared.clone()
+    }
+
+    /// Call the closure with the scene, if it's already created.
+    pub fn with_scene<R>(&self, callback: impl FnOnce(&mut S) -> R) -> Option<R> {
+        self.state.borrow_mut().scene.as_mut().map(callback)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state.borrow().running.get()
+    }
+
+    pub fn stop(&self) {
+        self.state.borrow().running.set(false);
+    }
+
+    /// Continue the frame loop after [WorkerRenderer::stop].
+    pub fn resume(&self) {
+        let state = self.state.borrow();
+        if state.scene.is_some() && !state.running.get() {
+            state.running.set(true);
+            Self::schedule(&state);
+        }
+    }
+
+    fn schedule(state: &RendererState<S>) {
+        if let Some(on_frame) = state.on_frame.as_ref() {
+            if state
+                .scope
+                .request_animation_frame(on_frame.as_ref().unchecked_ref())
+                .is_err()
+            {
+                trace_event!(WARN, "worker animation frame can't be requested");
+                state.running.set(false);
+            }
+        }
+    }
+
+    fn on_message(state: &RefCell<RendererState<S>>, data: JsValue) -> Result<(), GlError> {
+        let kind = get(&data, "type").as_string().unwrap_or_default();
+        let mut state = state.borrow_mut();
+        let state = &mut *state;
+        match kind.as_str() {
+            INIT => {
+                let canvas: OffscreenCanvas = get(&data, "canvas")
+                    .dyn_into()
+                    .map_err(|_| GlError::UnknownError(Some("offscreen canvas expected".into())))?;
+                let version = match get(&data, "version").as_f64() {
+                    Some(version) if version >= 2.0 => WebGlVersion::WebGl2,
+                    _ => WebGlVersion::WebGl1,
+                };
+                let gl = Gl::with_offscreen_canvas(&canvas, version)?;
+                let factory = state.factory.take().ok_or_else(|| {
+                    GlError::UnknownError(Some("the canvas is already received".into()))
+                })?;
+                state.shared = get(&data, "shared")
+                    .dyn_into::<SharedArrayBuffer>()
+                    .ok()
+                    .map(SharedValues::from_buffer);
+                state.scene = Some(factory(gl.clone())?);
+                state.gl = Some(gl);
+                state.canvas = Some(canvas);
+                state.running.set(true);
+                Self::schedule(state);
+            }
+            RESIZE => {
+                let width = get(&data, "width").as_f64().unwrap_or(0.0) as u32;
+                let height = get(&data, "height").as_f64().unwrap_or(0.0) as u32;
+                if let Some(canvas) = state.canvas.as_ref() {
+                    canvas.set_width(width);
+                    canvas.set_height(height);
+                }
+                if let (Some(gl), Some(scene)) = (state.gl.as_ref(), state.scene.as_mut()) {
+                    scene.resize(gl, width, height)?;
+                }
+            }
+            UPDATE => {
+                if let (Some(gl), Some(scene)) = (state.gl.as_ref(), state.scene.as_mut()) {
+                    scene.update(gl, get(&data, "data"))?;
+                }
+            }
+            STOP => state.running.set(false),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn on_frame(state: &RefCell<RendererState<S>>, time: f64) -> Result<(), GlError> {
+        let mut state = state.borrow_mut();
+        let state = &mut *state;
+        if !state.running.get() {
+            return Ok(());
+        }
+        // The next frame is requested first, so a failed frame doesn't stop the loop
+        Self::schedule(state);
+        if let (Some(gl), Some(scene)) = (state.gl.as_ref(), state.scene.as_mut()) {
+            gl.begin_frame(time);
+            scene.render(gl, state.shared.as_ref(), time)?;
+        }
+        Ok(())
+    }
+
+    fn report(state: &RefCell<RendererState<S>>, result: Result<(), GlError>) {
+        if let Err(error) = result {
+            trace_event!(ERROR, %error, "worker rendering failed");
+            let message = message(ERROR, &[("message", error.to_string().into())]);
+            let _ = state.borrow().scope.post_message(&message);
+        }
+    }
+}
+
+impl<S: WorkerScene + 'static> Drop for WorkerRenderer<S> {
+    fn drop(&mut self) {
+        let state = self.state.borrow();
+        state.running.set(false);
+        state.scope.set_onmessage(None);
+    }
+}
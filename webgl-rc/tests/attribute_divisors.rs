@@ -0,0 +1,117 @@
+//! Attribute divisors of instanced and plain draws, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[derive(Clone, Copy, Attributes)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+#[derive(Clone, Copy, Instances)]
+struct Instance {
+    offset: [f32; 2],
+}
+
+const FRAGMENT: &str = "precision mediump float;\nvoid main() { gl_FragColor = vec4(1.0); }";
+const VERTEX: &str = "attribute vec2 a_position;\nattribute vec2 i_offset;\nvoid main() { gl_Position = vec4(a_position + i_offset, 0.0, 1.0); }";
+
+fn divisors(recording: &Recording) -> Vec<(u32, u32)> {
+    recording
+        .commands()
+        .iter()
+        .filter_map(|command| match command {
+            Command::VertexAttribDivisor { location, divisor } => Some((*location, *divisor)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn plain_draw_works_without_instanced_arrays() {
+    let gl = Gl::mock_without_extensions(&["ANGLE_instanced_arrays"]);
+    assert!(!gl.supports(Feature::Instancing));
+    let program = gl
+        .program(
+            FRAGMENT,
+            "attribute vec2 a_position;\nvoid main() { gl_Position = vec4(a_position, 0.0, 1.0); }",
+        )
+        .unwrap();
+    let vertices = gl
+        .items_buffer(
+            &[Vertex {
+                position: [0.0, 0.0],
+            }; 3],
+            BufferUsage::Static,
+        )
+        .unwrap();
+    let (result, recording) =
+        gl.record(|| program.draw_arrays(PrimitiveType::Triangles, &(), &vertices));
+    assert_eq!(result, Ok(()));
+    assert_eq!(divisors(&recording), Vec::new());
+}
+
+#[test]
+fn divisors_are_set_only_when_they_change() {
+    let gl = Gl::mock();
+    let program = gl.program(FRAGMENT, VERTEX).unwrap();
+    let vertices = gl
+        .items_buffer(
+            &[Vertex {
+                position: [0.0, 0.0],
+            }; 3],
+            BufferUsage::Static,
+        )
+        .unwrap();
+    let instances = gl
+        .items_buffer(&[Instance { offset: [0.0, 0.0] }; 2], BufferUsage::Static)
+        .unwrap();
+    let (_, recording) = gl.record(|| {
+        program
+            .draw_instances(PrimitiveType::Triangles, &(), &vertices, &instances)
+            .unwrap();
+        program
+            .draw_instances(PrimitiveType::Triangles, &(), &vertices, &instances)
+            .unwrap();
+    });
+    assert_eq!(divisors(&recording), vec![(1, 1)]);
+}
+
+#[test]
+fn replayed_divisors_are_reset_by_plain_draws() {
+    let gl = Gl::mock();
+    let program = gl
+        .program(
+            FRAGMENT,
+            "attribute vec2 a_position;\nvoid main() { gl_Position = vec4(a_position, 0.0, 1.0); }",
+        )
+        .unwrap();
+    let vertices = gl
+        .items_buffer(
+            &[Vertex {
+                position: [0.0, 0.0],
+            }; 3],
+            BufferUsage::Static,
+        )
+        .unwrap();
+    let (_, recording) =
+        gl.record(|| program.draw_arrays(PrimitiveType::Triangles, &(), &vertices));
+    let location = recording
+        .commands()
+        .iter()
+        .find_map(|command| match command {
+            Command::VertexAttribPointer { location, .. } => Some(*location),
+            _ => None,
+        })
+        .unwrap();
+    Recording::new(vec![Command::VertexAttribDivisor {
+        location,
+        divisor: 1,
+    }])
+    .replay(&gl)
+    .unwrap();
+    let (result, recording) =
+        gl.record(|| program.draw_arrays(PrimitiveType::Triangles, &(), &vertices));
+    assert_eq!(result, Ok(()));
+    assert_eq!(divisors(&recording), vec![(location, 0)]);
+}
@@ -0,0 +1,50 @@
+//! Clearing through the settings cache, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[test]
+fn clear_enables_write_masks_of_cleared_buffers() {
+    let gl = Gl::mock();
+    let (_, recording) = gl.record(|| {
+        gl.apply(
+            Gl::settings()
+                .color_mask(false, false, false, false)
+                .depth_mask(false)
+                .stencil_mask(0),
+            || {
+                gl.clear(ClearParams {
+                    depth: Some(1.0),
+                    ..Default::default()
+                })
+            },
+        )
+    });
+    let commands = recording.commands();
+    let clear = commands
+        .iter()
+        .position(|command| matches!(command, Command::Clear { .. }))
+        .unwrap();
+    let before = &commands[..clear];
+    assert!(matches!(
+        before
+            .iter()
+            .rev()
+            .find(|command| matches!(command, Command::DepthMask { .. })),
+        Some(Command::DepthMask { value: true })
+    ));
+    assert!(matches!(
+        before
+            .iter()
+            .rev()
+            .find(|command| matches!(command, Command::ColorMask { .. })),
+        Some(Command::ColorMask { r: false, .. })
+    ));
+    assert!(matches!(
+        before
+            .iter()
+            .rev()
+            .find(|command| matches!(command, Command::StencilMask { .. })),
+        Some(Command::StencilMask { mask: 0 })
+    ));
+}
@@ -0,0 +1,81 @@
+//! Declarations compared by `check_program!`.
+
+use webgl_rc::program_check::{Declaration, DeclaredAttributes, DeclaredUniforms};
+use webgl_rc::types::{DataType, TypeMark};
+use webgl_rc::uniforms::{IntoUniform, UniformValue};
+use webgl_rc::*;
+
+/// Uniform implemented downstream without a compile time type.
+#[derive(Clone)]
+struct Seed(u32);
+
+impl IntoUniform for Seed {
+    fn into_uniform(&self) -> UniformValue {
+        UniformValue::Int(self.0 as i32)
+    }
+}
+
+/// Attribute type implemented downstream with only the runtime type.
+#[derive(Clone, Copy)]
+struct Weight(f32);
+
+impl TypeMark for Weight {
+    fn data_type() -> DataType {
+        DataType::Float
+    }
+}
+
+impl Writable for Weight {
+    fn write(&self, output: &mut Vec<f32>) {
+        output.push(self.0);
+    }
+    fn stride() -> usize {
+        1
+    }
+}
+
+#[derive(Clone, Uniforms)]
+struct Params {
+    seed: Seed,
+    scale: f32,
+}
+
+#[derive(Clone, Copy, Attributes)]
+struct Vertex {
+    position: [f32; 2],
+    weight: Weight,
+}
+
+#[test]
+fn custom_uniforms_are_declared_without_type() {
+    assert_eq!(
+        Params::UNIFORMS,
+        &[
+            Declaration {
+                name: "u_seed",
+                data_type: None,
+            },
+            Declaration {
+                name: "u_scale",
+                data_type: Some(DataType::Float),
+            },
+        ]
+    );
+}
+
+#[test]
+fn custom_attributes_are_declared_without_type() {
+    assert_eq!(
+        Vertex::ATTRIBUTES,
+        &[
+            Declaration {
+                name: "a_position",
+                data_type: Some(DataType::Vec2),
+            },
+            Declaration {
+                name: "a_weight",
+                data_type: None,
+            },
+        ]
+    );
+}
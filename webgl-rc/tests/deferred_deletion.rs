@@ -0,0 +1,60 @@
+//! Deletions deferred until the settings are restored, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+fn texture(gl: &Gl) -> Texture {
+    gl.texture(
+        1,
+        1,
+        TextureType::Byte,
+        TextureFormat::Rgba,
+        TextureContent::None,
+    )
+    .unwrap()
+}
+
+fn deletions(recording: &Recording) -> usize {
+    recording
+        .commands()
+        .iter()
+        .filter(|command| matches!(command, Command::DeleteTexture { .. }))
+        .count()
+}
+
+#[test]
+fn texture_dropped_outside_apply_is_deleted_immediately() {
+    let gl = Gl::mock();
+    let texture = texture(&gl);
+    let ((), recording) = gl.record(|| drop(texture));
+    assert_eq!(deletions(&recording), 1);
+    assert_eq!(gl.pending_deletions(), 0);
+}
+
+#[test]
+fn texture_dropped_inside_apply_is_deleted_after_it() {
+    let gl = Gl::mock();
+    let texture = texture(&gl);
+    let ((), recording) = gl.record(|| {
+        gl.apply(Gl::settings().texture(0, texture.clone()), || {
+            gl.apply(Gl::settings().viewport(0, 0, 1, 1), || {
+                drop(texture);
+                assert_eq!(gl.pending_deletions(), 0);
+            });
+        });
+        assert_eq!(gl.pending_deletions(), 0);
+    });
+    assert_eq!(deletions(&recording), 1);
+}
+
+#[test]
+fn collect_deletes_the_pending_resources() {
+    let gl = Gl::mock();
+    gl.apply(Gl::settings().viewport(0, 0, 1, 1), || {
+        drop(texture(&gl));
+        assert_eq!(gl.pending_deletions(), 1);
+        let ((), recording) = gl.record(|| gl.collect());
+        assert_eq!(deletions(&recording), 1);
+        assert_eq!(gl.pending_deletions(), 0);
+    });
+}
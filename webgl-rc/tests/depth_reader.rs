@@ -0,0 +1,26 @@
+//! Reading depth textures, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[test]
+fn regions_out_of_bounds_are_rejected() {
+    let gl = Gl::mock();
+    let depth = gl
+        .texture(
+            4,
+            4,
+            TextureType::Short,
+            TextureFormat::Depth,
+            TextureContent::None,
+        )
+        .unwrap();
+    let reader = DepthReader::new(gl).unwrap();
+    assert!(reader.read(&depth, 3, 0, 2, 1).is_err());
+    assert!(reader.read(&depth, u32::MAX, 0, 2, 1).is_err());
+    assert!(reader.read(&depth, 0, 2, 1, u32::MAX).is_err());
+    assert_eq!(
+        reader.read(&depth, 0, 0, 2, 2).map(|values| values.len()),
+        Ok(4)
+    );
+}
@@ -0,0 +1,57 @@
+//! Draw parameters checked in every build, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[derive(Clone, Copy, Attributes)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+#[derive(Clone, Copy, Instances)]
+struct Instance {
+    offset: [f32; 2],
+}
+
+const FRAGMENT: &str = "precision mediump float;\nvoid main() { gl_FragColor = vec4(1.0); }";
+const VERTEX: &str = "attribute vec2 a_position;\nattribute vec2 i_offset;\nvoid main() { gl_Position = vec4(a_position + i_offset, 0.0, 1.0); }";
+
+fn vertices(gl: &Gl, count: usize) -> ItemsBuffer<Vertex> {
+    gl.items_buffer(
+        &vec![
+            Vertex {
+                position: [0.0, 0.0],
+            };
+            count
+        ],
+        BufferUsage::Static,
+    )
+    .unwrap()
+}
+
+#[test]
+fn count_not_fitting_the_primitive_fails() {
+    let gl = Gl::mock();
+    let program = gl.program(FRAGMENT, VERTEX).unwrap();
+    let vertices = vertices(&gl, 4);
+    let (result, recording) =
+        gl.record(|| program.draw_arrays(PrimitiveType::Triangles, &(), &vertices));
+    assert!(matches!(result, Err(GlError::InvalidDraw(_))));
+    assert!(recording.is_empty());
+    assert_eq!(
+        program.draw_arrays(PrimitiveType::TriangleStrip, &(), &vertices),
+        Ok(())
+    );
+}
+
+#[test]
+fn empty_instances_fail() {
+    let gl = Gl::mock();
+    let program = gl.program(FRAGMENT, VERTEX).unwrap();
+    let vertices = vertices(&gl, 3);
+    let instances: ItemsBuffer<Instance> = gl.items_buffer(&[], BufferUsage::Static).unwrap();
+    let (result, recording) =
+        gl.record(|| program.draw_instances(PrimitiveType::Triangles, &(), &vertices, &instances));
+    assert!(matches!(result, Err(GlError::InvalidDraw(_))));
+    assert!(recording.is_empty());
+}
@@ -0,0 +1,24 @@
+//! Named settings presets, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[test]
+fn unknown_preset_fails_without_calling_back() {
+    let gl = Gl::mock();
+    gl.register_preset("transparent", Gl::settings().blend(true));
+    let mut called = false;
+    let (result, recording) = gl.record(|| gl.apply_preset("transparnt", || called = true));
+    assert_eq!(result, Err(GlError::UnknownPreset("transparnt".into())));
+    assert!(!called);
+    assert!(recording.is_empty());
+}
+
+#[test]
+fn registered_preset_is_applied() {
+    let gl = Gl::mock();
+    gl.register_preset("blend", Gl::settings().blend(true));
+    let (result, recording) = gl.record(|| gl.apply_preset("blend", || 42));
+    assert_eq!(result, Ok(42));
+    assert!(!recording.is_empty());
+}
@@ -0,0 +1,60 @@
+//! Clearing texture regions, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+fn texture(gl: &Gl) -> Texture {
+    gl.texture(
+        4,
+        4,
+        TextureType::Byte,
+        TextureFormat::Rgba,
+        TextureContent::None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn regions_out_of_bounds_are_rejected() {
+    let gl = Gl::mock();
+    let texture = texture(&gl);
+    assert!(texture
+        .clear_region(2, 0, 3, 1, 0.0, 0.0, 0.0, 1.0)
+        .is_err());
+    assert!(texture
+        .clear_region(u32::MAX, 0, 2, 1, 0.0, 0.0, 0.0, 1.0)
+        .is_err());
+    assert!(texture
+        .clear_region(0, 1, 1, u32::MAX, 0.0, 0.0, 0.0, 1.0)
+        .is_err());
+    assert!(texture.clear_region(0, 0, 4, 4, 0.0, 0.0, 0.0, 1.0).is_ok());
+}
+
+#[test]
+fn clear_region_restores_the_initial_viewport() {
+    let gl = Gl::mock();
+    let texture = texture(&gl);
+    let (result, recording) = gl.record(|| texture.clear_region(1, 1, 2, 2, 1.0, 0.0, 0.0, 1.0));
+    assert_eq!(result, Ok(()));
+    let viewports: Vec<(i32, i32)> = recording
+        .commands()
+        .iter()
+        .filter_map(|command| match command {
+            Command::Viewport { width, height, .. } => Some((*width, *height)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(viewports, vec![(4, 4), (300, 150)]);
+}
+
+#[test]
+fn initial_viewport_covers_the_drawing_buffer() {
+    let gl = Gl::mock();
+    let viewport = gl.viewport();
+    assert_eq!((viewport.width, viewport.height), (300, 150));
+    let cells = viewport.split(2, 1);
+    assert_eq!(
+        (cells[1].x, cells[1].width, cells[1].height),
+        (150, 150, 150)
+    );
+}
@@ -0,0 +1,88 @@
+//! Texture unit budgeting, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::types::DataType;
+use webgl_rc::*;
+
+#[derive(Clone, Copy, Attributes)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+fn program(gl: &Gl, samplers: usize) -> Program {
+    let declarations: String = (0..samplers)
+        .map(|i| format!("uniform sampler2D u_texture_{};\n", i))
+        .collect();
+    let fragment = format!(
+        "precision mediump float;\n{}void main() {{ gl_FragColor = vec4(1.0); }}",
+        declarations
+    );
+    let vertex = "attribute vec2 a_position;\nvoid main() { gl_Position = vec4(a_position, 0.0, 1.0); }";
+    gl.program(&fragment, vertex).unwrap()
+}
+
+fn draw(gl: &Gl, textures: usize) -> Result<(), GlError> {
+    let texture = gl
+        .texture(1, 1, TextureType::Byte, TextureFormat::Rgba, TextureContent::None)
+        .unwrap();
+    let uniforms: Vec<(String, UniformValue)> = (0..textures)
+        .map(|i| (format!("u_texture_{}", i), UniformValue::Texture(texture.clone())))
+        .collect();
+    let uniforms: Vec<(&str, UniformValue)> = uniforms
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.clone()))
+        .collect();
+    let vertices = gl
+        .items_buffer(&[Vertex { position: [0.0, 0.0] }; 3], BufferUsage::Static)
+        .unwrap();
+    program(gl, textures).draw_arrays(PrimitiveType::Triangles, &uniforms[..], &vertices)
+}
+
+fn root_cause(error: GlError) -> GlError {
+    match error {
+        GlError::Context { source, .. } => root_cause(*source),
+        error => error,
+    }
+}
+
+#[test]
+fn texture_units_are_capped_by_the_list_size() {
+    assert_eq!(Gl::mock().texture_units(), TEXTURES_COUNT);
+}
+
+#[test]
+fn draw_uses_all_the_texture_units() {
+    let gl = Gl::mock();
+    assert_eq!(draw(&gl, TEXTURES_COUNT as usize), Ok(()));
+}
+
+#[test]
+fn draw_with_too_many_textures_fails() {
+    let gl = Gl::mock();
+    let error = draw(&gl, TEXTURES_COUNT as usize + 1).unwrap_err();
+    assert_eq!(
+        root_cause(error),
+        GlError::TooManyTextures {
+            required: TEXTURES_COUNT as usize + 1,
+            available: TEXTURES_COUNT as usize,
+        }
+    );
+}
+
+#[test]
+fn failed_draw_issues_no_draw_command() {
+    let gl = Gl::mock();
+    let (result, recording) = gl.record(|| draw(&gl, TEXTURES_COUNT as usize + 1));
+    assert!(result.is_err());
+    assert!(!recording
+        .commands()
+        .iter()
+        .any(|command| matches!(command, Command::DrawArrays { .. })));
+    let sampler = u32::from(DataType::Sampler);
+    assert!(!recording.commands().iter().any(|command| match command {
+        Command::Uniform {
+            data_type, values, ..
+        } => *data_type == sampler && values.iter().any(|unit| *unit >= TEXTURES_COUNT as f32),
+        _ => false,
+    }));
+}
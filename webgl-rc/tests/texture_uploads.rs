@@ -0,0 +1,30 @@
+//! Texture uploads, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[test]
+fn failed_upload_keeps_premultiplied_flag() {
+    let gl = Gl::mock();
+    let texture = gl
+        .texture(
+            1,
+            1,
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::None,
+        )
+        .unwrap();
+    texture.set_premultiplied(true);
+    gl.simulate_context_loss().unwrap();
+    assert_eq!(
+        texture
+            .write_bytes(&vec![0; 4])
+            .map_err(|error| error.root_cause().clone()),
+        Err(GlError::ContextLost)
+    );
+    assert!(texture.is_premultiplied());
+    gl.restore_context().unwrap();
+    assert_eq!(texture.write_bytes(&vec![0; 4]), Ok(()));
+    assert!(!texture.is_premultiplied());
+}
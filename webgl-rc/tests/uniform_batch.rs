@@ -0,0 +1,69 @@
+//! Packing per-object uniforms into instances, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[derive(Clone, Copy, Attributes)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+#[derive(Clone, Uniforms)]
+struct Object {
+    offset: [f32; 2],
+    color: [f32; 4],
+    layer: i32,
+}
+
+fn object(offset: f32, layer: i32) -> Object {
+    Object {
+        offset: [offset, 0.0],
+        color: [1.0, 0.0, 0.0, 1.0],
+        layer,
+    }
+}
+
+const FRAGMENT: &str = "precision mediump float;\nuniform int u_layer;\nvarying vec4 v_color;\nvoid main() { gl_FragColor = v_color; }";
+
+fn draws(gl: &Gl, vertex: &str, objects: &[Object]) -> (usize, usize) {
+    let program = gl.program(FRAGMENT, vertex).unwrap();
+    let vertices = gl
+        .items_buffer(&[Vertex { position: [0.0, 0.0] }; 3], BufferUsage::Static)
+        .unwrap();
+    let batch = UniformBatch::new(program);
+    let (result, recording) = gl.record(|| {
+        batch.draw(
+            DrawParams::new(PrimitiveType::Triangles, &(), &vertices),
+            objects,
+        )
+    });
+    assert_eq!(result, Ok(()));
+    let count = |instanced: bool| {
+        recording
+            .commands()
+            .iter()
+            .filter(|command| match command {
+                Command::DrawArrays { .. } => !instanced,
+                Command::DrawArraysInstanced { .. } => instanced,
+                _ => false,
+            })
+            .count()
+    };
+    (count(false), count(true))
+}
+
+#[test]
+fn objects_with_equal_uniforms_share_an_instanced_draw() {
+    let gl = Gl::mock();
+    let vertex = "attribute vec2 a_position;\nattribute vec2 i_offset;\nattribute vec4 i_color;\nvarying vec4 v_color;\nvoid main() { v_color = i_color; gl_Position = vec4(a_position + i_offset, 0.0, 1.0); }";
+    let objects = [object(0.0, 0), object(1.0, 0), object(2.0, 1), object(3.0, 1)];
+    assert_eq!(draws(&gl, vertex, &objects), (0, 2));
+}
+
+#[test]
+fn objects_without_instance_attributes_are_drawn_one_by_one() {
+    let gl = Gl::mock();
+    let vertex = "attribute vec2 a_position;\nuniform vec2 u_offset;\nuniform vec4 u_color;\nvarying vec4 v_color;\nvoid main() { v_color = u_color; gl_Position = vec4(a_position + u_offset, 0.0, 1.0); }";
+    let objects = [object(0.0, 0), object(1.0, 0), object(2.0, 0)];
+    assert_eq!(draws(&gl, vertex, &objects), (3, 0));
+}
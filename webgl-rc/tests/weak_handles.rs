@@ -0,0 +1,37 @@
+//! Weak resource handles, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[test]
+fn weak_texture_upgrades_while_the_texture_is_alive() {
+    let gl = Gl::mock();
+    let texture = gl
+        .texture(
+            1,
+            1,
+            TextureType::Byte,
+            TextureFormat::Rgba,
+            TextureContent::None,
+        )
+        .unwrap();
+    let weak = texture.downgrade();
+    assert!(weak.is_alive());
+    assert_eq!(weak.id(), texture.id());
+    assert_eq!(weak.upgrade(), Some(texture.clone()));
+
+    drop(texture);
+    assert!(!weak.is_alive());
+    assert_eq!(weak.upgrade(), None);
+}
+
+#[test]
+fn weak_buffer_doesnt_keep_the_buffer_registered() {
+    let gl = Gl::mock();
+    let buffer = gl.elements_buffer(&[0, 1, 2], BufferUsage::Static).unwrap();
+    let weak = buffer.downgrade();
+    let id = buffer.id();
+    drop(buffer);
+    assert!(!weak.is_alive());
+    assert!(gl.resources().iter().all(|resource| resource.id != id));
+}
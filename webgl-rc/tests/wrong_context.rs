@@ -0,0 +1,85 @@
+//! Resources used with another context, run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use webgl_rc::*;
+
+#[derive(Clone, Copy, Attributes)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+const VERTEX: &str =
+    "attribute vec2 a_position;\nvoid main() { gl_Position = vec4(a_position, 0.0, 1.0); }";
+
+fn texture(gl: &Gl) -> Texture {
+    gl.texture(
+        1,
+        1,
+        TextureType::Byte,
+        TextureFormat::Rgba,
+        TextureContent::None,
+    )
+    .unwrap()
+}
+
+fn vertices(gl: &Gl) -> ItemsBuffer<Vertex> {
+    gl.items_buffer(
+        &[Vertex {
+            position: [0.0, 0.0],
+        }; 3],
+        BufferUsage::Static,
+    )
+    .unwrap()
+}
+
+fn is_wrong_context<T>(result: Result<T, GlError>) -> bool {
+    matches!(result, Err(GlError::WrongContext(_)))
+}
+
+#[test]
+fn drawing_vertices_of_another_context_fails() {
+    let gl = Gl::mock();
+    let other = Gl::mock();
+    let program = gl
+        .program("void main() { gl_FragColor = vec4(1.0); }", VERTEX)
+        .unwrap();
+    let (result, recording) =
+        gl.record(|| program.draw_arrays(PrimitiveType::Triangles, &(), &vertices(&other)));
+    assert!(is_wrong_context(result));
+    assert!(recording.is_empty());
+}
+
+#[test]
+fn drawing_texture_of_another_context_fails() {
+    let gl = Gl::mock();
+    let other = Gl::mock();
+    let program = gl
+        .program(
+            "precision mediump float;\nuniform sampler2D u_texture;\nvoid main() { gl_FragColor = texture2D(u_texture, vec2(0.0)); }",
+            VERTEX,
+        )
+        .unwrap();
+    let vertices = vertices(&gl);
+    let uniforms = [("u_texture", UniformValue::Texture(texture(&other)))];
+    let (result, recording) =
+        gl.record(|| program.draw_arrays(PrimitiveType::Triangles, &uniforms[..], &vertices));
+    assert!(is_wrong_context(result));
+    assert!(!recording
+        .commands()
+        .iter()
+        .any(|command| matches!(command, Command::DrawArrays { .. })));
+}
+
+#[test]
+fn attaching_texture_of_another_context_fails() {
+    let gl = Gl::mock();
+    let other = Gl::mock();
+    let mut frame_buffer = gl.frame_buffer().unwrap();
+    assert!(is_wrong_context(
+        frame_buffer.set_color_buffer(Some(texture(&other)))
+    ));
+    assert!(is_wrong_context(
+        gl.frame_buffer_with_color(texture(&other))
+    ));
+    assert!(frame_buffer.set_color_buffer(Some(texture(&gl))).is_ok());
+}